@@ -459,6 +459,55 @@ fn define_control_flow(
         .is_call(true),
     );
 
+    let FN = &Operand::new("FN", &entities.func_ref)
+        .with_doc("function to call, declared by `function`");
+    let args = &Operand::new("args", &entities.varargs).with_doc("call arguments");
+    ig.push(
+        Inst::new(
+            "return_call",
+            r#"
+        Direct tail call.
+
+        Tail call a function which has been declared in the preamble. The
+        argument types must match the function's signature, and the called
+        function must have the same return types and calling convention as
+        the current function.
+
+        The current function's stack frame is torn down before control is
+        transferred, so the called function returns directly to the current
+        function's caller.
+        "#,
+            &formats.call,
+        )
+        .operands_in(vec![FN, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
+    let SIG = &Operand::new("SIG", &entities.sig_ref).with_doc("function signature");
+    let callee = &Operand::new("callee", iAddr).with_doc("address of function to call");
+    let args = &Operand::new("args", &entities.varargs).with_doc("call arguments");
+    ig.push(
+        Inst::new(
+            "return_call_indirect",
+            r#"
+        Indirect tail call.
+
+        Tail call the function pointed to by `callee` with the given
+        arguments. The called function must match the specified signature,
+        which must have the same return types and calling convention as the
+        current function.
+
+        See `return_call` for the semantics of tail calls, and
+        `call_indirect` for how `callee` is interpreted.
+        "#,
+            &formats.call_indirect,
+        )
+        .operands_in(vec![SIG, callee, args])
+        .is_call(true)
+        .is_terminator(true),
+    );
+
     let FN = &Operand::new("FN", &entities.func_ref)
         .with_doc("function to call, declared by `function`");
     let addr = &Operand::new("addr", iAddr);
@@ -1041,6 +1090,111 @@ pub(crate) fn define(
         .can_store(true),
     );
 
+    let AtomicMem = &TypeVar::new(
+        "AtomicMem",
+        "Any type that can be accessed atomically in memory",
+        TypeSetBuilder::new().ints(8..64).build(),
+    );
+    let x = &Operand::new("x", AtomicMem).with_doc("Value to be stored");
+    let a = &Operand::new("a", AtomicMem).with_doc("Value previously in memory");
+    let e = &Operand::new("e", AtomicMem).with_doc("Expected value in memory");
+    let AtomicRmwOp = &Operand::new("AtomicRmwOp", &imm.atomic_rmw_op);
+
+    ig.push(
+        Inst::new(
+            "atomic_rmw",
+            r#"
+        Atomically read-modify-write memory at ``p``.
+
+        Loads the value at ``p``, applies ``AtomicRmwOp`` to it and ``x``, and
+        stores the result back, all as a single sequentially consistent atomic
+        operation. Returns the value which was previously in memory.
+
+        ``p`` must be aligned to the size of the accessed type.
+        "#,
+            &formats.atomic_rmw,
+        )
+        .operands_in(vec![MemFlags, AtomicRmwOp, p, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_cas",
+            r#"
+        Atomically compare and swap memory at ``p``.
+
+        Stores ``x`` to memory at ``p`` if the value there equals ``e``, as a
+        single sequentially consistent atomic operation. Returns the value
+        which was previously in memory, whether or not the store happened.
+
+        ``p`` must be aligned to the size of the accessed type.
+        "#,
+            &formats.atomic_cas,
+        )
+        .operands_in(vec![MemFlags, p, e, x])
+        .operands_out(vec![a])
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    let a = &Operand::new("a", AtomicMem).with_doc("Value loaded");
+
+    ig.push(
+        Inst::new(
+            "atomic_load",
+            r#"
+        Atomically load from memory at ``p + Offset``.
+
+        This is a sequentially consistent load, which isn't reordered with
+        other atomic operations. ``p + Offset`` must be aligned to the size of
+        the loaded type.
+        "#,
+            &formats.load,
+        )
+        .operands_in(vec![MemFlags, p, Offset])
+        .operands_out(vec![a])
+        .can_load(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "atomic_store",
+            r#"
+        Atomically store ``x`` to memory at ``p + Offset``.
+
+        This is a sequentially consistent store, which isn't reordered with
+        other atomic operations. ``p + Offset`` must be aligned to the size of
+        the stored type.
+        "#,
+            &formats.store,
+        )
+        .operands_in(vec![MemFlags, x, p, Offset])
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
+    ig.push(
+        Inst::new(
+            "fence",
+            r#"
+        A memory fence.
+
+        No memory access before the fence, atomic or not, is reordered with
+        any memory access after it.
+        "#,
+            &formats.nullary,
+        )
+        .can_load(true)
+        .can_store(true)
+        .other_side_effects(true),
+    );
+
     let x = &Operand::new("x", Mem).with_doc("Value to be stored");
     let a = &Operand::new("a", Mem).with_doc("Value loaded");
     let Offset =
@@ -135,8 +135,15 @@ pub(crate) fn define() -> SettingGroup {
         "tls_model",
         r#"
             Defines the model used to perform TLS accesses.
+
+            - `elf_gd` uses the general-dynamic model, calling `__tls_get_addr`.
+            - `elf_le` uses the local-exec model, adding a link-time constant
+              offset to the thread pointer. Only valid for symbols defined in
+              the main executable.
+            - `macho` calls the accessor stored in the symbol's `__thread_vars`
+              entry.
         "#,
-        vec!["none", "elf_gd", "macho", "coff"],
+        vec!["none", "elf_gd", "elf_le", "macho", "coff"],
     );
 
     // Settings specific to the `baldrdash` calling convention.
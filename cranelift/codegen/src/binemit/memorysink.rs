@@ -170,6 +170,11 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         let stackmap = Stackmap::from_values(&val_list, func, isa);
         self.stackmaps.add_stackmap(ofs, stackmap);
     }
+
+    fn add_mach_stackmap(&mut self, stackmap: Stackmap) {
+        let ofs = self.offset();
+        self.stackmaps.add_stackmap(ofs, stackmap);
+    }
 }
 
 /// A `RelocSink` implementation that does nothing, which is convenient when
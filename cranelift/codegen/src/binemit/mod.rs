@@ -64,6 +64,27 @@ pub enum Reloc {
 
     /// Mach-O x86_64 32 bit signed PC relative offset to a `__thread_vars` entry.
     MachOX86_64Tlv,
+
+    /// Elf x86_64 32 bit signed offset of a LE symbol from the thread pointer.
+    ElfX86_64TpOff32,
+
+    /// Arm64 GD TLS: 21 bit page number of the GOT entries for the symbol (`ADRP`).
+    Arm64TlsGdAdrPage21,
+
+    /// Arm64 GD TLS: low 12 bits of the address of the GOT entries for the symbol (`ADD`).
+    Arm64TlsGdAddLo12Nc,
+
+    /// Arm64 LE TLS: bits [23:12] of the offset of the symbol from the thread pointer (`ADD`).
+    Arm64TlsLeAddTprelHi12,
+
+    /// Arm64 LE TLS: low 12 bits of the offset of the symbol from the thread pointer (`ADD`).
+    Arm64TlsLeAddTprelLo12Nc,
+
+    /// Mach-O Arm64 21 bit page number of a `__thread_vars` entry (`ADRP`).
+    MachOAarch64TlsAdrPage21,
+
+    /// Mach-O Arm64 low 12 bits of the address of a `__thread_vars` entry (`LDR`).
+    MachOAarch64TlsAdrPageOff12,
 }
 
 impl fmt::Display for Reloc {
@@ -82,6 +103,13 @@ impl fmt::Display for Reloc {
 
             Self::ElfX86_64TlsGd => write!(f, "ElfX86_64TlsGd"),
             Self::MachOX86_64Tlv => write!(f, "MachOX86_64Tlv"),
+            Self::ElfX86_64TpOff32 => write!(f, "ElfX86_64TpOff32"),
+            Self::Arm64TlsGdAdrPage21 => write!(f, "Arm64TlsGdAdrPage21"),
+            Self::Arm64TlsGdAddLo12Nc => write!(f, "Arm64TlsGdAddLo12Nc"),
+            Self::Arm64TlsLeAddTprelHi12 => write!(f, "Arm64TlsLeAddTprelHi12"),
+            Self::Arm64TlsLeAddTprelLo12Nc => write!(f, "Arm64TlsLeAddTprelLo12Nc"),
+            Self::MachOAarch64TlsAdrPage21 => write!(f, "MachOAarch64TlsAdrPage21"),
+            Self::MachOAarch64TlsAdrPageOff12 => write!(f, "MachOAarch64TlsAdrPageOff12"),
         }
     }
 }
@@ -164,6 +192,9 @@ pub trait CodeSink {
 
     /// Add a stackmap at the current code offset.
     fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa);
+
+    /// Add a stackmap computed by a MachInst backend at the current code offset.
+    fn add_mach_stackmap(&mut self, _: Stackmap);
 }
 
 /// Type of the frame unwind information.
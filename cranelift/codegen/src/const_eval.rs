@@ -0,0 +1,45 @@
+//! Helpers for evaluating integer operations on constants.
+//!
+//! Integer values are represented as `u64`s holding the bits of a value of a type of at most 64
+//! bits, with the bits above the width of the type cleared.
+
+use crate::ir::condcodes::IntCC;
+use crate::ir::Type;
+
+/// Truncate `x` to the width of the integer type `ty`.
+pub fn mask(ty: Type, x: u64) -> u64 {
+    let bits = u32::from(ty.bits());
+    if bits >= 64 {
+        x
+    } else {
+        x & ((1 << bits) - 1)
+    }
+}
+
+/// Sign-extend `x` from the width of the integer type `ty`.
+pub fn sext(ty: Type, x: u64) -> i64 {
+    let shift = 64 - u32::from(ty.bits());
+    ((x << shift) as i64) >> shift
+}
+
+/// Evaluate an integer comparison of two values of type `ty`.
+pub fn icmp(cond: IntCC, ty: Type, x: u64, y: u64) -> bool {
+    let (sx, sy) = (sext(ty, x), sext(ty, y));
+    match cond {
+        IntCC::Equal => x == y,
+        IntCC::NotEqual => x != y,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => x < y,
+        IntCC::UnsignedGreaterThanOrEqual => x >= y,
+        IntCC::UnsignedGreaterThan => x > y,
+        IntCC::UnsignedLessThanOrEqual => x <= y,
+        IntCC::Overflow | IntCC::NotOverflow => {
+            let diff = i128::from(sx) - i128::from(sy);
+            let overflow = diff != i128::from(sext(ty, diff as u64));
+            overflow == (cond == IntCC::Overflow)
+        }
+    }
+}
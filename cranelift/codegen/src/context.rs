@@ -28,6 +28,7 @@ use crate::postopt::do_postopt;
 use crate::redundant_reload_remover::RedundantReloadRemover;
 use crate::regalloc;
 use crate::result::CodegenResult;
+use crate::sccp::do_sccp;
 use crate::settings::{FlagsOrIsa, OptLevel};
 use crate::simple_gvn::do_simple_gvn;
 use crate::simple_preopt::do_preopt;
@@ -240,7 +241,13 @@ impl Context {
         kind: FrameUnwindKind,
         sink: &mut dyn FrameUnwindSink,
     ) {
-        isa.emit_unwind_info(&self.func, kind, sink);
+        if let Some(ref result) = &self.mach_compile_result {
+            isa.get_mach_backend()
+                .unwrap()
+                .emit_unwind_info(result, kind, sink);
+        } else {
+            isa.emit_unwind_info(&self.func, kind, sink);
+        }
     }
 
     /// Run the verifier on the function.
@@ -300,6 +307,13 @@ impl Context {
         Ok(())
     }
 
+    /// Perform sparse conditional constant propagation on the function.
+    pub fn sccp<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CodegenResult<()> {
+        do_sccp(&mut self.func);
+        self.verify_if(fisa)?;
+        Ok(())
+    }
+
     /// Perform NaN canonicalizing rewrites on the function.
     pub fn canonicalize_nans(&mut self, isa: &dyn TargetIsa) -> CodegenResult<()> {
         do_nan_canonicalization(&mut self.func);
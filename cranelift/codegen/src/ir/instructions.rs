@@ -61,6 +61,15 @@ impl Opcode {
     pub fn constraints(self) -> OpcodeConstraints {
         OPCODE_CONSTRAINTS[self as usize - 1]
     }
+
+    /// Is this a tail call, which replaces the current function's frame with
+    /// the callee's?
+    pub fn is_tail_call(self) -> bool {
+        match self {
+            Self::ReturnCall | Self::ReturnCallIndirect => true,
+            _ => false,
+        }
+    }
 }
 
 // This trait really belongs in cranelift-reader where it is used by the `.clif` file parser, but since
@@ -24,6 +24,13 @@ pub enum TrapCode {
     /// offset-guard pages.
     HeapOutOfBounds,
 
+    /// An atomic heap access was not naturally aligned.
+    HeapMisaligned,
+
+    /// A thread tried to wait on a heap which isn't shared with any other
+    /// thread, so nothing could ever wake it.
+    HeapUnshared,
+
     /// A `table_addr` instruction detected an out-of-bounds error.
     TableOutOfBounds,
 
@@ -52,6 +59,9 @@ pub enum TrapCode {
     /// This trap is resumable.
     Interrupt,
 
+    /// Execution has consumed all of the fuel it was given.
+    OutOfFuel,
+
     /// A user-defined trap code.
     User(u16),
 }
@@ -62,6 +72,8 @@ impl Display for TrapCode {
         let identifier = match *self {
             StackOverflow => "stk_ovf",
             HeapOutOfBounds => "heap_oob",
+            HeapMisaligned => "heap_misaligned",
+            HeapUnshared => "heap_unshared",
             TableOutOfBounds => "table_oob",
             OutOfBounds => "oob",
             IndirectCallToNull => "icall_null",
@@ -71,6 +83,7 @@ impl Display for TrapCode {
             BadConversionToInteger => "bad_toint",
             UnreachableCodeReached => "unreachable",
             Interrupt => "interrupt",
+            OutOfFuel => "out_of_fuel",
             User(x) => return write!(f, "user{}", x),
         };
         f.write_str(identifier)
@@ -85,6 +98,8 @@ impl FromStr for TrapCode {
         match s {
             "stk_ovf" => Ok(StackOverflow),
             "heap_oob" => Ok(HeapOutOfBounds),
+            "heap_misaligned" => Ok(HeapMisaligned),
+            "heap_unshared" => Ok(HeapUnshared),
             "table_oob" => Ok(TableOutOfBounds),
             "oob" => Ok(OutOfBounds),
             "icall_null" => Ok(IndirectCallToNull),
@@ -94,6 +109,7 @@ impl FromStr for TrapCode {
             "bad_toint" => Ok(BadConversionToInteger),
             "unreachable" => Ok(UnreachableCodeReached),
             "interrupt" => Ok(Interrupt),
+            "out_of_fuel" => Ok(OutOfFuel),
             _ if s.starts_with("user") => s[4..].parse().map(User).map_err(|_| ()),
             _ => Err(()),
         }
@@ -106,9 +122,11 @@ mod tests {
     use alloc::string::ToString;
 
     // Everything but user-defined codes.
-    const CODES: [TrapCode; 11] = [
+    const CODES: [TrapCode; 14] = [
         TrapCode::StackOverflow,
         TrapCode::HeapOutOfBounds,
+        TrapCode::HeapMisaligned,
+        TrapCode::HeapUnshared,
         TrapCode::TableOutOfBounds,
         TrapCode::OutOfBounds,
         TrapCode::IndirectCallToNull,
@@ -118,6 +136,7 @@ mod tests {
         TrapCode::BadConversionToInteger,
         TrapCode::UnreachableCodeReached,
         TrapCode::Interrupt,
+        TrapCode::OutOfFuel,
     ];
 
     #[test]
@@ -2,6 +2,7 @@
 
 #![allow(dead_code)]
 
+use crate::binemit::Stackmap;
 use crate::ir;
 use crate::ir::types;
 use crate::ir::types::*;
@@ -96,6 +97,7 @@ fn in_int_reg(ty: types::Type) -> bool {
     match ty {
         types::I8 | types::I16 | types::I32 | types::I64 => true,
         types::B1 | types::B8 | types::B16 | types::B32 | types::B64 => true,
+        types::R32 | types::R64 => true,
         _ => false,
     }
 }
@@ -127,6 +129,60 @@ impl ARM64ABIBody {
             frame_size: None,
         }
     }
+
+    /// Generate the part of the epilogue which restores the clobbered
+    /// registers and tears down the frame, which tail calls share with
+    /// returns.
+    fn gen_frame_teardown(&self) -> Vec<Inst> {
+        let mut insts = vec![];
+
+        // Restore clobbered registers.
+        let clobbered = get_callee_saves(self.clobbered.to_vec());
+        for reg_pair in clobbered.chunks(2).rev() {
+            let (r1, r2) = if reg_pair.len() == 2 {
+                (
+                    reg_pair[0].map(|r| r.to_reg()),
+                    reg_pair[1].map(|r| r.to_reg()),
+                )
+            } else {
+                (reg_pair[0].map(|r| r.to_reg()), writable_zero_reg())
+            };
+
+            assert!(r1.to_reg().get_class() == RegClass::I64);
+            assert!(r2.to_reg().get_class() == RegClass::I64);
+
+            // ldp r1, r2, [sp], #16
+            insts.push(Inst::LoadP64 {
+                rt: r1,
+                rt2: r2,
+                mem: PairMemArg::PostIndexed(
+                    writable_stack_reg(),
+                    SImm7Scaled::maybe_from_i64(16, types::I64).unwrap(),
+                ),
+            });
+        }
+
+        // The MOV (alias of ORR) interprets x31 as XZR, so use an ADD here.
+        // MOV to SP is an alias of ADD.
+        insts.push(Inst::AluRRImm12 {
+            alu_op: ALUOp::Add64,
+            rd: writable_stack_reg(),
+            rn: fp_reg(),
+            imm12: Imm12 {
+                bits: 0,
+                shift12: false,
+            },
+        });
+        insts.push(Inst::LoadP64 {
+            rt: writable_fp_reg(),
+            rt2: writable_link_reg(),
+            mem: PairMemArg::PostIndexed(
+                writable_stack_reg(),
+                SImm7Scaled::maybe_from_i64(16, types::I64).unwrap(),
+            ),
+        });
+        insts
+    }
 }
 
 // Get a sequence of instructions and a memory argument that together
@@ -142,8 +198,8 @@ fn load_stack(fp_offset: i64, into_reg: Writable<Reg>, ty: Type) -> Inst {
     match ty {
         types::B1 | types::B8 | types::I8 => Inst::ULoad8 { rd: into_reg, mem },
         types::B16 | types::I16 => Inst::ULoad16 { rd: into_reg, mem },
-        types::B32 | types::I32 => Inst::ULoad32 { rd: into_reg, mem },
-        types::B64 | types::I64 => Inst::ULoad64 { rd: into_reg, mem },
+        types::B32 | types::I32 | types::R32 => Inst::ULoad32 { rd: into_reg, mem },
+        types::B64 | types::I64 | types::R64 => Inst::ULoad64 { rd: into_reg, mem },
         _ => unimplemented!(),
     }
 }
@@ -155,8 +211,8 @@ fn store_stack(fp_offset: i64, from_reg: Reg, ty: Type) -> Inst {
     match ty {
         types::B1 | types::B8 | types::I8 => Inst::Store8 { rd: from_reg, mem },
         types::B16 | types::I16 => Inst::Store16 { rd: from_reg, mem },
-        types::B32 | types::I32 => Inst::Store32 { rd: from_reg, mem },
-        types::B64 | types::I64 => Inst::Store64 { rd: from_reg, mem },
+        types::B32 | types::I32 | types::R32 => Inst::Store32 { rd: from_reg, mem },
+        types::B64 | types::I64 | types::R64 => Inst::Store64 { rd: from_reg, mem },
         _ => unimplemented!(),
     }
 }
@@ -195,7 +251,8 @@ fn get_callee_saves(regs: Vec<Writable<RealReg>>) -> Vec<Writable<RealReg>> {
         .collect()
 }
 
-fn get_caller_saves_set() -> Set<Writable<Reg>> {
+/// All registers that a call may clobber.
+pub(crate) fn get_caller_saves_set() -> Set<Writable<Reg>> {
     let mut set = Set::empty();
     for i in 0..28 {
         let x = writable_xreg(i);
@@ -401,63 +458,38 @@ impl ABIBody<Inst> for ARM64ABIBody {
     }
 
     fn gen_epilogue(&self) -> Vec<Inst> {
-        let mut insts = vec![];
-
-        // Restore clobbered registers.
-        let clobbered = get_callee_saves(self.clobbered.to_vec());
-        for reg_pair in clobbered.chunks(2).rev() {
-            let (r1, r2) = if reg_pair.len() == 2 {
-                (
-                    reg_pair[0].map(|r| r.to_reg()),
-                    reg_pair[1].map(|r| r.to_reg()),
-                )
-            } else {
-                (reg_pair[0].map(|r| r.to_reg()), writable_zero_reg())
-            };
-
-            assert!(r1.to_reg().get_class() == RegClass::I64);
-            assert!(r2.to_reg().get_class() == RegClass::I64);
-
-            // ldp r1, r2, [sp], #16
-            insts.push(Inst::LoadP64 {
-                rt: r1,
-                rt2: r2,
-                mem: PairMemArg::PostIndexed(
-                    writable_stack_reg(),
-                    SImm7Scaled::maybe_from_i64(16, types::I64).unwrap(),
-                ),
-            });
-        }
-
-        // The MOV (alias of ORR) interprets x31 as XZR, so use an ADD here.
-        // MOV to SP is an alias of ADD.
-        insts.push(Inst::AluRRImm12 {
-            alu_op: ALUOp::Add64,
-            rd: writable_stack_reg(),
-            rn: fp_reg(),
-            imm12: Imm12 {
-                bits: 0,
-                shift12: false,
-            },
-        });
-        insts.push(Inst::LoadP64 {
-            rt: writable_fp_reg(),
-            rt2: writable_link_reg(),
-            mem: PairMemArg::PostIndexed(
-                writable_stack_reg(),
-                SImm7Scaled::maybe_from_i64(16, types::I64).unwrap(),
-            ),
-        });
+        let mut insts = self.gen_frame_teardown();
         insts.push(Inst::Ret {});
         debug!("Epilogue: {:?}", insts);
         insts
     }
 
+    fn gen_tail_call_epilogue(&self) -> Vec<Inst> {
+        let insts = self.gen_frame_teardown();
+        debug!("Tail call epilogue: {:?}", insts);
+        insts
+    }
+
     fn frame_size(&self) -> u32 {
         self.frame_size
             .expect("frame size not computed before prologue generation") as u32
     }
 
+    fn stackmap(&self, ref_slots: &[StackSlot]) -> Stackmap {
+        // At a call, SP is at the bottom of the clobbered registers saved by
+        // the prologue, which are right below the stackslots and spillslots.
+        let num_clobbered = get_callee_saves(self.clobbered.to_vec()).len() as i64;
+        let sp_to_fp = self.frame_size() as i64 + 16 * ((num_clobbered + 1) / 2);
+        let mut words = vec![false; (sp_to_fp / 8) as usize];
+        for slot in ref_slots {
+            // Offset from beginning of stackslot area, which is at FP - stackslots_size.
+            let stack_off = self.stackslots[slot.as_u32() as usize] as i64;
+            let fp_off: i64 = -(self.stackslots_size as i64) + stack_off;
+            words[((sp_to_fp + fp_off) / 8) as usize] = true;
+        }
+        Stackmap::from_slice(&words)
+    }
+
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32 {
         // We allocate in terms of 8-byte slots.
         match (rc, ty) {
@@ -570,4 +602,15 @@ impl ABICall<Inst> for ARM64ABICall {
             },
         }
     }
+
+    fn gen_tail_call(&self) -> Inst {
+        let uses = self.uses.clone();
+        match &self.dest {
+            &CallDest::ExtName(ref name) => Inst::TailCall {
+                dest: name.clone(),
+                uses,
+            },
+            &CallDest::Reg(reg) => Inst::TailCallInd { rn: reg, uses },
+        }
+    }
 }
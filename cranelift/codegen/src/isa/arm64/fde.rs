@@ -0,0 +1,210 @@
+//! Support for FDE data generation.
+
+use crate::binemit::{FrameUnwindOffset, FrameUnwindSink, Reloc};
+use crate::machinst::MachCompileResult;
+use alloc::vec::Vec;
+use core::convert::TryInto;
+use gimli::write::{
+    Address, CallFrameInstruction, CommonInformationEntry, EhFrame, EndianVec,
+    FrameDescriptionEntry, FrameTable, Result, Writer,
+};
+use gimli::{Encoding, Format, LittleEndian, Register};
+
+/// DWARF numbers of the registers describing a frame.
+const FP: Register = Register(29);
+const LR: Register = Register(30);
+const SP: Register = Register(31);
+
+pub type FDERelocEntry = (FrameUnwindOffset, Reloc);
+
+const FUNCTION_ENTRY_ADDRESS: Address = Address::Symbol {
+    symbol: 0,
+    addend: 0,
+};
+
+#[derive(Clone)]
+struct FDEWriter {
+    vec: EndianVec<LittleEndian>,
+    relocs: Vec<FDERelocEntry>,
+}
+
+impl FDEWriter {
+    fn new() -> Self {
+        Self {
+            vec: EndianVec::new(LittleEndian),
+            relocs: Vec::new(),
+        }
+    }
+    fn into_vec_and_relocs(self) -> (Vec<u8>, Vec<FDERelocEntry>) {
+        (self.vec.into_vec(), self.relocs)
+    }
+}
+
+impl Writer for FDEWriter {
+    type Endian = LittleEndian;
+    fn endian(&self) -> Self::Endian {
+        LittleEndian
+    }
+    fn len(&self) -> usize {
+        self.vec.len()
+    }
+    fn write(&mut self, bytes: &[u8]) -> Result<()> {
+        self.vec.write(bytes)
+    }
+    fn write_at(&mut self, offset: usize, bytes: &[u8]) -> Result<()> {
+        self.vec.write_at(offset, bytes)
+    }
+    fn write_address(&mut self, address: Address, size: u8) -> Result<()> {
+        match address {
+            Address::Constant(_) => self.vec.write_address(address, size),
+            Address::Symbol { .. } => {
+                assert_eq!(address, FUNCTION_ENTRY_ADDRESS);
+                let rt = match size {
+                    4 => Reloc::Abs4,
+                    8 => Reloc::Abs8,
+                    _ => {
+                        panic!("Unexpected address size at FDEWriter::write_address");
+                    }
+                };
+                self.relocs.push((self.vec.len().try_into().unwrap(), rt));
+                self.vec.write_udata(0, size)
+            }
+        }
+    }
+}
+
+/// Creates FDE structure for a function compiled by the ARM64 backend.
+///
+/// Every function starts with the same two instructions:
+///
+/// ```text
+///   stp fp, lr, [sp, #-16]!
+///   mov fp, sp
+/// ```
+///
+/// after which the frame is described by the frame pointer alone, as the
+/// rest of the frame is allocated below it. This holds until the epilogue
+/// restores the caller's frame pointer, which only leaves the final `ret` (or
+/// tail call branch) undescribed; that's fine for walking the stack from call
+/// sites, which is what this information is used for. Callee-saved registers
+/// other than the frame pointer aren't described.
+pub fn emit_fde(result: &MachCompileResult, sink: &mut dyn FrameUnwindSink) {
+    let len = result.sections.total_size();
+
+    let encoding = Encoding {
+        format: Format::Dwarf32,
+        version: 1,
+        address_size: 8,
+    };
+    let mut frames = FrameTable::default();
+
+    let mut cie = CommonInformationEntry::new(
+        encoding, /* code_alignment_factor = */ 4, /* data_alignment_factor = */ -8, LR,
+    );
+    cie.add_instruction(CallFrameInstruction::Cfa(SP, 0));
+
+    let cie_id = frames.add_cie(cie);
+
+    let mut fde = FrameDescriptionEntry::new(FUNCTION_ENTRY_ADDRESS, len);
+
+    // After `stp fp, lr, [sp, #-16]!`.
+    fde.add_instruction(4, CallFrameInstruction::CfaOffset(16));
+    fde.add_instruction(4, CallFrameInstruction::Offset(FP, -16));
+    fde.add_instruction(4, CallFrameInstruction::Offset(LR, -8));
+    // After `mov fp, sp`.
+    fde.add_instruction(8, CallFrameInstruction::CfaRegister(FP));
+
+    frames.add_fde(cie_id, fde);
+
+    let mut eh_frame = EhFrame::from(FDEWriter::new());
+    frames.write_eh_frame(&mut eh_frame).unwrap();
+
+    let (bytes, relocs) = eh_frame.clone().into_vec_and_relocs();
+
+    let unwind_start = sink.len();
+    sink.bytes(&bytes);
+
+    for (off, r) in relocs {
+        sink.reloc(r, off + unwind_start);
+    }
+
+    let cie_len = u32::from_le_bytes(bytes.as_slice()[..4].try_into().unwrap());
+    let fde_offset = cie_len as usize + 4;
+    sink.set_entry_offset(unwind_start + fde_offset);
+
+    // Need 0 marker for GCC unwind to end FDE "list".
+    sink.bytes(&[0, 0, 0, 0]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::binemit::{FrameUnwindOffset, Reloc};
+    use crate::cursor::{Cursor, FuncCursor};
+    use crate::ir::{ExternalName, Function, InstBuilder, Signature};
+    use crate::isa::arm64::Arm64Backend;
+    use crate::isa::CallConv;
+    use crate::machinst::MachBackend;
+
+    struct SimpleUnwindSink(pub Vec<u8>, pub usize, pub Vec<(Reloc, usize)>);
+    impl FrameUnwindSink for SimpleUnwindSink {
+        fn len(&self) -> FrameUnwindOffset {
+            self.0.len()
+        }
+        fn bytes(&mut self, b: &[u8]) {
+            self.0.extend_from_slice(b);
+        }
+        fn reloc(&mut self, r: Reloc, off: FrameUnwindOffset) {
+            self.2.push((r, off));
+        }
+        fn set_entry_offset(&mut self, off: FrameUnwindOffset) {
+            self.1 = off;
+        }
+    }
+
+    #[test]
+    fn test_simple_func() {
+        let mut func = Function::with_name_signature(
+            ExternalName::user(0, 0),
+            Signature::new(CallConv::SystemV),
+        );
+        let block0 = func.dfg.make_block();
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(block0);
+        pos.ins().return_(&[]);
+
+        let result = Arm64Backend::new().compile_function(func, false).unwrap();
+
+        let mut sink = SimpleUnwindSink(Vec::new(), 0, Vec::new());
+        emit_fde(&result, &mut sink);
+
+        let len = result.sections.total_size() as u8;
+        assert_eq!(
+            sink.0,
+            vec![
+                12, 0, 0, 0, // CIE len
+                0, 0, 0, 0,   // CIE marker
+                1,   // version
+                0,   // augmentation string
+                4,   // code aligment = 4
+                120, // data alignment = -8
+                30,  // RA = x30
+                0x0c, 0x1f, 0x00, // DW_CFA_def_cfa sp, 0
+                36, 0, 0, 0, // FDE len
+                20, 0, 0, 0, // CIE offset
+                0, 0, 0, 0, 0, 0, 0, 0, // addr reloc
+                len, 0, 0, 0, 0, 0, 0, 0,    // function length
+                0x41, // DW_CFA_advance_loc 1
+                0x0e, 0x10, // DW_CFA_def_cfa_offset 16
+                0x9d, 0x02, // DW_CFA_offset x29, -8 * 2
+                0x9e, 0x01, // DW_CFA_offset x30, -8 * 1
+                0x41, // DW_CFA_advance_loc 1
+                0x0d, 0x1d, // DW_CFA_def_cfa_register x29
+                0, 0, 0, 0, 0, 0, // padding
+                0, 0, 0, 0, // End of FDEs
+            ]
+        );
+        assert_eq!(sink.1, 16);
+        assert_eq!(sink.2.len(), 1);
+    }
+}
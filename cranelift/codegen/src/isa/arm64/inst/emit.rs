@@ -6,7 +6,7 @@
 use crate::binemit::{CodeOffset, CodeSink, Reloc};
 use crate::ir::constant::ConstantData;
 use crate::ir::types::*;
-use crate::ir::Type;
+use crate::ir::{AtomicRmwOp, ExternalName, LibCall, Type};
 use crate::isa::arm64::inst::*;
 use crate::machinst::*;
 use cranelift_entity::EntityRef;
@@ -274,6 +274,14 @@ fn enc_adr(off: i32, rd: Writable<Reg>) -> u32 {
     (0b00010000 << 24) | (immlo << 29) | (immhi << 5) | machreg_to_gpr(rd.to_reg())
 }
 
+fn enc_adrp(rd: Writable<Reg>) -> u32 {
+    0b1_00_10000_0000000000000000000_00000 | machreg_to_gpr(rd.to_reg())
+}
+
+fn enc_mrs_tpidr_el0(rd: Writable<Reg>) -> u32 {
+    0xd53bd040 | machreg_to_gpr(rd.to_reg())
+}
+
 fn enc_csel(rd: Writable<Reg>, rn: Reg, rm: Reg, cond: Cond) -> u32 {
     0b100_11010100_00000_0000_00_00000_00000
         | (machreg_to_gpr(rm) << 16)
@@ -288,6 +296,51 @@ fn enc_cset(rd: Writable<Reg>, cond: Cond) -> u32 {
         | (cond.invert().bits() << 12)
 }
 
+/// The `size` field (bits 31:30) of an exclusive or acquire/release access.
+fn enc_ldst_size(ty: Type) -> u32 {
+    match ty {
+        I8 => 0b00,
+        I16 => 0b01,
+        I32 => 0b10,
+        I64 => 0b11,
+        _ => panic!("Unsupported type for an atomic access: {}", ty),
+    }
+}
+
+fn enc_ldaxr(ty: Type, rt: Writable<Reg>, rn: Reg) -> u32 {
+    0b00_001000_0_1_0_11111_1_11111_00000_00000
+        | (enc_ldst_size(ty) << 30)
+        | (machreg_to_gpr(rn) << 5)
+        | machreg_to_gpr(rt.to_reg())
+}
+
+fn enc_stlxr(ty: Type, rs: Writable<Reg>, rt: Reg, rn: Reg) -> u32 {
+    0b00_001000_0_0_0_00000_1_11111_00000_00000
+        | (enc_ldst_size(ty) << 30)
+        | (machreg_to_gpr(rs.to_reg()) << 16)
+        | (machreg_to_gpr(rn) << 5)
+        | machreg_to_gpr(rt)
+}
+
+fn enc_ldar(ty: Type, rt: Writable<Reg>, rn: Reg) -> u32 {
+    0b00_001000_1_1_0_11111_1_11111_00000_00000
+        | (enc_ldst_size(ty) << 30)
+        | (machreg_to_gpr(rn) << 5)
+        | machreg_to_gpr(rt.to_reg())
+}
+
+fn enc_stlr(ty: Type, rt: Reg, rn: Reg) -> u32 {
+    0b00_001000_1_0_0_11111_1_11111_00000_00000
+        | (enc_ldst_size(ty) << 30)
+        | (machreg_to_gpr(rn) << 5)
+        | machreg_to_gpr(rt)
+}
+
+/// Encode a 32-bit `CBNZ` with a backwards offset of `insns` instructions.
+fn enc_cbnz32_back(insns: u32, reg: Reg) -> u32 {
+    enc_cmpbr(0b0_011010_1, insns.wrapping_neg() & ((1 << 19) - 1), reg)
+}
+
 impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
     fn emit(&self, sink: &mut O, consts: &mut O, jt_offsets: &[CodeOffset]) {
         match self {
@@ -793,6 +846,13 @@ impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
             &Inst::CallInd { rn, .. } => {
                 sink.put4(0b1101011_0001_11111_000000_00000_00000 | (machreg_to_gpr(rn) << 5));
             }
+            &Inst::TailCall { ref dest, .. } => {
+                sink.add_reloc(Reloc::Arm64Call, dest, 0);
+                sink.put4(enc_jump26(0b000101, 0));
+            }
+            &Inst::TailCallInd { rn, .. } => {
+                sink.put4(enc_br(rn));
+            }
             &Inst::CondBr { .. } => panic!("Unlowered CondBr during binemit!"),
             &Inst::CondBrLowered { target, kind } => match kind {
                 CondBrKind::Zero(reg) => {
@@ -856,6 +916,130 @@ impl<O: MachSectionOutput> MachInstEmit<O> for Inst {
                 assert!(off < (1 << 20));
                 sink.put4(enc_adr(off, rd));
             }
+            &Inst::AtomicRMW { ty, op } => {
+                let x24 = writable_xreg(24);
+                let x25 = xreg(25);
+                let x26 = xreg(26);
+                let x27 = writable_xreg(27);
+                let x28 = writable_xreg(28);
+                // again:
+                //   ldaxr{b,h} x27, [x25]
+                //   <op> x28, x27, x26        (not for xchg)
+                //   stlxr{b,h} w24, x28, [x25] (x26 for xchg)
+                //   cbnz w24, again
+                sink.put4(enc_ldaxr(ty, x27, x25));
+                let alu_op = match op {
+                    AtomicRmwOp::Add => Some(ALUOp::Add64),
+                    AtomicRmwOp::Sub => Some(ALUOp::Sub64),
+                    AtomicRmwOp::And => Some(ALUOp::And64),
+                    AtomicRmwOp::Or => Some(ALUOp::Orr64),
+                    AtomicRmwOp::Xor => Some(ALUOp::Eor64),
+                    AtomicRmwOp::Xchg => None,
+                };
+                match alu_op {
+                    Some(alu_op) => {
+                        Inst::AluRRR {
+                            alu_op,
+                            rd: x28,
+                            rn: x27.to_reg(),
+                            rm: x26,
+                        }
+                        .emit(sink, consts, jt_offsets);
+                        sink.put4(enc_stlxr(ty, x24, x28.to_reg(), x25));
+                        sink.put4(enc_cbnz32_back(3, x24.to_reg()));
+                    }
+                    None => {
+                        sink.put4(enc_stlxr(ty, x24, x26, x25));
+                        sink.put4(enc_cbnz32_back(2, x24.to_reg()));
+                    }
+                }
+            }
+            &Inst::AtomicCAS { ty } => {
+                let x24 = writable_xreg(24);
+                let x25 = xreg(25);
+                let x26 = xreg(26);
+                let x27 = writable_xreg(27);
+                let x28 = xreg(28);
+                // again:
+                //   ldaxr{b,h} x27, [x25]
+                //   cmp x27, x26
+                //   b.ne out
+                //   stlxr{b,h} w24, x28, [x25]
+                //   cbnz w24, again
+                // out:
+                sink.put4(enc_ldaxr(ty, x27, x25));
+                Inst::AluRRR {
+                    alu_op: ALUOp::SubS64,
+                    rd: writable_zero_reg(),
+                    rn: x27.to_reg(),
+                    rm: x26,
+                }
+                .emit(sink, consts, jt_offsets);
+                sink.put4(enc_cbr(0b01010100, 3, 0b0, Cond::Ne.bits()));
+                sink.put4(enc_stlxr(ty, x24, x28, x25));
+                sink.put4(enc_cbnz32_back(4, x24.to_reg()));
+            }
+            &Inst::LoadAcquire { ty, rt, rn } => {
+                sink.put4(enc_ldar(ty, rt, rn));
+            }
+            &Inst::StoreRelease { ty, rt, rn } => {
+                sink.put4(enc_stlr(ty, rt, rn));
+            }
+            &Inst::Fence => {
+                sink.put4(0xd5033bbf);
+            }
+            &Inst::ElfTlsGetAddr { ref symbol } => {
+                let x0 = writable_xreg(0);
+                // adrp x0, :tlsgd:symbol
+                sink.add_reloc(Reloc::Arm64TlsGdAdrPage21, symbol, 0);
+                sink.put4(enc_adrp(x0));
+                // add x0, x0, :tlsgd_lo12:symbol
+                sink.add_reloc(Reloc::Arm64TlsGdAddLo12Nc, symbol, 0);
+                sink.put4(enc_arith_rr_imm12(0b100_10001, 0b00, 0, x0.to_reg(), x0));
+                // bl __tls_get_addr
+                let tls_get_addr = ExternalName::LibCall(LibCall::ElfTlsGetAddr);
+                sink.add_reloc(Reloc::Arm64Call, &tls_get_addr, 0);
+                sink.put4(enc_jump26(0b100101, 0));
+                // The linker may relax the sequence above into one that needs
+                // an extra instruction slot, so always reserve it.
+                sink.put4(0xd503201f);
+            }
+            &Inst::ElfTlsLocalExec { rd, ref symbol } => {
+                // mrs rd, tpidr_el0
+                sink.put4(enc_mrs_tpidr_el0(rd));
+                // add rd, rd, :tprel_hi12:symbol, lsl #12
+                sink.add_reloc(Reloc::Arm64TlsLeAddTprelHi12, symbol, 0);
+                sink.put4(enc_arith_rr_imm12(0b100_10001, 0b01, 0, rd.to_reg(), rd));
+                // add rd, rd, :tprel_lo12_nc:symbol
+                sink.add_reloc(Reloc::Arm64TlsLeAddTprelLo12Nc, symbol, 0);
+                sink.put4(enc_arith_rr_imm12(0b100_10001, 0b00, 0, rd.to_reg(), rd));
+            }
+            &Inst::MachOTlsGetAddr { ref symbol } => {
+                let x0 = writable_xreg(0);
+                let x1 = writable_xreg(1);
+                // adrp x0, symbol@TLVPPAGE
+                sink.add_reloc(Reloc::MachOAarch64TlsAdrPage21, symbol, 0);
+                sink.put4(enc_adrp(x0));
+                // ldr x0, [x0, symbol@TLVPPAGEOFF]
+                sink.add_reloc(Reloc::MachOAarch64TlsAdrPageOff12, symbol, 0);
+                sink.put4(enc_ldst_uimm12(
+                    0b1111100001,
+                    UImm12Scaled::zero(I64),
+                    x0.to_reg(),
+                    x0.to_reg(),
+                ));
+                // ldr x1, [x0]
+                sink.put4(enc_ldst_uimm12(
+                    0b1111100001,
+                    UImm12Scaled::zero(I64),
+                    x0.to_reg(),
+                    x1.to_reg(),
+                ));
+                // blr x1
+                sink.put4(
+                    0b1101011_0001_11111_000000_00000_00000 | (machreg_to_gpr(x1.to_reg()) << 5),
+                );
+            }
         }
     }
 }
@@ -2535,6 +2719,24 @@ mod test {
             "blr x10",
         ));
 
+        insns.push((
+            Inst::TailCall {
+                dest: ExternalName::testcase("test0"),
+                uses: Set::empty(),
+            },
+            "00000014",
+            "b 0",
+        ));
+
+        insns.push((
+            Inst::TailCallInd {
+                rn: xreg(16),
+                uses: Set::empty(),
+            },
+            "00021FD6",
+            "br x16",
+        ));
+
         insns.push((
             Inst::IndirectBr {
                 rn: xreg(3),
@@ -2546,6 +2748,107 @@ mod test {
 
         insns.push((Inst::Brk { trap_info: None }, "000020D4", "brk #0"));
 
+        insns.push((
+            Inst::AtomicRMW {
+                ty: I64,
+                op: AtomicRmwOp::Add,
+            },
+            "3BFF5FC87C031A8B3CFF18C8B8FFFF35",
+            "1: ldaxr x27, [x25] ; add x28, x27, x26 ; stlxr w24, x28, [x25] ; cbnz w24, 1b",
+        ));
+        insns.push((
+            Inst::AtomicRMW {
+                ty: I16,
+                op: AtomicRmwOp::Xor,
+            },
+            "3BFF5F487C031ACA3CFF1848B8FFFF35",
+            "1: ldaxrh w27, [x25] ; eor x28, x27, x26 ; stlxrh w24, w28, [x25] ; cbnz w24, 1b",
+        ));
+        insns.push((
+            Inst::AtomicRMW {
+                ty: I8,
+                op: AtomicRmwOp::Xchg,
+            },
+            "3BFF5F083AFF1808D8FFFF35",
+            "1: ldaxrb w27, [x25] ; stlxrb w24, w26, [x25] ; cbnz w24, 1b",
+        ));
+        insns.push((
+            Inst::AtomicCAS { ty: I32 },
+            "3BFF5F887F031AEB610000543CFF188898FFFF35",
+            "1: ldaxr w27, [x25] ; cmp x27, x26 ; b.ne 2f ; stlxr w24, w28, [x25] ; cbnz w24, 1b ; 2:",
+        ));
+        insns.push((
+            Inst::LoadAcquire {
+                ty: I32,
+                rt: writable_xreg(1),
+                rn: xreg(2),
+            },
+            "41FCDF88",
+            "ldar w1, [x2]",
+        ));
+        insns.push((
+            Inst::LoadAcquire {
+                ty: I8,
+                rt: writable_xreg(1),
+                rn: xreg(2),
+            },
+            "41FCDF08",
+            "ldarb w1, [x2]",
+        ));
+        insns.push((
+            Inst::LoadAcquire {
+                ty: I64,
+                rt: writable_xreg(1),
+                rn: xreg(2),
+            },
+            "41FCDFC8",
+            "ldar x1, [x2]",
+        ));
+        insns.push((
+            Inst::StoreRelease {
+                ty: I16,
+                rt: xreg(3),
+                rn: xreg(4),
+            },
+            "83FC9F48",
+            "stlrh w3, [x4]",
+        ));
+        insns.push((
+            Inst::StoreRelease {
+                ty: I64,
+                rt: xreg(3),
+                rn: xreg(4),
+            },
+            "83FC9FC8",
+            "stlr x3, [x4]",
+        ));
+        insns.push((Inst::Fence, "BF3B03D5", "dmb ish"));
+
+        insns.push((
+            Inst::ElfTlsGetAddr {
+                symbol: ExternalName::testcase("tls0"),
+            },
+            "0000009000000091000000941F2003D5",
+            "adrp x0, :tlsgd:%tls0 ; add x0, x0, :tlsgd_lo12:%tls0 ; bl %ElfTlsGetAddr ; nop",
+        ));
+
+        insns.push((
+            Inst::ElfTlsLocalExec {
+                rd: writable_xreg(5),
+                symbol: ExternalName::testcase("tls0"),
+            },
+            "45D03BD5A5004091A5000091",
+            "mrs x5, tpidr_el0 ; add x5, x5, :tprel_hi12:%tls0, lsl #12 ; add x5, x5, :tprel_lo12_nc:%tls0",
+        ));
+
+        insns.push((
+            Inst::MachOTlsGetAddr {
+                symbol: ExternalName::testcase("tls0"),
+            },
+            "00000090000040F9010040F920003FD6",
+            "adrp x0, %tls0@TLVPPAGE ; ldr x0, [x0, %tls0@TLVPPAGEOFF] ; ldr x1, [x0] ; blr x1",
+        ));
+
         insns.push((
             Inst::Adr {
                 rd: writable_xreg(15),
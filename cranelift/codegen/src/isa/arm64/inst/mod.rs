@@ -8,9 +8,12 @@
 use crate::binemit::CodeOffset;
 use crate::ir::constant::{ConstantData, ConstantOffset};
 use crate::ir::types::{
-    B1, B128, B16, B32, B64, B8, F32, F64, FFLAGS, I128, I16, I32, I64, I8, IFLAGS,
+    B1, B128, B16, B32, B64, B8, F32, F64, FFLAGS, I128, I16, I32, I64, I8, IFLAGS, R32, R64,
 };
-use crate::ir::{ExternalName, GlobalValue, JumpTable, Opcode, SourceLoc, TrapCode, Type};
+use crate::ir::{
+    AtomicRmwOp, ExternalName, GlobalValue, JumpTable, LibCall, Opcode, SourceLoc, TrapCode, Type,
+};
+use crate::isa::arm64::abi::get_caller_saves_set;
 use crate::machinst::*;
 
 use regalloc::Map as RegallocMap;
@@ -305,6 +308,32 @@ pub enum Inst {
     /// Set a register to 1 if condition, else 0.
     CondSet { rd: Writable<Reg>, cond: Cond },
 
+    /// An atomic read-modify-write of the 8-, 16-, 32- or 64-bit value at the
+    /// address in x25, with the operand in x26. This is a load-exclusive /
+    /// store-exclusive loop that leaves the old value in x27 and clobbers x24
+    /// and x28; the fixed registers keep the allocator from reusing an input
+    /// register for an output inside the loop.
+    AtomicRMW { ty: Type, op: AtomicRmwOp },
+
+    /// An atomic compare-and-swap of the value at the address in x25: if it is
+    /// equal to x26 (zero-extended), it is replaced by x28. Like `AtomicRMW`,
+    /// this is a load-exclusive / store-exclusive loop that leaves the old
+    /// value in x27 and clobbers x24.
+    AtomicCAS { ty: Type },
+
+    /// A load-acquire (`LDAR`) from the address in `rn`, zero-extended.
+    LoadAcquire {
+        ty: Type,
+        rt: Writable<Reg>,
+        rn: Reg,
+    },
+
+    /// A store-release (`STLR`) to the address in `rn`.
+    StoreRelease { ty: Type, rt: Reg, rn: Reg },
+
+    /// A full memory barrier (`DMB ISH`).
+    Fence,
+
     /// A machine call instruction.
     Call {
         dest: ExternalName,
@@ -317,6 +346,11 @@ pub enum Inst {
         uses: Set<Reg>,
         defs: Set<Writable<Reg>>,
     },
+    /// A machine tail-call instruction. The epilogue is inserted before it.
+    TailCall { dest: ExternalName, uses: Set<Reg> },
+    /// A machine indirect tail-call instruction. The epilogue is inserted
+    /// before it, so `rn` must be a caller-saved register.
+    TailCallInd { rn: Reg, uses: Set<Reg> },
 
     // ---- branches (exactly one must appear at end of BB) ----
     /// A machine return instruction.
@@ -365,6 +399,24 @@ pub enum Inst {
     /// Load the address (using a PC-relative offset) of a MemLabel, using the
     /// `ADR` instruction.
     Adr { rd: Writable<Reg>, label: MemLabel },
+
+    /// Compute the address of a thread-local symbol with the ELF general-dynamic
+    /// TLS model: `adrp` / `add` of the symbol's GOT entries into x0, followed by
+    /// a call to `__tls_get_addr`. The result is in x0, and all caller-saved
+    /// registers are clobbered.
+    ElfTlsGetAddr { symbol: ExternalName },
+
+    /// Compute the address of a thread-local symbol with the ELF local-exec TLS
+    /// model: the thread pointer plus a link-time constant offset.
+    ElfTlsLocalExec {
+        rd: Writable<Reg>,
+        symbol: ExternalName,
+    },
+
+    /// Compute the address of a thread-local symbol on Mach-O: load the
+    /// symbol's `__thread_vars` entry into x0 and call the accessor it holds.
+    /// The result is in x0, and all caller-saved registers are clobbered.
+    MachOTlsGetAddr { symbol: ExternalName },
 }
 
 impl Inst {
@@ -556,6 +608,13 @@ fn arm64_get_regs(inst: &Inst) -> InstRegUses {
             iru.defined.union(defs);
             iru.used.insert(rn);
         }
+        &Inst::TailCall { ref uses, .. } => {
+            iru.used.union(uses);
+        }
+        &Inst::TailCallInd { ref uses, rn } => {
+            iru.used.union(uses);
+            iru.used.insert(rn);
+        }
         &Inst::CondBr { ref kind, .. }
         | &Inst::CondBrLowered { ref kind, .. }
         | &Inst::CondBrLoweredCompound { ref kind, .. } => match kind {
@@ -568,10 +627,39 @@ fn arm64_get_regs(inst: &Inst) -> InstRegUses {
             iru.used.insert(rn);
         }
         &Inst::Nop | Inst::Nop4 => {}
+        &Inst::AtomicRMW { .. } => {
+            iru.used.insert(xreg(25));
+            iru.used.insert(xreg(26));
+            iru.defined.insert(writable_xreg(24));
+            iru.defined.insert(writable_xreg(27));
+            iru.defined.insert(writable_xreg(28));
+        }
+        &Inst::AtomicCAS { .. } => {
+            iru.used.insert(xreg(25));
+            iru.used.insert(xreg(26));
+            iru.used.insert(xreg(28));
+            iru.defined.insert(writable_xreg(24));
+            iru.defined.insert(writable_xreg(27));
+        }
+        &Inst::LoadAcquire { rt, rn, .. } => {
+            iru.defined.insert(rt);
+            iru.used.insert(rn);
+        }
+        &Inst::StoreRelease { rt, rn, .. } => {
+            iru.used.insert(rt);
+            iru.used.insert(rn);
+        }
+        &Inst::Fence => {}
         &Inst::Brk { .. } => {}
         &Inst::Adr { rd, .. } => {
             iru.defined.insert(rd);
         }
+        &Inst::ElfTlsGetAddr { .. } | &Inst::MachOTlsGetAddr { .. } => {
+            iru.defined.union(&get_caller_saves_set());
+        }
+        &Inst::ElfTlsLocalExec { rd, .. } => {
+            iru.defined.insert(rd);
+        }
     }
 
     // Enforce the invariant that if a register is in the 'modify' set, it
@@ -864,6 +952,18 @@ fn arm64_map_regs(
                 rn: map(u, rn),
             }
         }
+        &mut Inst::TailCall { ref dest, ref uses } => {
+            let uses = uses.map(|r| map(u, *r));
+            let dest = dest.clone();
+            Inst::TailCall { dest, uses }
+        }
+        &mut Inst::TailCallInd { rn, ref uses } => {
+            let uses = uses.map(|r| map(u, *r));
+            Inst::TailCallInd {
+                rn: map(u, rn),
+                uses,
+            }
+        }
         &mut Inst::CondBr {
             taken,
             not_taken,
@@ -892,11 +992,34 @@ fn arm64_map_regs(
         },
         &mut Inst::Nop => Inst::Nop,
         &mut Inst::Nop4 => Inst::Nop4,
+        &mut Inst::AtomicRMW { ty, op } => Inst::AtomicRMW { ty, op },
+        &mut Inst::AtomicCAS { ty } => Inst::AtomicCAS { ty },
+        &mut Inst::LoadAcquire { ty, rt, rn } => Inst::LoadAcquire {
+            ty,
+            rt: map_wr(d, rt),
+            rn: map(u, rn),
+        },
+        &mut Inst::StoreRelease { ty, rt, rn } => Inst::StoreRelease {
+            ty,
+            rt: map(u, rt),
+            rn: map(u, rn),
+        },
+        &mut Inst::Fence => Inst::Fence,
         &mut Inst::Brk { trap_info } => Inst::Brk { trap_info },
         &mut Inst::Adr { rd, ref label } => Inst::Adr {
             rd: map_wr(d, rd),
             label: label.clone(),
         },
+        &mut Inst::ElfTlsGetAddr { ref symbol } => Inst::ElfTlsGetAddr {
+            symbol: symbol.clone(),
+        },
+        &mut Inst::ElfTlsLocalExec { rd, ref symbol } => Inst::ElfTlsLocalExec {
+            rd: map_wr(d, rd),
+            symbol: symbol.clone(),
+        },
+        &mut Inst::MachOTlsGetAddr { ref symbol } => Inst::MachOTlsGetAddr {
+            symbol: symbol.clone(),
+        },
     };
     *inst = newval;
 }
@@ -927,6 +1050,7 @@ impl MachInst for Inst {
     fn is_term<'a>(&'a self) -> MachTerminator<'a> {
         match self {
             &Inst::Ret {} => MachTerminator::Ret,
+            &Inst::TailCall { .. } | &Inst::TailCallInd { .. } => MachTerminator::TailCall,
             &Inst::Jump { dest } => MachTerminator::Uncond(dest.as_block_index().unwrap()),
             &Inst::CondBr {
                 taken, not_taken, ..
@@ -949,6 +1073,13 @@ impl MachInst for Inst {
         }
     }
 
+    fn is_safepoint(&self) -> bool {
+        match self {
+            &Inst::Call { .. } | &Inst::CallInd { .. } => true,
+            _ => false,
+        }
+    }
+
     fn gen_move(to_reg: Writable<Reg>, from_reg: Reg) -> Inst {
         Inst::mov(to_reg, from_reg)
     }
@@ -966,6 +1097,7 @@ impl MachInst for Inst {
     fn rc_for_type(ty: Type) -> RegClass {
         match ty {
             I8 | I16 | I32 | I64 | B1 | B8 | B16 | B32 | B64 => RegClass::I64,
+            R32 | R64 => RegClass::I64,
             F32 | F64 => RegClass::V128,
             I128 | B128 => RegClass::V128,
             IFLAGS | FFLAGS => RegClass::I64,
@@ -1066,6 +1198,17 @@ impl MachInst for Inst {
 //=============================================================================
 // Pretty-printing of instructions.
 
+/// The mnemonic suffix of an exclusive or acquire/release access of the given
+/// type: `b`, `h`, or nothing for the 32- and 64-bit forms.
+fn atomic_size_suffix(ty: Type) -> &'static str {
+    match ty {
+        I8 => "b",
+        I16 => "h",
+        I32 | I64 => "",
+        _ => panic!("Unsupported type for an atomic access: {}", ty),
+    }
+}
+
 fn mem_finalize_for_show<O: MachSectionOutput>(
     mem: &MemArg,
     mb_rru: Option<&RealRegUniverse>,
@@ -1453,6 +1596,11 @@ impl Inst {
                 let rn = rn.show_rru(mb_rru);
                 format!("blr {}", rn)
             }
+            &Inst::TailCall { dest: _, .. } => format!("b 0"),
+            &Inst::TailCallInd { rn, .. } => {
+                let rn = rn.show_rru(mb_rru);
+                format!("br {}", rn)
+            }
             &Inst::Ret {} => "ret".to_string(),
             &Inst::EpiloguePlaceholder {} => "epilogue placeholder".to_string(),
             &Inst::Jump { ref dest } => {
@@ -1519,12 +1667,72 @@ impl Inst {
                 let rn = rn.show_rru(mb_rru);
                 format!("br {}", rn)
             }
+            &Inst::AtomicRMW { ty, op } => {
+                let sz = atomic_size_suffix(ty);
+                let r = if ty == I64 { "x" } else { "w" };
+                let alu_op = match op {
+                    AtomicRmwOp::Add => "add",
+                    AtomicRmwOp::Sub => "sub",
+                    AtomicRmwOp::And => "and",
+                    AtomicRmwOp::Or => "orr",
+                    AtomicRmwOp::Xor => "eor",
+                    AtomicRmwOp::Xchg => {
+                        return format!(
+                            "1: ldaxr{} {}27, [x25] ; stlxr{} w24, {}26, [x25] ; cbnz w24, 1b",
+                            sz, r, sz, r
+                        );
+                    }
+                };
+                format!(
+                    "1: ldaxr{} {}27, [x25] ; {} x28, x27, x26 ; stlxr{} w24, {}28, [x25] ; cbnz w24, 1b",
+                    sz, r, alu_op, sz, r
+                )
+            }
+            &Inst::AtomicCAS { ty } => {
+                let sz = atomic_size_suffix(ty);
+                let r = if ty == I64 { "x" } else { "w" };
+                format!(
+                    "1: ldaxr{} {}27, [x25] ; cmp x27, x26 ; b.ne 2f ; stlxr{} w24, {}28, [x25] ; cbnz w24, 1b ; 2:",
+                    sz, r, sz, r
+                )
+            }
+            &Inst::LoadAcquire { ty, rt, rn } => {
+                let sz = atomic_size_suffix(ty);
+                let rt = show_ireg_sized(rt.to_reg(), mb_rru, ty != I64);
+                let rn = rn.show_rru(mb_rru);
+                format!("ldar{} {}, [{}]", sz, rt, rn)
+            }
+            &Inst::StoreRelease { ty, rt, rn } => {
+                let sz = atomic_size_suffix(ty);
+                let rt = show_ireg_sized(rt, mb_rru, ty != I64);
+                let rn = rn.show_rru(mb_rru);
+                format!("stlr{} {}, [{}]", sz, rt, rn)
+            }
+            &Inst::Fence => "dmb ish".to_string(),
             &Inst::Brk { .. } => "brk #0".to_string(),
             &Inst::Adr { rd, ref label } => {
                 let rd = rd.show_rru(mb_rru);
                 let label = label.show_rru(mb_rru);
                 format!("adr {}, {}", rd, label)
             }
+            &Inst::ElfTlsGetAddr { ref symbol } => {
+                let tls_get_addr = ExternalName::LibCall(LibCall::ElfTlsGetAddr);
+                format!(
+                    "adrp x0, :tlsgd:{} ; add x0, x0, :tlsgd_lo12:{} ; bl {} ; nop",
+                    symbol, symbol, tls_get_addr
+                )
+            }
+            &Inst::ElfTlsLocalExec { rd, ref symbol } => {
+                let rd = rd.show_rru(mb_rru);
+                format!(
+                    "mrs {}, tpidr_el0 ; add {}, {}, :tprel_hi12:{}, lsl #12 ; add {}, {}, :tprel_lo12_nc:{}",
+                    rd, rd, rd, symbol, rd, rd, symbol
+                )
+            }
+            &Inst::MachOTlsGetAddr { ref symbol } => format!(
+                "adrp x0, {}@TLVPPAGE ; ldr x0, [x0, {}@TLVPPAGEOFF] ; ldr x1, [x0] ; blr x1",
+                symbol, symbol
+            ),
         }
     }
 }
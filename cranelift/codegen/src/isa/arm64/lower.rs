@@ -8,6 +8,8 @@ use crate::ir::Inst as IRInst;
 use crate::ir::{Block, InstructionData, Opcode, SourceLoc, TrapCode, Type};
 use crate::machinst::lower::*;
 use crate::machinst::*;
+use crate::result::{CodegenError, CodegenResult};
+use crate::settings::{Flags, TlsModel};
 
 use crate::isa::arm64::abi::*;
 use crate::isa::arm64::inst::*;
@@ -668,7 +670,11 @@ pub fn condcode_is_signed(cc: IntCC) -> bool {
 // Top-level instruction lowering entry point, for one instruction.
 
 /// Actually codegen an instruction's results into registers.
-fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
+fn lower_insn_to_regs<C: LowerCtx<Inst>>(
+    ctx: &mut C,
+    insn: IRInst,
+    flags: &Flags,
+) -> CodegenResult<()> {
     let op = ctx.data(insn).opcode();
     let inputs: SmallVec<[InsnInput; 4]> = (0..ctx.num_inputs(insn))
         .map(|i| InsnInput { insn, input: i })
@@ -1268,14 +1274,26 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             });
         }
 
-        Opcode::StackLoad => {
-            // TODO
-            unimplemented!()
-        }
-
-        Opcode::StackStore => {
-            // TODO
-            unimplemented!()
+        Opcode::StackLoad | Opcode::StackStore => {
+            let stack_slot = match ctx.data(insn) {
+                &InstructionData::StackLoad { stack_slot, .. }
+                | &InstructionData::StackStore { stack_slot, .. } => stack_slot,
+                _ => unreachable!(),
+            };
+            let offset = ldst_offset(ctx.data(insn)).unwrap();
+            assert!(offset >= 0);
+            let inst = if op == Opcode::StackLoad {
+                let rd = output_to_reg(ctx, outputs[0]);
+                let ty = ctx.output_ty(insn, 0);
+                ctx.abi()
+                    .load_stackslot(stack_slot, offset as usize, ty, rd)
+            } else {
+                let rd = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+                let ty = ctx.input_ty(insn, 0);
+                ctx.abi()
+                    .store_stackslot(stack_slot, offset as usize, ty, rd)
+            };
+            ctx.emit(inst);
         }
 
         Opcode::StackAddr => {
@@ -1578,6 +1596,40 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             }
         }
 
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            // The callee's address is kept in x16 until the jump, since the
+            // epilogue inserted before it restores the callee-saved registers.
+            let target = writable_xreg(16);
+            let (abi, inputs) = match op {
+                Opcode::ReturnCall => {
+                    let extname = ctx.call_target(insn).unwrap();
+                    let extname = extname.clone();
+                    // As for calls, get the function address with an Abs8 reloc
+                    // in the constant pool.
+                    ctx.emit(Inst::ULoad64 {
+                        rd: target,
+                        mem: MemArg::Label(MemLabel::ExtName(extname, 0)),
+                    });
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() == sig.params.len());
+                    (ARM64ABICall::from_ptr(sig, target.to_reg()), &inputs[..])
+                }
+                Opcode::ReturnCallIndirect => {
+                    let ptr = input_to_reg(ctx, inputs[0], NarrowValueMode::ZeroExtend64);
+                    ctx.emit(Inst::gen_move(target, ptr));
+                    let sig = ctx.call_sig(insn).unwrap();
+                    assert!(inputs.len() - 1 == sig.params.len());
+                    (ARM64ABICall::from_ptr(sig, target.to_reg()), &inputs[1..])
+                }
+                _ => unreachable!(),
+            };
+            for (i, input) in inputs.iter().enumerate() {
+                let arg_reg = input_to_reg(ctx, *input, NarrowValueMode::None);
+                ctx.emit(abi.gen_copy_reg_to_arg(i, arg_reg));
+            }
+            ctx.emit(abi.gen_tail_call());
+        }
+
         Opcode::GetPinnedReg
         | Opcode::SetPinnedReg
         | Opcode::Spill
@@ -1717,9 +1769,87 @@ fn lower_insn_to_regs<C: LowerCtx<Inst>>(ctx: &mut C, insn: IRInst) {
             panic!("x86-specific opcode in supposedly arch-neutral IR!");
         }
 
+        Opcode::TlsValue => {
+            let rd = output_to_reg(ctx, outputs[0]);
+            let (extname, _) = ctx.symbol_value(insn).unwrap();
+            let symbol = extname.clone();
+            match flags.tls_model() {
+                TlsModel::ElfGd => {
+                    ctx.emit(Inst::ElfTlsGetAddr { symbol });
+                    ctx.emit(Inst::mov(rd, xreg(0)));
+                }
+                TlsModel::ElfLe => {
+                    ctx.emit(Inst::ElfTlsLocalExec { rd, symbol });
+                }
+                TlsModel::Macho => {
+                    ctx.emit(Inst::MachOTlsGetAddr { symbol });
+                    ctx.emit(Inst::mov(rd, xreg(0)));
+                }
+                model => {
+                    return Err(CodegenError::Unsupported(format!(
+                        "tls_value with tls_model={}",
+                        model
+                    )));
+                }
+            }
+        }
+
         Opcode::AvgRound => unimplemented!(),
-        Opcode::TlsValue => unimplemented!(),
+
+        Opcode::AtomicRmw => {
+            let ty = ty.unwrap();
+            let op = match ctx.data(insn) {
+                &InstructionData::AtomicRmw { op, .. } => op,
+                _ => unreachable!(),
+            };
+            let r_addr = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let r_arg = input_to_reg(ctx, inputs[1], NarrowValueMode::None);
+            let r_dst = output_to_reg(ctx, outputs[0]);
+            // The exclusive-access loop works on fixed registers; see
+            // `Inst::AtomicRMW`.
+            ctx.emit(Inst::mov(writable_xreg(25), r_addr));
+            ctx.emit(Inst::mov(writable_xreg(26), r_arg));
+            ctx.emit(Inst::AtomicRMW { ty, op });
+            ctx.emit(Inst::mov(r_dst, xreg(27)));
+        }
+
+        Opcode::AtomicCas => {
+            let ty = ty.unwrap();
+            let r_addr = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            // The loaded value is zero-extended, so compare against the
+            // zero-extended expected value.
+            let r_expected = input_to_reg(ctx, inputs[1], NarrowValueMode::ZeroExtend64);
+            let r_replacement = input_to_reg(ctx, inputs[2], NarrowValueMode::None);
+            let r_dst = output_to_reg(ctx, outputs[0]);
+            ctx.emit(Inst::mov(writable_xreg(25), r_addr));
+            ctx.emit(Inst::mov(writable_xreg(26), r_expected));
+            ctx.emit(Inst::mov(writable_xreg(28), r_replacement));
+            ctx.emit(Inst::AtomicCAS { ty });
+            ctx.emit(Inst::mov(r_dst, xreg(27)));
+        }
+
+        Opcode::AtomicLoad => {
+            let ty = ty.unwrap();
+            let off = ldst_offset(ctx.data(insn)).unwrap();
+            let rn = lower_atomic_address(ctx, inputs[0], off);
+            let rt = output_to_reg(ctx, outputs[0]);
+            ctx.emit(Inst::LoadAcquire { ty, rt, rn });
+        }
+
+        Opcode::AtomicStore => {
+            let ty = ctx.input_ty(insn, 0);
+            let off = ldst_offset(ctx.data(insn)).unwrap();
+            let rt = input_to_reg(ctx, inputs[0], NarrowValueMode::None);
+            let rn = lower_atomic_address(ctx, inputs[1], off);
+            ctx.emit(Inst::StoreRelease { ty, rt, rn });
+        }
+
+        Opcode::Fence => {
+            ctx.emit(Inst::Fence);
+        }
     }
+
+    Ok(())
 }
 
 //=============================================================================
@@ -1731,6 +1861,8 @@ fn ty_bits(ty: Type) -> usize {
         B16 | I16 => 16,
         B32 | I32 | F32 => 32,
         B64 | I64 | F64 => 64,
+        R32 => 32,
+        R64 => 64,
         B128 | I128 => 128,
         IFLAGS | FFLAGS => 32,
         _ => panic!("ty_bits() on unknown type: {:?}", ty),
@@ -1763,6 +1895,24 @@ fn branch_target(data: &InstructionData) -> Option<Block> {
     }
 }
 
+/// Compute the address of an atomic access into a register: the exclusive and
+/// acquire/release instructions only take a bare base register.
+fn lower_atomic_address<C: LowerCtx<Inst>>(ctx: &mut C, addr: InsnInput, offset: i32) -> Reg {
+    let base = input_to_reg(ctx, addr, NarrowValueMode::None);
+    if offset == 0 {
+        return base;
+    }
+    let tmp = ctx.tmp(RegClass::I64, I64);
+    lower_constant(ctx, tmp, offset as i64 as u64);
+    ctx.emit(Inst::AluRRR {
+        alu_op: ALUOp::Add64,
+        rd: tmp,
+        rn: base,
+        rm: tmp.to_reg(),
+    });
+    tmp.to_reg()
+}
+
 fn ldst_offset(data: &InstructionData) -> Option<i32> {
     match data {
         &InstructionData::Load { offset, .. }
@@ -1843,8 +1993,8 @@ fn lower_ifcmp_to_flags<C: LowerCtx<Inst>>(ctx: &mut C, ifcmp_insn: IRInst, is_s
 impl LowerBackend for Arm64Backend {
     type MInst = Inst;
 
-    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) {
-        lower_insn_to_regs(ctx, ir_inst);
+    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
+        lower_insn_to_regs(ctx, ir_inst, &self.flags)
     }
 
     fn lower_branch_group<C: LowerCtx<Inst>>(
@@ -3,8 +3,14 @@
 #![allow(unused_imports)]
 
 use crate::binemit::{CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink};
+#[cfg(feature = "unwind")]
+use crate::binemit::{FrameUnwindKind, FrameUnwindSink};
 use crate::ir::Function;
-use crate::machinst::{compile, MachBackend, MachCompileResult, ShowWithRRU, VCode};
+use crate::isa::TargetIsa;
+use crate::machinst::TargetIsaAdapter;
+use crate::machinst::{
+    compile, spill_references, MachBackend, MachCompileResult, ShowWithRRU, VCode,
+};
 use crate::machinst::{ABIBody, ABICall};
 use crate::result::CodegenResult;
 use crate::settings;
@@ -19,6 +25,8 @@ use target_lexicon::Triple;
 
 // New backend:
 mod abi;
+#[cfg(feature = "unwind")]
+mod fde;
 mod inst;
 mod lower;
 
@@ -32,26 +40,40 @@ pub struct Arm64Backend {
 impl Arm64Backend {
     /// Create a new ARM64 backend.
     pub fn new() -> Arm64Backend {
-        Arm64Backend {
-            flags: settings::Flags::new(settings::builder()),
-        }
+        Self::new_with_flags(settings::Flags::new(settings::builder()))
+    }
+
+    /// Create a new ARM64 backend with the given (shared) flags.
+    pub fn new_with_flags(flags: settings::Flags) -> Arm64Backend {
+        Arm64Backend { flags }
     }
 
-    fn compile_vcode(&self, mut func: Function) -> VCode<inst::Inst> {
+    fn compile_vcode(&self, mut func: Function) -> CodegenResult<VCode<inst::Inst>> {
         // This performs lowering to VCode, register-allocates the code, computes
         // block layout and finalizes branches. The result is ready for binary emission.
+        let ref_slots = if self.flags.enable_safepoints() {
+            spill_references(&mut func)
+        } else {
+            vec![]
+        };
         let abi = Box::new(abi::ARM64ABIBody::new(&func));
-        compile::compile::<Arm64Backend>(&mut func, self, abi)
+        compile::compile::<Arm64Backend>(&mut func, self, abi, ref_slots)
     }
 }
 
+/// Create a `TargetIsa` wrapping an ARM64 backend configured with the given
+/// shared flags.
+pub fn isa_adapter(_triple: Triple, flags: settings::Flags) -> Box<dyn TargetIsa> {
+    Box::new(TargetIsaAdapter::new(Arm64Backend::new_with_flags(flags)))
+}
+
 impl MachBackend for Arm64Backend {
     fn compile_function(
         &self,
         func: Function,
         want_disasm: bool,
     ) -> CodegenResult<MachCompileResult> {
-        let vcode = self.compile_vcode(func);
+        let vcode = self.compile_vcode(func)?;
         let sections = vcode.emit();
         let frame_size = vcode.frame_size();
 
@@ -83,6 +105,19 @@ impl MachBackend for Arm64Backend {
     fn reg_universe(&self) -> RealRegUniverse {
         create_reg_universe()
     }
+
+    #[cfg(feature = "unwind")]
+    fn emit_unwind_info(
+        &self,
+        result: &MachCompileResult,
+        kind: FrameUnwindKind,
+        sink: &mut dyn FrameUnwindSink,
+    ) {
+        match kind {
+            FrameUnwindKind::Libunwind => fde::emit_fde(result, sink),
+            FrameUnwindKind::Fastcall => {}
+        }
+    }
 }
 
 #[cfg(test)]
@@ -91,8 +126,9 @@ mod test {
     use crate::binemit::{NullRelocSink, NullStackmapSink, NullTrapSink};
     use crate::cursor::{Cursor, FuncCursor};
     use crate::ir::types::*;
-    use crate::ir::{AbiParam, ExternalName, Function, InstBuilder, Signature};
+    use crate::ir::{AbiParam, ExtFuncData, ExternalName, Function, InstBuilder, Signature};
     use crate::isa::CallConv;
+    use crate::settings::Configurable;
 
     #[test]
     fn test_compile_function() {
@@ -129,4 +165,43 @@ mod test {
 
         assert_eq!(code, &golden);
     }
+
+    #[test]
+    fn test_stackmap_for_reference_live_across_call() {
+        let name = ExternalName::testcase("test0");
+        let mut sig = Signature::new(CallConv::SystemV);
+        sig.params.push(AbiParam::new(R64));
+        sig.returns.push(AbiParam::new(R64));
+        let mut func = Function::with_name_signature(name, sig);
+
+        let callee_sig = func.import_signature(Signature::new(CallConv::SystemV));
+        let callee = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("callee"),
+            signature: callee_sig,
+            colocated: false,
+        });
+
+        let bb0 = func.dfg.make_block();
+        let arg0 = func.dfg.append_block_param(bb0, R64);
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_block(bb0);
+        pos.ins().call(callee, &[]);
+        pos.ins().return_(&[arg0]);
+
+        let mut flags = settings::builder();
+        flags.enable("enable_safepoints").unwrap();
+        let backend = Arm64Backend::new_with_flags(settings::Flags::new(flags));
+        let sections = backend.compile_function(func, false).unwrap().sections;
+        let stackmaps = &sections.sections[0].stackmaps;
+        assert_eq!(stackmaps.len(), 1);
+
+        // The reference lives in the only stack slot, which is the topmost
+        // word of the frame below the saved FP and LR.
+        let stackmap = &stackmaps[0].stackmap;
+        let words = stackmap.mapped_words() as usize;
+        let refs = (0..words).filter(|&i| stackmap.get_bit(i)).count();
+        assert_eq!(refs, 1);
+        assert!(stackmap.get_bit(words - 1));
+    }
 }
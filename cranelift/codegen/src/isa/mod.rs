@@ -132,13 +132,15 @@ pub fn lookup(triple: Triple) -> Result<IsaBackend, LookupError> {
         Architecture::Riscv32 | Architecture::Riscv64 => isa_builder!(riscv, "riscv", triple),
         Architecture::Arm { .. } => isa_builder!(arm32, "arm32", triple),
         // ARM64 uses the new backend.
-        Architecture::Aarch64 { .. } => Ok(IsaBackend::Builder(Builder::wrap(Box::new(
-            TargetIsaAdapter::new(arm64::Arm64Backend::new()),
-        )))),
+        Architecture::Aarch64 { .. } => Ok(IsaBackend::Builder(Builder::mach_backend(
+            triple,
+            arm64::isa_adapter,
+        ))),
         // X86_64 uses the new backend.  Bwaha!
-        Architecture::X86_64 => Ok(IsaBackend::Builder(Builder::wrap(Box::new(
-            TargetIsaAdapter::new(x64::X64Backend::new()),
-        )))),
+        Architecture::X86_64 => Ok(IsaBackend::Builder(Builder::mach_backend(
+            triple,
+            x64::isa_adapter,
+        ))),
         _ => Err(LookupError::Unsupported),
     }
 }
@@ -178,6 +180,7 @@ pub struct Builder {
     triple: Triple,
     setup: Option<settings::Builder>,
     constructor: Option<fn(Triple, settings::Flags, settings::Builder) -> Box<dyn TargetIsa>>,
+    mach_constructor: Option<fn(Triple, settings::Flags) -> Box<dyn TargetIsa>>,
     wrapped: Option<Box<dyn TargetIsa>>,
 }
 
@@ -188,21 +191,47 @@ impl Builder {
             triple: tisa.triple().clone(),
             setup: None,
             constructor: None,
+            mach_constructor: None,
             wrapped: Some(tisa),
         }
     }
 
+    /// Create a Builder for a `MachBackend`, which is constructed (and wrapped
+    /// in a `TargetIsaAdapter`) by `constructor` from the shared flags passed
+    /// to `finish`. `MachBackend`s have no ISA-specific settings yet, so any
+    /// ISA-specific flags set on the builder are ignored, as they are for a
+    /// wrapped `TargetIsa`.
+    fn mach_backend(
+        triple: Triple,
+        constructor: fn(Triple, settings::Flags) -> Box<dyn TargetIsa>,
+    ) -> Builder {
+        Builder {
+            triple,
+            setup: None,
+            constructor: None,
+            mach_constructor: Some(constructor),
+            wrapped: None,
+        }
+    }
+
     /// Combine the ISA-specific settings with the provided ISA-independent settings and allocate a
     /// fully configured `TargetIsa` trait object.
     pub fn finish(self, shared_flags: settings::Flags) -> Box<dyn TargetIsa> {
         if let Some(ctor) = self.constructor {
             (ctor)(self.triple, shared_flags, self.setup.unwrap())
+        } else if let Some(ctor) = self.mach_constructor {
+            (ctor)(self.triple, shared_flags)
         } else {
             self.wrapped.unwrap()
         }
     }
 
     /// If this is a wrapped existing TargetIsa instance, return the wrapped instance.
+    ///
+    /// The builders `lookup` returns for the `MachBackend`s used on AArch64 and
+    /// x86_64 are no longer wrapped, since they need the shared flags to be
+    /// constructed, so this panics for them.
+    #[deprecated(note = "use `Builder::finish`, which works for every kind of Builder, instead")]
     pub fn get_wrapped(self) -> Box<dyn TargetIsa> {
         if let Some(w) = self.wrapped {
             w
@@ -1,4 +1,4 @@
-use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc};
+use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc, Stackmap};
 use crate::ir::Value;
 use crate::ir::{ConstantOffset, ExternalName, Function, JumpTable, SourceLoc, TrapCode};
 use crate::isa::TargetIsa;
@@ -71,4 +71,6 @@ impl CodeSink for TestCodeSink {
     fn end_codegen(&mut self) {}
 
     fn add_stackmap(&mut self, _val_list: &[Value], _func: &Function, _isa: &dyn TargetIsa) {}
+
+    fn add_mach_stackmap(&mut self, _stackmap: Stackmap) {}
 }
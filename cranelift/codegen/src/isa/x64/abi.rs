@@ -3,6 +3,7 @@
 #![allow(dead_code)]
 #![allow(non_snake_case)]
 
+use crate::binemit::Stackmap;
 use crate::ir;
 use crate::ir::types;
 use crate::ir::types::*;
@@ -67,6 +68,27 @@ fn get_intreg_for_arg_ELF(idx: usize) -> Option<Reg> {
     }
 }
 
+/// Returns the registers in which the arguments of a call with signature `sig`
+/// are passed, or `None` if some of them are passed in a way not supported yet.
+pub(crate) fn get_arg_regs(sig: &ir::Signature) -> Option<Vec<Reg>> {
+    let mut regs = vec![];
+    let mut next_int_arg = 0;
+    for param in &sig.params {
+        match param.purpose {
+            ir::ArgumentPurpose::Normal if in_int_reg(param.value_type) => {
+                regs.push(get_intreg_for_arg_ELF(next_int_arg)?);
+                next_int_arg += 1;
+            }
+            // VmContext is r14 in Baldrdash.
+            ir::ArgumentPurpose::VMContext if sig.call_conv.extends_baldrdash() => {
+                regs.push(reg_R14());
+            }
+            _ => return None,
+        }
+    }
+    Some(regs)
+}
+
 fn get_intreg_for_retval_ELF(idx: usize) -> Option<Reg> {
     match idx {
         0 => Some(reg_RAX()),
@@ -170,6 +192,57 @@ impl X64ABIBody {
             call_conv: f.signature.call_conv.clone(),
         }
     }
+
+    /// Undo what the prologue did, short of returning.  Tail calls share this
+    /// with returns.
+    fn gen_frame_teardown(&self) -> Vec<Inst> {
+        let r_rbp = reg_RBP();
+        let r_rsp = reg_RSP();
+        let w_rbp = Writable::<Reg>::from_reg(r_rbp);
+        let w_rsp = Writable::<Reg>::from_reg(r_rsp);
+        let mut insts = vec![];
+
+        // Clear the spill area and the 16-alignment padding below it.
+        let spill_area_sizeB = self.spill_area_sizeB.unwrap();
+        if spill_area_sizeB > 0 {
+            // FIXME JRS 2020Feb16: what if spill_area_size >= 2G?
+            insts.push(i_Alu_RMI_R(
+                true,
+                RMI_R_Op::Add,
+                ip_RMI_I(spill_area_sizeB as u32),
+                w_rsp,
+            ));
+        }
+
+        // Restore regs.
+        let mut tmp_insts = vec![];
+        let clobbered = get_callee_saves(self.clobbered.to_vec());
+        for w_real_reg in clobbered {
+            match w_real_reg.to_reg().get_class() {
+                RegClass::I64 => {
+                    // TODO: make these conversion sequences less cumbersome.
+                    tmp_insts.push(i_Pop64(Writable::<Reg>::from_reg(
+                        w_real_reg.to_reg().to_reg(),
+                    )))
+                }
+                _ => unimplemented!(),
+            }
+        }
+        tmp_insts.reverse();
+        for i in tmp_insts {
+            insts.push(i);
+        }
+
+        // Baldrdash has its own preamble.
+        if !self.call_conv.extends_baldrdash() {
+            // Undo the "traditional" pre-preamble
+            // RSP before the call will be 0 % 16.  So here, it is 8 % 16.
+            // uhhhh .. insts.push(i_Mov_R_R(true, r_rbp, w_rsp));
+            insts.push(i_Pop64(w_rbp));
+        }
+
+        insts
+    }
 }
 
 impl ABIBody<Inst> for X64ABIBody {
@@ -353,62 +426,27 @@ impl ABIBody<Inst> for X64ABIBody {
     }
 
     fn gen_epilogue(&self) -> Vec<Inst> {
-        let r_rbp = reg_RBP();
-        let r_rsp = reg_RSP();
-        let w_rbp = Writable::<Reg>::from_reg(r_rbp);
-        let w_rsp = Writable::<Reg>::from_reg(r_rsp);
-        let mut insts = vec![];
-
-        // Undo what we did in the prologue.
-
-        // Clear the spill area and the 16-alignment padding below it.
-        let spill_area_sizeB = self.spill_area_sizeB.unwrap();
-        if spill_area_sizeB > 0 {
-            // FIXME JRS 2020Feb16: what if spill_area_size >= 2G?
-            insts.push(i_Alu_RMI_R(
-                true,
-                RMI_R_Op::Add,
-                ip_RMI_I(spill_area_sizeB as u32),
-                w_rsp,
-            ));
-        }
-
-        // Restore regs.
-        let mut tmp_insts = vec![];
-        let clobbered = get_callee_saves(self.clobbered.to_vec());
-        for w_real_reg in clobbered {
-            match w_real_reg.to_reg().get_class() {
-                RegClass::I64 => {
-                    // TODO: make these conversion sequences less cumbersome.
-                    tmp_insts.push(i_Pop64(Writable::<Reg>::from_reg(
-                        w_real_reg.to_reg().to_reg(),
-                    )))
-                }
-                _ => unimplemented!(),
-            }
-        }
-        tmp_insts.reverse();
-        for i in tmp_insts {
-            insts.push(i);
-        }
-
+        let mut insts = self.gen_frame_teardown();
         // Baldrdash has its own preamble.
         if !self.call_conv.extends_baldrdash() {
-            // Undo the "traditional" pre-preamble
-            // RSP before the call will be 0 % 16.  So here, it is 8 % 16.
-            // uhhhh .. insts.push(i_Mov_R_R(true, r_rbp, w_rsp));
-            insts.push(i_Pop64(w_rbp));
             insts.push(i_Ret());
         }
-
         insts
     }
 
+    fn gen_tail_call_epilogue(&self) -> Vec<Inst> {
+        self.gen_frame_teardown()
+    }
+
     fn frame_size(&self) -> u32 {
         self.spill_area_sizeB
             .expect("frame size not computed before prologue generation") as u32
     }
 
+    fn stackmap(&self, _ref_slots: &[StackSlot]) -> Stackmap {
+        unimplemented!()
+    }
+
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32 {
         // We allocate in terms of 8-byte slots.
         match (rc, ty) {
@@ -8,7 +8,7 @@
 use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc};
 //zz use crate::ir::constant::{ConstantData, ConstantOffset};
 use crate::ir::types::{B1, B128, B16, B32, B64, B8, F32, F64, I128, I16, I32, I64, I8};
-use crate::ir::{ConstantOffset, ExternalName, Function, JumpTable, SourceLoc, TrapCode};
+use crate::ir::{ConstantOffset, ExternalName, Function, JumpTable, LibCall, SourceLoc, TrapCode};
 use crate::ir::{FuncRef, GlobalValue, Type, Value};
 use crate::isa::TargetIsa;
 use crate::machinst::*;
@@ -275,6 +275,12 @@ pub fn reg_R8() -> Reg {
 pub fn reg_R9() -> Reg {
     info_R9().0.to_reg()
 }
+pub fn reg_R10() -> Reg {
+    info_R10().0.to_reg()
+}
+pub fn reg_R11() -> Reg {
+    info_R11().0.to_reg()
+}
 pub fn reg_R14() -> Reg {
     info_R14().0.to_reg()
 }
@@ -286,6 +292,41 @@ pub fn reg_RBP() -> Reg {
     info_RBP().0.to_reg()
 }
 
+// The registers clobbered by a call, in the ELF x86_64 ABI.
+fn get_caller_saves_set_ELF() -> Set<Writable<Reg>> {
+    let mut set = Set::empty();
+    for (rreg, _) in &[
+        info_RSI(),
+        info_RDI(),
+        info_RAX(),
+        info_RCX(),
+        info_RDX(),
+        info_R8(),
+        info_R9(),
+        info_R10(),
+        info_R11(),
+        info_XMM0(),
+        info_XMM1(),
+        info_XMM2(),
+        info_XMM3(),
+        info_XMM4(),
+        info_XMM5(),
+        info_XMM6(),
+        info_XMM7(),
+        info_XMM8(),
+        info_XMM9(),
+        info_XMM10(),
+        info_XMM11(),
+        info_XMM12(),
+        info_XMM13(),
+        info_XMM14(),
+        info_XMM15(),
+    ] {
+        set.insert(Writable::from_reg(rreg.to_reg()));
+    }
+    set
+}
+
 /// Create the register universe for X64.
 pub fn create_reg_universe() -> RealRegUniverse {
     let mut regs = Vec::<(RealReg, String)>::new();
@@ -850,6 +891,41 @@ pub enum Inst {
     /// popq reg
     Pop64 { dst: Reg },
 
+    /// lock xadd (b w l q) reg addr.  |src| receives the old value.
+    LockXadd {
+        size: u8, // 1, 2, 4 or 8
+        src: Reg,
+        addr: Addr,
+    },
+
+    /// xchg (b w l q) reg addr, which is implicitly locked.  |src| receives
+    /// the old value.
+    Xchg_R_M {
+        size: u8, // 1, 2, 4 or 8
+        src: Reg,
+        addr: Addr,
+    },
+
+    /// lock cmpxchg (b w l q) reg addr.  The expected value is in %rax, which
+    /// receives the old value.
+    LockCmpxchg {
+        size: u8, // 1, 2, 4 or 8
+        src: Reg,
+        addr: Addr,
+    },
+
+    /// An atomic (and or xor) of %r10 into the (b w l q) value at address
+    /// %r9, as a lock cmpxchg loop.  The old value is left in %rax and %r11
+    /// is clobbered.  The fixed registers keep the allocator from reusing an
+    /// input register for an output inside the loop.
+    AtomicRmwSeq {
+        size: u8, // 1, 2, 4 or 8
+        op: RMI_R_Op,
+    },
+
+    /// mfence
+    Mfence,
+
     /// call simm32
     CallKnown {
         dest: ExternalName,
@@ -864,6 +940,13 @@ pub enum Inst {
         //defs: Set<Writable<Reg>>,
     },
 
+    /// jmp simm32, to another function once the epilogue has run
+    TailCallKnown { dest: ExternalName, uses: Set<Reg> },
+
+    /// jmpq *reg, to another function once the epilogue has run.  The epilogue
+    /// restores the callee-saved registers, so `target` must be caller-saved.
+    TailCallUnknown { target: Reg, uses: Set<Reg> },
+
     // ---- branches (exactly one must appear at end of BB) ----
     /// ret
     Ret {},
@@ -906,6 +989,20 @@ pub enum Inst {
 
     /// jmpq (reg mem)
     JmpUnknown { target: RM },
+
+    /// ELF general-dynamic TLS access:
+    /// data16 leaq symbol@tlsgd(%rip), %rdi ; data16 data16 callq __tls_get_addr@PLT
+    /// The result is in %rax, and all caller-saved registers are clobbered.
+    ElfTlsGetAddr { symbol: ExternalName },
+
+    /// ELF local-exec TLS access:
+    /// movq %fs:0, reg ; addq $symbol@tpoff, reg
+    ElfTlsLocalExec { symbol: ExternalName, dst: Reg },
+
+    /// Mach-O TLS access:
+    /// movq symbol@tlv(%rip), %rdi ; callq *(%rdi)
+    /// The result is in %rax, and all caller-saved registers are clobbered.
+    MachOTlsGetAddr { symbol: ExternalName },
 }
 
 // Handy constructors for Insts.
@@ -1023,6 +1120,57 @@ pub fn i_Pop64(wdst: Writable<Reg>) -> Inst {
     Inst::Pop64 { dst: wdst.to_reg() }
 }
 
+pub fn i_LockXadd(size: u8, wsrc: Writable<Reg>, addr: Addr) -> Inst {
+    let src = wsrc.to_reg();
+    debug_assert!(size == 8 || size == 4 || size == 2 || size == 1);
+    debug_assert!(src.get_class() == RegClass::I64);
+    Inst::LockXadd { size, src, addr }
+}
+
+pub fn i_Xchg_R_M(size: u8, wsrc: Writable<Reg>, addr: Addr) -> Inst {
+    let src = wsrc.to_reg();
+    debug_assert!(size == 8 || size == 4 || size == 2 || size == 1);
+    debug_assert!(src.get_class() == RegClass::I64);
+    Inst::Xchg_R_M { size, src, addr }
+}
+
+pub fn i_LockCmpxchg(size: u8, src: Reg, addr: Addr) -> Inst {
+    debug_assert!(size == 8 || size == 4 || size == 2 || size == 1);
+    debug_assert!(src.get_class() == RegClass::I64);
+    Inst::LockCmpxchg { size, src, addr }
+}
+
+pub fn i_AtomicRmwSeq(size: u8, op: RMI_R_Op) -> Inst {
+    debug_assert!(size == 8 || size == 4 || size == 2 || size == 1);
+    debug_assert!(op == RMI_R_Op::And || op == RMI_R_Op::Or || op == RMI_R_Op::Xor);
+    Inst::AtomicRmwSeq { size, op }
+}
+
+pub fn i_Mfence() -> Inst {
+    Inst::Mfence
+}
+
+// The pieces of an AtomicRmwSeq: the initial load of the old value, then the
+// body of the loop, which ends with a "jnz" back to its start.
+fn atomic_rmw_seq_parts(size: u8, op: RMI_R_Op) -> (Inst, [Inst; 3]) {
+    let addr = ip_Addr_IR(0, reg_R9());
+    let wRAX = Writable::from_reg(reg_RAX());
+    let wR11 = Writable::from_reg(reg_R11());
+    let load = match size {
+        1 => i_MovZX_M_R(ExtMode::BL, addr.clone(), wRAX),
+        2 => i_MovZX_M_R(ExtMode::WL, addr.clone(), wRAX),
+        4 => i_MovZX_M_R(ExtMode::LQ, addr.clone(), wRAX),
+        8 => i_Mov64_M_R(addr.clone(), wRAX),
+        _ => panic!("atomic_rmw_seq_parts: size"),
+    };
+    let body = [
+        i_Mov_R_R(true, reg_RAX(), wR11),
+        i_Alu_RMI_R(true, op, ip_RMI_R(reg_R10()), wR11),
+        i_LockCmpxchg(size, reg_R11(), addr),
+    ];
+    (load, body)
+}
+
 //pub fn i_CallKnown(target: FuncRef) -> Inst {
 //    Inst::CallKnown { target }
 //}
@@ -1031,6 +1179,29 @@ pub fn i_CallUnknown(dest: RM) -> Inst {
     Inst::CallUnknown { dest }
 }
 
+pub fn i_TailCallKnown(dest: ExternalName, uses: Set<Reg>) -> Inst {
+    Inst::TailCallKnown { dest, uses }
+}
+
+pub fn i_TailCallUnknown(target: Reg, uses: Set<Reg>) -> Inst {
+    debug_assert!(target.get_class() == RegClass::I64);
+    Inst::TailCallUnknown { target, uses }
+}
+
+pub fn i_ElfTlsGetAddr(symbol: ExternalName) -> Inst {
+    Inst::ElfTlsGetAddr { symbol }
+}
+
+pub fn i_ElfTlsLocalExec(symbol: ExternalName, wdst: Writable<Reg>) -> Inst {
+    let dst = wdst.to_reg();
+    debug_assert!(dst.get_class() == RegClass::I64);
+    Inst::ElfTlsLocalExec { symbol, dst }
+}
+
+pub fn i_MachOTlsGetAddr(symbol: ExternalName) -> Inst {
+    Inst::MachOTlsGetAddr { symbol }
+}
+
 pub fn i_Ret() -> Inst {
     Inst::Ret {}
 }
@@ -1210,6 +1381,36 @@ fn x64_show_rru(inst: &Inst, mb_rru: Option<&RealRegUniverse>) -> String {
             format!("{} {}", ljustify("pushq".to_string()), src.show_rru(mb_rru))
         }
         Inst::Pop64 { dst } => format!("{} {}", ljustify("popq".to_string()), dst.show_rru(mb_rru)),
+        Inst::LockXadd { size, src, addr } => format!(
+            "lock {} {}, {}",
+            ljustify2("xadd".to_string(), suffixBWLQ(*size)),
+            show_ireg_sized(*src, mb_rru, *size),
+            addr.show_rru(mb_rru)
+        ),
+        Inst::Xchg_R_M { size, src, addr } => format!(
+            "{} {}, {}",
+            ljustify2("xchg".to_string(), suffixBWLQ(*size)),
+            show_ireg_sized(*src, mb_rru, *size),
+            addr.show_rru(mb_rru)
+        ),
+        Inst::LockCmpxchg { size, src, addr } => format!(
+            "lock {} {}, {}",
+            ljustify2("cmpxchg".to_string(), suffixBWLQ(*size)),
+            show_ireg_sized(*src, mb_rru, *size),
+            addr.show_rru(mb_rru)
+        ),
+        Inst::AtomicRmwSeq { size, op } => {
+            let (load, body) = atomic_rmw_seq_parts(*size, op.clone());
+            format!(
+                "{} ; 1: {} ; {} ; {} ; {} 1b",
+                load.show_rru(mb_rru),
+                body[0].show_rru(mb_rru),
+                body[1].show_rru(mb_rru),
+                body[2].show_rru(mb_rru),
+                ljustify("jnz".to_string())
+            )
+        }
+        Inst::Mfence => "mfence".to_string(),
         //
         //Inst::CallKnown { target } => format!("{} {:?}", ljustify("call".to_string()), target),
         Inst::CallKnown { .. } => "**CallKnown**".to_string(),
@@ -1219,6 +1420,35 @@ fn x64_show_rru(inst: &Inst, mb_rru: Option<&RealRegUniverse>) -> String {
             ljustify("call".to_string()),
             dest.show_rru(mb_rru)
         ),
+        Inst::TailCallKnown { dest, uses: _ } => {
+            format!("{} {}", ljustify("jmp".to_string()), dest)
+        }
+        Inst::TailCallUnknown { target, uses: _ } => format!(
+            "{} *{}",
+            ljustify("jmp".to_string()),
+            target.show_rru(mb_rru)
+        ),
+        Inst::ElfTlsGetAddr { symbol } => format!(
+            "{} {}@tlsgd(%rip), %rdi ; {} {}@PLT",
+            ljustify("leaq".to_string()),
+            symbol,
+            ljustify("call".to_string()),
+            ExternalName::LibCall(LibCall::ElfTlsGetAddr)
+        ),
+        Inst::ElfTlsLocalExec { symbol, dst } => format!(
+            "{} %fs:0, {} ; {} ${}@tpoff, {}",
+            ljustify("movq".to_string()),
+            dst.show_rru(mb_rru),
+            ljustify("addq".to_string()),
+            symbol,
+            dst.show_rru(mb_rru)
+        ),
+        Inst::MachOTlsGetAddr { symbol } => format!(
+            "{} {}@tlv(%rip), %rdi ; {} *(%rdi)",
+            ljustify("movq".to_string()),
+            symbol,
+            ljustify("call".to_string())
+        ),
         Inst::Ret {} => "ret".to_string(),
         Inst::EpiloguePlaceholder {} => "epilogue placeholder".to_string(),
         Inst::JmpKnown { dest } => {
@@ -1384,6 +1614,22 @@ fn x64_get_regs(inst: &Inst) -> InstRegUses {
         Inst::Pop64 { dst } => {
             iru.defined.insert(Writable::from_reg(*dst));
         }
+        Inst::LockXadd { size: _, src, addr } | Inst::Xchg_R_M { size: _, src, addr } => {
+            addr.get_regs(&mut iru.used);
+            iru.modified.insert(Writable::from_reg(*src));
+        }
+        Inst::LockCmpxchg { size: _, src, addr } => {
+            iru.used.insert(*src);
+            addr.get_regs(&mut iru.used);
+            iru.modified.insert(Writable::from_reg(reg_RAX()));
+        }
+        Inst::AtomicRmwSeq { size: _, op: _ } => {
+            iru.used.insert(reg_R9());
+            iru.used.insert(reg_R10());
+            iru.defined.insert(Writable::from_reg(reg_R11()));
+            iru.defined.insert(Writable::from_reg(reg_RAX()));
+        }
+        Inst::Mfence => {}
         Inst::CallKnown {
             dest: _,
             uses: _,
@@ -1395,6 +1641,19 @@ fn x64_get_regs(inst: &Inst) -> InstRegUses {
         Inst::CallUnknown { dest } => {
             dest.get_regs(&mut iru.used);
         }
+        Inst::TailCallKnown { dest: _, uses } => {
+            iru.used.union(uses);
+        }
+        Inst::TailCallUnknown { target, uses } => {
+            iru.used.union(uses);
+            iru.used.insert(*target);
+        }
+        Inst::ElfTlsGetAddr { .. } | Inst::MachOTlsGetAddr { .. } => {
+            iru.defined.union(&get_caller_saves_set_ELF());
+        }
+        Inst::ElfTlsLocalExec { symbol: _, dst } => {
+            iru.defined.insert(Writable::from_reg(*dst));
+        }
         Inst::Ret {} => {}
         Inst::EpiloguePlaceholder {} => {}
         Inst::JmpKnown { dest: _ } => {}
@@ -1434,6 +1693,14 @@ fn apply_map(reg: &mut Reg, map: &RegallocMap<VirtualReg, RealReg>) {
     reg.apply_defs_or_uses(map)
 }
 
+fn apply_map_set(regs: &mut Set<Reg>, map: &RegallocMap<VirtualReg, RealReg>) {
+    *regs = regs.map(|reg| {
+        let mut reg = *reg;
+        apply_map(&mut reg, map);
+        reg
+    });
+}
+
 fn apply_maps(
     reg: &mut Reg,
     pre_map__aka__map_uses: &RegallocMap<VirtualReg, RealReg>,
@@ -1573,6 +1840,29 @@ fn x64_map_regs(
         Inst::Pop64 { ref mut dst } => {
             apply_map(dst, post_map);
         }
+        Inst::LockXadd {
+            size: _,
+            ref mut src,
+            ref mut addr,
+        }
+        | Inst::Xchg_R_M {
+            size: _,
+            ref mut src,
+            ref mut addr,
+        } => {
+            addr.apply_map(pre_map);
+            apply_maps(src, pre_map, post_map);
+        }
+        Inst::LockCmpxchg {
+            size: _,
+            ref mut src,
+            ref mut addr,
+        } => {
+            apply_map(src, pre_map);
+            addr.apply_map(pre_map);
+        }
+        Inst::AtomicRmwSeq { size: _, op: _ } => {}
+        Inst::Mfence => {}
         Inst::CallKnown {
             dest: _,
             uses: _,
@@ -1581,6 +1871,26 @@ fn x64_map_regs(
         Inst::CallUnknown { dest } => {
             dest.apply_map(pre_map);
         }
+        Inst::TailCallKnown {
+            dest: _,
+            ref mut uses,
+        } => {
+            apply_map_set(uses, pre_map);
+        }
+        Inst::TailCallUnknown {
+            ref mut target,
+            ref mut uses,
+        } => {
+            apply_map(target, pre_map);
+            apply_map_set(uses, pre_map);
+        }
+        Inst::ElfTlsGetAddr { .. } | Inst::MachOTlsGetAddr { .. } => {}
+        Inst::ElfTlsLocalExec {
+            symbol: _,
+            ref mut dst,
+        } => {
+            apply_map(dst, post_map);
+        }
         Inst::Ret {} => {}
         Inst::EpiloguePlaceholder {} => {}
         Inst::JmpKnown { dest: _ } => {}
@@ -1921,6 +2231,27 @@ fn emit_simm<O: MachSectionOutput>(sink: &mut O, size: u8, simm32: u32) {
 // * there's a shorter encoding for shl/shr/sar by a 1-bit immediate.  (Do we
 //   care?)
 
+// The REX and prefix flags for an insn whose register operand |reg| is
+// |size| bytes wide.  In the 8-bit case, a redundant REX prefix changes the
+// meaning of the instruction (%sil rather than %dh, etc), so it is retained
+// where necessary.
+fn flags_for_operand_size(size: u8, reg: Reg) -> u32 {
+    match size {
+        8 => F_NONE,
+        4 => F_CLEAR_REX_W,
+        2 => F_CLEAR_REX_W | F_PREFIX_66,
+        1 => {
+            let enc = iregEnc(reg);
+            if enc >= 4 && enc <= 7 {
+                F_CLEAR_REX_W | F_RETAIN_REDUNDANT_REX
+            } else {
+                F_CLEAR_REX_W
+            }
+        }
+        _ => panic!("flags_for_operand_size: unreachable"),
+    }
+}
+
 fn x64_emit<O: MachSectionOutput>(inst: &Inst, sink: &mut O) {
     match inst {
         Inst::Nop { len: 0 } => {}
@@ -2301,6 +2632,49 @@ fn x64_emit<O: MachSectionOutput>(inst: &Inst, sink: &mut O) {
             }
             sink.put1(0x58 + (encDst & 7));
         }
+        Inst::LockXadd { size, src, addr } => {
+            // LOCK XADD r8, r/m8 is F0 (REX.W==0) 0F C0 /r
+            // LOCK XADD r16/32/64, r/m16/32/64 is F0 (66) (REX.W) 0F C1 /r
+            sink.put1(0xF0);
+            let opcode = if *size == 1 { 0x0FC0 } else { 0x0FC1 };
+            let flags = flags_for_operand_size(*size, *src);
+            emit_REX_OPCODES_MODRM_SIB_IMM_regG_memE(sink, opcode, 2, *src, addr, flags)
+        }
+        Inst::Xchg_R_M { size, src, addr } => {
+            // XCHG r8, r/m8 is (REX.W==0) 86 /r
+            // XCHG r16/32/64, r/m16/32/64 is (66) (REX.W) 87 /r
+            let opcode = if *size == 1 { 0x86 } else { 0x87 };
+            let flags = flags_for_operand_size(*size, *src);
+            emit_REX_OPCODES_MODRM_SIB_IMM_regG_memE(sink, opcode, 1, *src, addr, flags)
+        }
+        Inst::LockCmpxchg { size, src, addr } => {
+            // LOCK CMPXCHG r8, r/m8 is F0 (REX.W==0) 0F B0 /r
+            // LOCK CMPXCHG r16/32/64, r/m16/32/64 is F0 (66) (REX.W) 0F B1 /r
+            sink.put1(0xF0);
+            let opcode = if *size == 1 { 0x0FB0 } else { 0x0FB1 };
+            let flags = flags_for_operand_size(*size, *src);
+            emit_REX_OPCODES_MODRM_SIB_IMM_regG_memE(sink, opcode, 2, *src, addr, flags)
+        }
+        Inst::AtomicRmwSeq { size, op } => {
+            let (load, body) = atomic_rmw_seq_parts(*size, op.clone());
+            x64_emit(&load, sink);
+            let loopStart = sink.cur_offset_from_start();
+            for inst in body.iter() {
+                x64_emit(inst, sink);
+            }
+            // JNZ rel8 back to the start of the loop.  The displacement is
+            // relative to the end of this 2-byte insn.
+            let disp = loopStart as i32 - (sink.cur_offset_from_start() as i32 + 2);
+            debug_assert!(disp >= -128);
+            sink.put1(0x70 + CC::NZ.get_enc());
+            sink.put1(disp as u8);
+        }
+        Inst::Mfence => {
+            // MFENCE is 0F AE F0
+            sink.put1(0x0F);
+            sink.put1(0xAE);
+            sink.put1(0xF0);
+        }
         //
         // ** Inst::CallKnown
         //
@@ -2329,6 +2703,68 @@ fn x64_emit<O: MachSectionOutput>(inst: &Inst, sink: &mut O) {
                 }
             }
         }
+        Inst::TailCallKnown { dest, uses: _ } => {
+            sink.put1(0xE9);
+            sink.add_reloc(Reloc::X86CallPCRel4, dest, -4);
+            sink.put4(0);
+        }
+        Inst::TailCallUnknown { target, uses: _ } => {
+            emit_REX_OPCODES_MODRM_encG_encE(
+                sink,
+                0xFF,
+                1,
+                4, /*subopcode*/
+                iregEnc(*target),
+                F_CLEAR_REX_W,
+            );
+        }
+        Inst::ElfTlsGetAddr { symbol } => {
+            // data16 leaq symbol@tlsgd(%rip), %rdi
+            sink.put1(0x66); // data16
+            sink.put1(0x48); // REX.W
+            sink.put1(0x8D); // lea
+            sink.put1(mkModRegRM(0b00, iregEnc(reg_RDI()), 0b101)); // %rdi, rip-relative
+            sink.add_reloc(Reloc::ElfX86_64TlsGd, symbol, -4);
+            sink.put4(0);
+            // data16 data16 callq __tls_get_addr@PLT
+            sink.put1(0x66); // data16
+            sink.put1(0x66); // data16
+            sink.put1(0x48); // REX.W
+            sink.put1(0xE8); // call
+            sink.add_reloc(
+                Reloc::X86CallPLTRel4,
+                &ExternalName::LibCall(LibCall::ElfTlsGetAddr),
+                -4,
+            );
+            sink.put4(0);
+        }
+        Inst::ElfTlsLocalExec { symbol, dst } => {
+            let encDst = iregEnc(*dst);
+            // movq %fs:0, dst
+            sink.put1(0x64); // %fs segment override
+            sink.put1(0x48 | (((encDst >> 3) & 1) << 2)); // REX.W, REX.R
+            sink.put1(0x8B);
+            sink.put1(mkModRegRM(0b00, encDst & 7, 0b100)); // SIB follows
+            sink.put1(mkSIB(0, 0b100, 0b101)); // disp32, no base, no index
+            sink.put4(0);
+            // addq $symbol@tpoff, dst
+            sink.put1(0x48 | ((encDst >> 3) & 1)); // REX.W, REX.B
+            sink.put1(0x81);
+            sink.put1(mkModRegRM(0b11, 0 /*subopcode*/, encDst & 7));
+            sink.add_reloc(Reloc::ElfX86_64TpOff32, symbol, 0);
+            sink.put4(0);
+        }
+        Inst::MachOTlsGetAddr { symbol } => {
+            // movq symbol@tlv(%rip), %rdi
+            sink.put1(0x48); // REX.W
+            sink.put1(0x8B); // mov
+            sink.put1(mkModRegRM(0b00, iregEnc(reg_RDI()), 0b101)); // %rdi, rip-relative
+            sink.add_reloc(Reloc::MachOX86_64Tlv, symbol, -4);
+            sink.put4(0);
+            // callq *(%rdi)
+            sink.put1(0xFF);
+            sink.put1(mkModRegRM(0b00, 2 /*subopcode*/, iregEnc(reg_RDI())));
+        }
         Inst::Ret {} => sink.put1(0xC3),
 
         Inst::JmpKnown {
@@ -2441,6 +2877,7 @@ impl MachInst for Inst {
         match self {
             // Interesting cases.
             &Inst::Ret {} | &Inst::EpiloguePlaceholder {} => MachTerminator::Ret,
+            &Inst::TailCallKnown { .. } | &Inst::TailCallUnknown { .. } => MachTerminator::TailCall,
             &Inst::JmpKnown { dest } => MachTerminator::Uncond(dest.as_block_index().unwrap()),
             &Inst::JmpCondSymm {
                 cc: _,
@@ -2458,6 +2895,13 @@ impl MachInst for Inst {
         }
     }
 
+    fn is_safepoint(&self) -> bool {
+        match self {
+            &Inst::CallKnown { .. } | &Inst::CallUnknown { .. } => true,
+            _ => false,
+        }
+    }
+
     fn gen_move(dst_reg: Writable<Reg>, src_reg: Reg) -> Inst {
         let rcD = dst_reg.to_reg().get_class();
         let rcS = src_reg.get_class();
@@ -4653,6 +5097,146 @@ fn test_x64_insn_encoding_and_printing() {
         "call    *321(%r10,%rdx,4)",
     ));
 
+    // ========================================================
+    // TLS accesses
+    insns.push((
+        i_ElfTlsGetAddr(ExternalName::testcase("tls0")),
+        "66488D3D00000000666648E800000000",
+        "leaq    %tls0@tlsgd(%rip), %rdi ; call    %ElfTlsGetAddr@PLT",
+    ));
+    insns.push((
+        i_ElfTlsLocalExec(ExternalName::testcase("tls0"), w_rax),
+        "64488B0425000000004881C000000000",
+        "movq    %fs:0, %rax ; addq    $%tls0@tpoff, %rax",
+    ));
+    insns.push((
+        i_MachOTlsGetAddr(ExternalName::testcase("tls0")),
+        "488B3D00000000FF17",
+        "movq    %tls0@tlv(%rip), %rdi ; call    *(%rdi)",
+    ));
+
+    // ========================================================
+    // LockXadd
+    insns.push((
+        i_LockXadd(8, w_r9, ip_Addr_IR(0, rsi)),
+        "F04C0FC10E",
+        "lock xaddq   %r9, 0(%rsi)",
+    ));
+    insns.push((
+        i_LockXadd(4, w_rcx, ip_Addr_IR(8, r10)),
+        "F0410FC14A08",
+        "lock xaddl   %ecx, 8(%r10)",
+    ));
+    insns.push((
+        i_LockXadd(2, w_rdx, ip_Addr_IR(0, rdi)),
+        "F0660FC117",
+        "lock xaddw   %dx, 0(%rdi)",
+    ));
+    insns.push((
+        i_LockXadd(1, w_rsi, ip_Addr_IR(0, rax)),
+        "F0400FC030",
+        "lock xaddb   %sil, 0(%rax)",
+    ));
+    insns.push((
+        i_LockXadd(1, w_rcx, ip_Addr_IR(0, rdx)),
+        "F00FC00A",
+        "lock xaddb   %cl, 0(%rdx)",
+    ));
+
+    // ========================================================
+    // Xchg_R_M
+    insns.push((
+        i_Xchg_R_M(8, w_r11, ip_Addr_IR(0, rsi)),
+        "4C871E",
+        "xchgq   %r11, 0(%rsi)",
+    ));
+    insns.push((
+        i_Xchg_R_M(4, w_rax, ip_Addr_IR(99, rdi)),
+        "874763",
+        "xchgl   %eax, 99(%rdi)",
+    ));
+    insns.push((
+        i_Xchg_R_M(2, w_rbx, ip_Addr_IR(0, rcx)),
+        "668719",
+        "xchgw   %bx, 0(%rcx)",
+    ));
+    insns.push((
+        i_Xchg_R_M(1, w_rdi, ip_Addr_IR(0, r8)),
+        "418638",
+        "xchgb   %dil, 0(%r8)",
+    ));
+
+    // ========================================================
+    // LockCmpxchg
+    insns.push((
+        i_LockCmpxchg(8, rsi, ip_Addr_IR(0, r9)),
+        "F0490FB131",
+        "lock cmpxchgq %rsi, 0(%r9)",
+    ));
+    insns.push((
+        i_LockCmpxchg(4, r12, ip_Addr_IR(0, rdi)),
+        "F0440FB127",
+        "lock cmpxchgl %r12d, 0(%rdi)",
+    ));
+    insns.push((
+        i_LockCmpxchg(2, rdi, ip_Addr_IR(0, rdx)),
+        "F0660FB13A",
+        "lock cmpxchgw %di, 0(%rdx)",
+    ));
+    insns.push((
+        i_LockCmpxchg(1, rbx, ip_Addr_IR(0, rdx)),
+        "F00FB01A",
+        "lock cmpxchgb %bl, 0(%rdx)",
+    ));
+
+    // ========================================================
+    // AtomicRmwSeq
+    insns.push((
+        i_AtomicRmwSeq(1, RMI_R_Op::And),
+        "410FB6014989C34D21D3F0450FB01975F3",
+        "movzbl  0(%r9), %eax ; 1: movq    %rax, %r11 ; andq    %r10, %r11 ; lock cmpxchgb %r11b, 0(%r9) ; jnz     1b",
+    ));
+    insns.push((
+        i_AtomicRmwSeq(2, RMI_R_Op::Or),
+        "410FB7014989C34D09D3F066450FB11975F2",
+        "movzwl  0(%r9), %eax ; 1: movq    %rax, %r11 ; orq     %r10, %r11 ; lock cmpxchgw %r11w, 0(%r9) ; jnz     1b",
+    ));
+    insns.push((
+        i_AtomicRmwSeq(4, RMI_R_Op::Xor),
+        "418B014989C34D31D3F0450FB11975F3",
+        "movl    0(%r9), %eax ; 1: movq    %rax, %r11 ; xorq    %r10, %r11 ; lock cmpxchgl %r11d, 0(%r9) ; jnz     1b",
+    ));
+    insns.push((
+        i_AtomicRmwSeq(8, RMI_R_Op::And),
+        "498B014989C34D21D3F04D0FB11975F3",
+        "movq    0(%r9), %rax ; 1: movq    %rax, %r11 ; andq    %r10, %r11 ; lock cmpxchgq %r11, 0(%r9) ; jnz     1b",
+    ));
+
+    // ========================================================
+    // Mfence
+    insns.push((i_Mfence(), "0FAEF0", "mfence"));
+
+    // ========================================================
+    // TailCallKnown
+    insns.push((
+        i_TailCallKnown(ExternalName::testcase("test0"), Set::empty()),
+        "E900000000",
+        "jmp     %test0",
+    ));
+
+    // ========================================================
+    // TailCallUnknown
+    insns.push((
+        i_TailCallUnknown(r11, Set::empty()),
+        "41FFE3",
+        "jmp     *%r11",
+    ));
+    insns.push((
+        i_TailCallUnknown(rax, Set::empty()),
+        "FFE0",
+        "jmp     *%rax",
+    ));
+
     // ========================================================
     // Ret
     insns.push((i_Ret(), "C3", "ret"));
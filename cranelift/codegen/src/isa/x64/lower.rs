@@ -6,19 +6,23 @@
 use crate::ir::condcodes::IntCC;
 //zz use crate::ir::types::*;
 use crate::ir::Inst as IRInst;
-use crate::ir::{Block, InstructionData, Opcode, Type};
+use crate::ir::{AtomicRmwOp, Block, InstructionData, Opcode, Type};
 
 use crate::ir::types;
 use crate::ir::types::*;
 
 use crate::machinst::lower::*;
 use crate::machinst::*;
+use crate::result::{CodegenError, CodegenResult};
+use crate::settings::{Flags, TlsModel};
 
+use crate::isa::x64::abi::get_arg_regs;
 use crate::isa::x64::inst::*;
 use crate::isa::x64::X64Backend;
 
-use regalloc::{RealReg, Reg, RegClass, VirtualReg, Writable};
+use regalloc::{RealReg, Reg, RegClass, Set, VirtualReg, Writable};
 
+use alloc::string::ToString;
 use smallvec::SmallVec;
 
 //============================================================================
@@ -106,7 +110,7 @@ fn intCC_to_x64_CC(cc: IntCC) -> CC {
 // Top-level instruction lowering entry point, for one instruction.
 
 /// Actually codegen an instruction's results into registers.
-fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
+fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst, flags: &Flags) -> CodegenResult<()> {
     let op = ctx.data(iri).opcode();
     let ty = if ctx.num_outputs(iri) == 1 {
         Some(ctx.output_ty(iri, 0))
@@ -355,6 +359,31 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
             unimplemented = true;
         }
 
+        Opcode::TlsValue => {
+            let regD = ctx.output(iri, 0);
+            let (extname, _) = ctx.symbol_value(iri).unwrap();
+            let symbol = extname.clone();
+            match flags.tls_model() {
+                TlsModel::ElfGd => {
+                    ctx.emit(i_ElfTlsGetAddr(symbol));
+                    ctx.emit(i_Mov_R_R(true, reg_RAX(), regD));
+                }
+                TlsModel::ElfLe => {
+                    ctx.emit(i_ElfTlsLocalExec(symbol, regD));
+                }
+                TlsModel::Macho => {
+                    ctx.emit(i_MachOTlsGetAddr(symbol));
+                    ctx.emit(i_Mov_R_R(true, reg_RAX(), regD));
+                }
+                model => {
+                    return Err(CodegenError::Unsupported(format!(
+                        "tls_value with tls_model={}",
+                        model
+                    )));
+                }
+            }
+        }
+
         Opcode::HeapAddr => {
             // TODO
             unimplemented = true;
@@ -639,12 +668,135 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
             panic!("x86-specific opcode in supposedly arch-neutral IR!");
         }
 
+        Opcode::AtomicRmw => {
+            let op = match ctx.data(iri) {
+                &InstructionData::AtomicRmw { op, .. } => op,
+                _ => unreachable!(),
+            };
+            let size = int_ty_to_sizeB(ty.unwrap());
+            let regP = ctx.input(iri, 0);
+            let regX = ctx.input(iri, 1);
+            let regD = ctx.output(iri, 0);
+            let addr = ip_Addr_IR(0, regP);
+            match op {
+                AtomicRmwOp::Add => {
+                    ctx.emit(i_Mov_R_R(true, regX, regD));
+                    ctx.emit(i_LockXadd(size, regD, addr));
+                }
+                AtomicRmwOp::Sub => {
+                    // Add the negated operand.
+                    ctx.emit(i_Imm_R(false, 0, regD));
+                    ctx.emit(i_Alu_RMI_R(true, RMI_R_Op::Sub, ip_RMI_R(regX), regD));
+                    ctx.emit(i_LockXadd(size, regD, addr));
+                }
+                AtomicRmwOp::Xchg => {
+                    ctx.emit(i_Mov_R_R(true, regX, regD));
+                    ctx.emit(i_Xchg_R_M(size, regD, addr));
+                }
+                AtomicRmwOp::And | AtomicRmwOp::Or | AtomicRmwOp::Xor => {
+                    let aluOp = match op {
+                        AtomicRmwOp::And => RMI_R_Op::And,
+                        AtomicRmwOp::Or => RMI_R_Op::Or,
+                        _ => RMI_R_Op::Xor,
+                    };
+                    // The cmpxchg loop works on fixed registers; see
+                    // Inst::AtomicRmwSeq.
+                    ctx.emit(i_Mov_R_R(true, regP, Writable::from_reg(reg_R9())));
+                    ctx.emit(i_Mov_R_R(true, regX, Writable::from_reg(reg_R10())));
+                    ctx.emit(i_AtomicRmwSeq(size, aluOp));
+                    ctx.emit(i_Mov_R_R(true, reg_RAX(), regD));
+                }
+            }
+        }
+
+        Opcode::AtomicCas => {
+            let size = int_ty_to_sizeB(ty.unwrap());
+            let regP = ctx.input(iri, 0);
+            let regE = ctx.input(iri, 1);
+            let regX = ctx.input(iri, 2);
+            let regD = ctx.output(iri, 0);
+            ctx.emit(i_Mov_R_R(true, regE, Writable::from_reg(reg_RAX())));
+            ctx.emit(i_LockCmpxchg(size, regX, ip_Addr_IR(0, regP)));
+            ctx.emit(i_Mov_R_R(true, reg_RAX(), regD));
+        }
+
+        Opcode::AtomicLoad => {
+            // Plain loads already have acquire semantics on x86.
+            let offset = match ctx.data(iri) {
+                &InstructionData::Load { offset, .. } => i32::from(offset),
+                _ => unreachable!(),
+            };
+            let regP = ctx.input(iri, 0);
+            let regD = ctx.output(iri, 0);
+            let addr = ip_Addr_IR(offset as u32, regP);
+            match int_ty_to_sizeB(ty.unwrap()) {
+                1 => ctx.emit(i_MovZX_M_R(ExtMode::BL, addr, regD)),
+                2 => ctx.emit(i_MovZX_M_R(ExtMode::WL, addr, regD)),
+                4 => ctx.emit(i_MovZX_M_R(ExtMode::LQ, addr, regD)),
+                _ => ctx.emit(i_Mov64_M_R(addr, regD)),
+            }
+        }
+
+        Opcode::AtomicStore => {
+            // A sequentially consistent store needs a full barrier after a
+            // plain store; an xchg, which is implicitly locked, does both.
+            let offset = match ctx.data(iri) {
+                &InstructionData::Store { offset, .. } => i32::from(offset),
+                _ => unreachable!(),
+            };
+            let size = int_ty_to_sizeB(ctx.input_ty(iri, 0));
+            let regX = ctx.input(iri, 0);
+            let regP = ctx.input(iri, 1);
+            let tmp = ctx.tmp(RegClass::I64, types::I64);
+            ctx.emit(i_Mov_R_R(true, regX, tmp));
+            ctx.emit(i_Xchg_R_M(size, tmp, ip_Addr_IR(offset as u32, regP)));
+        }
+
+        Opcode::Fence => {
+            ctx.emit(i_Mfence());
+        }
+
+        Opcode::ReturnCall | Opcode::ReturnCallIndirect => {
+            let arg_regs = match get_arg_regs(ctx.call_sig(iri).unwrap()) {
+                Some(regs) => regs,
+                None => {
+                    return Err(CodegenError::Unsupported(
+                        "tail calls with arguments on the stack".to_string(),
+                    ));
+                }
+            };
+            let args_start = if op == Opcode::ReturnCall { 0 } else { 1 };
+            assert!(ctx.num_inputs(iri) - args_start == arg_regs.len());
+
+            // The callee's address is kept in r11 until the jump, since the
+            // epilogue inserted before it restores the callee-saved registers.
+            let r11 = reg_R11();
+            if op == Opcode::ReturnCallIndirect {
+                let callee = ctx.input(iri, 0);
+                ctx.emit(i_Mov_R_R(true, callee, Writable::from_reg(r11)));
+            }
+            let mut uses = Set::empty();
+            for (i, arg_reg) in arg_regs.into_iter().enumerate() {
+                let src = ctx.input(iri, args_start + i);
+                ctx.emit(i_Mov_R_R(true, src, Writable::from_reg(arg_reg)));
+                uses.insert(arg_reg);
+            }
+            if op == Opcode::ReturnCall {
+                let dest = ctx.call_target(iri).unwrap().clone();
+                ctx.emit(i_TailCallKnown(dest, uses));
+            } else {
+                ctx.emit(i_TailCallUnknown(r11, uses));
+            }
+        }
+
         _ => panic!("Unsupported opcode!"),
     }
 
     if unimplemented {
         panic!("lower_insn_to_regs(x64): can't reduce: {:?}", ctx.data(iri));
     }
+
+    Ok(())
 }
 
 //=============================================================================
@@ -653,8 +805,8 @@ fn lower_insn_to_regs<'a>(ctx: Ctx<'a>, iri: IRInst) {
 impl LowerBackend for X64Backend {
     type MInst = Inst;
 
-    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) {
-        lower_insn_to_regs(ctx, ir_inst);
+    fn lower<C: LowerCtx<Inst>>(&self, ctx: &mut C, ir_inst: IRInst) -> CodegenResult<()> {
+        lower_insn_to_regs(ctx, ir_inst, &self.flags)
     }
 
     fn lower_branch_group<C: LowerCtx<Inst>>(
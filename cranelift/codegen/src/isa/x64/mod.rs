@@ -4,7 +4,11 @@
 
 use crate::binemit::{CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink};
 use crate::ir::Function;
-use crate::machinst::{compile, MachBackend, MachCompileResult, ShowWithRRU, VCode};
+use crate::isa::TargetIsa;
+use crate::machinst::TargetIsaAdapter;
+use crate::machinst::{
+    compile, spill_references, MachBackend, MachCompileResult, ShowWithRRU, VCode,
+};
 use crate::result::CodegenResult;
 use crate::settings;
 
@@ -31,26 +35,40 @@ pub struct X64Backend {
 impl X64Backend {
     /// Create a new X64 backend.
     pub fn new() -> X64Backend {
-        X64Backend {
-            flags: settings::Flags::new(settings::builder()),
-        }
+        Self::new_with_flags(settings::Flags::new(settings::builder()))
     }
 
-    fn compile_vcode(&self, mut func: Function) -> VCode<inst::Inst> {
+    /// Create a new X64 backend with the given (shared) flags.
+    pub fn new_with_flags(flags: settings::Flags) -> X64Backend {
+        X64Backend { flags }
+    }
+
+    fn compile_vcode(&self, mut func: Function) -> CodegenResult<VCode<inst::Inst>> {
         // This performs lowering to VCode, register-allocates the code, computes
         // block layout and finalizes branches. The result is ready for binary emission.
+        let ref_slots = if self.flags.enable_safepoints() {
+            spill_references(&mut func)
+        } else {
+            vec![]
+        };
         let abi = Box::new(abi::X64ABIBody::new(&func));
-        compile::compile::<X64Backend>(&mut func, self, abi)
+        compile::compile::<X64Backend>(&mut func, self, abi, ref_slots)
     }
 }
 
+/// Create a `TargetIsa` wrapping an X64 backend configured with the given
+/// shared flags.
+pub fn isa_adapter(_triple: Triple, flags: settings::Flags) -> Box<dyn TargetIsa> {
+    Box::new(TargetIsaAdapter::new(X64Backend::new_with_flags(flags)))
+}
+
 impl MachBackend for X64Backend {
     fn compile_function(
         &self,
         func: Function,
         want_disasm: bool,
     ) -> CodegenResult<MachCompileResult> {
-        let vcode = self.compile_vcode(func);
+        let vcode = self.compile_vcode(func)?;
         let sections = vcode.emit();
         let frame_size = vcode.frame_size();
 
@@ -42,6 +42,7 @@ pub fn isa_builder(triple: Triple) -> IsaBuilder {
         triple,
         setup: Some(settings::builder()),
         constructor: Some(isa_constructor),
+        mach_constructor: None,
         wrapped: None,
     }
 }
@@ -64,6 +64,7 @@ pub use cranelift_entity as entity;
 
 pub mod binemit;
 pub mod cfg_printer;
+pub mod const_eval;
 pub mod cursor;
 pub mod dbg;
 pub mod dominator_tree;
@@ -98,6 +99,7 @@ mod predicates;
 mod redundant_reload_remover;
 mod regalloc;
 mod result;
+mod sccp;
 mod scoped_hash_map;
 mod simple_gvn;
 mod simple_preopt;
@@ -1,5 +1,6 @@
 //! ABI definitions.
 
+use crate::binemit::Stackmap;
 use crate::ir;
 use crate::ir::StackSlot;
 use crate::machinst::*;
@@ -81,10 +82,20 @@ pub trait ABIBody<I: VCodeInst> {
     /// likely closely related.
     fn gen_epilogue(&self) -> Vec<I>;
 
+    /// Generate an epilogue to precede a tail call, post-regalloc. This tears
+    /// down the frame like `gen_epilogue`, but doesn't return: the tail call
+    /// that follows it leaves the function instead.
+    fn gen_tail_call_epilogue(&self) -> Vec<I>;
+
     /// Returns the full frame size for the given function, after prologue emission has run. This
     /// comprises the spill space, incoming argument space, alignment padding, etc.
     fn frame_size(&self) -> u32;
 
+    /// Returns the stack map of a safepoint in the function body, in which the
+    /// given stack slots hold references. Like `frame_size`, this is only
+    /// valid after prologue emission has run.
+    fn stackmap(&self, ref_slots: &[StackSlot]) -> Stackmap;
+
     /// Get the spill-slot size.
     fn get_spillslot_size(&self, rc: RegClass, ty: Type) -> u32;
 
@@ -128,4 +139,12 @@ pub trait ABICall<I: VCodeInst> {
     /// values are "defined" (to the regalloc) but "undefined" in every other
     /// sense.)
     fn gen_call(&self) -> I;
+
+    /// Generate a tail call, which must be the last instruction of its block.
+    ///
+    /// The returned instruction uses the argument registers, like the one from
+    /// `gen_call`, but never returns, so it defines no registers. It's
+    /// preceded by the caller's epilogue once registers are allocated, so the
+    /// callee returns directly to the caller's caller.
+    fn gen_tail_call(&self) -> I;
 }
@@ -1,23 +1,26 @@
 //! Compilation backend pipeline: optimized IR to VCode / binemit.
 
-use crate::ir::Function;
+use crate::ir::{Function, StackSlot};
 use crate::machinst::*;
+use crate::result::CodegenResult;
 
 use log::debug;
 use regalloc::{allocate_registers, RegAllocAlgorithm};
 
 /// Compile the given function down to VCode with allocated registers, ready
-/// for binary emission.
+/// for binary emission. `ref_slots` are the stack slots holding references
+/// at safepoints, as returned by `spill_references`.
 pub fn compile<B: LowerBackend>(
     f: &mut Function,
     b: &B,
     abi: Box<dyn ABIBody<B::MInst>>,
-) -> VCode<B::MInst>
+    ref_slots: Vec<StackSlot>,
+) -> CodegenResult<VCode<B::MInst>>
 where
     B::MInst: ShowWithRRU,
 {
     // This lowers the CL IR.
-    let mut vcode = Lower::new(f, abi).lower(b);
+    let mut vcode = Lower::new(f, abi, ref_slots).lower(b)?;
 
     let universe = &B::MInst::reg_universe();
 
@@ -47,5 +50,5 @@ where
 
     //println!("{}\n", vcode.show_rru(Some(&B::MInst::reg_universe())));
 
-    vcode
+    Ok(vcode)
 }
@@ -7,13 +7,14 @@ use crate::dce::has_side_effect;
 use crate::entity::SecondaryMap;
 use crate::ir::{
     Block, ExternalName, Function, GlobalValueData, Inst, InstructionData, Opcode, Signature,
-    SourceLoc, Type, Value, ValueDef,
+    SourceLoc, StackSlot, Type, Value, ValueDef,
 };
 use crate::isa::registers::RegUnit;
 use crate::machinst::{
     ABIBody, BlockIndex, MachInst, MachInstEmit, VCode, VCodeBuilder, VCodeInst,
 };
 use crate::num_uses::NumUses;
+use crate::result::CodegenResult;
 
 use regalloc::Function as RegallocFunction;
 use regalloc::{RealReg, Reg, RegClass, VirtualReg, Writable};
@@ -74,6 +75,8 @@ pub trait LowerCtx<I> {
     fn symbol_value<'b>(&'b self, ir_inst: Inst) -> Option<(&'b ExternalName, i64)>;
     /// Get the source location for a given instruction.
     fn srcloc(&self, ir_inst: Inst) -> SourceLoc;
+    /// Get the ABI object of the function being lowered.
+    fn abi(&mut self) -> &dyn ABIBody<I>;
 }
 
 /// A machine backend.
@@ -84,7 +87,10 @@ pub trait LowerBackend {
     /// Lower a single instruction. Instructions are lowered in reverse order.
     /// This function need not handle branches; those are always passed to
     /// `lower_branch_group` below.
-    fn lower<C: LowerCtx<Self::MInst>>(&self, ctx: &mut C, inst: Inst);
+    ///
+    /// Returns `CodegenError::Unsupported` for instructions the backend can't
+    /// lower yet.
+    fn lower<C: LowerCtx<Self::MInst>>(&self, ctx: &mut C, inst: Inst) -> CodegenResult<()>;
 
     /// Lower a block-terminating group of branches (which together can be seen as one
     /// N-way branch), given a vcode BlockIndex for each target.
@@ -141,8 +147,12 @@ enum GenerateReturn {
 
 impl<'a, I: VCodeInst> Lower<'a, I> {
     /// Prepare a new lowering context for the given IR function.
-    pub fn new(f: &'a Function, abi: Box<dyn ABIBody<I>>) -> Lower<'a, I> {
-        let mut vcode = VCodeBuilder::new(abi);
+    pub fn new(
+        f: &'a Function,
+        abi: Box<dyn ABIBody<I>>,
+        ref_slots: Vec<StackSlot>,
+    ) -> Lower<'a, I> {
+        let mut vcode = VCodeBuilder::new(abi, ref_slots);
 
         let num_uses = NumUses::compute(f).take_uses();
 
@@ -224,7 +234,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
     }
 
     /// Lower the function.
-    pub fn lower<B: LowerBackend<MInst = I>>(mut self, backend: &B) -> VCode<I> {
+    pub fn lower<B: LowerBackend<MInst = I>>(mut self, backend: &B) -> CodegenResult<VCode<I>> {
         // Work backward (reverse block order, reverse through each block), skipping insns with zero
         // uses.
         let mut bbs: SmallVec<[Block; 16]> = self.f.layout.blocks().collect();
@@ -334,7 +344,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
                     let num_uses = self.num_uses[inst];
                     let side_effect = has_side_effect(self.f, inst);
                     if side_effect || num_uses > 0 {
-                        backend.lower(&mut self, inst);
+                        backend.lower(&mut self, inst)?;
                         self.vcode.end_ir_inst();
                     } else {
                         // If we're skipping the instruction, we need to dec-ref
@@ -435,7 +445,7 @@ impl<'a, I: VCodeInst> Lower<'a, I> {
         }
 
         // Now that we've emitted all instructions into the VCodeBuilder, let's build the VCode.
-        self.vcode.build()
+        Ok(self.vcode.build())
     }
 
     /// Reduce the use-count of an IR instruction. Use this when, e.g., isel incorporates the
@@ -631,6 +641,11 @@ impl<'a, I: VCodeInst> LowerCtx<I> for Lower<'a, I> {
     fn srcloc(&self, ir_inst: Inst) -> SourceLoc {
         self.f.srclocs[ir_inst]
     }
+
+    /// Get the ABI object of the function being lowered.
+    fn abi(&mut self) -> &dyn ABIBody<I> {
+        self.vcode.abi()
+    }
 }
 
 fn branch_target(inst: &InstructionData) -> Option<Block> {
@@ -19,6 +19,12 @@
 //!
 //! |    ir::Function                (SSA IR, machine-independent opcodes)
 //! |        |
+//! |        |  [reference spilling, if safepoints are enabled]
+//! |        |
+//! |    ir::Function                (reference-typed values are stored to
+//! |        |                        stack slots and reloaded around their
+//! |        |                        uses)
+//! |        |
 //! |        |  [lower]
 //! |        |
 //! |    VCode<arch_backend::Inst>   (machine instructions:
@@ -96,7 +102,8 @@
 #![allow(unused_imports)]
 
 use crate::binemit::{
-    CodeInfo, CodeOffset, CodeSink, MemoryCodeSink, RelocSink, StackmapSink, TrapSink,
+    CodeInfo, CodeOffset, CodeSink, FrameUnwindKind, FrameUnwindSink, MemoryCodeSink, RelocSink,
+    StackmapSink, TrapSink,
 };
 use crate::entity::EntityRef;
 use crate::entity::SecondaryMap;
@@ -135,6 +142,8 @@ pub mod sections;
 pub use sections::*;
 pub mod adapter;
 pub use adapter::*;
+pub mod safepoints;
+pub use safepoints::*;
 
 /// A machine instruction.
 pub trait MachInst: Clone + Debug {
@@ -157,6 +166,10 @@ pub trait MachInst: Clone + Debug {
     /// (ret/uncond/cond) and target if applicable.
     fn is_term<'a>(&'a self) -> MachTerminator<'a>;
 
+    /// Is this a safepoint, i.e. a call which needs a stack map describing the
+    /// references held by the frame?
+    fn is_safepoint(&self) -> bool;
+
     /// Generate a move.
     fn gen_move(to_reg: Writable<Reg>, from_reg: Reg) -> Self;
 
@@ -229,6 +242,9 @@ pub enum MachTerminator<'a> {
     None,
     /// A return instruction.
     Ret,
+    /// A tail call, which leaves the function like a return but is preceded
+    /// by the epilogue rather than replaced by it.
+    TailCall,
     /// An unconditional branch to another block.
     Uncond(BlockIndex),
     /// A conditional branch to one of two other blocks.
@@ -300,4 +316,14 @@ pub trait MachBackend {
         // TODO: this is what x86 specifies. Is this right for arm64?
         IntCC::UnsignedLessThan
     }
+
+    /// Emit unwind information for a function compiled by this backend.
+    fn emit_unwind_info(
+        &self,
+        _result: &MachCompileResult,
+        _kind: FrameUnwindKind,
+        _sink: &mut dyn FrameUnwindSink,
+    ) {
+        // No-op by default
+    }
 }
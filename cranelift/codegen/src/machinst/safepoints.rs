@@ -0,0 +1,120 @@
+//! Keeping reference-typed values in stack slots across safepoints.
+//!
+//! The register allocator used by the MachInst backends doesn't track which
+//! registers and spill slots hold references, so it can't say where the live
+//! references are at a safepoint. Instead, before lowering, every reference
+//! value of a function containing a safepoint is given a stack slot of its
+//! own: it's stored there right after its definition and reloaded right
+//! before each of its uses. No reference then stays in a register across a
+//! call, and the stack map of any safepoint can simply list all of these
+//! slots.
+
+use crate::cursor::{Cursor, FuncCursor};
+use crate::entity::SecondaryMap;
+use crate::ir::{
+    Function, Inst, InstBuilder, StackSlot, StackSlotData, StackSlotKind, Value, ValueDef,
+};
+use crate::packed_option::PackedOption;
+
+use alloc::vec::Vec;
+
+/// Is the given instruction a safepoint, i.e. may the stack be scanned for
+/// references while it executes?
+fn is_safepoint(func: &Function, inst: Inst) -> bool {
+    let opcode = func.dfg[inst].opcode();
+    opcode.is_call() && !opcode.is_tail_call()
+}
+
+/// Moves every reference-typed value of `func` to a stack slot of its own, if
+/// `func` contains any safepoint, and returns these stack slots.
+///
+/// The slots are initialized to null on entry to the function, so they hold
+/// either null or a reference that was live at some point in the function.
+pub fn spill_references(func: &mut Function) -> Vec<StackSlot> {
+    let mut has_safepoint = false;
+    let mut refs: Vec<Value> = vec![];
+    for block in func.layout.blocks() {
+        refs.extend(
+            func.dfg
+                .block_params(block)
+                .iter()
+                .filter(|&&param| func.dfg.value_type(param).is_ref()),
+        );
+        for inst in func.layout.block_insts(block) {
+            has_safepoint |= is_safepoint(func, inst);
+            refs.extend(
+                func.dfg
+                    .inst_results(inst)
+                    .iter()
+                    .filter(|&&result| func.dfg.value_type(result).is_ref()),
+            );
+        }
+    }
+    if !has_safepoint || refs.is_empty() {
+        return vec![];
+    }
+
+    let mut slots = vec![];
+    let mut slot_of_value: SecondaryMap<Value, PackedOption<StackSlot>> = SecondaryMap::new();
+    for &value in &refs {
+        let size = func.dfg.value_type(value).bytes();
+        let slot = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, size));
+        slot_of_value[value] = slot.into();
+        slots.push(slot);
+    }
+
+    // Reload references right before they're used. Branches are lowered as a
+    // group, so their arguments are reloaded before the first of them.
+    let insts: Vec<Inst> = func
+        .layout
+        .blocks()
+        .flat_map(|block| func.layout.block_insts(block))
+        .collect();
+    let mut pos = FuncCursor::new(func);
+    for inst in insts {
+        let mut reload_point = inst;
+        if pos.func.dfg[inst].opcode().is_branch() {
+            while let Some(prev) = pos.func.layout.prev_inst(reload_point) {
+                if !pos.func.dfg[prev].opcode().is_branch() {
+                    break;
+                }
+                reload_point = prev;
+            }
+        }
+        for i in 0..pos.func.dfg.inst_args(inst).len() {
+            let arg = pos.func.dfg.inst_args(inst)[i];
+            let arg = pos.func.dfg.resolve_aliases(arg);
+            if let Some(slot) = slot_of_value[arg].expand() {
+                let ty = pos.func.dfg.value_type(arg);
+                pos.goto_inst(reload_point);
+                let reloaded = pos.ins().stack_load(ty, slot, 0);
+                pos.func.dfg.inst_args_mut(inst)[i] = reloaded;
+            }
+        }
+    }
+
+    // Store references right after they're defined.
+    for &value in &refs {
+        let slot = slot_of_value[value].unwrap();
+        match pos.func.dfg.value_def(value) {
+            ValueDef::Result(inst, _) => pos.goto_after_inst(inst),
+            ValueDef::Param(block, _) => pos.goto_first_insertion_point(block),
+        }
+        pos.ins().stack_store(value, slot, 0);
+    }
+
+    // Clear the slots which aren't set on entry, so that they never hold
+    // stale data from another frame.
+    let entry = pos.func.layout.entry_block().unwrap();
+    pos.goto_first_insertion_point(entry);
+    for &value in &refs {
+        if !pos.func.dfg.block_params(entry).contains(&value) {
+            let slot = slot_of_value[value].unwrap();
+            let ty = pos.func.dfg.value_type(value);
+            let null = pos.ins().null(ty);
+            pos.ins().stack_store(null, slot, 0);
+        }
+    }
+
+    slots
+}
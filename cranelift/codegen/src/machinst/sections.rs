@@ -3,7 +3,9 @@
 //! simultaneously, so we buffer the result in memory and hand off to the
 //! caller at the end of compilation.
 
-use crate::binemit::{Addend, CodeOffset, CodeSink, Reloc, RelocSink, StackmapSink, TrapSink};
+use crate::binemit::{
+    Addend, CodeOffset, CodeSink, Reloc, RelocSink, Stackmap, StackmapSink, TrapSink,
+};
 use crate::ir::{ExternalName, SourceLoc, TrapCode};
 
 use alloc::vec::Vec;
@@ -137,6 +139,9 @@ pub trait MachSectionOutput {
     /// Add a trap record at the current offset.
     fn add_trap(&mut self, loc: SourceLoc, code: TrapCode);
 
+    /// Add a stack map at the current offset.
+    fn add_stackmap(&mut self, stackmap: Stackmap);
+
     /// Align up to the given alignment.
     fn align_to(&mut self, align_to: CodeOffset) {
         assert!(align_to.is_power_of_two());
@@ -162,6 +167,8 @@ pub struct MachSection {
     pub relocs: Vec<MachReloc>,
     /// Any trap records referring to this section.
     pub traps: Vec<MachTrap>,
+    /// Any stack maps referring to this section.
+    pub stackmaps: Vec<MachStackmap>,
 }
 
 impl MachSection {
@@ -173,6 +180,7 @@ impl MachSection {
             data: vec![],
             relocs: vec![],
             traps: vec![],
+            stackmaps: vec![],
         }
     }
 
@@ -184,6 +192,7 @@ impl MachSection {
 
         let mut next_reloc = 0;
         let mut next_trap = 0;
+        let mut next_stackmap = 0;
         for (idx, byte) in self.data.iter().enumerate() {
             if next_reloc < self.relocs.len() {
                 let reloc = &self.relocs[next_reloc];
@@ -199,6 +208,13 @@ impl MachSection {
                     next_trap += 1;
                 }
             }
+            if next_stackmap < self.stackmaps.len() {
+                let stackmap = &self.stackmaps[next_stackmap];
+                if stackmap.offset == idx as CodeOffset {
+                    sink.add_mach_stackmap(stackmap.stackmap.clone());
+                    next_stackmap += 1;
+                }
+            }
             sink.put1(*byte);
         }
     }
@@ -240,6 +256,13 @@ impl MachSectionOutput for MachSection {
             code,
         });
     }
+
+    fn add_stackmap(&mut self, stackmap: Stackmap) {
+        self.stackmaps.push(MachStackmap {
+            offset: self.data.len() as CodeOffset,
+            stackmap,
+        });
+    }
 }
 
 /// A MachSectionOutput implementation that records only size.
@@ -287,6 +310,8 @@ impl MachSectionOutput for MachSectionSize {
     fn add_reloc(&mut self, _: Reloc, _: &ExternalName, _: Addend) {}
 
     fn add_trap(&mut self, _: SourceLoc, _: TrapCode) {}
+
+    fn add_stackmap(&mut self, _: Stackmap) {}
 }
 
 /// A relocation resulting from a compilation.
@@ -312,3 +337,12 @@ pub struct MachTrap {
     /// The trap code.
     pub code: TrapCode,
 }
+
+/// A stack map resulting from a compilation.
+pub struct MachStackmap {
+    /// The offset of the safepoint instruction, *relative to the containing
+    /// section*.
+    pub offset: CodeOffset,
+    /// The stack map.
+    pub stackmap: Stackmap,
+}
@@ -113,6 +113,9 @@ pub struct VCode<I: VCodeInst> {
 
     /// ABI object.
     abi: Box<dyn ABIBody<I>>,
+
+    /// Stack slots holding references at safepoints.
+    ref_slots: Vec<ir::StackSlot>,
 }
 
 /// A builder for a VCode function body. This builder is designed for the
@@ -146,8 +149,8 @@ pub struct VCodeBuilder<I: VCodeInst> {
 
 impl<I: VCodeInst> VCodeBuilder<I> {
     /// Create a new VCodeBuilder.
-    pub fn new(abi: Box<dyn ABIBody<I>>) -> VCodeBuilder<I> {
-        let vcode = VCode::new(abi);
+    pub fn new(abi: Box<dyn ABIBody<I>>, ref_slots: Vec<ir::StackSlot>) -> VCodeBuilder<I> {
+        let vcode = VCode::new(abi, ref_slots);
         VCodeBuilder {
             vcode,
             bb_insns: SmallVec::new(),
@@ -229,7 +232,7 @@ impl<I: VCodeInst> VCodeBuilder<I> {
     /// Push an instruction for the current BB and current IR inst within the BB.
     pub fn push(&mut self, insn: I) {
         match insn.is_term() {
-            MachTerminator::None | MachTerminator::Ret => {}
+            MachTerminator::None | MachTerminator::Ret | MachTerminator::TailCall => {}
             MachTerminator::Uncond(target) => {
                 self.vcode.block_succs.push(target);
             }
@@ -321,7 +324,7 @@ fn look_through_trivial_jumps<I: VCodeInst>(vcode: &VCode<I>, block: BlockIndex)
 
 impl<I: VCodeInst> VCode<I> {
     /// New empty VCode.
-    fn new(abi: Box<dyn ABIBody<I>>) -> VCode<I> {
+    fn new(abi: Box<dyn ABIBody<I>>, ref_slots: Vec<ir::StackSlot>) -> VCode<I> {
         VCode {
             liveins: abi.liveins(),
             liveouts: abi.liveouts(),
@@ -344,6 +347,7 @@ impl<I: VCodeInst> VCode<I> {
             jt_offsets: vec![],
             jt_entries: vec![],
             abi,
+            ref_slots,
         }
     }
 
@@ -411,13 +415,21 @@ impl<I: VCodeInst> VCode<I> {
                     continue;
                 }
 
-                // Whenever encountering a return instruction, replace it
-                // with the epilogue.
-                let is_ret = insn.is_term() == MachTerminator::Ret;
-                if is_ret {
-                    final_insns.extend(self.abi.gen_epilogue().into_iter());
-                } else {
-                    final_insns.push(insn.clone());
+                match insn.is_term() {
+                    // Whenever encountering a return instruction, replace it
+                    // with the epilogue.
+                    MachTerminator::Ret => {
+                        final_insns.extend(self.abi.gen_epilogue().into_iter());
+                    }
+                    // A tail call leaves the function too, but jumps to the
+                    // callee once the frame has been torn down.
+                    MachTerminator::TailCall => {
+                        final_insns.extend(self.abi.gen_tail_call_epilogue().into_iter());
+                        final_insns.push(insn.clone());
+                    }
+                    _ => {
+                        final_insns.push(insn.clone());
+                    }
                 }
             }
 
@@ -561,6 +573,14 @@ impl<I: VCodeInst> VCode<I> {
         let const_idx = sections.add_section(self.constants_start, self.constants_size);
         let (code_section, const_section) = sections.two_sections(code_idx, const_idx);
 
+        // All safepoints share a stack map, since references are kept in the
+        // same stack slots throughout the function.
+        let stackmap = if self.ref_slots.is_empty() {
+            None
+        } else {
+            Some(self.abi.stackmap(&self.ref_slots))
+        };
+
         for block in &self.final_block_order {
             let new_offset = I::align_basic_block(code_section.cur_offset_from_start());
             while new_offset > code_section.cur_offset_from_start() {
@@ -572,7 +592,13 @@ impl<I: VCodeInst> VCode<I> {
 
             let (start, end) = self.block_ranges[*block as usize];
             for iix in start..end {
-                self.insts[iix as usize].emit(code_section, const_section, &self.jt_offsets[..]);
+                let insn = &self.insts[iix as usize];
+                if let Some(stackmap) = &stackmap {
+                    if insn.is_safepoint() {
+                        code_section.add_stackmap(stackmap.clone());
+                    }
+                }
+                insn.emit(code_section, const_section, &self.jt_offsets[..]);
             }
         }
 
@@ -637,6 +663,9 @@ impl<I: VCodeInst> RegallocFunction for VCode<I> {
 
     fn is_ret(&self, insn: InstIx) -> bool {
         match self.insts[insn.get() as usize].is_term() {
+            // A tail call isn't a return as far as the register allocator is
+            // concerned: the return values are set by the callee, so they
+            // aren't live at the tail call.
             MachTerminator::Ret => true,
             _ => false,
         }
@@ -0,0 +1,680 @@
+//! Sparse conditional constant propagation.
+//!
+//! This is the Wegman-Zadeck algorithm: every SSA value starts out undefined and every block
+//! starts out unreachable. Blocks become executable when a feasible edge into them is found, and
+//! values only flow into block parameters along executable edges, so a value that is constant on
+//! every path that can actually be taken is found even across joins and loops.
+//!
+//! Once the analysis has reached a fixed point the function is rewritten:
+//!
+//! - instructions with a single constant result are replaced by a constant instruction,
+//! - conditional branches with a constant condition are removed or turned into jumps,
+//! - blocks that were never found to be executable are removed.
+//!
+//! Like `fold_constants`, this does not remove instructions that become unused; it's assumed that
+//! the DCE pass will take care of them.
+#![allow(clippy::float_arithmetic)]
+
+use crate::const_eval::{icmp, mask, sext};
+use crate::cursor::{Cursor, FuncCursor};
+use crate::entity::{EntitySet, SecondaryMap};
+use crate::ir::instructions::BranchInfo;
+use crate::ir::{self, immediates, Block, Inst, InstBuilder, InstructionData, Opcode, Type, Value};
+use crate::timing;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+
+/// A constant known at compile time.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Const {
+    /// An integer, zero-extended from the width of its type.
+    Int(u64),
+    Bool(bool),
+    /// The bits of an `f32`. Floats are compared bitwise so that NaNs meet consistently.
+    Ieee32(u32),
+    /// The bits of an `f64`.
+    Ieee64(u64),
+}
+
+impl Const {
+    fn truthiness(self) -> bool {
+        match self {
+            Const::Bool(b) => b,
+            Const::Int(x) => x != 0,
+            Const::Ieee32(_) | Const::Ieee64(_) => panic!("floats have no truthiness"),
+        }
+    }
+}
+
+/// The lattice of values tracked by the analysis.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LatticeValue {
+    /// Nothing is known yet: the definition has not been reached.
+    Top,
+    /// The value is this constant on every executable path.
+    Const(Const),
+    /// The value may differ at run time.
+    Bottom,
+}
+
+impl Default for LatticeValue {
+    fn default() -> Self {
+        LatticeValue::Top
+    }
+}
+
+impl LatticeValue {
+    fn meet(self, other: Self) -> Self {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Top, x) | (x, Top) => x,
+            (Const(a), Const(b)) if a == b => Const(a),
+            _ => Bottom,
+        }
+    }
+
+    /// Combine the lattice values of an operation's operands: if any is overdefined the result is
+    /// too, otherwise the operation can't be evaluated until every operand is known.
+    fn operands<F>(values: &[Self], eval: F) -> Self
+    where
+        F: FnOnce(&[Const]) -> Self,
+    {
+        let mut consts = [Const::Int(0); 3];
+        for (slot, value) in consts.iter_mut().zip(values) {
+            match *value {
+                LatticeValue::Bottom => return LatticeValue::Bottom,
+                LatticeValue::Top => {}
+                LatticeValue::Const(c) => *slot = c,
+            }
+        }
+        if values.iter().any(|v| *v == LatticeValue::Top) {
+            return LatticeValue::Top;
+        }
+        eval(&consts[..values.len()])
+    }
+}
+
+/// Is `ty` a type whose values the analysis can represent?
+fn is_tracked(ty: Type) -> bool {
+    (ty.is_int() && ty.bits() <= 64) || ty.is_bool() || ty.is_float()
+}
+
+fn int(ty: Type, x: u64) -> LatticeValue {
+    LatticeValue::Const(Const::Int(mask(ty, x)))
+}
+
+fn boolean(b: bool) -> LatticeValue {
+    LatticeValue::Const(Const::Bool(b))
+}
+
+fn evaluate_unary(opcode: Opcode, ty: Type, arg_ty: Type, imm: Const) -> LatticeValue {
+    use self::Const::*;
+    let bits = u32::from(ty.bits());
+    match (opcode, imm) {
+        (Opcode::Copy, c) => LatticeValue::Const(c),
+        (Opcode::Bnot, Int(x)) => int(ty, !x),
+        (Opcode::Bnot, Bool(b)) => boolean(!b),
+        (Opcode::Ineg, Int(x)) => int(ty, x.wrapping_neg()),
+        (Opcode::Clz, Int(x)) => int(ty, u64::from(x.leading_zeros() - (64 - bits))),
+        (Opcode::Cls, Int(x)) => {
+            let s = sext(ty, x);
+            let s = if s < 0 { !s } else { s };
+            int(ty, u64::from(s.leading_zeros() - (64 - bits) - 1))
+        }
+        (Opcode::Ctz, Int(x)) => int(ty, u64::from(x.trailing_zeros().min(bits))),
+        (Opcode::Popcnt, Int(x)) => int(ty, u64::from(x.count_ones())),
+        (Opcode::Bitrev, Int(x)) => int(ty, x.reverse_bits() >> (64 - bits)),
+        (Opcode::Uextend, Int(x)) | (Opcode::Ireduce, Int(x)) => int(ty, x),
+        (Opcode::Sextend, Int(x)) => int(ty, sext(arg_ty, x) as u64),
+        (Opcode::Bint, Bool(b)) => int(ty, u64::from(b)),
+        (Opcode::Bmask, Bool(b)) => int(ty, if b { !0 } else { 0 }),
+        (Opcode::Bextend, Bool(b)) | (Opcode::Breduce, Bool(b)) => boolean(b),
+        (Opcode::Fneg, Ieee32(x)) => LatticeValue::Const(Ieee32(x ^ (1 << 31))),
+        (Opcode::Fneg, Ieee64(x)) => LatticeValue::Const(Ieee64(x ^ (1 << 63))),
+        (Opcode::Fabs, Ieee32(x)) => LatticeValue::Const(Ieee32(x & !(1 << 31))),
+        (Opcode::Fabs, Ieee64(x)) => LatticeValue::Const(Ieee64(x & !(1 << 63))),
+        _ => LatticeValue::Bottom,
+    }
+}
+
+fn evaluate_binary(opcode: Opcode, ty: Type, imm0: Const, imm1: Const) -> LatticeValue {
+    use self::Const::*;
+    match (imm0, imm1) {
+        (Int(x), Int(y)) => evaluate_binary_int(opcode, ty, x, y),
+        (Bool(x), Bool(y)) => match opcode {
+            Opcode::Band => boolean(x & y),
+            Opcode::Bor => boolean(x | y),
+            Opcode::Bxor => boolean(x ^ y),
+            Opcode::BandNot => boolean(x & !y),
+            Opcode::BorNot => boolean(x | !y),
+            Opcode::BxorNot => boolean(x ^ !y),
+            _ => LatticeValue::Bottom,
+        },
+        (Ieee32(x), Ieee32(y)) => {
+            let (x, y) = (f32::from_bits(x), f32::from_bits(y));
+            let r = match opcode {
+                Opcode::Fadd => x + y,
+                Opcode::Fsub => x - y,
+                Opcode::Fmul => x * y,
+                Opcode::Fdiv => x / y,
+                _ => return LatticeValue::Bottom,
+            };
+            LatticeValue::Const(Ieee32(r.to_bits()))
+        }
+        (Ieee64(x), Ieee64(y)) => {
+            let (x, y) = (f64::from_bits(x), f64::from_bits(y));
+            let r = match opcode {
+                Opcode::Fadd => x + y,
+                Opcode::Fsub => x - y,
+                Opcode::Fmul => x * y,
+                Opcode::Fdiv => x / y,
+                _ => return LatticeValue::Bottom,
+            };
+            LatticeValue::Const(Ieee64(r.to_bits()))
+        }
+        _ => LatticeValue::Bottom,
+    }
+}
+
+fn evaluate_binary_int(opcode: Opcode, ty: Type, x: u64, y: u64) -> LatticeValue {
+    let bits = u32::from(ty.bits());
+    let (sx, sy) = (sext(ty, x), sext(ty, y));
+    let (smin, smax) = (sext(ty, 1 << (bits - 1)), sext(ty, mask(ty, !0) >> 1));
+    let clamp = |r: i128| int(ty, r.max(i128::from(smin)).min(i128::from(smax)) as u64);
+    let amt = (y as u32) & (bits - 1);
+    match opcode {
+        Opcode::Iadd => int(ty, x.wrapping_add(y)),
+        Opcode::Isub => int(ty, x.wrapping_sub(y)),
+        Opcode::Imul => int(ty, x.wrapping_mul(y)),
+        Opcode::Umulhi => int(ty, ((u128::from(x) * u128::from(y)) >> bits) as u64),
+        Opcode::Smulhi => int(ty, ((i128::from(sx) * i128::from(sy)) >> bits) as u64),
+        // Division by zero and signed overflow trap at run time, so they must not be folded.
+        Opcode::Udiv | Opcode::Urem | Opcode::Sdiv | Opcode::Srem if y == 0 => LatticeValue::Bottom,
+        Opcode::Sdiv if sx == smin && sy == -1 => LatticeValue::Bottom,
+        Opcode::Udiv => int(ty, x / y),
+        Opcode::Urem => int(ty, x % y),
+        Opcode::Sdiv => int(ty, (sx / sy) as u64),
+        Opcode::Srem => int(ty, sx.wrapping_rem(sy) as u64),
+        Opcode::UaddSat => int(ty, x.checked_add(y).map_or(!0, |r| r.min(mask(ty, !0)))),
+        Opcode::UsubSat => int(ty, x.saturating_sub(y)),
+        Opcode::SaddSat => clamp(i128::from(sx) + i128::from(sy)),
+        Opcode::SsubSat => clamp(i128::from(sx) - i128::from(sy)),
+        Opcode::AvgRound => int(ty, ((u128::from(x) + u128::from(y) + 1) >> 1) as u64),
+        Opcode::Imin => int(ty, sx.min(sy) as u64),
+        Opcode::Imax => int(ty, sx.max(sy) as u64),
+        Opcode::Umin => int(ty, x.min(y)),
+        Opcode::Umax => int(ty, x.max(y)),
+        Opcode::Band => int(ty, x & y),
+        Opcode::Bor => int(ty, x | y),
+        Opcode::Bxor => int(ty, x ^ y),
+        Opcode::BandNot => int(ty, x & !y),
+        Opcode::BorNot => int(ty, x | !y),
+        Opcode::BxorNot => int(ty, x ^ !y),
+        Opcode::Ishl => int(ty, x << amt),
+        Opcode::Ushr => int(ty, x >> amt),
+        Opcode::Sshr => int(ty, (sx >> amt) as u64),
+        Opcode::Rotl if amt == 0 => int(ty, x),
+        Opcode::Rotr if amt == 0 => int(ty, x),
+        Opcode::Rotl => int(ty, (x << amt) | (x >> (bits - amt))),
+        Opcode::Rotr => int(ty, (x >> amt) | (x << (bits - amt))),
+        _ => LatticeValue::Bottom,
+    }
+}
+
+/// Map an `_imm` opcode to the opcode it's a shorthand for. The boolean is true when the
+/// immediate is the left-hand operand.
+fn binary_imm_opcode(opcode: Opcode) -> Option<(Opcode, bool)> {
+    Some(match opcode {
+        Opcode::IaddImm => (Opcode::Iadd, false),
+        Opcode::ImulImm => (Opcode::Imul, false),
+        Opcode::UdivImm => (Opcode::Udiv, false),
+        Opcode::SdivImm => (Opcode::Sdiv, false),
+        Opcode::UremImm => (Opcode::Urem, false),
+        Opcode::SremImm => (Opcode::Srem, false),
+        Opcode::IrsubImm => (Opcode::Isub, true),
+        Opcode::BandImm => (Opcode::Band, false),
+        Opcode::BorImm => (Opcode::Bor, false),
+        Opcode::BxorImm => (Opcode::Bxor, false),
+        Opcode::RotlImm => (Opcode::Rotl, false),
+        Opcode::RotrImm => (Opcode::Rotr, false),
+        Opcode::IshlImm => (Opcode::Ishl, false),
+        Opcode::UshrImm => (Opcode::Ushr, false),
+        Opcode::SshrImm => (Opcode::Sshr, false),
+        _ => return None,
+    })
+}
+
+struct Sccp {
+    /// The lattice value of every (non-alias) value in the function.
+    values: SecondaryMap<Value, LatticeValue>,
+    /// Instructions using each value.
+    uses: SecondaryMap<Value, Vec<Inst>>,
+    /// Blocks reached by a feasible edge.
+    executable: EntitySet<Block>,
+    /// Instructions reached by the analysis. This excludes the tail of a block following a
+    /// branch that is always taken.
+    visited: EntitySet<Inst>,
+    block_worklist: Vec<Block>,
+    inst_worklist: Vec<Inst>,
+}
+
+impl Sccp {
+    fn new(func: &ir::Function) -> Self {
+        let mut uses = SecondaryMap::<Value, Vec<Inst>>::new();
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                for &arg in func.dfg.inst_args(inst) {
+                    uses[func.dfg.resolve_aliases(arg)].push(inst);
+                }
+            }
+        }
+
+        Self {
+            values: SecondaryMap::new(),
+            uses,
+            executable: EntitySet::new(),
+            visited: EntitySet::new(),
+            block_worklist: Vec::new(),
+            inst_worklist: Vec::new(),
+        }
+    }
+
+    fn value(&self, dfg: &ir::DataFlowGraph, value: Value) -> LatticeValue {
+        self.values[dfg.resolve_aliases(value)]
+    }
+
+    /// Lower `value` in the lattice, revisiting its users if it changed.
+    fn update(&mut self, value: Value, new: LatticeValue) {
+        let old = self.values[value];
+        let new = old.meet(new);
+        if new != old {
+            self.values[value] = new;
+            self.inst_worklist.extend_from_slice(&self.uses[value]);
+        }
+    }
+
+    /// Mark an edge to `dest`, passing `args`, as feasible.
+    fn flow_edge(&mut self, dfg: &ir::DataFlowGraph, dest: Block, args: &[Value]) {
+        if self.executable.insert(dest) {
+            self.block_worklist.push(dest);
+        }
+        for (&param, &arg) in dfg.block_params(dest).iter().zip(args) {
+            let value = self.value(dfg, arg);
+            self.update(param, value);
+        }
+    }
+
+    fn analyze(&mut self, func: &ir::Function, entry: Block) {
+        for &param in func.dfg.block_params(entry) {
+            self.values[param] = LatticeValue::Bottom;
+        }
+        self.executable.insert(entry);
+        self.block_worklist.push(entry);
+
+        loop {
+            if let Some(block) = self.block_worklist.pop() {
+                self.visit_from(func, func.layout.first_inst(block));
+            } else if let Some(inst) = self.inst_worklist.pop() {
+                // Instructions that haven't been reached yet will be evaluated once they are.
+                if self.visited.contains(inst)
+                    && self.visit_inst(func, inst)
+                    && func.dfg[inst].opcode().is_branch()
+                {
+                    // A branch that was assumed to be always taken may now fall through.
+                    self.visit_from(func, func.layout.next_inst(inst));
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Visit the instructions of a block, starting at `inst`, until control can't continue.
+    fn visit_from(&mut self, func: &ir::Function, mut inst: Option<Inst>) {
+        while let Some(i) = inst {
+            self.visited.insert(i);
+            if !self.visit_inst(func, i) {
+                break;
+            }
+            inst = func.layout.next_inst(i);
+        }
+    }
+
+    /// Evaluate `inst`, returning whether control may continue to the next instruction.
+    fn visit_inst(&mut self, func: &ir::Function, inst: Inst) -> bool {
+        let dfg = &func.dfg;
+        if dfg[inst].opcode().is_branch() {
+            return self.visit_branch(func, inst);
+        }
+
+        let results = dfg.inst_results(inst);
+        let value = match results.len() {
+            0 => return true,
+            1 => self.evaluate(dfg, inst),
+            _ => LatticeValue::Bottom,
+        };
+        for &result in results {
+            self.update(result, value);
+        }
+        true
+    }
+
+    /// Evaluate the condition of a conditional branch: a constant `true` means the branch is
+    /// always taken.
+    fn branch_condition(&self, dfg: &ir::DataFlowGraph, inst: Inst) -> LatticeValue {
+        match dfg[inst] {
+            InstructionData::Branch { opcode, .. } => {
+                let arg = dfg.inst_args(inst)[0];
+                LatticeValue::operands(&[self.value(dfg, arg)], |c| {
+                    boolean(c[0].truthiness() == (opcode == Opcode::Brnz))
+                })
+            }
+            InstructionData::BranchIcmp { cond, .. } => {
+                let args = dfg.inst_args(inst);
+                let ty = dfg.value_type(args[0]);
+                let values = [self.value(dfg, args[0]), self.value(dfg, args[1])];
+                LatticeValue::operands(&values, |c| match (c[0], c[1]) {
+                    (Const::Int(x), Const::Int(y)) => boolean(icmp(cond, ty, x, y)),
+                    _ => LatticeValue::Bottom,
+                })
+            }
+            // Branches on CPU flags are never folded.
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    fn visit_branch(&mut self, func: &ir::Function, inst: Inst) -> bool {
+        let dfg = &func.dfg;
+        match dfg[inst] {
+            InstructionData::Jump { destination, .. } => {
+                self.flow_edge(dfg, destination, dfg.inst_args(inst));
+                false
+            }
+            InstructionData::BranchTable {
+                arg,
+                destination,
+                table,
+                ..
+            } => {
+                let jt = &func.jump_tables[table];
+                match self.value(dfg, arg) {
+                    LatticeValue::Top => {}
+                    LatticeValue::Const(Const::Int(index)) => {
+                        let dest = usize::try_from(index)
+                            .ok()
+                            .and_then(|i| jt.as_slice().get(i))
+                            .copied()
+                            .unwrap_or(destination);
+                        self.flow_edge(dfg, dest, &[]);
+                    }
+                    _ => {
+                        for &dest in jt.iter() {
+                            self.flow_edge(dfg, dest, &[]);
+                        }
+                        self.flow_edge(dfg, destination, &[]);
+                    }
+                }
+                false
+            }
+            InstructionData::IndirectJump { table, .. } => {
+                for &dest in func.jump_tables[table].iter() {
+                    self.flow_edge(dfg, dest, &[]);
+                }
+                false
+            }
+            _ => {
+                let (destination, args) = match dfg.analyze_branch(inst) {
+                    BranchInfo::SingleDest(destination, args) => (destination, args),
+                    _ => unreachable!("conditional branch with a single destination"),
+                };
+                match self.branch_condition(dfg, inst) {
+                    LatticeValue::Top => false,
+                    LatticeValue::Const(c) if c.truthiness() => {
+                        self.flow_edge(dfg, destination, args);
+                        false
+                    }
+                    LatticeValue::Const(_) => true,
+                    LatticeValue::Bottom => {
+                        self.flow_edge(dfg, destination, args);
+                        true
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluate an instruction with a single result.
+    fn evaluate(&self, dfg: &ir::DataFlowGraph, inst: Inst) -> LatticeValue {
+        let ty = dfg.value_type(dfg.first_result(inst));
+        let opcode = dfg[inst].opcode();
+        if !is_tracked(ty)
+            || opcode.can_load()
+            || opcode.can_store()
+            || opcode.is_call()
+            || opcode.other_side_effects()
+        {
+            return LatticeValue::Bottom;
+        }
+
+        match dfg[inst] {
+            InstructionData::UnaryImm {
+                opcode: Opcode::Iconst,
+                imm,
+            } => int(ty, imm.bits() as u64),
+            InstructionData::UnaryBool {
+                opcode: Opcode::Bconst,
+                imm,
+            } => boolean(imm),
+            InstructionData::UnaryIeee32 {
+                opcode: Opcode::F32const,
+                imm,
+            } => LatticeValue::Const(Const::Ieee32(imm.bits())),
+            InstructionData::UnaryIeee64 {
+                opcode: Opcode::F64const,
+                imm,
+            } => LatticeValue::Const(Const::Ieee64(imm.bits())),
+            InstructionData::Unary { opcode, arg } => {
+                let arg_ty = dfg.value_type(arg);
+                LatticeValue::operands(&[self.value(dfg, arg)], |c| {
+                    evaluate_unary(opcode, ty, arg_ty, c[0])
+                })
+            }
+            InstructionData::Binary { opcode, args } => {
+                let values = [self.value(dfg, args[0]), self.value(dfg, args[1])];
+                LatticeValue::operands(&values, |c| evaluate_binary(opcode, ty, c[0], c[1]))
+            }
+            InstructionData::BinaryImm { opcode, arg, imm } => match binary_imm_opcode(opcode) {
+                Some((opcode, reversed)) => {
+                    let imm = Const::Int(mask(ty, imm.bits() as u64));
+                    LatticeValue::operands(&[self.value(dfg, arg)], |c| {
+                        if reversed {
+                            evaluate_binary(opcode, ty, imm, c[0])
+                        } else {
+                            evaluate_binary(opcode, ty, c[0], imm)
+                        }
+                    })
+                }
+                None => LatticeValue::Bottom,
+            },
+            InstructionData::IntCompare { cond, args, .. } => {
+                let arg_ty = dfg.value_type(args[0]);
+                let values = [self.value(dfg, args[0]), self.value(dfg, args[1])];
+                LatticeValue::operands(&values, |c| match (c[0], c[1]) {
+                    (Const::Int(x), Const::Int(y)) => boolean(icmp(cond, arg_ty, x, y)),
+                    _ => LatticeValue::Bottom,
+                })
+            }
+            InstructionData::IntCompareImm { cond, arg, imm, .. } => {
+                let arg_ty = dfg.value_type(arg);
+                let y = mask(arg_ty, imm.bits() as u64);
+                LatticeValue::operands(&[self.value(dfg, arg)], |c| match c[0] {
+                    Const::Int(x) => boolean(icmp(cond, arg_ty, x, y)),
+                    _ => LatticeValue::Bottom,
+                })
+            }
+            InstructionData::Ternary {
+                opcode: Opcode::Select,
+                args,
+            } => match self.value(dfg, args[0]) {
+                LatticeValue::Top => LatticeValue::Top,
+                LatticeValue::Const(c) if c.truthiness() => self.value(dfg, args[1]),
+                LatticeValue::Const(_) => self.value(dfg, args[2]),
+                LatticeValue::Bottom => self.value(dfg, args[1]).meet(self.value(dfg, args[2])),
+            },
+            InstructionData::Ternary {
+                opcode: Opcode::Bitselect,
+                args,
+            } => {
+                let values = [
+                    self.value(dfg, args[0]),
+                    self.value(dfg, args[1]),
+                    self.value(dfg, args[2]),
+                ];
+                LatticeValue::operands(&values, |c| match (c[0], c[1], c[2]) {
+                    (Const::Int(c), Const::Int(x), Const::Int(y)) => int(ty, (x & c) | (y & !c)),
+                    _ => LatticeValue::Bottom,
+                })
+            }
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    /// Rewrite the function using the results of the analysis.
+    fn rewrite(&self, func: &mut ir::Function) {
+        let mut pos = FuncCursor::new(func);
+        while let Some(block) = pos.next_block() {
+            if !self.executable.contains(block) {
+                // Move the cursor out of the way before removing the block.
+                pos.prev_block();
+                while let Some(inst) = pos.func.layout.first_inst(block) {
+                    pos.func.layout.remove_inst(inst);
+                }
+                pos.func.layout.remove_block(block);
+                continue;
+            }
+
+            while let Some(inst) = pos.next_inst() {
+                if pos.func.dfg[inst].opcode().is_branch() {
+                    self.rewrite_branch(&mut pos, inst);
+                } else {
+                    self.rewrite_inst(&mut pos.func.dfg, inst);
+                }
+            }
+        }
+
+        self.prune_jump_tables(func);
+    }
+
+    fn rewrite_inst(&self, dfg: &mut ir::DataFlowGraph, inst: Inst) {
+        match dfg[inst].opcode() {
+            Opcode::Iconst | Opcode::Bconst | Opcode::F32const | Opcode::F64const => return,
+            _ => {}
+        }
+        if dfg.inst_results(inst).len() != 1 {
+            return;
+        }
+        let c = match self.values[dfg.first_result(inst)] {
+            LatticeValue::Const(c) => c,
+            _ => return,
+        };
+
+        let ty = dfg.value_type(dfg.first_result(inst));
+        match c {
+            Const::Int(x) => {
+                dfg.replace(inst).iconst(ty, sext(ty, x));
+            }
+            Const::Bool(b) => {
+                dfg.replace(inst).bconst(ty, b);
+            }
+            Const::Ieee32(bits) => {
+                dfg.replace(inst)
+                    .f32const(immediates::Ieee32::with_bits(bits));
+            }
+            Const::Ieee64(bits) => {
+                dfg.replace(inst)
+                    .f64const(immediates::Ieee64::with_bits(bits));
+            }
+        }
+    }
+
+    fn rewrite_branch(&self, pos: &mut FuncCursor, inst: Inst) {
+        let dfg = &pos.func.dfg;
+        let (destination, args) = match dfg[inst] {
+            InstructionData::BranchTable {
+                arg,
+                destination,
+                table,
+                ..
+            } => match self.value(dfg, arg) {
+                LatticeValue::Const(Const::Int(index)) => {
+                    let dest = usize::try_from(index)
+                        .ok()
+                        .and_then(|i| pos.func.jump_tables[table].as_slice().get(i))
+                        .copied()
+                        .unwrap_or(destination);
+                    pos.func.dfg.replace(inst).jump(dest, &[]);
+                    return;
+                }
+                _ => return,
+            },
+            InstructionData::Branch { .. } | InstructionData::BranchIcmp { .. } => {
+                match dfg.analyze_branch(inst) {
+                    BranchInfo::SingleDest(destination, args) => (destination, args.to_vec()),
+                    _ => unreachable!(),
+                }
+            }
+            _ => return,
+        };
+
+        match self.branch_condition(&pos.func.dfg, inst) {
+            LatticeValue::Const(c) if c.truthiness() => {
+                pos.func.dfg.replace(inst).jump(destination, &args);
+                // Remove the rest of the block, which is unreachable.
+                while let Some(next_inst) = pos.func.layout.next_inst(inst) {
+                    pos.func.layout.remove_inst(next_inst);
+                }
+            }
+            LatticeValue::Const(_) => {
+                pos.remove_inst_and_step_back();
+            }
+            _ => {}
+        }
+    }
+
+    /// Empty the jump tables that are no longer used and refer to removed blocks.
+    fn prune_jump_tables(&self, func: &mut ir::Function) {
+        let mut used = EntitySet::new();
+        for block in func.layout.blocks() {
+            for inst in func.layout.block_insts(block) {
+                match func.dfg[inst] {
+                    InstructionData::BranchTable { table, .. }
+                    | InstructionData::BranchTableEntry { table, .. }
+                    | InstructionData::BranchTableBase { table, .. }
+                    | InstructionData::IndirectJump { table, .. } => {
+                        used.insert(table);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for (jt, data) in func.jump_tables.iter_mut() {
+            if !used.contains(jt) && data.iter().any(|&b| !self.executable.contains(b)) {
+                *data = ir::JumpTableData::new();
+            }
+        }
+    }
+}
+
+/// Perform sparse conditional constant propagation on `func`.
+pub fn do_sccp(func: &mut ir::Function) {
+    let _tt = timing::sccp();
+    let entry = match func.layout.entry_block() {
+        Some(entry) => entry,
+        None => return,
+    };
+
+    let mut sccp = Sccp::new(func);
+    sccp.analyze(func, entry);
+    sccp.rewrite(func);
+}
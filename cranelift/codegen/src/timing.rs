@@ -57,6 +57,7 @@ define_passes! {
     loop_analysis: "Loop analysis",
     postopt: "Post-legalization rewriting",
     preopt: "Pre-legalization rewriting",
+    sccp: "Sparse conditional constant propagation",
     dce: "Dead code elimination",
     legalize: "Legalization",
     gvn: "Global value numbering",
@@ -40,6 +40,8 @@
 //!   function.
 //! - All return instructions must have return value operands matching the current
 //!   function signature.
+//! - Tail calls must call functions with the same return types and calling convention as the
+//!   current function.
 //!
 //! Global values
 //!
@@ -65,8 +67,9 @@ use crate::ir;
 use crate::ir::entities::AnyEntity;
 use crate::ir::instructions::{BranchInfo, CallInfo, InstructionFormat, ResolvedConstraint};
 use crate::ir::{
-    types, ArgumentLoc, Block, FuncRef, Function, GlobalValue, Inst, InstructionData, JumpTable,
-    Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef, ValueList, ValueLoc,
+    types, AbiParam, ArgumentLoc, ArgumentPurpose, Block, FuncRef, Function, GlobalValue, Inst,
+    InstructionData, JumpTable, Opcode, SigRef, StackSlot, StackSlotKind, Type, Value, ValueDef,
+    ValueList, ValueLoc,
 };
 use crate::isa::TargetIsa;
 use crate::iterators::IteratorExtras;
@@ -762,6 +765,8 @@ impl<'a> Verifier<'a> {
             | IntSelect { .. }
             | Load { .. }
             | Store { .. }
+            | AtomicRmw { .. }
+            | AtomicCas { .. }
             | RegMove { .. }
             | CopySpecial { .. }
             | CopyToSsa { .. }
@@ -1344,6 +1349,7 @@ impl<'a> Verifier<'a> {
                     .map(|a| a.value_type);
                 self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
                 self.check_outgoing_args(inst, sig_ref, errors)?;
+                self.typecheck_tail_call(inst, sig_ref, errors)?;
             }
             CallInfo::Indirect(sig_ref, _) => {
                 let arg_types = self.func.dfg.signatures[sig_ref]
@@ -1352,12 +1358,56 @@ impl<'a> Verifier<'a> {
                     .map(|a| a.value_type);
                 self.typecheck_variable_args_iterator(inst, arg_types, errors)?;
                 self.check_outgoing_args(inst, sig_ref, errors)?;
+                self.typecheck_tail_call(inst, sig_ref, errors)?;
             }
             CallInfo::NotACall => {}
         }
         Ok(())
     }
 
+    /// Check that the callee of a tail call returns the same values as the current function, in
+    /// the same way.
+    fn typecheck_tail_call(
+        &self,
+        inst: Inst,
+        sig_ref: SigRef,
+        errors: &mut VerifierErrors,
+    ) -> VerifierStepResult<()> {
+        if !self.func.dfg[inst].opcode().is_tail_call() {
+            return Ok(());
+        }
+
+        let sig = &self.func.dfg.signatures[sig_ref];
+        if sig.call_conv != self.func.signature.call_conv {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                format!(
+                    "tail call to {} function from {} function",
+                    sig.call_conv, self.func.signature.call_conv
+                ),
+            ));
+        }
+
+        // Only compare the normal return values; legalization and the prologue insertion add
+        // special-purpose values to the current function's signature.
+        let normal_returns = |returns: &[AbiParam]| {
+            returns
+                .iter()
+                .filter(|r| r.purpose == ArgumentPurpose::Normal)
+                .map(|r| r.value_type)
+                .collect::<Vec<_>>()
+        };
+        if normal_returns(&sig.returns) != normal_returns(&self.func.signature.returns) {
+            return errors.nonfatal((
+                inst,
+                self.context(inst),
+                "return types of tail call must match function signature",
+            ));
+        }
+        Ok(())
+    }
+
     fn typecheck_variable_args_iterator<I: Iterator<Item = Type>>(
         &self,
         inst: Inst,
@@ -1873,7 +1923,9 @@ impl<'a> Verifier<'a> {
 
         match *inst_data {
             ir::InstructionData::Store { flags, .. }
-            | ir::InstructionData::StoreComplex { flags, .. } => {
+            | ir::InstructionData::StoreComplex { flags, .. }
+            | ir::InstructionData::AtomicRmw { flags, .. }
+            | ir::InstructionData::AtomicCas { flags, .. } => {
                 if flags.readonly() {
                     errors.fatal((
                         inst,
@@ -29,9 +29,22 @@ pub fn raw_relocation(reloc: Reloc, triple: &Triple) -> (u32, i64) {
                             // R_X86_64_GOTPCRELX/R_X86_64_REX_GOTPCRELX.
                             Reloc::X86CallPLTRel4 => elf::reloc::R_X86_64_PLT32,
                             Reloc::X86GOTPCRel4 => elf::reloc::R_X86_64_GOTPCREL,
+                            Reloc::ElfX86_64TlsGd => elf::reloc::R_X86_64_TLSGD,
+                            Reloc::ElfX86_64TpOff32 => elf::reloc::R_X86_64_TPOFF32,
                             _ => unimplemented!(),
                         }
                     }
+                    Architecture::Aarch64 { .. } => match reloc {
+                        Reloc::Abs8 => elf::reloc::R_AARCH64_ABS64,
+                        Reloc::Arm64Call => elf::reloc::R_AARCH64_CALL26,
+                        Reloc::Arm64TlsGdAdrPage21 => elf::reloc::R_AARCH64_TLSGD_ADR_PAGE21,
+                        Reloc::Arm64TlsGdAddLo12Nc => elf::reloc::R_AARCH64_TLSGD_ADD_LO12_NC,
+                        Reloc::Arm64TlsLeAddTprelHi12 => elf::reloc::R_AARCH64_TLSLE_ADD_TPREL_HI12,
+                        Reloc::Arm64TlsLeAddTprelLo12Nc => {
+                            elf::reloc::R_AARCH64_TLSLE_ADD_TPREL_LO12_NC
+                        }
+                        _ => unimplemented!(),
+                    },
                     _ => unimplemented!("unsupported architecture: {}", triple),
                 },
                 // Most ELF relocations do not include an implicit addend.
@@ -54,9 +67,23 @@ pub fn raw_relocation(reloc: Reloc, triple: &Triple) -> (u32, i64) {
                         Reloc::X86GOTPCRel4 => {
                             (u32::from(mach::relocation::X86_64_RELOC_GOT_LOAD), 4)
                         }
+                        Reloc::MachOX86_64Tlv => (u32::from(mach::relocation::X86_64_RELOC_TLV), 4),
                         _ => unimplemented!("unsupported mach-o reloc: {}", reloc),
                     }
                 }
+                Architecture::Aarch64 { .. } => match reloc {
+                    Reloc::Abs8 => (u32::from(mach::relocation::ARM64_RELOC_UNSIGNED), 0),
+                    Reloc::Arm64Call => (u32::from(mach::relocation::ARM64_RELOC_BRANCH26), 0),
+                    Reloc::MachOAarch64TlsAdrPage21 => (
+                        u32::from(mach::relocation::ARM64_RELOC_TLVP_LOAD_PAGE21),
+                        0,
+                    ),
+                    Reloc::MachOAarch64TlsAdrPageOff12 => (
+                        u32::from(mach::relocation::ARM64_RELOC_TLVP_LOAD_PAGEOFF12),
+                        0,
+                    ),
+                    _ => unimplemented!("unsupported mach-o reloc: {}", reloc),
+                },
                 _ => unimplemented!("unsupported architecture: {}", triple),
             }
         }
@@ -35,6 +35,16 @@ impl FunctionRunner {
     /// interpreted as a successful test execution and mapped to Ok whereas a 'false' value is
     /// interpreted as a failed test and mapped to Err.
     pub fn run(&self) -> Result<(), String> {
+        if self.execute()? {
+            Ok(())
+        } else {
+            Err(format!("Failed: {}", self.function.name.to_string()))
+        }
+    }
+
+    /// Compile and execute a single function, returning the boolean it returns. Errors are only
+    /// returned if the function has the wrong signature or fails to compile.
+    pub fn execute(&self) -> Result<bool, String> {
         let func = self.function.clone();
         if !(func.signature.params.is_empty()
             && func.signature.returns.len() == 1
@@ -79,11 +89,7 @@ impl FunctionRunner {
         let callable_fn: fn() -> bool = unsafe { mem::transmute(code_page.as_ptr()) };
 
         // execute
-        if callable_fn() {
-            Ok(())
-        } else {
-            Err(format!("Failed: {}", context.func.name.to_string()))
-        }
+        Ok(callable_fn())
     }
 }
 
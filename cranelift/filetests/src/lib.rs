@@ -52,6 +52,7 @@ mod test_regalloc;
 mod test_rodata;
 mod test_run;
 mod test_safepoint;
+mod test_sccp;
 mod test_shrink;
 mod test_simple_gvn;
 mod test_simple_preopt;
@@ -139,6 +140,7 @@ fn new_subtest(parsed: &TestCommand) -> subtest::SubtestResult<Box<dyn subtest::
         "verifier" => test_verifier::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
         "safepoint" => test_safepoint::subtest(parsed),
+        "sccp" => test_sccp::subtest(parsed),
         "unwind" => test_unwind::subtest(parsed),
         "fde" => test_fde::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
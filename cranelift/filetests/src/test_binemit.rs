@@ -114,6 +114,7 @@ impl binemit::CodeSink for TextSink {
         _: &dyn isa::TargetIsa,
     ) {
     }
+    fn add_mach_stackmap(&mut self, _: binemit::Stackmap) {}
 }
 
 impl SubTest for TestBinEmit {
@@ -119,4 +119,5 @@ impl binemit::CodeSink for SizeSink {
         _: &dyn isa::TargetIsa,
     ) {
     }
+    fn add_mach_stackmap(&mut self, _: binemit::Stackmap) {}
 }
@@ -120,4 +120,5 @@ impl binemit::CodeSink for RodataSink {
         assert!(self.in_rodata);
     }
     fn add_stackmap(&mut self, _: &[Value], _: &Function, _: &dyn TargetIsa) {}
+    fn add_mach_stackmap(&mut self, _: binemit::Stackmap) {}
 }
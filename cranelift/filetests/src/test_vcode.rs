@@ -52,7 +52,7 @@ impl SubTest for TestVCode {
         let mut isa = lookup(triple)
             .map_err(|_| format!("Could not look up backend for arch '{}'", self.arch))?
             .as_builder()
-            .get_wrapped();
+            .finish(context.flags.clone());
 
         let mut codectx = CodegenContext::for_function(func);
         codectx.set_disasm(true);
@@ -4,6 +4,7 @@ use crate::DataContext;
 use crate::DataId;
 use crate::FuncId;
 use crate::Linkage;
+use crate::ModuleError;
 use crate::ModuleNamespace;
 use crate::ModuleResult;
 use crate::TrapSite;
@@ -88,6 +89,25 @@ where
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction>;
 
+    /// Define a new body for the already defined function `id`, producing it
+    /// from the given `Context`. `previous` is the artifact being replaced.
+    ///
+    /// References to the function, including ones from code that has already
+    /// been finalized, must resolve to the new body once it is finalized.
+    /// Backends that can't do this keep the default, which reports a
+    /// duplicate definition.
+    fn redefine_function(
+        &mut self,
+        _id: FuncId,
+        name: &str,
+        _ctx: &Context,
+        _namespace: &ModuleNamespace<Self>,
+        _code_size: u32,
+        _previous: &Self::CompiledFunction,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        Err(ModuleError::DuplicateDefinition(name.to_owned()))
+    }
+
     /// Define a function, taking the function body from the given `bytes`.
     ///
     /// Functions must be declared before being defined.
@@ -164,6 +164,9 @@ where
     pub decl: FunctionDeclaration,
     /// The compiled artifact, once it's available.
     pub compiled: Option<B::CompiledFunction>,
+    /// The number of times this function has been defined; zero until the
+    /// first definition, and incremented by each redefinition.
+    pub version: u32,
 }
 
 impl<B> ModuleFunction<B>
@@ -446,6 +449,7 @@ where
                         signature: signature.clone(),
                     },
                     compiled: None,
+                    version: 0,
                 });
                 entry.insert(FuncOrDataId::Func(id));
                 self.backend.declare_function(id, name, linkage);
@@ -583,10 +587,62 @@ where
         )?);
 
         self.contents.functions[func].compiled = compiled;
+        self.contents.functions[func].version = 1;
         self.functions_to_finalize.push(func);
         Ok(total_size)
     }
 
+    /// Replace the body of an already defined function, producing the new body
+    /// from the given `Context`.
+    ///
+    /// The function may already have been finalized. Once `finalize_definitions`
+    /// is called again, existing references to the function resolve to the new
+    /// body. This is only supported by backends which can redirect references
+    /// to a function; others return `ModuleError::DuplicateDefinition`.
+    ///
+    /// Returns the size of the function's new code.
+    pub fn redefine_function(
+        &mut self,
+        func: FuncId,
+        ctx: &mut Context,
+    ) -> ModuleResult<binemit::CodeOffset> {
+        if self.contents.functions[func].compiled.is_none() {
+            return self.define_function(func, ctx);
+        }
+        info!(
+            "redefining function {}: {}",
+            func,
+            ctx.func.display(self.backend.isa())
+        );
+        let CodeInfo { total_size, .. } = ctx.compile(self.backend.isa())?;
+        let info = &self.contents.functions[func];
+
+        let compiled = Some(self.backend.redefine_function(
+            func,
+            &info.decl.name,
+            ctx,
+            &ModuleNamespace::<B> {
+                contents: &self.contents,
+            },
+            total_size,
+            info.compiled.as_ref().unwrap(),
+        )?);
+
+        let info = &mut self.contents.functions[func];
+        info.compiled = compiled;
+        info.version += 1;
+        if !self.functions_to_finalize.contains(&func) {
+            self.functions_to_finalize.push(func);
+        }
+        Ok(total_size)
+    }
+
+    /// Return the number of times `func` has been defined, counting its
+    /// initial definition and every redefinition since.
+    pub fn function_version(&self, func: FuncId) -> u32 {
+        self.contents.functions[func].version
+    }
+
     /// Define a function, taking the function body from the given `bytes`.
     ///
     /// This function is generally only useful if you need to precisely specify
@@ -625,6 +681,7 @@ where
         )?);
 
         self.contents.functions[func].compiled = compiled;
+        self.contents.functions[func].version = 1;
         self.functions_to_finalize.push(func);
         Ok(total_size)
     }
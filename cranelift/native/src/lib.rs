@@ -127,6 +127,17 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn host_features() {
+        // Every x86_64 host is supported. The ISA-specific flags detected with
+        // cpuid are accepted, but the x86_64 backend has no ISA-specific
+        // settings yet and ignores them.
+        let isa_builder = builder().unwrap();
+        let isa = isa_builder.finish(settings::Flags::new(settings::builder()));
+        assert_eq!(isa.pointer_bits(), 64);
+    }
 }
 
 /// Version number of this crate.
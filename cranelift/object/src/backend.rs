@@ -601,6 +601,83 @@ impl RelocSink for ObjectRelocSink {
                     32,
                 )
             }
+            Reloc::ElfX86_64TpOff32 => {
+                assert_eq!(
+                    self.format,
+                    BinaryFormat::Elf,
+                    "ElfX86_64TpOff32 is not supported for this file format"
+                );
+                (
+                    RelocationKind::Elf(goblin::elf64::reloc::R_X86_64_TPOFF32),
+                    RelocationEncoding::Generic,
+                    32,
+                )
+            }
+            Reloc::Arm64Call => match self.format {
+                BinaryFormat::Elf => (
+                    RelocationKind::Elf(goblin::elf64::reloc::R_AARCH64_CALL26),
+                    RelocationEncoding::Generic,
+                    32,
+                ),
+                BinaryFormat::Macho => (
+                    RelocationKind::MachO {
+                        value: goblin::mach::relocation::ARM64_RELOC_BRANCH26,
+                        relative: true,
+                    },
+                    RelocationEncoding::Generic,
+                    32,
+                ),
+                _ => unimplemented!("Arm64Call is not supported for this file format"),
+            },
+            Reloc::Arm64TlsGdAdrPage21
+            | Reloc::Arm64TlsGdAddLo12Nc
+            | Reloc::Arm64TlsLeAddTprelHi12
+            | Reloc::Arm64TlsLeAddTprelLo12Nc => {
+                assert_eq!(
+                    self.format,
+                    BinaryFormat::Elf,
+                    "{} is not supported for this file format",
+                    reloc
+                );
+                let r_type = match reloc {
+                    Reloc::Arm64TlsGdAdrPage21 => goblin::elf64::reloc::R_AARCH64_TLSGD_ADR_PAGE21,
+                    Reloc::Arm64TlsGdAddLo12Nc => goblin::elf64::reloc::R_AARCH64_TLSGD_ADD_LO12_NC,
+                    Reloc::Arm64TlsLeAddTprelHi12 => {
+                        goblin::elf64::reloc::R_AARCH64_TLSLE_ADD_TPREL_HI12
+                    }
+                    Reloc::Arm64TlsLeAddTprelLo12Nc => {
+                        goblin::elf64::reloc::R_AARCH64_TLSLE_ADD_TPREL_LO12_NC
+                    }
+                    _ => unreachable!(),
+                };
+                (
+                    RelocationKind::Elf(r_type),
+                    RelocationEncoding::Generic,
+                    32,
+                )
+            }
+            Reloc::MachOAarch64TlsAdrPage21 | Reloc::MachOAarch64TlsAdrPageOff12 => {
+                assert_eq!(
+                    self.format,
+                    BinaryFormat::Macho,
+                    "{} is not supported for this file format",
+                    reloc
+                );
+                let (value, relative) = match reloc {
+                    Reloc::MachOAarch64TlsAdrPage21 => {
+                        (goblin::mach::relocation::ARM64_RELOC_TLVP_LOAD_PAGE21, true)
+                    }
+                    Reloc::MachOAarch64TlsAdrPageOff12 => {
+                        (goblin::mach::relocation::ARM64_RELOC_TLVP_LOAD_PAGEOFF12, false)
+                    }
+                    _ => unreachable!(),
+                };
+                (
+                    RelocationKind::MachO { value, relative },
+                    RelocationEncoding::Generic,
+                    32,
+                )
+            }
             // FIXME
             _ => unimplemented!(),
         };
@@ -45,3 +45,15 @@ where
     ctx.verify_if(fisa)?;
     Ok(())
 }
+
+/// Perform sparse conditional constant propagation.
+///
+/// This subsumes `fold_constants`: besides folding operations on constants in straight-line
+/// code, it propagates constants through block parameters, removes branches whose condition is
+/// known, and removes the blocks that become unreachable.
+pub fn sccp<'a, FOI>(ctx: &mut Context, fisa: FOI) -> CodegenResult<()>
+where
+    FOI: Into<FlagsOrIsa<'a>>,
+{
+    ctx.sccp(fisa)
+}
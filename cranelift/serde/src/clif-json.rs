@@ -53,7 +53,9 @@ fn call_de(file: &File) -> Result<(), String> {
         Result::Ok(val) => val,
         Result::Err(err) => panic!("{}", err),
     };
-    println!("{:?}", de);
+    for func in de.to_functions()? {
+        println!("{}", func);
+    }
     Ok(())
 }
 
@@ -73,7 +75,7 @@ fn main() {
         )
         .subcommand(
             SubCommand::with_name("deserialize")
-                .about("Deserializes Cranelift IR into JSON.")
+                .about("Deserializes JSON into Cranelift IR.")
                 .arg(
                     Arg::with_name("FILE")
                         .required(true)
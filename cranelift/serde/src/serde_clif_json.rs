@@ -1,5 +1,16 @@
-use cranelift_codegen::ir::{Block, Function, Inst, InstructionData, Signature};
+use cranelift_codegen::entity::EntityRef;
+use cranelift_codegen::ir::immediates::{Imm64, Offset32, Uimm64};
+use cranelift_codegen::ir::{
+    types, AbiParam, ArgumentExtension, ArgumentLoc, Block, Constant, ConstantData, ExtFuncData,
+    ExternalName, FuncRef, Function, GlobalValue, GlobalValueData, Heap, HeapData, HeapStyle,
+    Immediate, Inst, InstructionData, JumpTable, JumpTableData, MemFlags, Opcode, SigRef,
+    Signature, SourceLoc, StackSlot, StackSlotData, StackSlotKind, Table, TableData, Type, Value,
+    ValueLabelAssignments, ValueLabelStart, ValueList, ValueLoc,
+};
+use cranelift_codegen::isa::Encoding;
 use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
 
 /// Serializable version of the original Cranelift IR
 #[derive(Clone, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
@@ -24,6 +35,10 @@ pub enum SerInstData {
         opcode: String,
         imm: bool,
     },
+    UnaryConst {
+        opcode: String,
+        constant_handle: String,
+    },
     UnaryGlobalValue {
         opcode: String,
         global_value: String,
@@ -181,6 +196,17 @@ pub enum SerInstData {
         flags: String,
         offset: String,
     },
+    AtomicRmw {
+        opcode: String,
+        args: [String; 2],
+        flags: String,
+        op: String,
+    },
+    AtomicCas {
+        opcode: String,
+        args: [String; 3],
+        flags: String,
+    },
     StackLoad {
         opcode: String,
         stack_slot: String,
@@ -339,25 +365,15 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
         InstructionData::UnaryConst {
             opcode,
             constant_handle,
-        } => {
-            let constant = func.dfg.constants.get(constant_handle);
-            SerInstData::UnaryImm {
-                opcode: opcode.to_string(),
-                imm: format!("{:?}", constant),
-            }
-        }
-        InstructionData::Shuffle { opcode, args, mask } => {
-            let mask = func
-                .dfg
-                .immediates
-                .get(mask)
-                .expect("Expected shuffle mask to already be inserted in immediate mapping");
-            SerInstData::Shuffle {
-                opcode: opcode.to_string(),
-                args: [args[0].to_string(), args[1].to_string()],
-                mask: format!("{:?}", mask),
-            }
-        }
+        } => SerInstData::UnaryConst {
+            opcode: opcode.to_string(),
+            constant_handle: constant_handle.to_string(),
+        },
+        InstructionData::Shuffle { opcode, args, mask } => SerInstData::Shuffle {
+            opcode: opcode.to_string(),
+            args: [args[0].to_string(), args[1].to_string()],
+            mask: mask.to_string(),
+        },
         InstructionData::IntCompare { opcode, args, cond } => {
             let hold_args = [args[0].to_string(), args[1].to_string()];
             SerInstData::IntCompare {
@@ -624,6 +640,36 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
                 offset: offset.to_string(),
             }
         }
+        InstructionData::AtomicRmw {
+            opcode,
+            args,
+            flags,
+            op,
+        } => {
+            let hold_args = [args[0].to_string(), args[1].to_string()];
+            SerInstData::AtomicRmw {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                flags: flags.to_string(),
+                op: op.to_string(),
+            }
+        }
+        InstructionData::AtomicCas {
+            opcode,
+            args,
+            flags,
+        } => {
+            let hold_args = [
+                args[0].to_string(),
+                args[1].to_string(),
+                args[2].to_string(),
+            ];
+            SerInstData::AtomicCas {
+                opcode: opcode.to_string(),
+                args: hold_args,
+                flags: flags.to_string(),
+            }
+        }
         InstructionData::StackLoad {
             opcode,
             stack_slot,
@@ -747,22 +793,57 @@ pub fn get_inst_data(inst_index: Inst, func: &Function) -> SerInstData {
 pub struct SerInst {
     pub inst_name: String,
     pub inst_data: SerInstData,
+    pub results: Vec<String>,
+    pub ctrl_typevar: Option<String>,
+    pub srcloc: Option<String>,
+    pub encoding: Option<String>,
 }
 
 impl SerInst {
     pub fn new(inst: Inst, func: &Function) -> Self {
+        let ctrl_typevar = func.dfg.ctrl_typevar(inst);
+        let srcloc = func.srclocs[inst];
+        let encoding = func.encodings[inst];
         Self {
             inst_name: inst.to_string(),
             inst_data: get_inst_data(inst, func),
+            results: func
+                .dfg
+                .inst_results(inst)
+                .iter()
+                .map(|result| result.to_string())
+                .collect(),
+            ctrl_typevar: if ctrl_typevar.is_invalid() {
+                None
+            } else {
+                Some(ctrl_typevar.to_string())
+            },
+            srcloc: if srcloc.is_default() {
+                None
+            } else {
+                Some(srcloc.to_string())
+            },
+            encoding: if encoding.is_legal() {
+                Some(encoding.to_string())
+            } else {
+                None
+            },
         }
     }
 }
 
+/// Serializable version of a Block parameter.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerBlockParam {
+    pub value: String,
+    pub value_type: String,
+}
+
 /// Serializable version of Cranelift IR Blocks.
 #[derive(Clone, Deserialize, Serialize, Debug)]
 pub struct SerBlock {
     pub block: String,
-    pub params: Vec<String>,
+    pub params: Vec<SerBlockParam>,
     pub insts: Vec<SerInst>,
 }
 
@@ -787,19 +868,80 @@ pub fn populate_inst(func: &Function, block: Block) -> Vec<SerInst> {
 }
 
 /// Translating Block parameters into serializable parameters.
-pub fn populate_params(func: &Function, block: Block) -> Vec<String> {
-    let mut ser_vec: Vec<String> = Vec::new();
+pub fn populate_params(func: &Function, block: Block) -> Vec<SerBlockParam> {
+    let mut ser_vec: Vec<SerBlockParam> = Vec::new();
     let parameters = func.dfg.block_params(block);
-    for param in parameters {
-        ser_vec.push(param.to_string());
+    for &param in parameters {
+        ser_vec.push(SerBlockParam {
+            value: param.to_string(),
+            value_type: func.dfg.value_type(param).to_string(),
+        });
     }
     ser_vec
 }
 
+/// Serializable value alias, `alias -> original`.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerValueAlias {
+    pub alias: String,
+    pub original: String,
+}
+
+/// Serializable signature reference, as declared in the function preamble.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct SerSigRef {
+    pub name: String,
+    pub signature: SerSignature,
+}
+
+/// Serializable external function reference.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerExtFunc {
+    pub name: String,
+    pub func_name: String,
+    pub signature: String,
+    pub colocated: bool,
+}
+
+/// Serializable constant, used both for the constant pool and for large immediates. The data is
+/// the hexadecimal representation of the bytes, in the order they are stored.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerConstant {
+    pub name: String,
+    pub data: String,
+}
+
+/// Serializable value label assignments.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub enum SerValueLabelAssignments {
+    Starts(Vec<SerValueLabelStart>),
+    Alias { from: String, value: String },
+}
+
+/// Serializable start of a value label.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerValueLabelStart {
+    pub from: String,
+    pub label: String,
+}
+
+/// Serializable value labels of a single value.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerValueLabels {
+    pub value: String,
+    pub assignments: SerValueLabelAssignments,
+}
+
 /// Serializable Data Flow Graph.
 #[derive(Deserialize, Serialize, Debug)]
 pub struct SerDataFlowGraph {
     blocks: Vec<SerBlock>,
+    value_aliases: Vec<SerValueAlias>,
+    signatures: Vec<SerSigRef>,
+    ext_funcs: Vec<SerExtFunc>,
+    constants: Vec<SerConstant>,
+    immediates: Vec<SerConstant>,
+    value_labels: Option<Vec<SerValueLabels>>,
 }
 
 /// Serialize all parts of the Cranelift Block data structure, this includes name, parameters, and
@@ -815,11 +957,98 @@ pub fn populate_blocks(func: &Function) -> Vec<SerBlock> {
     block_vec
 }
 
+/// Serialize the value aliases.
+pub fn populate_value_aliases(func: &Function) -> Vec<SerValueAlias> {
+    let mut alias_vec: Vec<SerValueAlias> = Vec::new();
+    for value in func.dfg.values() {
+        if let Some(original) = func.dfg.value_alias_dest_for_serialization(value) {
+            alias_vec.push(SerValueAlias {
+                alias: value.to_string(),
+                original: original.to_string(),
+            });
+        }
+    }
+    alias_vec
+}
+
+/// Serialize the value labels, sorted by value so that the output is deterministic.
+pub fn populate_value_labels(func: &Function) -> Option<Vec<SerValueLabels>> {
+    let values_labels = func.dfg.values_labels.as_ref()?;
+    let mut values: Vec<Value> = values_labels.keys().cloned().collect();
+    values.sort();
+    let mut labels_vec: Vec<SerValueLabels> = Vec::new();
+    for value in values {
+        let assignments = match values_labels[&value] {
+            ValueLabelAssignments::Starts(ref starts) => SerValueLabelAssignments::Starts(
+                starts
+                    .iter()
+                    .map(|start| SerValueLabelStart {
+                        from: start.from.to_string(),
+                        label: start.label.to_string(),
+                    })
+                    .collect(),
+            ),
+            ValueLabelAssignments::Alias { from, value } => SerValueLabelAssignments::Alias {
+                from: from.to_string(),
+                value: value.to_string(),
+            },
+        };
+        labels_vec.push(SerValueLabels {
+            value: value.to_string(),
+            assignments,
+        });
+    }
+    Some(labels_vec)
+}
+
 /// Serializable Cranelift IR data flow graph, including all blocks.
 impl SerDataFlowGraph {
     pub fn create_new(func: &Function) -> Self {
+        let mut signatures = Vec::new();
+        for (sig_ref, sig) in func.dfg.signatures.iter() {
+            signatures.push(SerSigRef {
+                name: sig_ref.to_string(),
+                signature: SerSignature::create_new(sig),
+            });
+        }
+
+        let mut ext_funcs = Vec::new();
+        for (func_ref, ext_func) in func.dfg.ext_funcs.iter() {
+            // Placeholders created by the parser to pad the numbering have no signature.
+            if ext_func.signature != SigRef::reserved_value() {
+                ext_funcs.push(SerExtFunc {
+                    name: func_ref.to_string(),
+                    func_name: ext_func.name.to_string(),
+                    signature: ext_func.signature.to_string(),
+                    colocated: ext_func.colocated,
+                });
+            }
+        }
+
+        let mut constants = Vec::new();
+        for (constant, data) in func.dfg.constants.iter() {
+            constants.push(SerConstant {
+                name: constant.to_string(),
+                data: bytes_to_hex(data.iter()),
+            });
+        }
+
+        let mut immediates = Vec::new();
+        for (immediate, data) in func.dfg.immediates.iter() {
+            immediates.push(SerConstant {
+                name: immediate.to_string(),
+                data: bytes_to_hex(data.iter()),
+            });
+        }
+
         Self {
             blocks: populate_blocks(func),
+            value_aliases: populate_value_aliases(func),
+            signatures,
+            ext_funcs,
+            constants,
+            immediates,
+            value_labels: populate_value_labels(func),
         }
     }
 
@@ -833,6 +1062,7 @@ impl SerDataFlowGraph {
 pub struct SerSignature {
     pub func_params: Vec<String>,
     pub func_returns: Vec<String>,
+    pub call_conv: String,
 }
 
 impl SerSignature {
@@ -849,6 +1079,7 @@ impl SerSignature {
         Self {
             func_params: params_vec,
             func_returns: returns_vec,
+            call_conv: sig.call_conv.to_string(),
         }
     }
 
@@ -857,28 +1088,231 @@ impl SerSignature {
     }
 }
 
+/// Serializable stack slot.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerStackSlot {
+    pub name: String,
+    pub kind: String,
+    pub size: String,
+    pub offset: Option<String>,
+}
+
+/// Serializable version of the global value definitions.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub enum SerGlobalValueData {
+    VMContext,
+    Load {
+        base: String,
+        offset: String,
+        global_type: String,
+        readonly: bool,
+    },
+    IAddImm {
+        base: String,
+        offset: String,
+        global_type: String,
+    },
+    Symbol {
+        name: String,
+        offset: String,
+        colocated: bool,
+        tls: bool,
+    },
+}
+
+/// Serializable global value.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerGlobalValue {
+    pub name: String,
+    pub data: SerGlobalValueData,
+}
+
+/// Serializable version of the heap styles.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub enum SerHeapStyle {
+    Dynamic { bound_gv: String },
+    Static { bound: String },
+}
+
+/// Serializable heap.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerHeap {
+    pub name: String,
+    pub base: String,
+    pub min_size: String,
+    pub offset_guard_size: String,
+    pub style: SerHeapStyle,
+    pub index_type: String,
+}
+
+/// Serializable table.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerTable {
+    pub name: String,
+    pub base_gv: String,
+    pub min_size: String,
+    pub bound_gv: String,
+    pub element_size: String,
+    pub index_type: String,
+}
+
+/// Serializable jump table.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerJumpTable {
+    pub name: String,
+    pub blocks: Vec<String>,
+}
+
+/// Serializable value location, assigned by the register allocator.
+#[derive(Clone, Deserialize, Serialize, Debug)]
+pub struct SerValueLoc {
+    pub value: String,
+    pub location: String,
+}
+
 /// Serializable Function type, including name, signature, global values, and data flow graph.
 #[derive(Serialize, Deserialize, Debug)]
 pub struct SerFunction {
     pub name: String,
     pub signature: SerSignature,
-    pub globals: Vec<String>,
+    pub old_signature: Option<SerSignature>,
+    pub stack_slots: Vec<SerStackSlot>,
+    pub globals: Vec<SerGlobalValue>,
+    pub heaps: Vec<SerHeap>,
+    pub tables: Vec<SerTable>,
+    pub jump_tables: Vec<SerJumpTable>,
     pub dfg: SerDataFlowGraph,
+    pub value_locations: Vec<SerValueLoc>,
+    pub prologue_end: Option<String>,
 }
 
 impl SerFunction {
     /// Creates serializable global values, as well as the functions signature, name, and data flow
     /// graph.
     fn create_new(func: &Function) -> Self {
-        let mut global_vec: Vec<String> = Vec::new();
-        for (glob_name, _) in func.global_values.iter() {
-            global_vec.push(glob_name.to_string());
+        let mut stack_slot_vec: Vec<SerStackSlot> = Vec::new();
+        for (ss, data) in func.stack_slots.iter() {
+            stack_slot_vec.push(SerStackSlot {
+                name: ss.to_string(),
+                kind: data.kind.to_string(),
+                size: data.size.to_string(),
+                offset: data.offset.map(|offset| offset.to_string()),
+            });
+        }
+
+        let mut global_vec: Vec<SerGlobalValue> = Vec::new();
+        for (glob_name, glob_data) in func.global_values.iter() {
+            let data = match *glob_data {
+                GlobalValueData::VMContext => SerGlobalValueData::VMContext,
+                GlobalValueData::Load {
+                    base,
+                    offset,
+                    global_type,
+                    readonly,
+                } => SerGlobalValueData::Load {
+                    base: base.to_string(),
+                    offset: offset.to_string(),
+                    global_type: global_type.to_string(),
+                    readonly,
+                },
+                GlobalValueData::IAddImm {
+                    base,
+                    offset,
+                    global_type,
+                } => SerGlobalValueData::IAddImm {
+                    base: base.to_string(),
+                    offset: offset.to_string(),
+                    global_type: global_type.to_string(),
+                },
+                GlobalValueData::Symbol {
+                    ref name,
+                    offset,
+                    colocated,
+                    tls,
+                } => SerGlobalValueData::Symbol {
+                    name: name.to_string(),
+                    offset: offset.to_string(),
+                    colocated,
+                    tls,
+                },
+            };
+            global_vec.push(SerGlobalValue {
+                name: glob_name.to_string(),
+                data,
+            });
+        }
+
+        let mut heap_vec: Vec<SerHeap> = Vec::new();
+        for (heap, heap_data) in func.heaps.iter() {
+            // Placeholders created by the parser to pad the numbering have an invalid index type.
+            if heap_data.index_type.is_invalid() {
+                continue;
+            }
+            heap_vec.push(SerHeap {
+                name: heap.to_string(),
+                base: heap_data.base.to_string(),
+                min_size: heap_data.min_size.to_string(),
+                offset_guard_size: heap_data.offset_guard_size.to_string(),
+                style: match heap_data.style {
+                    HeapStyle::Dynamic { bound_gv } => SerHeapStyle::Dynamic {
+                        bound_gv: bound_gv.to_string(),
+                    },
+                    HeapStyle::Static { bound } => SerHeapStyle::Static {
+                        bound: bound.to_string(),
+                    },
+                },
+                index_type: heap_data.index_type.to_string(),
+            });
+        }
+
+        let mut table_vec: Vec<SerTable> = Vec::new();
+        for (table, table_data) in func.tables.iter() {
+            if table_data.index_type.is_invalid() {
+                continue;
+            }
+            table_vec.push(SerTable {
+                name: table.to_string(),
+                base_gv: table_data.base_gv.to_string(),
+                min_size: table_data.min_size.to_string(),
+                bound_gv: table_data.bound_gv.to_string(),
+                element_size: table_data.element_size.to_string(),
+                index_type: table_data.index_type.to_string(),
+            });
+        }
+
+        let mut jump_table_vec: Vec<SerJumpTable> = Vec::new();
+        for (jt, jt_data) in func.jump_tables.iter() {
+            jump_table_vec.push(SerJumpTable {
+                name: jt.to_string(),
+                blocks: jt_data.iter().map(|block| block.to_string()).collect(),
+            });
+        }
+
+        let mut value_loc_vec: Vec<SerValueLoc> = Vec::new();
+        for value in func.dfg.values() {
+            let location = match func.locations[value] {
+                ValueLoc::Unassigned => continue,
+                ValueLoc::Reg(reg) => format!("%{}", reg),
+                ValueLoc::Stack(ss) => ss.to_string(),
+            };
+            value_loc_vec.push(SerValueLoc {
+                value: value.to_string(),
+                location,
+            });
         }
+
         Self {
             name: func.name.to_string(),
             signature: SerSignature::new(&func),
+            old_signature: func.old_signature.as_ref().map(SerSignature::create_new),
+            stack_slots: stack_slot_vec,
             globals: global_vec,
+            heaps: heap_vec,
+            tables: table_vec,
+            jump_tables: jump_table_vec,
             dfg: SerDataFlowGraph::new(&func),
+            value_locations: value_loc_vec,
+            prologue_end: func.prologue_end.map(|inst| inst.to_string()),
         }
     }
 
@@ -907,4 +1341,1307 @@ impl SerObj {
         }
         Self::create_new(func_vec)
     }
+
+    /// Rebuild the Cranelift IR functions from their serialized form.
+    pub fn to_functions(&self) -> Result<Vec<Function>, String> {
+        self.functions
+            .iter()
+            .map(SerFunction::to_function)
+            .collect()
+    }
+}
+
+/// Hexadecimal representation of bytes, in the given order.
+fn bytes_to_hex<'a>(bytes: impl Iterator<Item = &'a u8>) -> String {
+    bytes.map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parse an entity reference such as `v12` or `block3`.
+fn parse_entity<E: EntityRef>(s: &str, prefix: &str) -> Result<E, String> {
+    if s.starts_with(prefix) {
+        if let Ok(number) = s[prefix.len()..].parse::<u32>() {
+            if number != u32::MAX {
+                return Ok(E::new(number as usize));
+            }
+        }
+    }
+    Err(format!("invalid reference '{}', expected {}N", s, prefix))
+}
+
+fn parse_value(s: &str) -> Result<Value, String> {
+    parse_entity(s, "v")
+}
+
+fn parse_values(args: &[String]) -> Result<Vec<Value>, String> {
+    args.iter().map(|arg| parse_value(arg)).collect()
+}
+
+fn parse_value_list(args: &[String], func: &mut Function) -> Result<ValueList, String> {
+    let values = parse_values(args)?;
+    Ok(ValueList::from_slice(&values, &mut func.dfg.value_lists))
+}
+
+fn parse_block(s: &str) -> Result<Block, String> {
+    parse_entity(s, "block")
+}
+
+/// Parse an immediate using its `FromStr` implementation, which is the inverse of its `Display`
+/// implementation.
+fn parse_imm<T: FromStr>(s: &str, what: &str) -> Result<T, String> {
+    s.parse().map_err(|_| format!("invalid {} '{}'", what, s))
+}
+
+fn parse_opcode(s: &str) -> Result<Opcode, String> {
+    parse_imm(s, "opcode")
+}
+
+fn parse_offset32(s: &str) -> Result<Offset32, String> {
+    // A zero offset displays as an empty string.
+    if s.is_empty() {
+        Ok(Offset32::new(0))
+    } else {
+        parse_imm(s, "offset")
+    }
+}
+
+fn parse_memflags(s: &str) -> Result<MemFlags, String> {
+    let mut flags = MemFlags::new();
+    for name in s.split_whitespace() {
+        if !flags.set_by_name(name) {
+            return Err(format!("invalid memory flag '{}'", name));
+        }
+    }
+    Ok(flags)
+}
+
+fn parse_type(s: &str) -> Result<Type, String> {
+    let (scalar, lanes) = match s.find('x') {
+        Some(x) => (&s[..x], Some(&s[x + 1..])),
+        None => (s, None),
+    };
+    let lane_type = match scalar {
+        "i8" => types::I8,
+        "i16" => types::I16,
+        "i32" => types::I32,
+        "i64" => types::I64,
+        "i128" => types::I128,
+        "f32" => types::F32,
+        "f64" => types::F64,
+        "b1" => types::B1,
+        "b8" => types::B8,
+        "b16" => types::B16,
+        "b32" => types::B32,
+        "b64" => types::B64,
+        "b128" => types::B128,
+        "r32" => types::R32,
+        "r64" => types::R64,
+        "iflags" if lanes.is_none() => types::IFLAGS,
+        "fflags" if lanes.is_none() => types::FFLAGS,
+        _ => return Err(format!("invalid type '{}'", s)),
+    };
+    match lanes {
+        Some(lanes) => lanes
+            .parse()
+            .ok()
+            .and_then(|lanes| lane_type.by(lanes))
+            .ok_or_else(|| format!("invalid type '{}'", s)),
+        None => Ok(lane_type),
+    }
+}
+
+fn parse_external_name(s: &str) -> Result<ExternalName, String> {
+    if s.starts_with('%') {
+        return parse_imm(&s[1..], "external name");
+    }
+    if s.starts_with('u') {
+        if let Some(colon) = s.find(':') {
+            if let (Ok(namespace), Ok(index)) = (s[1..colon].parse(), s[colon + 1..].parse()) {
+                return Ok(ExternalName::user(namespace, index));
+            }
+        }
+    }
+    Err(format!("invalid external name '{}'", s))
+}
+
+fn parse_srcloc(s: &str) -> Result<SourceLoc, String> {
+    if s == "@-" {
+        return Ok(SourceLoc::default());
+    }
+    if s.starts_with('@') {
+        if let Ok(bits) = u32::from_str_radix(&s[1..], 16) {
+            return Ok(SourceLoc::new(bits));
+        }
+    }
+    Err(format!("invalid source location '{}'", s))
+}
+
+fn parse_encoding(s: &str) -> Result<Encoding, String> {
+    if let Some(hash) = s.find('#') {
+        if let (Ok(recipe), Ok(bits)) = (s[..hash].parse(), u16::from_str_radix(&s[hash + 1..], 16))
+        {
+            return Ok(Encoding::new(recipe, bits));
+        }
+    }
+    Err(format!("invalid encoding '{}'", s))
+}
+
+fn parse_value_loc(s: &str) -> Result<ValueLoc, String> {
+    if s.starts_with('%') {
+        Ok(ValueLoc::Reg(parse_imm(&s[1..], "register unit")?))
+    } else {
+        Ok(ValueLoc::Stack(parse_entity(s, "ss")?))
+    }
+}
+
+/// Parse an `AbiParam`, as displayed without register names, e.g. `i32 uext sret [%5]`.
+fn parse_abi_param(s: &str) -> Result<AbiParam, String> {
+    let mut words = s.split_whitespace();
+    let value_type = parse_type(words.next().unwrap_or(""))?;
+    let mut param = AbiParam::new(value_type);
+    for word in words {
+        match word {
+            "uext" => param.extension = ArgumentExtension::Uext,
+            "sext" => param.extension = ArgumentExtension::Sext,
+            _ if word.starts_with('[') && word.ends_with(']') => {
+                let location = &word[1..word.len() - 1];
+                param.location = if location.starts_with('%') {
+                    ArgumentLoc::Reg(parse_imm(&location[1..], "register unit")?)
+                } else {
+                    ArgumentLoc::Stack(parse_imm(location, "stack offset")?)
+                };
+            }
+            _ => param.purpose = parse_imm(word, "argument purpose")?,
+        }
+    }
+    Ok(param)
+}
+
+fn hex_to_bytes(s: &str) -> Result<Vec<u8>, String> {
+    if s.len() % 2 != 0 || !s.is_ascii() {
+        return Err(format!("invalid hexadecimal data '{}'", s));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&s[i..i + 2], 16)
+                .map_err(|_| format!("invalid hexadecimal data '{}'", s))
+        })
+        .collect()
+}
+
+impl SerSignature {
+    /// Rebuild the signature.
+    pub fn to_signature(&self) -> Result<Signature, String> {
+        let mut sig = Signature::new(parse_imm(&self.call_conv, "calling convention")?);
+        for param in &self.func_params {
+            sig.params.push(parse_abi_param(param)?);
+        }
+        for ret in &self.func_returns {
+            sig.returns.push(parse_abi_param(ret)?);
+        }
+        Ok(sig)
+    }
+}
+
+/// Convert JSON instructions back to Cranelift IR instruction data. Value lists are allocated in
+/// `func`.
+pub fn make_inst_data(ser: &SerInstData, func: &mut Function) -> Result<InstructionData, String> {
+    Ok(match *ser {
+        SerInstData::Unary {
+            ref opcode,
+            ref arg,
+        } => InstructionData::Unary {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+        },
+        SerInstData::UnaryImm {
+            ref opcode,
+            ref imm,
+        } => InstructionData::UnaryImm {
+            opcode: parse_opcode(opcode)?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::UnaryIeee32 {
+            ref opcode,
+            ref imm,
+        } => InstructionData::UnaryIeee32 {
+            opcode: parse_opcode(opcode)?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::UnaryIeee64 {
+            ref opcode,
+            ref imm,
+        } => InstructionData::UnaryIeee64 {
+            opcode: parse_opcode(opcode)?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::UnaryBool { ref opcode, imm } => InstructionData::UnaryBool {
+            opcode: parse_opcode(opcode)?,
+            imm,
+        },
+        SerInstData::UnaryConst {
+            ref opcode,
+            ref constant_handle,
+        } => InstructionData::UnaryConst {
+            opcode: parse_opcode(opcode)?,
+            constant_handle: parse_entity(constant_handle, "const")?,
+        },
+        SerInstData::UnaryGlobalValue {
+            ref opcode,
+            ref global_value,
+        } => InstructionData::UnaryGlobalValue {
+            opcode: parse_opcode(opcode)?,
+            global_value: parse_entity(global_value, "gv")?,
+        },
+        SerInstData::Binary {
+            ref opcode,
+            ref args,
+        } => InstructionData::Binary {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+        },
+        SerInstData::BinaryImm {
+            ref opcode,
+            ref arg,
+            ref imm,
+        } => InstructionData::BinaryImm {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::Ternary {
+            ref opcode,
+            ref args,
+        } => InstructionData::Ternary {
+            opcode: parse_opcode(opcode)?,
+            args: [
+                parse_value(&args[0])?,
+                parse_value(&args[1])?,
+                parse_value(&args[2])?,
+            ],
+        },
+        SerInstData::MultiAry {
+            ref opcode,
+            ref args,
+        } => InstructionData::MultiAry {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+        },
+        SerInstData::NullAry { ref opcode } => InstructionData::NullAry {
+            opcode: parse_opcode(opcode)?,
+        },
+        SerInstData::InsertLane {
+            ref opcode,
+            ref args,
+            ref lane,
+        } => InstructionData::InsertLane {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            lane: parse_imm(lane, "lane")?,
+        },
+        SerInstData::ExtractLane {
+            ref opcode,
+            ref arg,
+            ref lane,
+        } => InstructionData::ExtractLane {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            lane: parse_imm(lane, "lane")?,
+        },
+        SerInstData::Shuffle {
+            ref opcode,
+            ref args,
+            ref mask,
+        } => InstructionData::Shuffle {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            mask: parse_entity(mask, "imm")?,
+        },
+        SerInstData::IntCompare {
+            ref opcode,
+            ref args,
+            ref cond,
+        } => InstructionData::IntCompare {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            cond: parse_imm(cond, "condition code")?,
+        },
+        SerInstData::IntCompareImm {
+            ref opcode,
+            ref arg,
+            ref cond,
+            ref imm,
+        } => InstructionData::IntCompareImm {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            cond: parse_imm(cond, "condition code")?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::IntCond {
+            ref opcode,
+            ref arg,
+            ref cond,
+        } => InstructionData::IntCond {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            cond: parse_imm(cond, "condition code")?,
+        },
+        SerInstData::FloatCompare {
+            ref opcode,
+            ref args,
+            ref cond,
+        } => InstructionData::FloatCompare {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            cond: parse_imm(cond, "condition code")?,
+        },
+        SerInstData::FloatCond {
+            ref opcode,
+            ref arg,
+            ref cond,
+        } => InstructionData::FloatCond {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            cond: parse_imm(cond, "condition code")?,
+        },
+        SerInstData::IntSelect {
+            ref opcode,
+            ref args,
+            ref cond,
+        } => InstructionData::IntSelect {
+            opcode: parse_opcode(opcode)?,
+            args: [
+                parse_value(&args[0])?,
+                parse_value(&args[1])?,
+                parse_value(&args[2])?,
+            ],
+            cond: parse_imm(cond, "condition code")?,
+        },
+        SerInstData::Jump {
+            ref opcode,
+            ref args,
+            ref destination,
+        } => InstructionData::Jump {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            destination: parse_block(destination)?,
+        },
+        SerInstData::Branch {
+            ref opcode,
+            ref args,
+            ref destination,
+        } => InstructionData::Branch {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            destination: parse_block(destination)?,
+        },
+        SerInstData::BranchInt {
+            ref opcode,
+            ref args,
+            ref cond,
+            ref destination,
+        } => InstructionData::BranchInt {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            cond: parse_imm(cond, "condition code")?,
+            destination: parse_block(destination)?,
+        },
+        SerInstData::BranchFloat {
+            ref opcode,
+            ref args,
+            ref cond,
+            ref destination,
+        } => InstructionData::BranchFloat {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            cond: parse_imm(cond, "condition code")?,
+            destination: parse_block(destination)?,
+        },
+        SerInstData::BranchIcmp {
+            ref opcode,
+            ref args,
+            ref cond,
+            ref destination,
+        } => InstructionData::BranchIcmp {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            cond: parse_imm(cond, "condition code")?,
+            destination: parse_block(destination)?,
+        },
+        SerInstData::BranchTable {
+            ref opcode,
+            ref arg,
+            ref destination,
+            ref table,
+        } => InstructionData::BranchTable {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            destination: parse_block(destination)?,
+            table: parse_entity(table, "jt")?,
+        },
+        SerInstData::BranchTableEntry {
+            ref opcode,
+            ref args,
+            ref imm,
+            ref table,
+        } => InstructionData::BranchTableEntry {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            imm: parse_imm(imm, "immediate")?,
+            table: parse_entity(table, "jt")?,
+        },
+        SerInstData::BranchTableBase {
+            ref opcode,
+            ref table,
+        } => InstructionData::BranchTableBase {
+            opcode: parse_opcode(opcode)?,
+            table: parse_entity(table, "jt")?,
+        },
+        SerInstData::IndirectJump {
+            ref opcode,
+            ref arg,
+            ref table,
+        } => InstructionData::IndirectJump {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            table: parse_entity(table, "jt")?,
+        },
+        SerInstData::Call {
+            ref opcode,
+            ref args,
+            ref func_ref,
+        } => InstructionData::Call {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            func_ref: parse_entity(func_ref, "fn")?,
+        },
+        SerInstData::CallIndirect {
+            ref opcode,
+            ref args,
+            ref sig_ref,
+        } => InstructionData::CallIndirect {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            sig_ref: parse_entity(sig_ref, "sig")?,
+        },
+        SerInstData::FuncAddr {
+            ref opcode,
+            ref func_ref,
+        } => InstructionData::FuncAddr {
+            opcode: parse_opcode(opcode)?,
+            func_ref: parse_entity(func_ref, "fn")?,
+        },
+        SerInstData::Load {
+            ref opcode,
+            ref arg,
+            ref flags,
+            ref offset,
+        } => InstructionData::Load {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            flags: parse_memflags(flags)?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::LoadComplex {
+            ref opcode,
+            ref args,
+            ref flags,
+            ref offset,
+        } => InstructionData::LoadComplex {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            flags: parse_memflags(flags)?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::Store {
+            ref opcode,
+            ref args,
+            ref flags,
+            ref offset,
+        } => InstructionData::Store {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            flags: parse_memflags(flags)?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::StoreComplex {
+            ref opcode,
+            ref args,
+            ref flags,
+            ref offset,
+        } => InstructionData::StoreComplex {
+            opcode: parse_opcode(opcode)?,
+            args: parse_value_list(args, func)?,
+            flags: parse_memflags(flags)?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::AtomicRmw {
+            ref opcode,
+            ref args,
+            ref flags,
+            ref op,
+        } => InstructionData::AtomicRmw {
+            opcode: parse_opcode(opcode)?,
+            args: [parse_value(&args[0])?, parse_value(&args[1])?],
+            flags: parse_memflags(flags)?,
+            op: parse_imm(op, "atomic rmw operation")?,
+        },
+        SerInstData::AtomicCas {
+            ref opcode,
+            ref args,
+            ref flags,
+        } => InstructionData::AtomicCas {
+            opcode: parse_opcode(opcode)?,
+            args: [
+                parse_value(&args[0])?,
+                parse_value(&args[1])?,
+                parse_value(&args[2])?,
+            ],
+            flags: parse_memflags(flags)?,
+        },
+        SerInstData::StackLoad {
+            ref opcode,
+            ref stack_slot,
+            ref offset,
+        } => InstructionData::StackLoad {
+            opcode: parse_opcode(opcode)?,
+            stack_slot: parse_entity(stack_slot, "ss")?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::StackStore {
+            ref opcode,
+            ref arg,
+            ref stack_slot,
+            ref offset,
+        } => InstructionData::StackStore {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            stack_slot: parse_entity(stack_slot, "ss")?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::HeapAddr {
+            ref opcode,
+            ref arg,
+            ref heap,
+            ref imm,
+        } => InstructionData::HeapAddr {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            heap: parse_entity(heap, "heap")?,
+            imm: parse_imm(imm, "immediate")?,
+        },
+        SerInstData::TableAddr {
+            ref opcode,
+            ref arg,
+            ref table,
+            ref offset,
+        } => InstructionData::TableAddr {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            table: parse_entity(table, "table")?,
+            offset: parse_offset32(offset)?,
+        },
+        SerInstData::RegMove {
+            ref opcode,
+            ref arg,
+            ref src,
+            ref dst,
+        } => InstructionData::RegMove {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            src: parse_imm(src, "register unit")?,
+            dst: parse_imm(dst, "register unit")?,
+        },
+        SerInstData::CopySpecial {
+            ref opcode,
+            ref src,
+            ref dst,
+        } => InstructionData::CopySpecial {
+            opcode: parse_opcode(opcode)?,
+            src: parse_imm(src, "register unit")?,
+            dst: parse_imm(dst, "register unit")?,
+        },
+        SerInstData::CopyToSsa {
+            ref opcode,
+            ref src,
+        } => InstructionData::CopyToSsa {
+            opcode: parse_opcode(opcode)?,
+            src: parse_imm(src, "register unit")?,
+        },
+        SerInstData::RegSpill {
+            ref opcode,
+            ref arg,
+            ref src,
+            ref dst,
+        } => InstructionData::RegSpill {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            src: parse_imm(src, "register unit")?,
+            dst: parse_entity(dst, "ss")?,
+        },
+        SerInstData::RegFill {
+            ref opcode,
+            ref arg,
+            ref src,
+            ref dst,
+        } => InstructionData::RegFill {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            src: parse_entity(src, "ss")?,
+            dst: parse_imm(dst, "register unit")?,
+        },
+        SerInstData::Trap {
+            ref opcode,
+            ref code,
+        } => InstructionData::Trap {
+            opcode: parse_opcode(opcode)?,
+            code: parse_imm(code, "trap code")?,
+        },
+        SerInstData::CondTrap {
+            ref opcode,
+            ref arg,
+            ref code,
+        } => InstructionData::CondTrap {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            code: parse_imm(code, "trap code")?,
+        },
+        SerInstData::IntCondTrap {
+            ref opcode,
+            ref arg,
+            ref cond,
+            ref code,
+        } => InstructionData::IntCondTrap {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            cond: parse_imm(cond, "condition code")?,
+            code: parse_imm(code, "trap code")?,
+        },
+        SerInstData::FloatCondTrap {
+            ref opcode,
+            ref arg,
+            ref cond,
+            ref code,
+        } => InstructionData::FloatCondTrap {
+            opcode: parse_opcode(opcode)?,
+            arg: parse_value(arg)?,
+            cond: parse_imm(cond, "condition code")?,
+            code: parse_imm(code, "trap code")?,
+        },
+    })
+}
+
+/// Pad the value numbering of `func` so that `value` can be defined.
+fn pad_values(func: &mut Function, value: Value) {
+    while func.dfg.num_values() <= value.index() {
+        func.dfg.make_invalid_value_for_parser();
+    }
+}
+
+impl SerFunction {
+    /// Rebuild the function. All entities keep the numbers they had in the serialized function;
+    /// gaps in the numbering are padded the same way the parser pads them.
+    pub fn to_function(&self) -> Result<Function, String> {
+        let mut func = Function::with_name_signature(
+            parse_external_name(&self.name)?,
+            self.signature.to_signature()?,
+        );
+        if let Some(ref old_signature) = self.old_signature {
+            func.old_signature = Some(old_signature.to_signature()?);
+        }
+
+        for ser_ss in &self.stack_slots {
+            let ss: StackSlot = parse_entity(&ser_ss.name, "ss")?;
+            let mut data = StackSlotData::new(
+                parse_imm(&ser_ss.kind, "stack slot kind")?,
+                parse_imm(&ser_ss.size, "stack slot size")?,
+            );
+            if let Some(ref offset) = ser_ss.offset {
+                data.offset = Some(parse_imm(offset, "stack slot offset")?);
+            }
+            while func.stack_slots.next_key().index() <= ss.index() {
+                func.create_stack_slot(StackSlotData::new(StackSlotKind::SpillSlot, 0));
+            }
+            func.stack_slots[ss] = data;
+        }
+
+        for ser_gv in &self.globals {
+            let gv: GlobalValue = parse_entity(&ser_gv.name, "gv")?;
+            let data = match ser_gv.data {
+                SerGlobalValueData::VMContext => GlobalValueData::VMContext,
+                SerGlobalValueData::Load {
+                    ref base,
+                    ref offset,
+                    ref global_type,
+                    readonly,
+                } => GlobalValueData::Load {
+                    base: parse_entity(base, "gv")?,
+                    offset: parse_offset32(offset)?,
+                    global_type: parse_type(global_type)?,
+                    readonly,
+                },
+                SerGlobalValueData::IAddImm {
+                    ref base,
+                    ref offset,
+                    ref global_type,
+                } => GlobalValueData::IAddImm {
+                    base: parse_entity(base, "gv")?,
+                    offset: parse_imm(offset, "immediate")?,
+                    global_type: parse_type(global_type)?,
+                },
+                SerGlobalValueData::Symbol {
+                    ref name,
+                    ref offset,
+                    colocated,
+                    tls,
+                } => GlobalValueData::Symbol {
+                    name: parse_external_name(name)?,
+                    offset: parse_imm(offset, "immediate")?,
+                    colocated,
+                    tls,
+                },
+            };
+            while func.global_values.next_key().index() <= gv.index() {
+                func.create_global_value(GlobalValueData::Symbol {
+                    name: ExternalName::testcase(""),
+                    offset: Imm64::new(0),
+                    colocated: false,
+                    tls: false,
+                });
+            }
+            func.global_values[gv] = data;
+        }
+
+        for ser_heap in &self.heaps {
+            let heap: Heap = parse_entity(&ser_heap.name, "heap")?;
+            let data = HeapData {
+                base: parse_entity(&ser_heap.base, "gv")?,
+                min_size: parse_imm(&ser_heap.min_size, "heap size")?,
+                offset_guard_size: parse_imm(&ser_heap.offset_guard_size, "heap guard size")?,
+                style: match ser_heap.style {
+                    SerHeapStyle::Dynamic { ref bound_gv } => HeapStyle::Dynamic {
+                        bound_gv: parse_entity(bound_gv, "gv")?,
+                    },
+                    SerHeapStyle::Static { ref bound } => HeapStyle::Static {
+                        bound: parse_imm(bound, "heap bound")?,
+                    },
+                },
+                index_type: parse_type(&ser_heap.index_type)?,
+            };
+            while func.heaps.next_key().index() <= heap.index() {
+                func.create_heap(HeapData {
+                    base: GlobalValue::reserved_value(),
+                    min_size: Uimm64::new(0),
+                    offset_guard_size: Uimm64::new(0),
+                    style: HeapStyle::Static {
+                        bound: Uimm64::new(0),
+                    },
+                    index_type: types::INVALID,
+                });
+            }
+            func.heaps[heap] = data;
+        }
+
+        for ser_table in &self.tables {
+            let table: Table = parse_entity(&ser_table.name, "table")?;
+            let data = TableData {
+                base_gv: parse_entity(&ser_table.base_gv, "gv")?,
+                min_size: parse_imm(&ser_table.min_size, "table size")?,
+                bound_gv: parse_entity(&ser_table.bound_gv, "gv")?,
+                element_size: parse_imm(&ser_table.element_size, "table element size")?,
+                index_type: parse_type(&ser_table.index_type)?,
+            };
+            while func.tables.next_key().index() <= table.index() {
+                func.create_table(TableData {
+                    base_gv: GlobalValue::reserved_value(),
+                    min_size: Uimm64::new(0),
+                    bound_gv: GlobalValue::reserved_value(),
+                    element_size: Uimm64::new(0),
+                    index_type: types::INVALID,
+                });
+            }
+            func.tables[table] = data;
+        }
+
+        for ser_sig in &self.dfg.signatures {
+            let sig_ref: SigRef = parse_entity(&ser_sig.name, "sig")?;
+            let sig = ser_sig.signature.to_signature()?;
+            while func.dfg.signatures.next_key().index() <= sig_ref.index() {
+                func.import_signature(Signature::new(sig.call_conv));
+            }
+            func.dfg.signatures[sig_ref] = sig;
+        }
+
+        for ser_ext_func in &self.dfg.ext_funcs {
+            let func_ref: FuncRef = parse_entity(&ser_ext_func.name, "fn")?;
+            let data = ExtFuncData {
+                name: parse_external_name(&ser_ext_func.func_name)?,
+                signature: parse_entity(&ser_ext_func.signature, "sig")?,
+                colocated: ser_ext_func.colocated,
+            };
+            while func.dfg.ext_funcs.next_key().index() <= func_ref.index() {
+                func.import_function(ExtFuncData {
+                    name: ExternalName::testcase(""),
+                    signature: SigRef::reserved_value(),
+                    colocated: false,
+                });
+            }
+            func.dfg.ext_funcs[func_ref] = data;
+        }
+
+        for ser_jt in &self.jump_tables {
+            let jt: JumpTable = parse_entity(&ser_jt.name, "jt")?;
+            let mut data = JumpTableData::with_capacity(ser_jt.blocks.len());
+            for block in &ser_jt.blocks {
+                data.push_entry(parse_block(block)?);
+            }
+            while func.jump_tables.next_key().index() <= jt.index() {
+                func.create_jump_table(JumpTableData::new());
+            }
+            func.jump_tables[jt] = data;
+        }
+
+        // The constant pool hands out handles in insertion order.
+        for ser_constant in &self.dfg.constants {
+            let expected: Constant = parse_entity(&ser_constant.name, "const")?;
+            let data = ConstantData::from(hex_to_bytes(&ser_constant.data)?);
+            if func.dfg.constants.insert(data) != expected {
+                return Err(format!(
+                    "constant {} is a duplicate or out of order",
+                    ser_constant.name
+                ));
+            }
+        }
+
+        for ser_immediate in &self.dfg.immediates {
+            let immediate: Immediate = parse_entity(&ser_immediate.name, "imm")?;
+            while func.dfg.immediates.next_key().index() <= immediate.index() {
+                func.dfg.immediates.push(ConstantData::default());
+            }
+            func.dfg.immediates[immediate] = ConstantData::from(hex_to_bytes(&ser_immediate.data)?);
+        }
+
+        // Create the blocks with their parameters, and collect the instructions in layout order.
+        let mut layout_insts = Vec::new();
+        for ser_block in &self.dfg.blocks {
+            let block = parse_block(&ser_block.block)?;
+            while func.dfg.num_blocks() <= block.index() {
+                func.dfg.make_block();
+            }
+            if func.layout.is_block_inserted(block) {
+                return Err(format!("duplicate definition of {}", block));
+            }
+            func.layout.append_block(block);
+
+            for param in &ser_block.params {
+                let value = parse_value(&param.value)?;
+                pad_values(&mut func, value);
+                let ty = parse_type(&param.value_type)?;
+                func.dfg.append_block_param_for_parser(block, ty, value);
+            }
+
+            for ser_inst in &ser_block.insts {
+                let inst: Inst = parse_entity(&ser_inst.inst_name, "inst")?;
+                layout_insts.push((inst, block, ser_inst));
+            }
+        }
+
+        // Instructions can only be created in the order of their numbers.
+        let mut numbered_insts = layout_insts.clone();
+        numbered_insts.sort_by_key(|&(inst, _, _)| inst);
+        for &(inst, _, ser_inst) in &numbered_insts {
+            if func.dfg.num_insts() > inst.index() {
+                return Err(format!("duplicate definition of {}", inst));
+            }
+            // Fill the gaps in the numbering with instructions that aren't in the layout.
+            while func.dfg.num_insts() < inst.index() {
+                func.dfg.make_inst(InstructionData::NullAry {
+                    opcode: Opcode::Nop,
+                });
+            }
+
+            let data = make_inst_data(&ser_inst.inst_data, &mut func)?;
+            let results = parse_values(&ser_inst.results)?;
+            for &result in &results {
+                pad_values(&mut func, result);
+            }
+            let ctrl_typevar = match ser_inst.ctrl_typevar {
+                Some(ref ty) => parse_type(ty)?,
+                None => types::INVALID,
+            };
+            func.dfg.make_inst(data);
+            let num_results = func
+                .dfg
+                .make_inst_results_for_parser(inst, ctrl_typevar, &results);
+            if num_results != results.len() {
+                return Err(format!(
+                    "{} produces {} result values, {} given",
+                    inst,
+                    num_results,
+                    results.len()
+                ));
+            }
+
+            if let Some(ref srcloc) = ser_inst.srcloc {
+                func.srclocs[inst] = parse_srcloc(srcloc)?;
+            }
+            if let Some(ref encoding) = ser_inst.encoding {
+                func.encodings[inst] = parse_encoding(encoding)?;
+            }
+        }
+
+        for &(inst, block, _) in &layout_insts {
+            func.layout.append_inst(inst, block);
+        }
+
+        // Aliases may refer to aliases defined later, so only compute their types once they are
+        // all defined.
+        let mut aliases = Vec::new();
+        for ser_alias in &self.dfg.value_aliases {
+            let alias = parse_value(&ser_alias.alias)?;
+            let original = parse_value(&ser_alias.original)?;
+            pad_values(&mut func, alias);
+            pad_values(&mut func, original);
+            func.dfg.make_value_alias_for_serialization(original, alias);
+            aliases.push(alias);
+        }
+        for alias in aliases {
+            if !func.dfg.set_alias_type_for_parser(alias) {
+                return Err(format!("alias cycle involving {}", alias));
+            }
+        }
+
+        for ser_loc in &self.value_locations {
+            let value = parse_value(&ser_loc.value)?;
+            func.locations[value] = parse_value_loc(&ser_loc.location)?;
+        }
+
+        if let Some(ref values_labels) = self.dfg.value_labels {
+            let mut map = HashMap::new();
+            for ser_labels in values_labels {
+                let assignments = match ser_labels.assignments {
+                    SerValueLabelAssignments::Starts(ref starts) => {
+                        let mut label_starts = Vec::new();
+                        for start in starts {
+                            label_starts.push(ValueLabelStart {
+                                from: parse_srcloc(&start.from)?,
+                                label: parse_entity(&start.label, "val")?,
+                            });
+                        }
+                        ValueLabelAssignments::Starts(label_starts)
+                    }
+                    SerValueLabelAssignments::Alias {
+                        ref from,
+                        ref value,
+                    } => ValueLabelAssignments::Alias {
+                        from: parse_srcloc(from)?,
+                        value: parse_value(value)?,
+                    },
+                };
+                map.insert(parse_value(&ser_labels.value)?, assignments);
+            }
+            func.dfg.values_labels = Some(map);
+        }
+
+        if let Some(ref prologue_end) = self.prologue_end {
+            func.prologue_end = Some(parse_entity(prologue_end, "inst")?);
+        }
+
+        Ok(func)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cranelift_codegen::ir::{DataFlowGraph, ValueLabel};
+    use cranelift_codegen::isa::TargetIsa;
+    use cranelift_codegen::{settings, verify_function};
+    use cranelift_reader::{parse_functions, parse_test, ParseOptions};
+    use std::collections::BTreeMap;
+    use std::fmt;
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    fn round_trip(func: &Function) -> Function {
+        let json = serde_json::to_string(&SerObj::new(&[func.clone()])).unwrap();
+        let ser_obj: SerObj = serde_json::from_str(&json).unwrap();
+        let mut funcs = ser_obj.to_functions().unwrap();
+        assert_eq!(funcs.len(), 1);
+        funcs.pop().unwrap()
+    }
+
+    fn printed<T: fmt::Display>(items: impl Iterator<Item = T>) -> Vec<String> {
+        items.map(|item| item.to_string()).collect()
+    }
+
+    /// Compare two functions entity by entity, rather than by how they print, so that anything
+    /// the printer leaves out or the deserializer renumbers shows up as a difference.
+    fn assert_same_function(expected: &Function, actual: &Function, isa: Option<&dyn TargetIsa>) {
+        let name = &expected.name;
+        let flags = settings::Flags::new(settings::builder());
+        let verify = |func: &Function| match isa {
+            Some(isa) => verify_function(func, isa),
+            None => verify_function(func, &flags),
+        };
+        if verify(expected).is_ok() {
+            if let Err(errors) = verify(actual) {
+                panic!(
+                    "{} no longer verifies after the round trip:\n{}",
+                    name, errors
+                );
+            }
+        }
+
+        assert_eq!(actual.name, expected.name);
+        assert_eq!(actual.signature, expected.signature, "{}", name);
+        assert_eq!(actual.stack_slots, expected.stack_slots, "{}", name);
+        assert_eq!(actual.dfg.signatures, expected.dfg.signatures, "{}", name);
+
+        assert_eq!(
+            printed(actual.global_values.values()),
+            printed(expected.global_values.values()),
+            "{}",
+            name
+        );
+        assert_eq!(
+            printed(actual.heaps.values()),
+            printed(expected.heaps.values()),
+            "{}",
+            name
+        );
+        assert_eq!(
+            printed(actual.tables.values()),
+            printed(expected.tables.values()),
+            "{}",
+            name
+        );
+        assert_eq!(
+            printed(actual.dfg.ext_funcs.values()),
+            printed(expected.dfg.ext_funcs.values()),
+            "{}",
+            name
+        );
+        assert_eq!(
+            actual.jump_tables.len(),
+            expected.jump_tables.len(),
+            "{}",
+            name
+        );
+        for (jt, data) in expected.jump_tables.iter() {
+            assert_eq!(
+                actual.jump_tables[jt].as_slice(),
+                data.as_slice(),
+                "{}",
+                name
+            );
+        }
+        assert_eq!(
+            actual.dfg.constants.iter().collect::<Vec<_>>(),
+            expected.dfg.constants.iter().collect::<Vec<_>>(),
+            "{}",
+            name
+        );
+
+        let blocks: Vec<Block> = expected.layout.blocks().collect();
+        assert_eq!(
+            actual.layout.blocks().collect::<Vec<_>>(),
+            blocks,
+            "{}",
+            name
+        );
+        for block in blocks {
+            let params = expected.dfg.block_params(block);
+            assert_eq!(actual.dfg.block_params(block), params, "{}", name);
+            for &param in params {
+                assert_eq!(
+                    actual.dfg.value_type(param),
+                    expected.dfg.value_type(param),
+                    "{} {}",
+                    name,
+                    param
+                );
+                assert_eq!(actual.locations[param], expected.locations[param]);
+            }
+
+            let insts: Vec<Inst> = expected.layout.block_insts(block).collect();
+            let actual_insts: Vec<Inst> = actual.layout.block_insts(block).collect();
+            assert_eq!(actual_insts.len(), insts.len(), "{} {}", name, block);
+            for (&inst, &actual_inst) in insts.iter().zip(&actual_insts) {
+                let (expected_dfg, actual_dfg) = (&expected.dfg, &actual.dfg);
+                let what = expected_dfg.display_inst(inst, isa).to_string();
+                assert_eq!(
+                    actual_dfg[actual_inst].opcode(),
+                    expected_dfg[inst].opcode(),
+                    "{}: {}",
+                    name,
+                    what
+                );
+                assert_eq!(
+                    actual_dfg.display_inst(actual_inst, isa).to_string(),
+                    what,
+                    "{}",
+                    name
+                );
+                let args = |dfg: &DataFlowGraph, inst| -> Vec<Value> {
+                    dfg.inst_args(inst)
+                        .iter()
+                        .map(|&arg| dfg.resolve_aliases(arg))
+                        .collect()
+                };
+                assert_eq!(
+                    args(actual_dfg, actual_inst),
+                    args(expected_dfg, inst),
+                    "{}: {}",
+                    name,
+                    what
+                );
+                let results = expected_dfg.inst_results(inst);
+                assert_eq!(
+                    actual_dfg.inst_results(actual_inst),
+                    results,
+                    "{}: {}",
+                    name,
+                    what
+                );
+                for &result in results {
+                    assert_eq!(
+                        actual_dfg.value_type(result),
+                        expected_dfg.value_type(result)
+                    );
+                    assert_eq!(actual.locations[result], expected.locations[result]);
+                }
+                assert_eq!(
+                    actual_dfg.ctrl_typevar(actual_inst),
+                    expected_dfg.ctrl_typevar(inst),
+                    "{}: {}",
+                    name,
+                    what
+                );
+                assert_eq!(
+                    actual.srclocs[actual_inst], expected.srclocs[inst],
+                    "{}: {}",
+                    name, what
+                );
+                assert_eq!(
+                    actual.encodings[actual_inst], expected.encodings[inst],
+                    "{}: {}",
+                    name, what
+                );
+            }
+        }
+
+        let labels = |func: &Function| {
+            func.dfg.values_labels.as_ref().map(|labels| {
+                labels
+                    .iter()
+                    .map(|(value, assignments)| (*value, format!("{:?}", assignments)))
+                    .collect::<BTreeMap<_, _>>()
+            })
+        };
+        assert_eq!(labels(actual), labels(expected), "{}", name);
+    }
+
+    fn clif_files(dir: &Path, files: &mut Vec<(PathBuf, String)>) {
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                clif_files(&path, files);
+            } else if path.extension().map_or(false, |ext| ext == "clif") {
+                let text = fs::read_to_string(&path).unwrap();
+                files.push((path, text));
+            }
+        }
+    }
+
+    /// Every function of the file tests must come back unchanged after going through JSON.
+    #[test]
+    fn round_trip_filetests() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("../filetests/filetests");
+        let mut files = Vec::new();
+        clif_files(&dir, &mut files);
+        assert!(!files.is_empty());
+
+        let mut unexpected = Vec::new();
+        for (path, text) in &files {
+            let test_file = match parse_test(text, ParseOptions::default()) {
+                Ok(test_file) => test_file,
+                Err(err) => {
+                    // Files may only be skipped when they need a target that isn't built in, or
+                    // when they expect errors.
+                    let message = err.to_string();
+                    if !message.contains("unsupported target")
+                        && !message.contains("support disabled target")
+                        && !text.contains("; error:")
+                    {
+                        unexpected.push(format!("{}: {}", path.display(), message));
+                    }
+                    continue;
+                }
+            };
+            let isa = test_file.isa_spec.unique_isa();
+            for (func, _) in &test_file.functions {
+                assert_same_function(func, &round_trip(func), isa);
+            }
+        }
+        assert!(
+            unexpected.is_empty(),
+            "file tests that couldn't be parsed:\n{}",
+            unexpected.join("\n")
+        );
+    }
+
+    #[test]
+    fn round_trip_srclocs_and_value_labels() {
+        let mut func = parse_functions(
+            "function %f(i32) -> i32 system_v {
+            block0(v0: i32):
+                @0010 v1 = iadd_imm v0, 1
+                @0012 return v1
+            }",
+        )
+        .unwrap()
+        .remove(0);
+        let (v0, v1) = (Value::new(0), Value::new(1));
+        func.collect_debug_info();
+        let labels = func.dfg.values_labels.as_mut().unwrap();
+        labels.insert(
+            v0,
+            ValueLabelAssignments::Starts(vec![ValueLabelStart {
+                from: SourceLoc::new(0x10),
+                label: ValueLabel::new(3),
+            }]),
+        );
+        labels.insert(
+            v1,
+            ValueLabelAssignments::Alias {
+                from: SourceLoc::new(0x12),
+                value: v0,
+            },
+        );
+
+        let copy = round_trip(&func);
+        assert_same_function(&func, &copy, None);
+
+        let insts: Vec<Inst> = copy.layout.block_insts(Block::new(0)).collect();
+        assert_eq!(copy.srclocs[insts[0]], SourceLoc::new(0x10));
+        assert_eq!(copy.srclocs[insts[1]], SourceLoc::new(0x12));
+
+        let labels = copy.dfg.values_labels.as_ref().unwrap();
+        match labels[&v0] {
+            ValueLabelAssignments::Starts(ref starts) => {
+                assert_eq!(starts.len(), 1);
+                assert_eq!(starts[0].from, SourceLoc::new(0x10));
+                assert_eq!(starts[0].label, ValueLabel::new(3));
+            }
+            _ => panic!("expected label starts for {}", v0),
+        }
+        match labels[&v1] {
+            ValueLabelAssignments::Alias { from, value } => {
+                assert_eq!(from, SourceLoc::new(0x12));
+                assert_eq!(value, v0);
+            }
+            _ => panic!("expected a label alias for {}", v1),
+        }
+    }
 }
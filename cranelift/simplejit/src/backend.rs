@@ -7,8 +7,8 @@ use cranelift_codegen::binemit::{
 use cranelift_codegen::isa::TargetIsa;
 use cranelift_codegen::{self, ir, settings};
 use cranelift_module::{
-    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleNamespace,
-    ModuleResult, TrapSite,
+    Backend, DataContext, DataDescription, DataId, FuncId, Init, Linkage, ModuleError,
+    ModuleNamespace, ModuleResult, TrapSite,
 };
 use cranelift_native;
 #[cfg(not(windows))]
@@ -16,8 +16,10 @@ use libc;
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::io::Write;
+use std::mem;
 use std::ptr;
-use target_lexicon::PointerWidth;
+use std::sync::atomic::{AtomicPtr, Ordering};
+use target_lexicon::{Architecture, PointerWidth};
 #[cfg(windows)]
 use winapi;
 
@@ -25,11 +27,16 @@ const EXECUTABLE_DATA_ALIGNMENT: u8 = 0x10;
 const WRITABLE_DATA_ALIGNMENT: u8 = 0x8;
 const READONLY_DATA_ALIGNMENT: u8 = 0x1;
 
+/// The size of the stub emitted in front of each function when hot
+/// redefinition is enabled: `movabs r11, slot; mov r11, [r11]; jmp r11`.
+const HOTSWAP_STUB_SIZE: usize = 16;
+
 /// A builder for `SimpleJITBackend`.
 pub struct SimpleJITBuilder {
     isa: Box<dyn TargetIsa>,
     symbols: HashMap<String, *const u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
+    hotswap: bool,
 }
 
 impl SimpleJITBuilder {
@@ -70,7 +77,30 @@ impl SimpleJITBuilder {
             isa,
             symbols,
             libcall_names,
+            hotswap: false,
+        }
+    }
+
+    /// Enable or disable hot redefinition of functions.
+    ///
+    /// When enabled, each defined function is entered through a small stub
+    /// which jumps via a writable pointer slot, so that
+    /// `Module::redefine_function` can replace a function after it has been
+    /// finalized without patching its callers. Pointers returned by
+    /// `get_finalized_function` point at the stub and therefore stay valid
+    /// across redefinitions.
+    ///
+    /// Hot redefinition is currently only supported on x86-64; enabling it
+    /// for any other target returns an error.
+    pub fn hotswap(&mut self, enabled: bool) -> ModuleResult<&Self> {
+        if enabled && self.isa.triple().architecture != Architecture::X86_64 {
+            return Err(ModuleError::Backend(format!(
+                "SimpleJIT doesn't support hot redefinition on {}",
+                self.isa.triple().architecture
+            )));
         }
+        self.hotswap = enabled;
+        Ok(self)
     }
 
     /// Define a symbol in the internal symbol table.
@@ -119,6 +149,7 @@ pub struct SimpleJITBackend {
     symbols: HashMap<String, *const u8>,
     libcall_names: Box<dyn Fn(ir::LibCall) -> String>,
     memory: SimpleJITMemoryHandle,
+    hotswap: bool,
 }
 
 /// A record of a relocation to perform.
@@ -136,10 +167,28 @@ struct StackmapRecord {
     stackmap: Stackmap,
 }
 
+/// The stub and pointer slot through which a redefinable function is entered.
+#[derive(Clone, Copy)]
+struct Indirection {
+    stub: *mut u8,
+    slot: *const AtomicPtr<u8>,
+}
+
 pub struct SimpleJITCompiledFunction {
     code: *mut u8,
     size: usize,
     relocs: Vec<RelocRecord>,
+    indirection: Option<Indirection>,
+}
+
+impl SimpleJITCompiledFunction {
+    /// The address other code should use to call this function.
+    fn entry(&self) -> *const u8 {
+        match self.indirection {
+            Some(indirection) => indirection.stub,
+            None => self.code,
+        }
+    }
 }
 
 pub struct SimpleJITCompiledData {
@@ -173,7 +222,7 @@ impl SimpleJITBackend {
                 if namespace.is_function(name) {
                     let (def, name_str, _signature) = namespace.get_function_definition(&name);
                     match def {
-                        Some(compiled) => compiled.code,
+                        Some(compiled) => compiled.entry(),
                         None => self.lookup_symbol(name_str),
                     }
                 } else {
@@ -192,6 +241,75 @@ impl SimpleJITBackend {
         }
     }
 
+    /// Allocate a pointer slot and a stub which jumps through it.
+    fn allocate_indirection(&mut self) -> Indirection {
+        let slot = self
+            .memory
+            .writable
+            .allocate(mem::size_of::<AtomicPtr<u8>>(), WRITABLE_DATA_ALIGNMENT)
+            .expect("TODO: handle OOM etc.") as *mut AtomicPtr<u8>;
+        let stub = self
+            .memory
+            .code
+            .allocate(HOTSWAP_STUB_SIZE, EXECUTABLE_DATA_ALIGNMENT)
+            .expect("TODO: handle OOM etc.");
+
+        let mut bytes = [0u8; HOTSWAP_STUB_SIZE];
+        // movabs r11, slot
+        bytes[0..2].copy_from_slice(&[0x49, 0xbb]);
+        bytes[2..10].copy_from_slice(&(slot as u64).to_le_bytes());
+        // mov r11, [r11]
+        bytes[10..13].copy_from_slice(&[0x4d, 0x8b, 0x1b]);
+        // jmp r11
+        bytes[13..16].copy_from_slice(&[0x41, 0xff, 0xe3]);
+        unsafe {
+            ptr::write(slot, AtomicPtr::new(ptr::null_mut()));
+            ptr::copy_nonoverlapping(bytes.as_ptr(), stub, HOTSWAP_STUB_SIZE);
+        }
+
+        Indirection { stub, slot }
+    }
+
+    /// Emit the code for `ctx` into freshly allocated executable memory.
+    fn emit_function(
+        &mut self,
+        name: &str,
+        ctx: &cranelift_codegen::Context,
+        code_size: u32,
+        indirection: Option<Indirection>,
+    ) -> SimpleJITCompiledFunction {
+        let size = code_size as usize;
+        let ptr = self
+            .memory
+            .code
+            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
+            .expect("TODO: handle OOM etc.");
+
+        self.record_function_for_perf(ptr, size, name);
+
+        let mut reloc_sink = SimpleJITRelocSink::new();
+        // Ignore traps for now. For now, frontends should just avoid generating code
+        // that traps.
+        let mut trap_sink = NullTrapSink {};
+        let mut stackmap_sink = SimpleJITStackmapSink::new();
+        unsafe {
+            ctx.emit_to_memory(
+                &*self.isa,
+                ptr,
+                &mut reloc_sink,
+                &mut trap_sink,
+                &mut stackmap_sink,
+            )
+        };
+
+        SimpleJITCompiledFunction {
+            code: ptr,
+            size,
+            relocs: reloc_sink.relocs,
+            indirection,
+        }
+    }
+
     fn record_function_for_perf(&self, ptr: *mut u8, size: usize, name: &str) {
         // The Linux perf tool supports JIT code via a /tmp/perf-$PID.map file,
         // which contains memory regions and their associated names.  If we
@@ -247,6 +365,7 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             symbols: builder.symbols,
             libcall_names: builder.libcall_names,
             memory,
+            hotswap: builder.hotswap,
         }
     }
 
@@ -279,35 +398,31 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
         _namespace: &ModuleNamespace<Self>,
         code_size: u32,
     ) -> ModuleResult<Self::CompiledFunction> {
-        let size = code_size as usize;
-        let ptr = self
-            .memory
-            .code
-            .allocate(size, EXECUTABLE_DATA_ALIGNMENT)
-            .expect("TODO: handle OOM etc.");
-
-        self.record_function_for_perf(ptr, size, name);
-
-        let mut reloc_sink = SimpleJITRelocSink::new();
-        // Ignore traps for now. For now, frontends should just avoid generating code
-        // that traps.
-        let mut trap_sink = NullTrapSink {};
-        let mut stackmap_sink = SimpleJITStackmapSink::new();
-        unsafe {
-            ctx.emit_to_memory(
-                &*self.isa,
-                ptr,
-                &mut reloc_sink,
-                &mut trap_sink,
-                &mut stackmap_sink,
-            )
+        let indirection = if self.hotswap {
+            Some(self.allocate_indirection())
+        } else {
+            None
         };
+        Ok(self.emit_function(name, ctx, code_size, indirection))
+    }
 
-        Ok(Self::CompiledFunction {
-            code: ptr,
-            size,
-            relocs: reloc_sink.relocs,
-        })
+    /// With hot redefinition enabled, the new body reuses the stub of the
+    /// previous one; the stub is switched over to it in `finalize_function`.
+    /// The previous body is kept alive until `free_memory`, since it may
+    /// still be running on some stack.
+    fn redefine_function(
+        &mut self,
+        _id: FuncId,
+        name: &str,
+        ctx: &cranelift_codegen::Context,
+        _namespace: &ModuleNamespace<Self>,
+        code_size: u32,
+        previous: &Self::CompiledFunction,
+    ) -> ModuleResult<Self::CompiledFunction> {
+        match previous.indirection {
+            Some(indirection) => Ok(self.emit_function(name, ctx, code_size, Some(indirection))),
+            None => Err(ModuleError::DuplicateDefinition(name.to_owned())),
+        }
     }
 
     fn define_function_bytes(
@@ -331,10 +446,16 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
             ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, size);
         }
 
+        let indirection = if self.hotswap {
+            Some(self.allocate_indirection())
+        } else {
+            None
+        };
         Ok(Self::CompiledFunction {
             code: ptr,
             size,
             relocs: vec![],
+            indirection,
         })
     }
 
@@ -480,11 +601,15 @@ impl<'simple_jit_backend> Backend for SimpleJITBackend {
                 _ => unimplemented!(),
             }
         }
-        func.code
+        if let Some(indirection) = func.indirection {
+            // Redirect the stub to the now fully relocated body.
+            unsafe { (*indirection.slot).store(func.code, Ordering::Release) };
+        }
+        func.entry()
     }
 
     fn get_finalized_function(&self, func: &Self::CompiledFunction) -> Self::FinalizedFunction {
-        func.code
+        func.entry()
     }
 
     fn finalize_data(
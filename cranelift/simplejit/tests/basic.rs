@@ -195,3 +195,110 @@ fn libcall_function() {
 
     module.finalize_definitions();
 }
+
+fn define_constant_function(func_id: FuncId, sig: &Signature, value: i64) -> Context {
+    let mut ctx = Context::new();
+    ctx.func = Function::with_name_signature(ExternalName::user(0, func_id.as_u32()), sig.clone());
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.switch_to_block(block);
+        let value = bcx.ins().iconst(types::I32, value);
+        bcx.ins().return_(&[value]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    ctx
+}
+
+#[test]
+#[cfg(target_arch = "x86_64")]
+fn redefine_finalized_function() {
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    builder.hotswap(true).unwrap();
+    let mut module: Module<SimpleJITBackend> = Module::new(builder);
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![AbiParam::new(types::I32)],
+        call_conv: CallConv::SystemV,
+    };
+    let callee_id = module
+        .declare_function("callee", Linkage::Local, &sig)
+        .unwrap();
+    let caller_id = module
+        .declare_function("caller", Linkage::Local, &sig)
+        .unwrap();
+
+    let mut ctx = define_constant_function(callee_id, &sig, 1);
+    module.define_function(callee_id, &mut ctx).unwrap();
+
+    let mut ctx = Context::new();
+    ctx.func =
+        Function::with_name_signature(ExternalName::user(0, caller_id.as_u32()), sig.clone());
+    let mut func_ctx = FunctionBuilderContext::new();
+    {
+        let mut bcx: FunctionBuilder = FunctionBuilder::new(&mut ctx.func, &mut func_ctx);
+        let block = bcx.create_block();
+        bcx.switch_to_block(block);
+        let callee = module.declare_func_in_func(callee_id, &mut bcx.func);
+        let call = bcx.ins().call(callee, &[]);
+        let result = bcx.inst_results(call)[0];
+        bcx.ins().return_(&[result]);
+        bcx.seal_all_blocks();
+        bcx.finalize();
+    }
+    module.define_function(caller_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    let caller = module.get_finalized_function(caller_id);
+    let caller = unsafe { std::mem::transmute::<_, extern "C" fn() -> i32>(caller) };
+    let callee = module.get_finalized_function(callee_id);
+    assert_eq!(caller(), 1);
+    assert_eq!(module.function_version(callee_id), 1);
+
+    let mut ctx = define_constant_function(callee_id, &sig, 2);
+    module.redefine_function(callee_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    // The caller wasn't touched, but now reaches the new body, and the
+    // callee's own entry point is unchanged.
+    assert_eq!(caller(), 2);
+    assert_eq!(module.get_finalized_function(callee_id), callee);
+    assert_eq!(module.function_version(callee_id), 2);
+}
+
+#[test]
+#[cfg(not(target_arch = "x86_64"))]
+fn hotswap_unsupported() {
+    let mut builder = SimpleJITBuilder::new(default_libcall_names());
+    match builder.hotswap(true) {
+        Err(ModuleError::Backend(_)) => {}
+        _ => panic!("expected hot redefinition to be rejected"),
+    }
+}
+
+#[test]
+fn redefine_without_hotswap() {
+    let mut module: Module<SimpleJITBackend> =
+        Module::new(SimpleJITBuilder::new(default_libcall_names()));
+
+    let sig = Signature {
+        params: vec![],
+        returns: vec![AbiParam::new(types::I32)],
+        call_conv: CallConv::SystemV,
+    };
+    let func_id = module
+        .declare_function("abc", Linkage::Local, &sig)
+        .unwrap();
+    let mut ctx = define_constant_function(func_id, &sig, 1);
+    module.define_function(func_id, &mut ctx).unwrap();
+    module.finalize_definitions();
+
+    let mut ctx = define_constant_function(func_id, &sig, 2);
+    match module.redefine_function(func_id, &mut ctx) {
+        Err(ModuleError::DuplicateDefinition(name)) => assert_eq!(name, "abc"),
+        _ => panic!("expected a duplicate definition error"),
+    }
+}
@@ -13,7 +13,7 @@
     )
 )]
 
-use clap::{App, Arg, SubCommand};
+use clap::{App, AppSettings, Arg, SubCommand};
 use cranelift_codegen::dbg::LOG_FILENAME_PREFIX;
 use cranelift_codegen::VERSION;
 use std::io::{self, Write};
@@ -24,6 +24,7 @@ mod bugpoint;
 mod cat;
 mod compile;
 mod disasm;
+mod interpret;
 mod print_cfg;
 mod run;
 mod utils;
@@ -210,11 +211,52 @@ fn main() {
         )
         .subcommand(
             SubCommand::with_name("bugpoint")
-                .about("Reduce size of clif file causing panic during compilation.")
+                .about(
+                    "Reduce size of clif file causing panic during compilation, or being \
+                     miscompiled.",
+                )
                 .arg(add_single_input_file_arg())
                 .arg(add_set_flag())
                 .arg(add_target_flag())
+                .arg(
+                    Arg::with_name("backend")
+                        .long("backend")
+                        .takes_value(true)
+                        .possible_values(&["arm64", "x64"])
+                        .help("Compile with the given new-style backend"),
+                )
+                .arg(
+                    Arg::with_name("panic-message")
+                        .long("panic-message")
+                        .takes_value(true)
+                        .help("Only consider panics whose message contains this string"),
+                )
+                .arg(Arg::with_name("wrong-code").long("wrong-code").help(
+                    "Reduce a function returning false on the host, but true when \
+                             compiled with the reference settings or interpreted",
+                ))
+                .arg(
+                    Arg::with_name("reference-set")
+                        .long("reference-set")
+                        .takes_value(true)
+                        .multiple(true)
+                        .requires("wrong-code")
+                        .help("Cranelift settings to compile the reference with"),
+                )
+                .arg(
+                    Arg::with_name("interpret")
+                        .long("interpret")
+                        .requires("wrong-code")
+                        .conflicts_with("reference-set")
+                        .help("Use the interpreter instead of a reference compilation"),
+                )
                 .arg(add_verbose_flag()),
+        )
+        .subcommand(
+            SubCommand::with_name("bugpoint-run")
+                .setting(AppSettings::Hidden)
+                .about("Run a function read from stdin for bugpoint, printing its result.")
+                .arg(add_set_flag()),
         );
 
     let res_util = match app_cmds.get_matches().subcommand() {
@@ -314,13 +356,29 @@ fn main() {
                 target_val = clap_target;
             }
 
+            let wrong_code = if !rest_cmd.is_present("wrong-code") {
+                None
+            } else if rest_cmd.is_present("interpret") {
+                Some(bugpoint::Oracle::Interpreter)
+            } else {
+                Some(bugpoint::Oracle::Reference(get_vec(
+                    rest_cmd.values_of("reference-set"),
+                )))
+            };
+
             bugpoint::run(
                 rest_cmd.value_of("single-file").unwrap(),
                 &get_vec(rest_cmd.values_of("set")),
                 target_val,
+                rest_cmd.value_of("backend"),
+                rest_cmd.value_of("panic-message"),
+                wrong_code,
                 rest_cmd.is_present("verbose"),
             )
         }
+        ("bugpoint-run", Some(rest_cmd)) => {
+            bugpoint::run_candidate(&get_vec(rest_cmd.values_of("set")))
+        }
         _ => Err("Invalid subcommand.".to_owned()),
     };
 
@@ -0,0 +1,461 @@
+//! A simple interpreter for CLIF functions following the `test run` convention.
+//!
+//! This is used by bugpoint as an oracle when reducing wrong-code bugs: the interpreter doesn't
+//! share any code with the optimizer or the backends, so its result is what the function is
+//! expected to return when compiled and run natively. Only the arithmetic, comparison and control
+//! flow instructions that appear in target-independent code are supported; functions using
+//! anything else, e.g. memory or calls, can't be interpreted.
+#![allow(clippy::float_arithmetic)]
+
+use cranelift_codegen::const_eval::{icmp, mask, sext};
+use cranelift_codegen::ir::condcodes::FloatCC;
+use cranelift_codegen::ir::instructions::BranchInfo;
+use cranelift_codegen::ir::{
+    types, Block, DataFlowGraph, Function, Inst, InstructionData, Opcode, Type, Value,
+};
+use cranelift_entity::SecondaryMap;
+use std::convert::TryFrom;
+
+/// The number of instructions executed before a function is assumed to loop forever.
+const FUEL: usize = 10_000_000;
+
+/// A value computed by the interpreter.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Val {
+    /// An integer, zero-extended from the width of its type.
+    Int(u64),
+    Bool(bool),
+    F32(f32),
+    F64(f64),
+}
+
+impl Val {
+    fn truthiness(self) -> Result<bool, String> {
+        match self {
+            Val::Int(x) => Ok(x != 0),
+            Val::Bool(b) => Ok(b),
+            Val::F32(_) | Val::F64(_) => Err("floats have no truthiness".to_string()),
+        }
+    }
+}
+
+/// What to do after executing an instruction.
+enum Control {
+    /// Continue with the next instruction, after defining the instruction's results, if any.
+    Next(Option<Val>),
+    /// Continue at the start of a block, passing it arguments.
+    Jump(Block, Vec<Val>),
+    /// Return from the function.
+    Return(Vec<Val>),
+}
+
+/// Interpret `func`, which must take no arguments and return a single boolean, returning the
+/// boolean it returns.
+///
+/// Errors are returned if the function has the wrong signature, uses an instruction that isn't
+/// supported, traps, or doesn't terminate after a large number of instructions.
+pub fn interpret(func: &Function) -> Result<bool, String> {
+    if !(func.signature.params.is_empty()
+        && func.signature.returns.len() == 1
+        && func.signature.returns[0].value_type.is_bool())
+    {
+        return Err(String::from(
+            "Functions must have a signature like: () -> boolean",
+        ));
+    }
+
+    let mut values = SecondaryMap::<Value, Option<Val>>::new();
+    let mut block = func
+        .layout
+        .entry_block()
+        .ok_or_else(|| String::from("function has no blocks"))?;
+    let mut fuel = FUEL;
+    loop {
+        let mut next = None;
+        for inst in func.layout.block_insts(block) {
+            if fuel == 0 {
+                return Err(String::from("function doesn't terminate"));
+            }
+            fuel -= 1;
+
+            match execute(func, &values, inst)? {
+                Control::Next(result) => {
+                    if let Some(result) = result {
+                        values[func.dfg.first_result(inst)] = Some(result);
+                    }
+                }
+                Control::Jump(dest, args) => {
+                    for (&param, arg) in func.dfg.block_params(dest).iter().zip(args) {
+                        values[param] = Some(arg);
+                    }
+                    next = Some(dest);
+                    break;
+                }
+                Control::Return(results) => return results[0].truthiness(),
+            }
+        }
+        block = next.ok_or_else(|| format!("{} doesn't end with a terminator", block))?;
+    }
+}
+
+fn get(
+    values: &SecondaryMap<Value, Option<Val>>,
+    dfg: &DataFlowGraph,
+    value: Value,
+) -> Result<Val, String> {
+    values[dfg.resolve_aliases(value)]
+        .ok_or_else(|| format!("{} is used before it's defined", value))
+}
+
+fn execute(
+    func: &Function,
+    values: &SecondaryMap<Value, Option<Val>>,
+    inst: Inst,
+) -> Result<Control, String> {
+    let dfg = &func.dfg;
+    let unsupported = || unsupported_inst(dfg, inst);
+    let args = dfg
+        .inst_args(inst)
+        .iter()
+        .map(|&arg| get(values, dfg, arg))
+        .collect::<Result<Vec<_>, _>>()?;
+    let ty = match dfg.inst_results(inst).first() {
+        Some(&result) => dfg.value_type(result),
+        None => types::INVALID,
+    };
+    let arg_ty = |value: Value| dfg.value_type(value);
+
+    let result = match dfg[inst] {
+        InstructionData::UnaryImm {
+            opcode: Opcode::Iconst,
+            imm,
+        } => Val::Int(mask(ty, imm.bits() as u64)),
+        InstructionData::UnaryBool {
+            opcode: Opcode::Bconst,
+            imm,
+        } => Val::Bool(imm),
+        InstructionData::UnaryIeee32 {
+            opcode: Opcode::F32const,
+            imm,
+        } => Val::F32(f32::from_bits(imm.bits())),
+        InstructionData::UnaryIeee64 {
+            opcode: Opcode::F64const,
+            imm,
+        } => Val::F64(f64::from_bits(imm.bits())),
+        InstructionData::Unary { opcode, arg } => {
+            unary(opcode, ty, arg_ty(arg), args[0]).ok_or_else(unsupported)?
+        }
+        InstructionData::Binary { opcode, .. } => {
+            binary(opcode, ty, args[0], args[1])?.ok_or_else(unsupported)?
+        }
+        InstructionData::BinaryImm { opcode, imm, .. } => {
+            let imm = Val::Int(mask(ty, imm.bits() as u64));
+            let result = match opcode {
+                Opcode::IrsubImm => binary(Opcode::Isub, ty, imm, args[0])?,
+                _ => match binary_imm_opcode(opcode) {
+                    Some(opcode) => binary(opcode, ty, args[0], imm)?,
+                    None => None,
+                },
+            };
+            result.ok_or_else(unsupported)?
+        }
+        InstructionData::IntCompare {
+            cond, args: ref a, ..
+        } => match (args[0], args[1]) {
+            (Val::Int(x), Val::Int(y)) => Val::Bool(icmp(cond, arg_ty(a[0]), x, y)),
+            _ => return Err(unsupported()),
+        },
+        InstructionData::IntCompareImm { cond, arg, imm, .. } => match args[0] {
+            Val::Int(x) => {
+                let arg_ty = arg_ty(arg);
+                Val::Bool(icmp(cond, arg_ty, x, mask(arg_ty, imm.bits() as u64)))
+            }
+            _ => return Err(unsupported()),
+        },
+        InstructionData::FloatCompare { cond, .. } => match (args[0], args[1]) {
+            (Val::F32(x), Val::F32(y)) => Val::Bool(fcmp(cond, f64::from(x), f64::from(y))),
+            (Val::F64(x), Val::F64(y)) => Val::Bool(fcmp(cond, x, y)),
+            _ => return Err(unsupported()),
+        },
+        InstructionData::Ternary {
+            opcode: Opcode::Select,
+            ..
+        } => {
+            if args[0].truthiness()? {
+                args[1]
+            } else {
+                args[2]
+            }
+        }
+        InstructionData::NullAry {
+            opcode: Opcode::Nop,
+        } => return Ok(Control::Next(None)),
+        InstructionData::Trap { code, .. } => return Err(format!("trap: {}", code)),
+        InstructionData::CondTrap { opcode, code, .. } => {
+            if args[0].truthiness()? == (opcode == Opcode::Trapnz) {
+                return Err(format!("trap: {}", code));
+            }
+            return Ok(Control::Next(None));
+        }
+        InstructionData::MultiAry {
+            opcode: Opcode::Return,
+            ..
+        } => return Ok(Control::Return(args)),
+        InstructionData::BranchTable {
+            destination, table, ..
+        } => {
+            let dest = match args[0] {
+                Val::Int(index) => usize::try_from(index)
+                    .ok()
+                    .and_then(|i| func.jump_tables[table].as_slice().get(i))
+                    .copied()
+                    .unwrap_or(destination),
+                _ => return Err(unsupported()),
+            };
+            return Ok(Control::Jump(dest, Vec::new()));
+        }
+        InstructionData::Jump { .. } => {
+            let dest = match dfg.analyze_branch(inst) {
+                BranchInfo::SingleDest(dest, _) => dest,
+                _ => return Err(unsupported()),
+            };
+            return Ok(Control::Jump(dest, args));
+        }
+        InstructionData::Branch { opcode, .. } => {
+            // The condition comes before the block arguments.
+            if args[0].truthiness()? == (opcode == Opcode::Brnz) {
+                return branch(dfg, inst, args[1..].to_vec());
+            }
+            return Ok(Control::Next(None));
+        }
+        InstructionData::BranchIcmp {
+            cond, args: ref a, ..
+        } => {
+            let taken = match (args[0], args[1]) {
+                (Val::Int(x), Val::Int(y)) => {
+                    icmp(cond, arg_ty(a.as_slice(&dfg.value_lists)[0]), x, y)
+                }
+                _ => return Err(unsupported()),
+            };
+            if taken {
+                return branch(dfg, inst, args[2..].to_vec());
+            }
+            return Ok(Control::Next(None));
+        }
+        _ => return Err(unsupported()),
+    };
+    Ok(Control::Next(Some(result)))
+}
+
+/// Take the conditional branch `inst`, passing `args` to its destination.
+fn branch(dfg: &DataFlowGraph, inst: Inst, args: Vec<Val>) -> Result<Control, String> {
+    match dfg.analyze_branch(inst) {
+        BranchInfo::SingleDest(dest, _) => Ok(Control::Jump(dest, args)),
+        _ => Err(unsupported_inst(dfg, inst)),
+    }
+}
+
+fn unsupported_inst(dfg: &DataFlowGraph, inst: Inst) -> String {
+    format!("can't interpret `{}`", dfg.display_inst(inst, None))
+}
+
+fn fcmp(cond: FloatCC, x: f64, y: f64) -> bool {
+    let unordered = x.is_nan() || y.is_nan();
+    match cond {
+        FloatCC::Ordered => !unordered,
+        FloatCC::Unordered => unordered,
+        FloatCC::Equal => x == y,
+        FloatCC::NotEqual => unordered || x != y,
+        FloatCC::OrderedNotEqual => !unordered && x != y,
+        FloatCC::UnorderedOrEqual => unordered || x == y,
+        FloatCC::LessThan => x < y,
+        FloatCC::LessThanOrEqual => x <= y,
+        FloatCC::GreaterThan => x > y,
+        FloatCC::GreaterThanOrEqual => x >= y,
+        FloatCC::UnorderedOrLessThan => unordered || x < y,
+        FloatCC::UnorderedOrLessThanOrEqual => unordered || x <= y,
+        FloatCC::UnorderedOrGreaterThan => unordered || x > y,
+        FloatCC::UnorderedOrGreaterThanOrEqual => unordered || x >= y,
+    }
+}
+
+fn unary(opcode: Opcode, ty: Type, arg_ty: Type, arg: Val) -> Option<Val> {
+    let bits = u32::from(ty.bits());
+    Some(match (opcode, arg) {
+        (Opcode::Copy, arg) => arg,
+        (Opcode::Bnot, Val::Int(x)) => Val::Int(mask(ty, !x)),
+        (Opcode::Bnot, Val::Bool(b)) => Val::Bool(!b),
+        (Opcode::Ineg, Val::Int(x)) => Val::Int(mask(ty, x.wrapping_neg())),
+        (Opcode::Clz, Val::Int(x)) => Val::Int(u64::from(x.leading_zeros() - (64 - bits))),
+        (Opcode::Ctz, Val::Int(x)) => Val::Int(u64::from(x.trailing_zeros().min(bits))),
+        (Opcode::Popcnt, Val::Int(x)) => Val::Int(u64::from(x.count_ones())),
+        (Opcode::Uextend, Val::Int(x)) | (Opcode::Ireduce, Val::Int(x)) => Val::Int(mask(ty, x)),
+        (Opcode::Sextend, Val::Int(x)) => Val::Int(mask(ty, sext(arg_ty, x) as u64)),
+        (Opcode::Bint, Val::Bool(b)) => Val::Int(u64::from(b)),
+        (Opcode::Bmask, Val::Bool(b)) => Val::Int(if b { mask(ty, !0) } else { 0 }),
+        (Opcode::Bextend, Val::Bool(b)) | (Opcode::Breduce, Val::Bool(b)) => Val::Bool(b),
+        (Opcode::Fneg, Val::F32(x)) => Val::F32(-x),
+        (Opcode::Fneg, Val::F64(x)) => Val::F64(-x),
+        (Opcode::Fabs, Val::F32(x)) => Val::F32(x.abs()),
+        (Opcode::Fabs, Val::F64(x)) => Val::F64(x.abs()),
+        (Opcode::Sqrt, Val::F32(x)) => Val::F32(x.sqrt()),
+        (Opcode::Sqrt, Val::F64(x)) => Val::F64(x.sqrt()),
+        (Opcode::Fpromote, Val::F32(x)) => Val::F64(f64::from(x)),
+        (Opcode::Fdemote, Val::F64(x)) => Val::F32(x as f32),
+        _ => return None,
+    })
+}
+
+/// Evaluate a binary operation. The outer error is a trap; `None` means the operation isn't
+/// supported.
+fn binary(opcode: Opcode, ty: Type, x: Val, y: Val) -> Result<Option<Val>, String> {
+    Ok(match (x, y) {
+        (Val::Int(x), Val::Int(y)) => binary_int(opcode, ty, x, y)?.map(|r| Val::Int(mask(ty, r))),
+        (Val::Bool(x), Val::Bool(y)) => match opcode {
+            Opcode::Band => Some(Val::Bool(x & y)),
+            Opcode::Bor => Some(Val::Bool(x | y)),
+            Opcode::Bxor => Some(Val::Bool(x ^ y)),
+            _ => None,
+        },
+        (Val::F32(x), Val::F32(y)) => match opcode {
+            Opcode::Fadd => Some(Val::F32(x + y)),
+            Opcode::Fsub => Some(Val::F32(x - y)),
+            Opcode::Fmul => Some(Val::F32(x * y)),
+            Opcode::Fdiv => Some(Val::F32(x / y)),
+            _ => None,
+        },
+        (Val::F64(x), Val::F64(y)) => match opcode {
+            Opcode::Fadd => Some(Val::F64(x + y)),
+            Opcode::Fsub => Some(Val::F64(x - y)),
+            Opcode::Fmul => Some(Val::F64(x * y)),
+            Opcode::Fdiv => Some(Val::F64(x / y)),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+fn binary_int(opcode: Opcode, ty: Type, x: u64, y: u64) -> Result<Option<u64>, String> {
+    let bits = u32::from(ty.bits());
+    let (sx, sy) = (sext(ty, x), sext(ty, y));
+    let amt = (y as u32) & (bits - 1);
+    Ok(Some(match opcode {
+        Opcode::Iadd => x.wrapping_add(y),
+        Opcode::Isub => x.wrapping_sub(y),
+        Opcode::Imul => x.wrapping_mul(y),
+        Opcode::Udiv | Opcode::Urem | Opcode::Sdiv | Opcode::Srem if y == 0 => {
+            return Err(String::from("trap: int_divz"));
+        }
+        Opcode::Sdiv if sx == sext(ty, 1 << (bits - 1)) && sy == -1 => {
+            return Err(String::from("trap: int_ovf"));
+        }
+        Opcode::Udiv => x / y,
+        Opcode::Urem => x % y,
+        Opcode::Sdiv => (sx / sy) as u64,
+        Opcode::Srem => sx.wrapping_rem(sy) as u64,
+        Opcode::Imin => sx.min(sy) as u64,
+        Opcode::Imax => sx.max(sy) as u64,
+        Opcode::Umin => x.min(y),
+        Opcode::Umax => x.max(y),
+        Opcode::Band => x & y,
+        Opcode::Bor => x | y,
+        Opcode::Bxor => x ^ y,
+        Opcode::BandNot => x & !y,
+        Opcode::BorNot => x | !y,
+        Opcode::BxorNot => x ^ !y,
+        Opcode::Ishl => x << amt,
+        Opcode::Ushr => x >> amt,
+        Opcode::Sshr => (sx >> amt) as u64,
+        Opcode::Rotl | Opcode::Rotr if amt == 0 => x,
+        Opcode::Rotl => (x << amt) | (x >> (bits - amt)),
+        Opcode::Rotr => (x >> amt) | (x << (bits - amt)),
+        _ => return Ok(None),
+    }))
+}
+
+/// Map an `_imm` opcode to the opcode it's a shorthand for, with the immediate as the right-hand
+/// operand.
+fn binary_imm_opcode(opcode: Opcode) -> Option<Opcode> {
+    Some(match opcode {
+        Opcode::IaddImm => Opcode::Iadd,
+        Opcode::ImulImm => Opcode::Imul,
+        Opcode::UdivImm => Opcode::Udiv,
+        Opcode::SdivImm => Opcode::Sdiv,
+        Opcode::UremImm => Opcode::Urem,
+        Opcode::SremImm => Opcode::Srem,
+        Opcode::BandImm => Opcode::Band,
+        Opcode::BorImm => Opcode::Bor,
+        Opcode::BxorImm => Opcode::Bxor,
+        Opcode::RotlImm => Opcode::Rotl,
+        Opcode::RotrImm => Opcode::Rotr,
+        Opcode::IshlImm => Opcode::Ishl,
+        Opcode::UshrImm => Opcode::Ushr,
+        Opcode::SshrImm => Opcode::Sshr,
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::interpret;
+    use cranelift_reader::{parse_test, ParseOptions};
+
+    fn run(text: &str) -> Result<bool, String> {
+        let test_file = parse_test(text, ParseOptions::default()).unwrap();
+        interpret(&test_file.functions[0].0)
+    }
+
+    #[test]
+    fn loops_and_arithmetic() {
+        // Sum 1..=10 and check the result.
+        let text = "function %sum() -> b1 {
+block0:
+    v0 = iconst.i32 0
+    v1 = iconst.i32 1
+    jump block1(v0, v1)
+
+block1(v2: i32, v3: i32):
+    v4 = iadd v2, v3
+    v5 = iadd_imm v3, 1
+    v6 = icmp_imm sgt v5, 10
+    brz v6, block1(v4, v5)
+    jump block2
+
+block2:
+    v7 = icmp_imm eq v4, 55
+    return v7
+}";
+        assert_eq!(run(text), Ok(true));
+    }
+
+    #[test]
+    fn narrow_types_and_traps() {
+        let text = "function %wrap() -> b1 {
+block0:
+    v0 = iconst.i8 -1
+    v1 = iadd_imm v0, 1
+    v2 = sextend.i32 v0
+    v3 = icmp_imm eq v2, -1
+    v4 = icmp_imm eq v1, 0
+    v5 = band v3, v4
+    return v5
+}";
+        assert_eq!(run(text), Ok(true));
+
+        let text = "function %divz() -> b1 {
+block0:
+    v0 = iconst.i32 1
+    v1 = iconst.i32 0
+    v2 = udiv v0, v1
+    v3 = icmp_imm eq v2, 0
+    return v3
+}";
+        assert!(run(text).is_err());
+
+        let text = "function %forever() -> b1 {
+block0:
+    jump block0
+}";
+        assert!(run(text).is_err());
+    }
+}
@@ -986,74 +986,128 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
         Operator::RefFunc { function_index } => {
             state.push1(environ.translate_ref_func(builder.cursor(), *function_index)?);
         }
-        Operator::AtomicNotify { .. }
-        | Operator::I32AtomicWait { .. }
-        | Operator::I64AtomicWait { .. }
-        | Operator::I32AtomicLoad { .. }
-        | Operator::I64AtomicLoad { .. }
-        | Operator::I32AtomicLoad8U { .. }
-        | Operator::I32AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad8U { .. }
-        | Operator::I64AtomicLoad16U { .. }
-        | Operator::I64AtomicLoad32U { .. }
-        | Operator::I32AtomicStore { .. }
-        | Operator::I64AtomicStore { .. }
-        | Operator::I32AtomicStore8 { .. }
-        | Operator::I32AtomicStore16 { .. }
-        | Operator::I64AtomicStore8 { .. }
-        | Operator::I64AtomicStore16 { .. }
-        | Operator::I64AtomicStore32 { .. }
-        | Operator::I32AtomicRmwAdd { .. }
-        | Operator::I64AtomicRmwAdd { .. }
-        | Operator::I32AtomicRmw8AddU { .. }
-        | Operator::I32AtomicRmw16AddU { .. }
-        | Operator::I64AtomicRmw8AddU { .. }
-        | Operator::I64AtomicRmw16AddU { .. }
-        | Operator::I64AtomicRmw32AddU { .. }
-        | Operator::I32AtomicRmwSub { .. }
-        | Operator::I64AtomicRmwSub { .. }
-        | Operator::I32AtomicRmw8SubU { .. }
-        | Operator::I32AtomicRmw16SubU { .. }
-        | Operator::I64AtomicRmw8SubU { .. }
-        | Operator::I64AtomicRmw16SubU { .. }
-        | Operator::I64AtomicRmw32SubU { .. }
-        | Operator::I32AtomicRmwAnd { .. }
-        | Operator::I64AtomicRmwAnd { .. }
-        | Operator::I32AtomicRmw8AndU { .. }
-        | Operator::I32AtomicRmw16AndU { .. }
-        | Operator::I64AtomicRmw8AndU { .. }
-        | Operator::I64AtomicRmw16AndU { .. }
-        | Operator::I64AtomicRmw32AndU { .. }
-        | Operator::I32AtomicRmwOr { .. }
-        | Operator::I64AtomicRmwOr { .. }
-        | Operator::I32AtomicRmw8OrU { .. }
-        | Operator::I32AtomicRmw16OrU { .. }
-        | Operator::I64AtomicRmw8OrU { .. }
-        | Operator::I64AtomicRmw16OrU { .. }
-        | Operator::I64AtomicRmw32OrU { .. }
-        | Operator::I32AtomicRmwXor { .. }
-        | Operator::I64AtomicRmwXor { .. }
-        | Operator::I32AtomicRmw8XorU { .. }
-        | Operator::I32AtomicRmw16XorU { .. }
-        | Operator::I64AtomicRmw8XorU { .. }
-        | Operator::I64AtomicRmw16XorU { .. }
-        | Operator::I64AtomicRmw32XorU { .. }
-        | Operator::I32AtomicRmwXchg { .. }
-        | Operator::I64AtomicRmwXchg { .. }
-        | Operator::I32AtomicRmw8XchgU { .. }
-        | Operator::I32AtomicRmw16XchgU { .. }
-        | Operator::I64AtomicRmw8XchgU { .. }
-        | Operator::I64AtomicRmw16XchgU { .. }
-        | Operator::I64AtomicRmw32XchgU { .. }
-        | Operator::I32AtomicRmwCmpxchg { .. }
-        | Operator::I64AtomicRmwCmpxchg { .. }
-        | Operator::I32AtomicRmw8CmpxchgU { .. }
-        | Operator::I32AtomicRmw16CmpxchgU { .. }
-        | Operator::I64AtomicRmw8CmpxchgU { .. }
-        | Operator::I64AtomicRmw16CmpxchgU { .. }
-        | Operator::I64AtomicRmw32CmpxchgU { .. }
-        | Operator::AtomicFence { .. } => {
-            return Err(wasm_unsupported!("proposed thread operator {:?}", op));
+        /******************************* Atomic instructions *********************************
+         * Atomic accesses trap unless they're naturally aligned, and are performed on the
+         * native address of the accessed memory.
+         ************************************************************************************/
+        Operator::AtomicNotify { memarg } => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (addr32, count) = state.pop2();
+            let addr = translate_atomic_addr(addr32, memarg.offset, I32, heap, builder, environ);
+            state.push1(environ.translate_atomic_notify(
+                builder.cursor(),
+                heap_index,
+                heap,
+                addr,
+                count,
+            )?);
+        }
+        Operator::I32AtomicWait { memarg } | Operator::I64AtomicWait { memarg } => {
+            let heap_index = MemoryIndex::from_u32(0);
+            let heap = state.get_heap(builder.func, 0, environ)?;
+            let (addr32, expected, timeout) = state.pop3();
+            let expected_ty = builder.func.dfg.value_type(expected);
+            let addr =
+                translate_atomic_addr(addr32, memarg.offset, expected_ty, heap, builder, environ);
+            state.push1(environ.translate_atomic_wait(
+                builder.cursor(),
+                heap_index,
+                heap,
+                addr,
+                expected,
+                timeout,
+            )?);
+        }
+        Operator::AtomicFence { flags: _ } => {
+            builder.ins().fence();
+        }
+        Operator::I32AtomicLoad { memarg }
+        | Operator::I64AtomicLoad { memarg }
+        | Operator::I32AtomicLoad8U { memarg }
+        | Operator::I32AtomicLoad16U { memarg }
+        | Operator::I64AtomicLoad8U { memarg }
+        | Operator::I64AtomicLoad16U { memarg }
+        | Operator::I64AtomicLoad32U { memarg } => {
+            translate_atomic_load(op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicStore { memarg }
+        | Operator::I64AtomicStore { memarg }
+        | Operator::I32AtomicStore8 { memarg }
+        | Operator::I32AtomicStore16 { memarg }
+        | Operator::I64AtomicStore8 { memarg }
+        | Operator::I64AtomicStore16 { memarg }
+        | Operator::I64AtomicStore32 { memarg } => {
+            translate_atomic_store(op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwAdd { memarg }
+        | Operator::I64AtomicRmwAdd { memarg }
+        | Operator::I32AtomicRmw8AddU { memarg }
+        | Operator::I32AtomicRmw16AddU { memarg }
+        | Operator::I64AtomicRmw8AddU { memarg }
+        | Operator::I64AtomicRmw16AddU { memarg }
+        | Operator::I64AtomicRmw32AddU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::Add;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwSub { memarg }
+        | Operator::I64AtomicRmwSub { memarg }
+        | Operator::I32AtomicRmw8SubU { memarg }
+        | Operator::I32AtomicRmw16SubU { memarg }
+        | Operator::I64AtomicRmw8SubU { memarg }
+        | Operator::I64AtomicRmw16SubU { memarg }
+        | Operator::I64AtomicRmw32SubU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::Sub;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwAnd { memarg }
+        | Operator::I64AtomicRmwAnd { memarg }
+        | Operator::I32AtomicRmw8AndU { memarg }
+        | Operator::I32AtomicRmw16AndU { memarg }
+        | Operator::I64AtomicRmw8AndU { memarg }
+        | Operator::I64AtomicRmw16AndU { memarg }
+        | Operator::I64AtomicRmw32AndU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::And;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwOr { memarg }
+        | Operator::I64AtomicRmwOr { memarg }
+        | Operator::I32AtomicRmw8OrU { memarg }
+        | Operator::I32AtomicRmw16OrU { memarg }
+        | Operator::I64AtomicRmw8OrU { memarg }
+        | Operator::I64AtomicRmw16OrU { memarg }
+        | Operator::I64AtomicRmw32OrU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::Or;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwXor { memarg }
+        | Operator::I64AtomicRmwXor { memarg }
+        | Operator::I32AtomicRmw8XorU { memarg }
+        | Operator::I32AtomicRmw16XorU { memarg }
+        | Operator::I64AtomicRmw8XorU { memarg }
+        | Operator::I64AtomicRmw16XorU { memarg }
+        | Operator::I64AtomicRmw32XorU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::Xor;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwXchg { memarg }
+        | Operator::I64AtomicRmwXchg { memarg }
+        | Operator::I32AtomicRmw8XchgU { memarg }
+        | Operator::I32AtomicRmw16XchgU { memarg }
+        | Operator::I64AtomicRmw8XchgU { memarg }
+        | Operator::I64AtomicRmw16XchgU { memarg }
+        | Operator::I64AtomicRmw32XchgU { memarg } => {
+            let rmw_op = ir::AtomicRmwOp::Xchg;
+            translate_atomic_rmw(op, rmw_op, memarg.offset, builder, state, environ)?;
+        }
+        Operator::I32AtomicRmwCmpxchg { memarg }
+        | Operator::I64AtomicRmwCmpxchg { memarg }
+        | Operator::I32AtomicRmw8CmpxchgU { memarg }
+        | Operator::I32AtomicRmw16CmpxchgU { memarg }
+        | Operator::I64AtomicRmw8CmpxchgU { memarg }
+        | Operator::I64AtomicRmw16CmpxchgU { memarg }
+        | Operator::I64AtomicRmw32CmpxchgU { memarg } => {
+            translate_atomic_cas(op, memarg.offset, builder, state, environ)?;
         }
         Operator::MemoryCopy => {
             // The WebAssembly MVP only supports one linear memory and
@@ -1713,6 +1767,130 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     Ok(())
 }
 
+/// Computes the native address of an atomic access of type `access_ty` at `addr32 + offset` in
+/// `heap`, trapping if it isn't naturally aligned.
+fn translate_atomic_addr<FE: FuncEnvironment + ?Sized>(
+    addr32: ir::Value,
+    offset: u32,
+    access_ty: Type,
+    heap: ir::Heap,
+    builder: &mut FunctionBuilder,
+    environ: &mut FE,
+) -> ir::Value {
+    let access_size = i64::from(access_ty.bytes());
+    if access_size > 1 {
+        // Only the low bits matter, so the addition can't be fooled by overflow.
+        let effective_addr = builder.ins().iadd_imm(addr32, i64::from(offset));
+        let misalignment = builder.ins().band_imm(effective_addr, access_size - 1);
+        builder
+            .ins()
+            .trapnz(misalignment, ir::TrapCode::HeapMisaligned);
+    }
+    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.pointer_type(), builder);
+    if offset == 0 {
+        base
+    } else {
+        builder.ins().iadd_imm(base, i64::from(offset))
+    }
+}
+
+/// Translate an atomic load, zero-extended to the type of the operator's result.
+fn translate_atomic_load<FE: FuncEnvironment + ?Sized>(
+    operator: &Operator,
+    offset: u32,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (widened_ty, access_ty) = atomic_access_types(operator);
+    let addr32 = state.pop1();
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = translate_atomic_addr(addr32, offset, access_ty, heap, builder, environ);
+    let flags = MemFlags::new();
+    let mut value = builder.ins().atomic_load(access_ty, flags, addr, 0);
+    if access_ty != widened_ty {
+        value = builder.ins().uextend(widened_ty, value);
+    }
+    state.push1(value);
+    Ok(())
+}
+
+/// Translate an atomic store, which may only store the low bits of its operand.
+fn translate_atomic_store<FE: FuncEnvironment + ?Sized>(
+    operator: &Operator,
+    offset: u32,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (_, access_ty) = atomic_access_types(operator);
+    let (addr32, mut value) = state.pop2();
+    if builder.func.dfg.value_type(value) != access_ty {
+        value = builder.ins().ireduce(access_ty, value);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = translate_atomic_addr(addr32, offset, access_ty, heap, builder, environ);
+    let flags = MemFlags::new();
+    builder.ins().atomic_store(flags, value, addr, 0);
+    Ok(())
+}
+
+/// Translate an atomic read-modify-write, whose previous value is zero-extended to the type of
+/// the operator's result.
+fn translate_atomic_rmw<FE: FuncEnvironment + ?Sized>(
+    operator: &Operator,
+    op: ir::AtomicRmwOp,
+    offset: u32,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (widened_ty, access_ty) = atomic_access_types(operator);
+    let (addr32, mut arg) = state.pop2();
+    if access_ty != widened_ty {
+        arg = builder.ins().ireduce(access_ty, arg);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = translate_atomic_addr(addr32, offset, access_ty, heap, builder, environ);
+    let flags = MemFlags::new();
+    let mut previous = builder.ins().atomic_rmw(flags, op, addr, arg);
+    if access_ty != widened_ty {
+        previous = builder.ins().uextend(widened_ty, previous);
+    }
+    state.push1(previous);
+    Ok(())
+}
+
+/// Translate an atomic compare-and-swap, whose previous value is zero-extended to the type of the
+/// operator's result.
+fn translate_atomic_cas<FE: FuncEnvironment + ?Sized>(
+    operator: &Operator,
+    offset: u32,
+    builder: &mut FunctionBuilder,
+    state: &mut FuncTranslationState,
+    environ: &mut FE,
+) -> WasmResult<()> {
+    let (widened_ty, access_ty) = atomic_access_types(operator);
+    let (addr32, mut expected, mut replacement) = state.pop3();
+    if access_ty != widened_ty {
+        expected = builder.ins().ireduce(access_ty, expected);
+        replacement = builder.ins().ireduce(access_ty, replacement);
+    }
+    // We don't yet support multiple linear memories.
+    let heap = state.get_heap(builder.func, 0, environ)?;
+    let addr = translate_atomic_addr(addr32, offset, access_ty, heap, builder, environ);
+    let flags = MemFlags::new();
+    let mut previous = builder.ins().atomic_cas(flags, addr, expected, replacement);
+    if access_ty != widened_ty {
+        previous = builder.ins().uextend(widened_ty, previous);
+    }
+    state.push1(previous);
+    Ok(())
+}
+
 fn translate_icmp(cc: IntCC, builder: &mut FunctionBuilder, state: &mut FuncTranslationState) {
     let (arg0, arg1) = state.pop2();
     let val = builder.ins().icmp(cc, arg0, arg1);
@@ -1790,6 +1968,84 @@ fn translate_br_if_args(
     (br_destination, inputs)
 }
 
+/// Determine the type of the value of an atomic memory operator, and the type of the memory it
+/// accesses, which may be narrower.
+fn atomic_access_types(operator: &Operator) -> (Type, Type) {
+    match operator {
+        Operator::I32AtomicLoad { .. }
+        | Operator::I32AtomicStore { .. }
+        | Operator::I32AtomicRmwAdd { .. }
+        | Operator::I32AtomicRmwSub { .. }
+        | Operator::I32AtomicRmwAnd { .. }
+        | Operator::I32AtomicRmwOr { .. }
+        | Operator::I32AtomicRmwXor { .. }
+        | Operator::I32AtomicRmwXchg { .. }
+        | Operator::I32AtomicRmwCmpxchg { .. } => (I32, I32),
+
+        Operator::I64AtomicLoad { .. }
+        | Operator::I64AtomicStore { .. }
+        | Operator::I64AtomicRmwAdd { .. }
+        | Operator::I64AtomicRmwSub { .. }
+        | Operator::I64AtomicRmwAnd { .. }
+        | Operator::I64AtomicRmwOr { .. }
+        | Operator::I64AtomicRmwXor { .. }
+        | Operator::I64AtomicRmwXchg { .. }
+        | Operator::I64AtomicRmwCmpxchg { .. } => (I64, I64),
+
+        Operator::I32AtomicLoad8U { .. }
+        | Operator::I32AtomicStore8 { .. }
+        | Operator::I32AtomicRmw8AddU { .. }
+        | Operator::I32AtomicRmw8SubU { .. }
+        | Operator::I32AtomicRmw8AndU { .. }
+        | Operator::I32AtomicRmw8OrU { .. }
+        | Operator::I32AtomicRmw8XorU { .. }
+        | Operator::I32AtomicRmw8XchgU { .. }
+        | Operator::I32AtomicRmw8CmpxchgU { .. } => (I32, I8),
+
+        Operator::I32AtomicLoad16U { .. }
+        | Operator::I32AtomicStore16 { .. }
+        | Operator::I32AtomicRmw16AddU { .. }
+        | Operator::I32AtomicRmw16SubU { .. }
+        | Operator::I32AtomicRmw16AndU { .. }
+        | Operator::I32AtomicRmw16OrU { .. }
+        | Operator::I32AtomicRmw16XorU { .. }
+        | Operator::I32AtomicRmw16XchgU { .. }
+        | Operator::I32AtomicRmw16CmpxchgU { .. } => (I32, I16),
+
+        Operator::I64AtomicLoad8U { .. }
+        | Operator::I64AtomicStore8 { .. }
+        | Operator::I64AtomicRmw8AddU { .. }
+        | Operator::I64AtomicRmw8SubU { .. }
+        | Operator::I64AtomicRmw8AndU { .. }
+        | Operator::I64AtomicRmw8OrU { .. }
+        | Operator::I64AtomicRmw8XorU { .. }
+        | Operator::I64AtomicRmw8XchgU { .. }
+        | Operator::I64AtomicRmw8CmpxchgU { .. } => (I64, I8),
+
+        Operator::I64AtomicLoad16U { .. }
+        | Operator::I64AtomicStore16 { .. }
+        | Operator::I64AtomicRmw16AddU { .. }
+        | Operator::I64AtomicRmw16SubU { .. }
+        | Operator::I64AtomicRmw16AndU { .. }
+        | Operator::I64AtomicRmw16OrU { .. }
+        | Operator::I64AtomicRmw16XorU { .. }
+        | Operator::I64AtomicRmw16XchgU { .. }
+        | Operator::I64AtomicRmw16CmpxchgU { .. } => (I64, I16),
+
+        Operator::I64AtomicLoad32U { .. }
+        | Operator::I64AtomicStore32 { .. }
+        | Operator::I64AtomicRmw32AddU { .. }
+        | Operator::I64AtomicRmw32SubU { .. }
+        | Operator::I64AtomicRmw32AndU { .. }
+        | Operator::I64AtomicRmw32OrU { .. }
+        | Operator::I64AtomicRmw32XorU { .. }
+        | Operator::I64AtomicRmw32XchgU { .. }
+        | Operator::I64AtomicRmw32CmpxchgU { .. } => (I64, I32),
+
+        _ => unreachable!("not an atomic memory access: {:?}", operator),
+    }
+}
+
 /// Determine the returned value type of a WebAssembly operator
 fn type_of(operator: &Operator) -> Type {
     match operator {
@@ -335,6 +335,46 @@ pub trait FuncEnvironment: TargetEnvironment {
     /// Translate a `data.drop` WebAssembly instruction.
     fn translate_data_drop(&mut self, pos: FuncCursor, seg_index: u32) -> WasmResult<()>;
 
+    /// Translate a `memory.atomic.wait32` or `memory.atomic.wait64` WebAssembly instruction,
+    /// depending on the type of `expected`.
+    ///
+    /// The `index` provided identifies the linear memory to wait on, and `heap` is the heap
+    /// reference returned by `make_heap` for the same index. `addr` is the native address of the
+    /// awaited value, which has already been checked to be in bounds and naturally aligned.
+    ///
+    /// The `timeout` value is the maximum time to wait for in nanoseconds, or negative to wait
+    /// forever.
+    ///
+    /// Returns 0 if woken by a `memory.atomic.notify`, 1 if the value in memory didn't match
+    /// `expected` and 2 if the wait timed out.
+    fn translate_atomic_wait(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        addr: ir::Value,
+        expected: ir::Value,
+        timeout: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
+    /// Translate a `memory.atomic.notify` WebAssembly instruction.
+    ///
+    /// The `index` provided identifies the linear memory to notify waiters on, and `heap` is the
+    /// heap reference returned by `make_heap` for the same index. `addr` is the native address
+    /// waiters wait on, which has already been checked to be in bounds and naturally aligned.
+    ///
+    /// The `count` value is the maximum number of waiters to wake.
+    ///
+    /// Returns the number of waiters which were woken.
+    fn translate_atomic_notify(
+        &mut self,
+        pos: FuncCursor,
+        index: MemoryIndex,
+        heap: ir::Heap,
+        addr: ir::Value,
+        count: ir::Value,
+    ) -> WasmResult<ir::Value>;
+
     /// Translate a `table.size` WebAssembly instruction.
     fn translate_table_size(
         &mut self,
@@ -438,6 +478,31 @@ pub trait FuncEnvironment: TargetEnvironment {
         Ok(())
     }
 
+    /// Optional callback for the `FunctionEnvironment` performing this translation to learn
+    /// how many `Variable`s were declared for the function's parameters and locals, so that
+    /// it can declare variables of its own after them.
+    fn after_locals(&mut self, _num_locals_defined: usize) {}
+
+    /// Optional callback for the `FunctionEnvironment` performing this translation to emit
+    /// code at the start of the function body, after the locals have been declared.
+    fn before_translate_function(
+        &mut self,
+        _builder: &mut FunctionBuilder,
+        _state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Optional callback for the `FunctionEnvironment` performing this translation to emit
+    /// code just before the function's fallthrough return, if it is reachable.
+    fn after_translate_function(
+        &mut self,
+        _builder: &mut FunctionBuilder,
+        _state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        Ok(())
+    }
+
     /// Optional callback for the `FunctionEnvironment` performing this translation to maintain
     /// internal state or prepare custom state for the operator to translate
     fn before_translate_operator(
@@ -660,7 +725,25 @@ pub trait ModuleEnvironment<'data>: TargetEnvironment {
         Ok(())
     }
 
-    /// Indicates that a custom section has been found in the wasm file
+    /// Declares the name of a function's local to the environment.
+    ///
+    /// By default this does nothing, but implementations can use this to read
+    /// the local name subsection of the custom name section if desired. Locals
+    /// are indexed the same way as in `local.get`, parameters first.
+    fn declare_local_name(
+        &mut self,
+        _func_index: FuncIndex,
+        _local_index: u32,
+        _name: &'data str,
+    ) -> WasmResult<()> {
+        Ok(())
+    }
+
+    /// Indicates that a custom section has been found in the wasm file.
+    ///
+    /// This is called for every custom section, including the name section
+    /// after its contents have been passed to `declare_func_name` and
+    /// `declare_local_name`.
     fn custom_section(&mut self, _name: &'data str, _data: &'data [u8]) -> WasmResult<()> {
         Ok(())
     }
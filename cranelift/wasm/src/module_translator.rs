@@ -75,17 +75,15 @@ pub fn translate_module<'data>(
                 name,
                 binary,
                 content,
-            } => match content {
-                Some(CustomSectionContent::Name(names)) => {
+            } => {
+                if let Some(CustomSectionContent::Name(names)) = content {
                     parse_name_section(names, environ)?;
                 }
-                _ => {
-                    let mut reader = binary.clone();
-                    let len = reader.bytes_remaining();
-                    let payload = reader.read_bytes(len)?;
-                    environ.custom_section(name, payload)?;
-                }
-            },
+                let mut reader = binary.clone();
+                let len = reader.bytes_remaining();
+                let payload = reader.read_bytes(len)?;
+                environ.custom_section(name, payload)?;
+            }
         }
     }
 
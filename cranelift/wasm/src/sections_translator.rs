@@ -25,9 +25,9 @@ use std::vec::Vec;
 use wasmparser::{
     self, CodeSectionReader, Data, DataKind, DataSectionReader, Element, ElementItem, ElementItems,
     ElementKind, ElementSectionReader, Export, ExportSectionReader, ExternalKind, FuncType,
-    FunctionSectionReader, GlobalSectionReader, GlobalType, ImportSectionEntryType,
-    ImportSectionReader, MemorySectionReader, MemoryType, NameSectionReader, Naming, NamingReader,
-    Operator, TableSectionReader, Type, TypeSectionReader,
+    FunctionLocalReader, FunctionSectionReader, GlobalSectionReader, GlobalType,
+    ImportSectionEntryType, ImportSectionReader, MemorySectionReader, MemoryType,
+    NameSectionReader, Naming, NamingReader, Operator, TableSectionReader, Type, TypeSectionReader,
 };
 
 /// Parses the Type section of the wasm module.
@@ -430,14 +430,44 @@ pub fn parse_name_section<'data>(
                         environ.declare_func_name(index, name)?;
                     }
                 }
-                return Ok(());
             }
-            wasmparser::Name::Local(_) | wasmparser::Name::Module(_) => {}
+            wasmparser::Name::Local(local_subsection) => {
+                if let Some(local_names) = local_subsection
+                    .get_function_local_reader()
+                    .ok()
+                    .and_then(parse_local_name_subsection)
+                {
+                    for (func_index, local_index, name) in local_names {
+                        environ.declare_local_name(func_index, local_index, name)?;
+                    }
+                }
+            }
+            wasmparser::Name::Module(_) => {}
         };
     }
     Ok(())
 }
 
+fn parse_local_name_subsection(
+    mut function_local_reader: FunctionLocalReader<'_>,
+) -> Option<Vec<(FuncIndex, u32, &str)>> {
+    let mut local_names = Vec::new();
+    for _ in 0..function_local_reader.get_count() {
+        let function = function_local_reader.read().ok()?;
+        if function.func_index == std::u32::MAX {
+            // We reserve `u32::MAX` for our own use in cranelift-entity.
+            return None;
+        }
+        let func_index = FuncIndex::from_u32(function.func_index);
+        let mut naming_reader = function.get_map().ok()?;
+        for _ in 0..naming_reader.get_count() {
+            let Naming { index, name } = naming_reader.read().ok()?;
+            local_names.push((func_index, index, name));
+        }
+    }
+    Some(local_names)
+}
+
 fn parse_function_name_subsection(
     mut naming_reader: NamingReader<'_>,
 ) -> Option<HashMap<FuncIndex, &str>> {
@@ -0,0 +1,18 @@
+(module
+  (memory 1 1 shared)
+  (func (param i32 i64) (result i64)
+    (i32.atomic.store (local.get 0) (i32.atomic.load (local.get 0)))
+    (i64.atomic.store8 (local.get 0) (i64.atomic.load16_u (local.get 0)))
+    (drop (i32.atomic.rmw.add (local.get 0) (i32.const 1)))
+    (drop (i32.atomic.rmw8.sub_u (local.get 0) (i32.const 1)))
+    (drop (i64.atomic.rmw16.and_u (local.get 0) (local.get 1)))
+    (drop (i64.atomic.rmw32.or_u (local.get 0) (local.get 1)))
+    (drop (i64.atomic.rmw.xor (local.get 0) (local.get 1)))
+    (drop (i32.atomic.rmw.xchg (local.get 0) (i32.const 2)))
+    (drop (i32.atomic.rmw16.cmpxchg_u offset=2 (local.get 0) (i32.const 2) (i32.const 3)))
+    (drop (i32.atomic.wait (local.get 0) (i32.const 0) (i64.const -1)))
+    (drop (atomic.notify (local.get 0) (i32.const 1)))
+    (atomic.fence)
+    (i64.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (i64.const 0))
+  )
+)
@@ -0,0 +1,74 @@
+//! Measures how long instantiating modules takes with each instance
+//! allocation strategy.
+//!
+//! Run with `cargo bench -p wasmtime --bench instantiation`.
+
+use anyhow::Result;
+use std::time::{Duration, Instant};
+use wasmtime::*;
+
+const ITERATIONS: u32 = 10_000;
+
+const MODULES: &[(&str, &str)] = &[
+    ("empty", "(module)"),
+    (
+        "memory and table",
+        r#"
+            (module
+                (memory 1)
+                (table 10 anyfunc)
+                (func $f)
+                (elem (i32.const 0) $f $f $f)
+                (data (i32.const 0) "hello world")
+            )
+        "#,
+    ),
+    (
+        "growing memory",
+        r#"
+            (module
+                (memory 1)
+                (func $start
+                    (drop (memory.grow (i32.const 4)))
+                    (i32.store (i32.const 0x40000) (i32.const 1)))
+                (start $start)
+            )
+        "#,
+    ),
+];
+
+fn bench(strategy: InstanceAllocationStrategy, wat: &str) -> Result<Duration> {
+    let mut config = Config::new();
+    config.allocation_strategy(strategy)?;
+    let store = Store::new(&Engine::new(&config));
+    let module = Module::new(&store, wat)?;
+
+    // Warm up before measuring anything.
+    for _ in 0..100 {
+        Instance::new(&module, &[])?;
+    }
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        Instance::new(&module, &[])?;
+    }
+    Ok(start.elapsed() / ITERATIONS)
+}
+
+fn main() -> Result<()> {
+    for (name, wat) in MODULES {
+        let on_demand = bench(InstanceAllocationStrategy::OnDemand, wat)?;
+        let pooling = bench(
+            InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 10,
+                ..PoolingLimits::default()
+            }),
+            wat,
+        )?;
+        println!(
+            "{:<20} on-demand: {:>10.2?}   pooling: {:>10.2?}",
+            name, on_demand, pooling
+        );
+    }
+    Ok(())
+}
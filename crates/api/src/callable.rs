@@ -3,10 +3,11 @@ use crate::trampoline::generate_func_export;
 use crate::trap::Trap;
 use crate::types::FuncType;
 use crate::values::Val;
+use std::mem;
 use std::ptr;
 use std::rc::Rc;
 use wasmtime_environ::ir;
-use wasmtime_runtime::{Export, InstanceHandle};
+use wasmtime_runtime::{Export, InstanceHandle, VMContext, VMFunctionBody};
 
 /// A trait representing a function that can be imported and called from inside
 /// WebAssembly.
@@ -86,7 +87,8 @@ pub trait Callable {
 }
 
 pub(crate) trait WrappedCallable {
-    fn call(&self, params: &[Val], results: &mut [Val]) -> Result<(), Trap>;
+    /// Calls this function, which has the wasm type `ty`.
+    fn call(&self, ty: &FuncType, params: &[Val], results: &mut [Val]) -> Result<(), Trap>;
     fn signature(&self) -> &ir::Signature {
         match self.wasmtime_export() {
             Export::Function { signature, .. } => signature,
@@ -97,6 +99,53 @@ pub(crate) trait WrappedCallable {
     fn wasmtime_export(&self) -> &Export;
 }
 
+/// Calls the wasm function `body` with the native signature `signature`
+/// through a trampoline, which reads the arguments `store_args` writes into
+/// `values_vec` and writes the results back into it.
+pub(crate) unsafe fn call_with_values_vec(
+    store: &Store,
+    vmctx: *mut VMContext,
+    body: *const VMFunctionBody,
+    signature: &ir::Signature,
+    values_vec: &mut [i128],
+    store_args: impl FnOnce(&mut [i128]) -> Result<(), Trap>,
+) -> Result<(), Trap> {
+    // Get the trampoline to call for this function.
+    let exec_code_buf = store
+        .compiler_mut()
+        .get_published_trampoline(signature, mem::size_of::<u128>())
+        .map_err(|e| Trap::new(format!("trampoline error: {:?}", e)))?;
+
+    call_trampoline(store, vmctx, exec_code_buf, body, values_vec, store_args)
+}
+
+/// Calls the wasm function `body` through `trampoline`, which reads the
+/// arguments `store_args` writes into `values_vec` and writes the results
+/// back into it.
+pub(crate) unsafe fn call_trampoline(
+    store: &Store,
+    vmctx: *mut VMContext,
+    trampoline: *const VMFunctionBody,
+    body: *const VMFunctionBody,
+    values_vec: &mut [i128],
+    store_args: impl FnOnce(&mut [i128]) -> Result<(), Trap>,
+) -> Result<(), Trap> {
+    // Collect garbage before rather than while storing the arguments, as
+    // the `anyref`s among them aren't rooted until wasm receives them.
+    store.externref_activations_table().maybe_gc();
+    store_args(values_vec)?;
+
+    // Call the trampoline.
+    wasmtime_runtime::wasmtime_call_trampoline(
+        vmctx,
+        ptr::null_mut(),
+        trampoline,
+        body,
+        values_vec.as_mut_ptr() as *mut u8,
+    )
+    .map_err(Trap::from_jit)
+}
+
 pub(crate) struct WasmtimeFn {
     store: Store,
     instance: InstanceHandle,
@@ -114,9 +163,8 @@ impl WasmtimeFn {
 }
 
 impl WrappedCallable for WasmtimeFn {
-    fn call(&self, params: &[Val], results: &mut [Val]) -> Result<(), Trap> {
+    fn call(&self, ty: &FuncType, params: &[Val], results: &mut [Val]) -> Result<(), Trap> {
         use std::cmp::max;
-        use std::mem;
 
         let (vmctx, body, signature) = match self.wasmtime_export() {
             Export::Function {
@@ -141,46 +189,34 @@ impl WrappedCallable for WasmtimeFn {
             )));
         }
 
-        let value_size = mem::size_of::<u128>();
         let mut values_vec = vec![0; max(params.len(), results.len())];
 
-        // Store the argument values into `values_vec`.
-        let param_tys = signature.params.iter().skip(2);
-        for ((arg, slot), ty) in params.iter().zip(&mut values_vec).zip(param_tys) {
-            if arg.ty().get_wasmtime_type() != Some(ty.value_type) {
-                return Err(Trap::new("argument type mismatch"));
-            }
-            unsafe {
-                arg.write_value_to(slot);
-            }
-        }
-
-        // Get the trampoline to call for this function.
-        let exec_code_buf = self
-            .store
-            .compiler_mut()
-            .get_published_trampoline(&signature, value_size)
-            .map_err(|e| Trap::new(format!("trampoline error: {:?}", e)))?;
-
-        // Call the trampoline.
-        if let Err(error) = unsafe {
-            wasmtime_runtime::wasmtime_call_trampoline(
+        unsafe {
+            call_with_values_vec(
+                &self.store,
                 vmctx,
-                ptr::null_mut(),
-                exec_code_buf,
                 body,
-                values_vec.as_mut_ptr() as *mut u8,
-            )
-        } {
-            return Err(Trap::from_jit(error));
+                &signature,
+                &mut values_vec,
+                |values_vec| {
+                    // Store the argument values into `values_vec`.
+                    for ((arg, slot), ty) in params.iter().zip(values_vec).zip(ty.params()) {
+                        if !arg.matches(ty) {
+                            return Err(Trap::new("argument type mismatch"));
+                        }
+                        arg.write_value_to(&self.store, slot);
+                    }
+                    Ok(())
+                },
+            )?;
         }
 
         // Load the return values out of `values_vec`.
-        for (index, abi_param) in signature.returns.iter().enumerate() {
+        for (index, ty) in ty.results().iter().enumerate() {
             unsafe {
                 let ptr = values_vec.as_ptr().add(index);
 
-                results[index] = Val::read_value_from(ptr, abi_param.value_type);
+                results[index] = Val::read_value_from(&self.store, ptr, ty);
             }
         }
 
@@ -213,7 +249,7 @@ impl NativeCallable {
 }
 
 impl WrappedCallable for NativeCallable {
-    fn call(&self, params: &[Val], results: &mut [Val]) -> Result<(), Trap> {
+    fn call(&self, _ty: &FuncType, params: &[Val], results: &mut [Val]) -> Result<(), Trap> {
         self.callable.call(params, results)
     }
     fn wasmtime_handle(&self) -> &InstanceHandle {
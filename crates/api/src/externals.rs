@@ -1,12 +1,16 @@
-use crate::trampoline::{generate_global_export, generate_memory_export, generate_table_export};
-use crate::values::{from_checked_anyfunc, into_checked_anyfunc, Val};
+use crate::trampoline::{
+    generate_global_export, generate_memory_export, generate_shared_memory_export,
+    generate_table_export,
+};
+use crate::values::{from_table_element, into_table_element, Val};
 use crate::Mutability;
+use crate::{Engine, Func, Store};
 use crate::{ExternType, GlobalType, MemoryType, TableType, ValType};
-use crate::{Func, Store};
 use anyhow::{anyhow, bail, Result};
 use std::slice;
-use wasmtime_environ::{ir, wasm};
-use wasmtime_runtime::{self as runtime, InstanceHandle};
+use target_lexicon::Triple;
+use wasmtime_environ::{ir, wasm, MemoryPlan};
+use wasmtime_runtime::{self as runtime, InstanceHandle, RuntimeLinearMemory, TableElement};
 
 // Externals
 
@@ -81,6 +85,16 @@ impl Extern {
         }
     }
 
+    pub(crate) fn comes_from_same_store(&self, store: &Store) -> bool {
+        let my_store = match self {
+            Extern::Func(f) => f.store(),
+            Extern::Global(g) => &g.store,
+            Extern::Memory(m) => &m.store,
+            Extern::Table(t) => &t.store,
+        };
+        Store::same(my_store, store)
+    }
+
     pub(crate) fn get_wasmtime_export(&self) -> wasmtime_runtime::Export {
         match self {
             Extern::Func(f) => f.wasmtime_export().clone(),
@@ -153,7 +167,7 @@ impl From<Table> for Extern {
 /// instances are equivalent in their functionality.
 #[derive(Clone)]
 pub struct Global {
-    _store: Store,
+    store: Store,
     ty: GlobalType,
     wasmtime_export: wasmtime_runtime::Export,
     wasmtime_handle: InstanceHandle,
@@ -177,7 +191,7 @@ impl Global {
         }
         let (wasmtime_handle, wasmtime_export) = generate_global_export(store, &ty, val)?;
         Ok(Global {
-            _store: store.clone(),
+            store: store.clone(),
             ty,
             wasmtime_export,
             wasmtime_handle,
@@ -259,7 +273,7 @@ impl Global {
         let ty = GlobalType::from_wasmtime_global(&global)
             .expect("core wasm global type should be supported");
         Global {
-            _store: store.clone(),
+            store: store.clone(),
             ty: ty,
             wasmtime_export: export,
             wasmtime_handle,
@@ -291,10 +305,10 @@ pub struct Table {
 }
 
 fn set_table_item(
-    handle: &mut InstanceHandle,
+    handle: &InstanceHandle,
     table_index: wasm::DefinedTableIndex,
     item_index: u32,
-    item: wasmtime_runtime::VMCallerCheckedAnyfunc,
+    item: TableElement,
 ) -> Result<()> {
     handle
         .table_set(table_index, item_index, item)
@@ -314,8 +328,8 @@ impl Table {
     ///
     /// Returns an error if `init` does not match the element type of the table.
     pub fn new(store: &Store, ty: TableType, init: Val) -> Result<Table> {
-        let item = into_checked_anyfunc(init, store)?;
-        let (mut wasmtime_handle, wasmtime_export) = generate_table_export(store, &ty)?;
+        let item = into_table_element(store, ty.element(), init)?;
+        let (wasmtime_handle, wasmtime_export) = generate_table_export(store, &ty)?;
 
         // Initialize entries with the init value.
         match wasmtime_export {
@@ -323,7 +337,7 @@ impl Table {
                 let index = wasmtime_handle.table_index(unsafe { &*definition });
                 let len = unsafe { (*definition).current_elements };
                 for i in 0..len {
-                    set_table_item(&mut wasmtime_handle, index, i, item.clone())?;
+                    set_table_item(&wasmtime_handle, index, i, item.clone())?;
                 }
             }
             _ => unreachable!("export should be a table"),
@@ -358,7 +372,7 @@ impl Table {
     pub fn get(&self, index: u32) -> Option<Val> {
         let table_index = self.wasmtime_table_index();
         let item = self.wasmtime_handle.table_get(table_index, index)?;
-        Some(from_checked_anyfunc(item, &self.store))
+        Some(from_table_element(&self.store, item))
     }
 
     /// Writes the `val` provided into `index` within this table.
@@ -369,9 +383,8 @@ impl Table {
     /// the right type to be stored in this table.
     pub fn set(&self, index: u32, val: Val) -> Result<()> {
         let table_index = self.wasmtime_table_index();
-        let mut wasmtime_handle = self.wasmtime_handle.clone();
-        let item = into_checked_anyfunc(val, &self.store)?;
-        set_table_item(&mut wasmtime_handle, table_index, index, item)
+        let item = into_table_element(&self.store, self.ty.element(), val)?;
+        set_table_item(&self.wasmtime_handle, table_index, index, item)
     }
 
     /// Returns the current size of this table.
@@ -387,6 +400,8 @@ impl Table {
     /// Grows the size of this table by `delta` more elements, initialization
     /// all new elements to `init`.
     ///
+    /// Returns the previous size of this table if successful.
+    ///
     /// # Errors
     ///
     /// Returns an error if the table cannot be grown by `delta`, for example
@@ -394,16 +409,10 @@ impl Table {
     /// error if `init` is not of the right type.
     pub fn grow(&self, delta: u32, init: Val) -> Result<u32> {
         let index = self.wasmtime_table_index();
-        let item = into_checked_anyfunc(init, &self.store)?;
-        if let Some(len) = self.wasmtime_handle.clone().table_grow(index, delta) {
-            let mut wasmtime_handle = self.wasmtime_handle.clone();
-            for i in 0..delta {
-                let i = len - (delta - i);
-                set_table_item(&mut wasmtime_handle, index, i, item.clone())?;
-            }
-            Ok(len)
-        } else {
-            bail!("failed to grow table by `{}`", delta)
+        let item = into_table_element(&self.store, self.ty.element(), init)?;
+        match self.wasmtime_handle.table_grow(index, delta, item) {
+            Some(size) => Ok(size),
+            None => bail!("failed to grow table by `{}`", delta),
         }
     }
 
@@ -413,7 +422,7 @@ impl Table {
     /// # Errors
     ///
     /// Returns an error if the range is out of bounds of either the source or
-    /// destination tables.
+    /// destination tables, or if the tables' element types differ.
     pub fn copy(
         dst_table: &Table,
         dst_index: u32,
@@ -421,6 +430,10 @@ impl Table {
         src_index: u32,
         len: u32,
     ) -> Result<()> {
+        if dst_table.ty.element() != src_table.ty.element() {
+            bail!("cannot copy between tables of different element types");
+        }
+
         // NB: We must use the `dst_table`'s `wasmtime_handle` for the
         // `dst_table_index` and vice versa for `src_table` since each table can
         // come from different modules.
@@ -483,12 +496,14 @@ impl Table {
 ///
 /// # `Memory` and threads
 ///
-/// It is intended that `Memory` is safe to share between threads. At this time
-/// this is not implemented in `wasmtime`, however. This is planned to be
-/// implemented though!
+/// A `Memory` belongs to its [`Store`], and so can't be sent to other
+/// threads. Shared memories, as introduced by the threads proposal, can be
+/// used from other threads through a [`SharedMemory`] though: retrieve it
+/// with [`Memory::shared`], send it to another thread, and turn it into a
+/// `Memory` of a store on that thread with [`Memory::from_shared`].
 #[derive(Clone)]
 pub struct Memory {
-    _store: Store,
+    store: Store,
     ty: MemoryType,
     wasmtime_handle: InstanceHandle,
     wasmtime_export: wasmtime_runtime::Export,
@@ -504,18 +519,50 @@ impl Memory {
         let (wasmtime_handle, wasmtime_export) =
             generate_memory_export(store, &ty).expect("generated memory");
         Memory {
-            _store: store.clone(),
+            store: store.clone(),
             ty,
             wasmtime_handle,
             wasmtime_export,
         }
     }
 
+    /// Creates a `Memory` of `store` which refers to the shared memory
+    /// `memory`, so that it can be imported into instances of `store`.
+    pub fn from_shared(store: &Store, memory: &SharedMemory) -> Memory {
+        let (wasmtime_handle, wasmtime_export) =
+            generate_shared_memory_export(store, &memory.memory).expect("generated memory");
+        Memory {
+            store: store.clone(),
+            ty: memory.ty.clone(),
+            wasmtime_handle,
+            wasmtime_export,
+        }
+    }
+
     /// Returns the underlying type of this memory.
     pub fn ty(&self) -> &MemoryType {
         &self.ty
     }
 
+    /// Returns this memory as a [`SharedMemory`] which can be sent to other
+    /// threads, or `None` if it isn't a shared memory.
+    pub fn shared(&self) -> Option<SharedMemory> {
+        let memory = self.runtime_shared_memory()?;
+        Some(SharedMemory {
+            ty: self.ty.clone(),
+            memory,
+        })
+    }
+
+    fn runtime_shared_memory(&self) -> Option<runtime::SharedMemory> {
+        if !self.ty.is_shared() {
+            return None;
+        }
+        let definition = unsafe { &*self.wasmtime_memory_definition() };
+        let index = self.wasmtime_handle.memory_index(definition);
+        self.wasmtime_handle.shared_memory(index)
+    }
+
     fn wasmtime_memory_definition(&self) -> *mut wasmtime_runtime::VMMemoryDefinition {
         match self.wasmtime_export {
             wasmtime_runtime::Export::Memory { definition, .. } => definition,
@@ -634,10 +681,172 @@ impl Memory {
         };
         let ty = MemoryType::from_wasmtime_memory(&memory.memory);
         Memory {
-            _store: store.clone(),
+            store: store.clone(),
             ty: ty,
             wasmtime_handle: instance_handle,
             wasmtime_export: export,
         }
     }
 }
+
+/// A WebAssembly linear memory which can be shared between threads, as
+/// introduced by the [threads proposal][threads].
+///
+/// Unlike [`Memory`], a `SharedMemory` isn't tied to a [`Store`] and is both
+/// `Send` and `Sync`. Use [`Memory::from_shared`] to import it into the
+/// instances of a store, on any thread. Clones refer to the same memory.
+///
+/// Shared memories are allocated with enough address space for their maximum
+/// size up front, so growing them never moves them. They're grown through
+/// [`Memory::grow`] so that the [`ResourceLimiter`](crate::ResourceLimiter)
+/// of the store doing so is consulted.
+///
+/// [threads]: https://github.com/webassembly/threads
+#[derive(Clone)]
+pub struct SharedMemory {
+    ty: MemoryType,
+    memory: runtime::SharedMemory,
+}
+
+impl SharedMemory {
+    /// Creates a new shared WebAssembly memory given the configuration of
+    /// `ty`, which must be a [shared](MemoryType::shared) memory type.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the threads proposal isn't enabled for `engine`,
+    /// if `ty` isn't shared or has no maximum, or if the memory couldn't be
+    /// allocated.
+    pub fn new(engine: &Engine, ty: MemoryType) -> Result<SharedMemory> {
+        if !engine
+            .config()
+            .validating_config
+            .operator_config
+            .enable_threads
+        {
+            bail!("shared memories require the threads proposal to be enabled");
+        }
+        if !ty.is_shared() {
+            bail!("memory type isn't shared");
+        }
+        if ty.limits().max().is_none() {
+            bail!("shared memories must have a maximum size");
+        }
+        let memory = wasm::Memory {
+            minimum: ty.limits().min(),
+            maximum: ty.limits().max(),
+            shared: true,
+        };
+        let plan = MemoryPlan::for_memory(memory, &engine.tunables(&Triple::host()));
+        let memory = runtime::SharedMemory::new(&plan).map_err(|e| anyhow!(e))?;
+        Ok(SharedMemory { ty, memory })
+    }
+
+    /// Returns the underlying type of this memory.
+    pub fn ty(&self) -> &MemoryType {
+        &self.ty
+    }
+
+    /// Returns the base pointer, in the host's address space, that the memory
+    /// is located at.
+    ///
+    /// Other threads may be accessing the memory at the same time, so any
+    /// access through this pointer is a potential data race unless it's
+    /// atomic.
+    pub fn data_ptr(&self) -> *mut u8 {
+        self.memory.vmmemory().base
+    }
+
+    /// Returns the byte length of this memory.
+    ///
+    /// The returned value will be a multiple of the wasm page size, 64k.
+    pub fn data_size(&self) -> usize {
+        self.memory.vmmemory().current_length
+    }
+
+    /// Returns the size, in pages, of this wasm memory.
+    pub fn size(&self) -> u32 {
+        self.memory.size()
+    }
+}
+
+fn _assert_shared_memory_is_sync_and_send(m: &SharedMemory) -> (&dyn Sync, &dyn Send) {
+    (m, m)
+}
+
+/// A linear memory. This trait provides an interface for raw memory buffers
+/// which are used by wasmtime, e.g. inside [`Memory`]. Such buffers are in
+/// principle not thread safe. By implementing this trait together with
+/// [`MemoryCreator`], one can supply wasmtime with custom allocated host managed
+/// memory.
+///
+/// # Safety
+/// The memory should be page aligned and a multiple of page size.
+/// To prevent possible silent overflows, the memory should be protected by a
+/// guard page. Additionally the safety concerns explained in [`Memory`], for
+/// accessing the memory apply here as well.
+///
+/// Note that this is a relatively new and experimental feature and it is
+/// recommended to be familiar with wasmtime runtime code to use it.
+pub unsafe trait LinearMemory {
+    /// Returns the number of allocated wasm pages.
+    fn size(&self) -> u32;
+
+    /// Grow memory by the specified amount of wasm pages.
+    ///
+    /// Returns `None` if memory can't be grown by the specified amount
+    /// of wasm pages. Requests which would exceed the maximum of the
+    /// [`MemoryType`] this memory was created with are rejected before this
+    /// is called.
+    fn grow(&self, delta: u32) -> Option<u32>;
+
+    /// Return the allocated memory as a mutable pointer to u8.
+    fn as_ptr(&self) -> *mut u8;
+}
+
+/// A memory creator. Can be used to provide a memory creator
+/// to wasmtime which supplies host managed memory.
+///
+/// # Safety
+/// This trait is unsafe, as the memory safety depends on proper implementation
+/// of memory management. Memories created by the MemoryCreator should always be
+/// treated as owned by the wasmtime instance, and any modification of them outside
+/// of wasmtime invoked routines is unsafe and may lead to corruption.
+///
+/// Note that this is a relatively new and experimental feature and it is
+/// recommended to be familiar with wasmtime runtime code to use it.
+pub unsafe trait MemoryCreator: Send + Sync {
+    /// Create a new `LinearMemory` object from the specified parameters.
+    ///
+    /// The type of memory being created is specified by `ty` which indicates
+    /// both the minimum and maximum size, in wasm pages.
+    ///
+    /// The `reserved_size_in_bytes` value indicates the expected size of the
+    /// reservation that is to be made for this memory. If this value is `None`
+    /// than the implementation is free to allocate memory as it sees fit. If
+    /// the value is `Some`, however, then the implementation is expected to
+    /// reserve that many bytes for the memory's allocation, plus the guard
+    /// size at the end. Note that this reservation need only be a virtual
+    /// memory reservation, physical memory does not need to be allocated
+    /// immediately. In this case `grow` should never move the base pointer and
+    /// the maximum size of `ty` is guaranteed to fit within
+    /// `reserved_size_in_bytes`.
+    ///
+    /// The `guard_size_in_bytes` parameter indicates how many bytes of space,
+    /// after the memory allocation, is expected to be unmapped. JIT code will
+    /// elide bounds checks based on the `guard_size_in_bytes` provided, so for
+    /// JIT code to work correctly the memory returned will need to be properly
+    /// guarded with `guard_size_in_bytes` bytes left unmapped after the base
+    /// allocation.
+    ///
+    /// Note that the `reserved_size_in_bytes` and `guard_size_in_bytes`
+    /// options are derived from the memory tunables of the target that wasm
+    /// is compiled for. Additionally these two values are guaranteed to be
+    /// multiples of the system page size.
+    fn new_memory(
+        &self,
+        ty: MemoryType,
+        reserved_size_in_bytes: Option<u64>,
+        guard_size_in_bytes: u64,
+    ) -> Result<Box<dyn LinearMemory>, String>;
+}
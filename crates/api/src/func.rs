@@ -1,9 +1,12 @@
-use crate::callable::{NativeCallable, WasmtimeFn, WrappedCallable};
-use crate::{Callable, FuncType, Store, Trap, Val, ValType};
+use crate::callable::{call_with_values_vec, NativeCallable, WasmtimeFn, WrappedCallable};
+use crate::{Callable, Extern, FuncType, Store, Trap, TypedFunc, Val, ValType, WasmParams};
 use anyhow::{ensure, Context as _};
+use std::cmp;
 use std::fmt;
+use std::future::Future;
 use std::mem;
 use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
 use std::ptr;
 use std::rc::Rc;
 use wasmtime_runtime::{InstanceHandle, VMContext, VMFunctionBody};
@@ -26,7 +29,7 @@ use wasmtime_runtime::{InstanceHandle, VMContext, VMFunctionBody};
 /// instances are equivalent in their functionality.
 #[derive(Clone)]
 pub struct Func {
-    _store: Store,
+    store: Store,
     callable: Rc<dyn WrappedCallable + 'static>,
     ty: FuncType,
 }
@@ -43,49 +46,7 @@ macro_rules! wrappers {
             $($args: WasmTy,)*
             R: WasmRet,
         {
-            #[allow(non_snake_case)]
-            unsafe extern "C" fn shim<F, $($args,)* R>(
-                vmctx: *mut VMContext,
-                _caller_vmctx: *mut VMContext,
-                $($args: $args::Abi,)*
-            ) -> R::Abi
-            where
-                F: Fn($($args),*) -> R + 'static,
-                $($args: WasmTy,)*
-                R: WasmRet,
-            {
-                let ret = {
-                    let instance = InstanceHandle::from_vmctx(vmctx);
-                    let func = instance.host_state().downcast_ref::<F>().expect("state");
-                    panic::catch_unwind(AssertUnwindSafe(|| {
-                        func($($args::from_abi(_caller_vmctx, $args)),*)
-                    }))
-                };
-                match ret {
-                    Ok(ret) => ret.into_abi(),
-                    Err(panic) => wasmtime_runtime::resume_panic(panic),
-                }
-            }
-
-            let mut _args = Vec::new();
-            $($args::push(&mut _args);)*
-            let mut ret = Vec::new();
-            R::push(&mut ret);
-            let ty = FuncType::new(_args.into(), ret.into());
-            unsafe {
-                let (instance, export) = crate::trampoline::generate_raw_func_export(
-                    &ty,
-                    std::slice::from_raw_parts_mut(
-                        shim::<F, $($args,)* R> as *mut _,
-                        0,
-                    ),
-                    store,
-                    Box::new(func),
-                )
-                .expect("failed to generate export");
-                let callable = Rc::new(WasmtimeFn::new(store, instance, export));
-                Func::from_wrapped(store, ty, callable)
-            }
+            <F as IntoFunc<($($args,)*), R>>::into_func(func, store)
         }
     )*)
 }
@@ -101,7 +62,7 @@ macro_rules! getters {
             -> anyhow::Result<impl Fn($($args,)*) -> Result<R, Trap>>
         where
             $($args: WasmTy,)*
-            R: WasmTy,
+            R: WasmResults,
         {
             // Verify all the paramers match the expected parameters, and that
             // there are no extra parameters...
@@ -118,18 +79,51 @@ macro_rules! getters {
             let mut results = self.ty().results().iter().cloned();
             R::matches(&mut results)
                 .context("Type mismatch in return type")?;
-            ensure!(results.next().is_none(), "Type mismatch: too many return values (expected 1)");
+            let mut expected = Vec::new();
+            R::push(&mut expected);
+            ensure!(
+                results.next().is_none(),
+                "Type mismatch: too many return values (expected {})",
+                expected.len(),
+            );
 
             // ... and then once we've passed the typechecks we can hand out our
             // object since our `transmute` below should be safe!
-            let (address, vmctx) = match self.wasmtime_export() {
-                wasmtime_runtime::Export::Function { address, vmctx, signature: _} => {
-                    (*address, *vmctx)
+            let (address, vmctx, signature) = match self.wasmtime_export() {
+                wasmtime_runtime::Export::Function { address, vmctx, signature } => {
+                    (*address, *vmctx, signature.clone())
                 }
                 _ => panic!("expected function export"),
             };
+
+            // Multiple results can't be returned to Rust directly, so those
+            // calls go through a trampoline which passes the arguments and
+            // results in a values vector instead.
+            let store = self.store.clone();
+            let multi_value = expected.len() > 1;
+            let values_vec_len = cmp::max(n, expected.len());
             Ok(move |$($args: $args),*| -> Result<R, Trap> {
                 unsafe {
+                    if multi_value {
+                        let mut values_vec = vec![0i128; values_vec_len];
+                        call_with_values_vec(
+                            &store,
+                            vmctx,
+                            address,
+                            &signature,
+                            &mut values_vec,
+                            |_values_vec| {
+                                let mut _slot = _values_vec.as_mut_ptr();
+                                $(
+                                    ptr::write(_slot as *mut $args::Abi, $args::into_abi($args));
+                                    _slot = _slot.add(1);
+                                )*
+                                Ok(())
+                            },
+                        )?;
+                        return Ok(R::load_from(vmctx, values_vec.as_ptr()));
+                    }
+
                     let f = mem::transmute::<
                         *const VMFunctionBody,
                         unsafe extern "C" fn(
@@ -171,6 +165,131 @@ impl Func {
         Func::from_wrapped(store, ty, callable)
     }
 
+    /// Creates a new host-defined function which returns a future, used to
+    /// define functions which wait on asynchronous operations.
+    ///
+    /// This is the same as [`Func::new`] except that `func` returns a
+    /// [`Future`] which writes the results into its `results` argument once
+    /// it completes. While the future is pending, execution of the wasm
+    /// which called this function is suspended and the future returned by
+    /// [`Func::call_async`] is pending too, so the thread is free to make
+    /// progress on other tasks.
+    ///
+    /// Functions created here can only be called from wasm which was entered
+    /// through [`Func::call_async`]; calling them otherwise traps.
+    ///
+    /// # Panics
+    ///
+    /// Panics if async support isn't enabled in the configuration of `store`,
+    /// see [`Config::async_support`](crate::Config::async_support).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let store = Store::new(&Engine::new(Config::new().async_support(true)));
+    /// let ty = FuncType::new(Box::new([ValType::I32]), Box::new([ValType::I32]));
+    /// let double = Func::new_async(&store, ty, |params, results| {
+    ///     Box::new(async move {
+    ///         // ... wait on some asynchronous work here ...
+    ///         results[0] = Val::I32(params[0].unwrap_i32() * 2);
+    ///         Ok(())
+    ///     })
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_async<F>(store: &Store, ty: FuncType, func: F) -> Func
+    where
+        F: for<'a> Fn(&'a [Val], &'a mut [Val]) -> Box<dyn Future<Output = Result<(), Trap>> + 'a>
+            + 'static,
+    {
+        assert!(
+            store.engine().config().async_support,
+            "cannot use `new_async` without enabling async support in the config"
+        );
+
+        struct AsyncCallable<F> {
+            store: Store,
+            func: F,
+        }
+
+        impl<F> Callable for AsyncCallable<F>
+        where
+            F: for<'a> Fn(
+                &'a [Val],
+                &'a mut [Val],
+            ) -> Box<dyn Future<Output = Result<(), Trap>> + 'a>,
+        {
+            fn call(&self, params: &[Val], results: &mut [Val]) -> Result<(), Trap> {
+                let mut future = Pin::from((self.func)(params, results));
+                self.store.block_on(future.as_mut())?
+            }
+        }
+
+        let callable = AsyncCallable {
+            store: store.clone(),
+            func,
+        };
+        Func::new(store, ty, Rc::new(callable))
+    }
+
+    /// Creates a new `Func` from the given Rust closure.
+    ///
+    /// This is the same as the [`Func::wrap1`] family of functions, except
+    /// that the number of arguments is inferred from `func`, and that `func`
+    /// may take a [`Caller`] as its first argument. The [`Caller`] gives
+    /// access to the exports of the instance calling the function, such as
+    /// its memory, and to the [`Store`] along with its data (see
+    /// [`Store::set_data`]).
+    ///
+    /// For more information about which Rust types match up to which wasm
+    /// types, see the documentation on [`Func::wrap1`].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let store = Store::default();
+    /// store.set_data(String::new());
+    ///
+    /// // Appends a string from the caller's memory to the store's data.
+    /// let append = Func::wrap(&store, |caller: Caller<'_>, ptr: i32, len: i32| {
+    ///     let memory = match caller.get_export("memory") {
+    ///         Some(Extern::Memory(memory)) => memory,
+    ///         _ => return Err(Trap::new("missing memory export")),
+    ///     };
+    ///     let bytes = unsafe { &memory.data_unchecked()[ptr as usize..][..len as usize] };
+    ///     let s = std::str::from_utf8(bytes).map_err(|_| Trap::new("invalid utf-8"))?;
+    ///     caller.store().data_mut::<String>().unwrap().push_str(s);
+    ///     Ok(())
+    /// });
+    ///
+    /// let module = Module::new(
+    ///     &store,
+    ///     r#"
+    ///         (module
+    ///             (import "" "append" (func $append (param i32 i32)))
+    ///             (memory (export "memory") 1)
+    ///             (data (i32.const 0) "hello")
+    ///             (func (export "run")
+    ///                 (call $append (i32.const 0) (i32.const 5)))
+    ///         )
+    ///     "#,
+    /// )?;
+    /// let instance = Instance::new(&module, &[append.into()])?;
+    /// let run = instance.get_export("run").unwrap().func().unwrap().get0::<()>()?;
+    /// run()?;
+    /// assert_eq!(*store.data::<String>().unwrap(), "hello");
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn wrap<Params, Results>(store: &Store, func: impl IntoFunc<Params, Results>) -> Func {
+        func.into_func(store)
+    }
+
     wrappers! {
         /// Creates a new `Func` from the given Rust closure, which takes 0
         /// arguments.
@@ -199,10 +318,18 @@ impl Func {
         /// Any of the Rust types can be returned from the closure as well, in
         /// addition to some extra types
         ///
-        /// | Rust Return Type  | WebAssembly Return Type | Meaning           |
-        /// |-------------------|-------------------------|-------------------|
-        /// | `()`              | nothing                 | no return value   |
-        /// | `Result<T, Trap>` | `T`                     | function may trap |
+        /// | Rust Return Type            | WebAssembly Return Type | Meaning                |
+        /// |-----------------------------|-------------------------|------------------------|
+        /// | `()`                        | nothing                 | no return value        |
+        /// | `(A, B, ...)`               | `A B ...`               | multiple return values |
+        /// | `Result<T, Trap>`           | `T`                     | function may trap      |
+        /// | `Result<(A, B, ...), Trap>` | `A B ...`               | function may trap      |
+        ///
+        /// Returning multiple values requires the multi-value proposal to be
+        /// enabled with [`Config::wasm_multi_value`](crate::Config::wasm_multi_value)
+        /// for modules to be able to import the function. Tuples of up to 8
+        /// values are supported, and since they can't be returned through the
+        /// native ABI they're passed back to wasm through a small trampoline.
         ///
         /// Note that when using this API (and the related `wrap*` family of
         /// functions), the intention is to create as thin of a layer as
@@ -272,12 +399,16 @@ impl Func {
         callable: Rc<dyn WrappedCallable + 'static>,
     ) -> Func {
         Func {
-            _store: store.clone(),
+            store: store.clone(),
             callable,
             ty,
         }
     }
 
+    pub(crate) fn store(&self) -> &Store {
+        &self.store
+    }
+
     /// Returns the underlying wasm type that this `Func` has.
     pub fn ty(&self) -> &FuncType {
         &self.ty
@@ -304,10 +435,35 @@ impl Func {
     /// initiates a panic.
     pub fn call(&self, params: &[Val]) -> Result<Box<[Val]>, Trap> {
         let mut results = vec![Val::null(); self.result_arity()];
-        self.callable.call(params, &mut results)?;
+        self.callable.call(&self.ty, params, &mut results)?;
         Ok(results.into_boxed_slice())
     }
 
+    /// Invokes this function with the `params` given, returning a future
+    /// which resolves to the results, or a trap if one occurs.
+    ///
+    /// Unlike [`Func::call`] the wasm here runs on a separate native stack,
+    /// and whenever an async host function (see [`Func::new_async`]) waits on
+    /// a pending future the returned future yields back to the executor
+    /// polling it. Polling it again resumes execution of wasm where it left
+    /// off.
+    ///
+    /// Dropping the returned future before it completes abandons the call:
+    /// the async host function it was waiting on returns a trap, unwinding
+    /// the wasm and host functions it was running.
+    ///
+    /// # Panics
+    ///
+    /// Panics if async support isn't enabled in the configuration of this
+    /// function's store, see [`Config::async_support`](crate::Config::async_support).
+    pub async fn call_async(&self, params: &[Val]) -> Result<Box<[Val]>, Trap> {
+        assert!(
+            self.store.engine().config().async_support,
+            "cannot use `call_async` without enabling async support in the config"
+        );
+        self.store.on_fiber(|| self.call(params)).await?
+    }
+
     pub(crate) fn wasmtime_export(&self) -> &wasmtime_runtime::Export {
         self.callable.wasmtime_export()
     }
@@ -330,6 +486,46 @@ impl Func {
         Func::from_wrapped(store, ty, Rc::new(callable))
     }
 
+    /// Overrides the type of this function with `ty`, which must lower to the
+    /// same native signature.
+    ///
+    /// The type derived from a native signature can't tell `funcref`s apart
+    /// from `anyref`s, so this is used where the exact type is known.
+    pub(crate) fn with_ty(mut self, ty: FuncType) -> Func {
+        self.ty = ty;
+        self
+    }
+
+    /// Attempts to extract a statically typed handle to this `Func`, which
+    /// takes `Params` and returns `Results`.
+    ///
+    /// The signature is checked once here, and an error is returned if it
+    /// doesn't match the runtime type of this function. Calls through the
+    /// returned [`TypedFunc`] then skip the type checks and conversions to
+    /// [`Val`] which [`Func::call`] performs. Unlike the [`Func::get1`] family
+    /// of methods the parameters are given as a tuple, and `anyref` and
+    /// `funcref` parameters are supported as well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// let store = Store::default();
+    /// let add = Func::wrap2(&store, |a: i32, b: i32| a + b);
+    /// let add = add.typed::<(i32, i32), i32>()?;
+    /// assert_eq!(add.call((1, 2))?, 3);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn typed<Params, Results>(&self) -> anyhow::Result<TypedFunc<Params, Results>>
+    where
+        Params: WasmParams,
+        Results: WasmResults,
+    {
+        TypedFunc::new(self)
+    }
+
     getters! {
         /// Extracts a natively-callable object from this `Func`, if the
         /// signature matches.
@@ -359,9 +555,12 @@ impl Func {
         /// This function will return `None` if the type signature asserted
         /// statically does not match the runtime type signature. `Some`,
         /// however, will be returned if the underlying function takes one
-        /// parameter of type `A` and returns the parameter `R`. Currently `R`
-        /// can either be `()` (no return values) or one wasm type. At this time
-        /// a multi-value return isn't supported.
+        /// parameter of type `A` and returns the parameter `R`. `R` can either
+        /// be `()` (no return values), one wasm type, or a tuple of wasm types
+        /// for functions with multiple return values. Calls of functions with
+        /// multiple return values go through a trampoline which passes the
+        /// arguments and results in memory, rather than jumping directly into
+        /// JIT code.
         ///
         /// The returned closure will always return a `Result<R, Trap>` and an
         /// `Err` is returned if a trap happens while the wasm is executing.
@@ -574,15 +773,18 @@ pub trait WasmRet {
     #[doc(hidden)]
     type Abi;
     #[doc(hidden)]
+    type Retptr: Copy;
+    #[doc(hidden)]
     fn push(dst: &mut Vec<ValType>);
     #[doc(hidden)]
     fn matches(tys: impl Iterator<Item = ValType>) -> anyhow::Result<()>;
     #[doc(hidden)]
-    fn into_abi(self) -> Self::Abi;
+    unsafe fn into_abi(self, retptr: Self::Retptr) -> Self::Abi;
 }
 
 impl<T: WasmTy> WasmRet for T {
     type Abi = T::Abi;
+    type Retptr = ();
     fn push(dst: &mut Vec<ValType>) {
         T::push(dst)
     }
@@ -592,13 +794,14 @@ impl<T: WasmTy> WasmRet for T {
     }
 
     #[inline]
-    fn into_abi(self) -> Self::Abi {
+    unsafe fn into_abi(self, _retptr: ()) -> Self::Abi {
         T::into_abi(self)
     }
 }
 
 impl<T: WasmTy> WasmRet for Result<T, Trap> {
     type Abi = T::Abi;
+    type Retptr = ();
     fn push(dst: &mut Vec<ValType>) {
         T::push(dst)
     }
@@ -608,14 +811,311 @@ impl<T: WasmTy> WasmRet for Result<T, Trap> {
     }
 
     #[inline]
-    fn into_abi(self) -> Self::Abi {
+    unsafe fn into_abi(self, _retptr: ()) -> Self::Abi {
         match self {
-            Ok(val) => return val.into_abi(),
+            Ok(val) => return T::into_abi(val),
             Err(trap) => handle_trap(trap),
         }
+    }
+}
+
+fn handle_trap(trap: Trap) -> ! {
+    unsafe { wasmtime_runtime::raise_user_trap(Box::new(trap)) }
+}
+
+/// A trait implemented for types which can be returned from the closures
+/// returned by [`Func::get1`] and friends.
+///
+/// This trait should not be implemented by user types. This trait may change at
+/// any time internally. The types which implement this trait, however, are
+/// stable over time.
+///
+/// For more information see [`Func::get1`]
+pub trait WasmResults {
+    #[doc(hidden)]
+    type Abi: Copy;
+    #[doc(hidden)]
+    fn push(dst: &mut Vec<ValType>);
+    #[doc(hidden)]
+    fn matches(tys: impl Iterator<Item = ValType>) -> anyhow::Result<()>;
+    #[doc(hidden)]
+    fn from_abi(vmctx: *mut VMContext, abi: Self::Abi) -> Self;
+    #[doc(hidden)]
+    unsafe fn load_from(vmctx: *mut VMContext, values_vec: *const i128) -> Self;
+}
 
-        fn handle_trap(trap: Trap) -> ! {
-            unsafe { wasmtime_runtime::raise_user_trap(Box::new(trap)) }
+impl<T: WasmTy> WasmResults for T {
+    type Abi = T::Abi;
+    fn push(dst: &mut Vec<ValType>) {
+        T::push(dst)
+    }
+
+    fn matches(tys: impl Iterator<Item = ValType>) -> anyhow::Result<()> {
+        T::matches(tys)
+    }
+
+    #[inline]
+    fn from_abi(vmctx: *mut VMContext, abi: Self::Abi) -> Self {
+        T::from_abi(vmctx, abi)
+    }
+
+    #[inline]
+    unsafe fn load_from(vmctx: *mut VMContext, values_vec: *const i128) -> Self {
+        T::from_abi(vmctx, ptr::read(values_vec as *const T::Abi))
+    }
+}
+
+// Tuples are used for multiple return values. Those can't be returned through
+// the native ABI, so each value is passed in its own 16-byte slot of a values
+// vector instead, like `Func::call` does, and host functions receive a pointer
+// to that vector as their last argument.
+macro_rules! tuples {
+    ($(($($t:ident),*))*) => ($(
+        #[allow(non_snake_case)]
+        impl<$($t: WasmTy,)*> WasmRet for ($($t,)*) {
+            type Abi = ();
+            type Retptr = *mut i128;
+            fn push(dst: &mut Vec<ValType>) {
+                $($t::push(dst);)*
+            }
+
+            fn matches(mut tys: impl Iterator<Item = ValType>) -> anyhow::Result<()> {
+                $($t::matches(&mut tys)?;)*
+                Ok(())
+            }
+
+            #[inline]
+            unsafe fn into_abi(self, retptr: *mut i128) {
+                let ($($t,)*) = self;
+                let mut _slot = retptr;
+                $(
+                    ptr::write(_slot as *mut $t::Abi, $t::into_abi($t));
+                    _slot = _slot.add(1);
+                )*
+            }
+        }
+
+        impl<$($t: WasmTy,)*> WasmRet for Result<($($t,)*), Trap> {
+            type Abi = ();
+            type Retptr = *mut i128;
+            fn push(dst: &mut Vec<ValType>) {
+                <($($t,)*) as WasmRet>::push(dst)
+            }
+
+            fn matches(tys: impl Iterator<Item = ValType>) -> anyhow::Result<()> {
+                <($($t,)*) as WasmRet>::matches(tys)
+            }
+
+            #[inline]
+            unsafe fn into_abi(self, retptr: *mut i128) {
+                match self {
+                    Ok(vals) => vals.into_abi(retptr),
+                    Err(trap) => handle_trap(trap),
+                }
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<$($t: WasmTy,)*> WasmResults for ($($t,)*) {
+            type Abi = ();
+            fn push(dst: &mut Vec<ValType>) {
+                $($t::push(dst);)*
+            }
+
+            fn matches(mut tys: impl Iterator<Item = ValType>) -> anyhow::Result<()> {
+                $($t::matches(&mut tys)?;)*
+                Ok(())
+            }
+
+            fn from_abi(_vmctx: *mut VMContext, _abi: ()) -> Self {
+                unreachable!("multiple results are loaded from a values vector")
+            }
+
+            #[inline]
+            unsafe fn load_from(vmctx: *mut VMContext, values_vec: *const i128) -> Self {
+                let mut _slot = values_vec;
+                $(
+                    let $t = $t::from_abi(vmctx, ptr::read(_slot as *const $t::Abi));
+                    _slot = _slot.add(1);
+                )*
+                ($($t,)*)
+            }
         }
+    )*)
+}
+
+tuples! {
+    (A1, A2)
+    (A1, A2, A3)
+    (A1, A2, A3, A4)
+    (A1, A2, A3, A4, A5)
+    (A1, A2, A3, A4, A5, A6)
+    (A1, A2, A3, A4, A5, A6, A7)
+    (A1, A2, A3, A4, A5, A6, A7, A8)
+}
+
+/// Internal trait implemented for all closures which can be turned into a
+/// [`Func`] with [`Func::wrap`].
+///
+/// This trait should not be implemented by user types. This trait may change at
+/// any time internally. The types which implement this trait, however, are
+/// stable over time.
+///
+/// For more information see [`Func::wrap`]
+pub trait IntoFunc<Params, Results> {
+    #[doc(hidden)]
+    fn into_func(self, store: &Store) -> Func;
+}
+
+/// A structure representing the caller's context when creating a function via
+/// [`Func::wrap`].
+///
+/// This structure can be taken as the first parameter of a closure passed to
+/// [`Func::wrap`], and gives access to the [`Store`] the function belongs to,
+/// including any data attached to it with [`Store::set_data`], as well as to
+/// the exports of the instance which called the function, such as its memory.
+pub struct Caller<'a> {
+    store: &'a Store,
+    caller_vmctx: *mut VMContext,
+}
+
+impl Caller<'_> {
+    /// Looks up an export of the instance which called this function.
+    ///
+    /// This is typically used to access the `memory` of the calling instance,
+    /// for example to read strings which were passed as pointers into it.
+    ///
+    /// Returns `None` if the caller has no export named `name`, or if the
+    /// function wasn't called from wasm, for example if it was called with
+    /// [`Func::call`].
+    pub fn get_export(&self, name: &str) -> Option<Extern> {
+        if self.caller_vmctx.is_null() {
+            return None;
+        }
+        unsafe {
+            let instance = InstanceHandle::from_vmctx(self.caller_vmctx);
+            let export = instance.lookup(name)?;
+            Some(Extern::from_wasmtime_export(self.store, instance, export))
+        }
+    }
+
+    /// Returns the [`Store`] that this function belongs to.
+    pub fn store(&self) -> &Store {
+        self.store
     }
 }
+
+macro_rules! into_func {
+    ($(($($args:ident),*))*) => ($(
+        #[allow(non_snake_case)]
+        impl<F, $($args,)* R> IntoFunc<($($args,)*), R> for F
+        where
+            F: Fn($($args),*) -> R + 'static,
+            $($args: WasmTy,)*
+            R: WasmRet,
+        {
+            fn into_func(self, store: &Store) -> Func {
+                into_func!(@wrap store, ($($args),*), move |_: *mut VMContext, $($args: $args),*| {
+                    self($($args),*)
+                })
+            }
+        }
+
+        #[allow(non_snake_case)]
+        impl<F, $($args,)* R> IntoFunc<(Caller<'_>, $($args,)*), R> for F
+        where
+            F: Fn(Caller<'_>, $($args),*) -> R + 'static,
+            $($args: WasmTy,)*
+            R: WasmRet,
+        {
+            fn into_func(self, store: &Store) -> Func {
+                // Hold the store weakly so the function doesn't keep it alive
+                // by itself; the `Func` handles referring to it do.
+                let func_store = store.weak();
+                into_func!(@wrap store, ($($args),*), move |caller_vmctx, $($args: $args),*| {
+                    let store = func_store
+                        .upgrade()
+                        .expect("host function called after its store was dropped");
+                    let caller = Caller {
+                        store: &store,
+                        caller_vmctx,
+                    };
+                    self(caller, $($args),*)
+                })
+            }
+        }
+    )*);
+
+    (@wrap $store:ident, ($($args:ident),*), $func:expr) => {{
+        // The closure given receives the caller's `vmctx` along with the
+        // arguments of the function.
+        #[allow(non_snake_case)]
+        fn wrap<F, $($args,)* R>(store: &Store, func: F) -> Func
+        where
+            F: Fn(*mut VMContext, $($args),*) -> R + 'static,
+            $($args: WasmTy,)*
+            R: WasmRet,
+        {
+            #[allow(non_snake_case)]
+            unsafe extern "C" fn shim<F, $($args,)* R>(
+                vmctx: *mut VMContext,
+                caller_vmctx: *mut VMContext,
+                $($args: $args::Abi,)*
+                retptr: R::Retptr,
+            ) -> R::Abi
+            where
+                F: Fn(*mut VMContext, $($args),*) -> R + 'static,
+                $($args: WasmTy,)*
+                R: WasmRet,
+            {
+                let ret = {
+                    let instance = InstanceHandle::from_vmctx(vmctx);
+                    let func = instance.host_state().downcast_ref::<F>().expect("state");
+                    panic::catch_unwind(AssertUnwindSafe(|| {
+                        func(caller_vmctx, $($args::from_abi(caller_vmctx, $args)),*)
+                    }))
+                };
+                match ret {
+                    Ok(ret) => ret.into_abi(retptr),
+                    Err(panic) => wasmtime_runtime::resume_panic(panic),
+                }
+            }
+
+            let mut _args = Vec::new();
+            $($args::push(&mut _args);)*
+            let mut ret = Vec::new();
+            R::push(&mut ret);
+            let ty = FuncType::new(_args.into(), ret.into());
+            unsafe {
+                let (instance, export) = crate::trampoline::generate_raw_func_export(
+                    &ty,
+                    std::slice::from_raw_parts_mut(
+                        shim::<F, $($args,)* R> as *mut _,
+                        0,
+                    ),
+                    store,
+                    Box::new(func),
+                )
+                .expect("failed to generate export");
+                let callable = Rc::new(WasmtimeFn::new(store, instance, export));
+                Func::from_wrapped(store, ty, callable)
+            }
+        }
+
+        wrap($store, $func)
+    }};
+}
+
+into_func! {
+    ()
+    (A1)
+    (A1, A2)
+    (A1, A2, A3)
+    (A1, A2, A3, A4)
+    (A1, A2, A3, A4, A5)
+    (A1, A2, A3, A4, A5, A6)
+    (A1, A2, A3, A4, A5, A6, A7)
+    (A1, A2, A3, A4, A5, A6, A7, A8)
+    (A1, A2, A3, A4, A5, A6, A7, A8, A9)
+    (A1, A2, A3, A4, A5, A6, A7, A8, A9, A10)
+}
@@ -1,7 +1,8 @@
 use crate::externals::Extern;
 use crate::module::Module;
-use crate::runtime::{Config, Store};
+use crate::runtime::Store;
 use crate::trap::Trap;
+use crate::types::ExternType;
 use anyhow::{Error, Result};
 use wasmtime_jit::{CompiledModule, Resolver};
 use wasmtime_runtime::{Export, InstanceHandle, InstantiationError};
@@ -19,16 +20,24 @@ impl Resolver for SimpleResolver<'_> {
 }
 
 fn instantiate(
-    config: &Config,
+    store: &Store,
     compiled_module: &CompiledModule,
     imports: &[Extern],
 ) -> Result<InstanceHandle, Error> {
     let mut resolver = SimpleResolver { imports };
-    unsafe {
-        let instance = compiled_module
+    let config = store.engine().config();
+    let interrupts = store.compiler().interrupts().clone();
+    store.bump_resource_counts(compiled_module.module_ref())?;
+    let result = unsafe {
+        compiled_module
             .instantiate(
                 config.validating_config.operator_config.enable_bulk_memory,
                 &mut resolver,
+                interrupts,
+                store.externref_activations_table().clone(),
+                store.limiter(),
+                store.memory_creator(),
+                store.instance_pool(),
             )
             .map_err(|e| -> Error {
                 match e {
@@ -37,9 +46,14 @@ fn instantiate(
                     }
                     other => other.into(),
                 }
-            })?;
-        Ok(instance)
+            })
+    };
+    // The instance was never created, or was dropped along with its
+    // resources, so it no longer counts against the store's limits.
+    if result.is_err() {
+        store.release_resource_counts(compiled_module.module_ref());
     }
+    result
 }
 
 /// An instantiated WebAssembly module.
@@ -86,10 +100,10 @@ impl Instance {
     /// These [`Extern`] values are all then collected into a list and passed to
     /// this function.
     ///
-    /// Note that this function is intentionally relatively low level. It is the
-    /// intention that we'll soon provide a [higher level API][issue] which will
-    /// be much more ergonomic for instantiating modules. If you need the full
-    /// power of customization of imports, though, this is the method for you!
+    /// Note that this function is intentionally relatively low level. For an
+    /// easier time instantiating modules by name, see [`Linker`]. If you need
+    /// the full power of customization of imports, though, this is the method
+    /// for you!
     ///
     /// ## Errors
     ///
@@ -108,22 +122,26 @@ impl Instance {
     /// check for trap errors, you can use `error.downcast::<Trap>()`.
     ///
     /// [inst]: https://webassembly.github.io/spec/core/exec/modules.html#exec-instantiation
-    /// [issue]: https://github.com/bytecodealliance/wasmtime/issues/727
+    /// [`Linker`]: crate::Linker
     pub fn new(module: &Module, imports: &[Extern]) -> Result<Instance, Error> {
         let store = module.store();
-        let config = store.engine().config();
-        let instance_handle = instantiate(config, module.compiled_module(), imports)?;
+        let instance_handle = instantiate(store, module.compiled_module(), imports)?;
 
         let exports = {
             let mut exports = Vec::with_capacity(module.exports().len());
             for export in module.exports() {
                 let name = export.name().to_string();
+                let ty = export.ty();
                 let export = instance_handle.lookup(&name).expect("export");
-                exports.push(Extern::from_wasmtime_export(
-                    store,
-                    instance_handle.clone(),
-                    export,
-                ));
+                exports.push(
+                    match Extern::from_wasmtime_export(store, instance_handle.clone(), export) {
+                        Extern::Func(f) => match ty {
+                            ExternType::Func(ty) => Extern::Func(f.with_ty(ty.clone())),
+                            _ => unreachable!("export should be a function"),
+                        },
+                        other => other,
+                    },
+                );
             }
             exports.into_boxed_slice()
         };
@@ -179,8 +197,83 @@ impl Instance {
         Some(&self.exports()[i])
     }
 
+    /// Captures the current state of this [`Instance`], which it can later
+    /// be reset to with [`Instance::restore`].
+    ///
+    /// The snapshot holds the contents and size of each memory and table the
+    /// instance defines, the value of each mutable global it defines, and
+    /// which of its passive element segments haven't been dropped yet.
+    /// Imported items belong to another instance or the host, and aren't
+    /// included.
+    ///
+    /// This is useful to pay the cost of initializing an instance only once,
+    /// and then restore it to that state after each use. Where supported, the
+    /// contents of memories are kept in a file which restoring maps back in
+    /// copy-on-write, so restoring only costs as much as the pages that were
+    /// touched since.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if this instance defines a shared memory or a mutable
+    /// global of a reference type, which can't be captured.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use wasmtime::*;
+    /// # fn main() -> anyhow::Result<()> {
+    /// # let store = Store::default();
+    /// let wat = r#"
+    ///     (module
+    ///         (global $count (mut i32) (i32.const 0))
+    ///         (func (export "next") (result i32)
+    ///             (global.set $count (i32.add (global.get $count) (i32.const 1)))
+    ///             (global.get $count))
+    ///     )
+    /// "#;
+    /// let module = Module::new(&store, wat)?;
+    /// let instance = Instance::new(&module, &[])?;
+    /// let next = instance.get_export("next").unwrap().func().unwrap().get0::<i32>()?;
+    ///
+    /// let snapshot = instance.snapshot()?;
+    /// assert_eq!(next()?, 1);
+    /// assert_eq!(next()?, 2);
+    /// instance.restore(&snapshot)?;
+    /// assert_eq!(next()?, 1);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Result<InstanceSnapshot> {
+        let snapshot = self.instance_handle.snapshot().map_err(Error::msg)?;
+        Ok(InstanceSnapshot { snapshot })
+    }
+
+    /// Restores this [`Instance`] to the state captured by
+    /// [`Instance::snapshot`].
+    ///
+    /// Memories and tables which have grown since the snapshot was taken are
+    /// shrunk back to their previous size.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `snapshot` was taken of a different instance, or
+    /// if a memory or table can't be resized. In the latter case the instance
+    /// may have been partially restored.
+    pub fn restore(&self, snapshot: &InstanceSnapshot) -> Result<()> {
+        self.instance_handle
+            .restore(&snapshot.snapshot)
+            .map_err(Error::msg)
+    }
+
     #[doc(hidden)]
     pub fn handle(&self) -> &InstanceHandle {
         &self.instance_handle
     }
 }
+
+/// The state of an [`Instance`] captured by [`Instance::snapshot`].
+///
+/// It can only be restored to the instance it was taken of.
+pub struct InstanceSnapshot {
+    snapshot: wasmtime_runtime::InstanceSnapshot,
+}
@@ -13,25 +13,37 @@ mod externals;
 mod frame_info;
 mod func;
 mod instance;
+mod limits;
+mod linker;
 mod module;
+mod module_graph;
 mod r#ref;
 mod runtime;
 mod trampoline;
 mod trap;
+mod typed_func;
 mod types;
 mod values;
 
 pub use crate::callable::Callable;
 pub use crate::externals::*;
 pub use crate::frame_info::FrameInfo;
-pub use crate::func::{Func, WasmRet, WasmTy};
-pub use crate::instance::Instance;
+pub use crate::func::{Caller, Func, IntoFunc, WasmResults, WasmRet, WasmTy};
+pub use crate::instance::{Instance, InstanceSnapshot};
+pub use crate::limits::{ResourceLimiter, StoreLimits, StoreLimitsBuilder};
+pub use crate::linker::Linker;
 pub use crate::module::Module;
+pub use crate::module_graph::ModuleGraph;
 pub use crate::r#ref::{AnyRef, HostInfo, HostRef};
-pub use crate::runtime::{Config, Engine, OptLevel, Store, Strategy};
-pub use crate::trap::Trap;
+pub use crate::runtime::{
+    Config, Engine, InstanceAllocationStrategy, InterruptHandle, OptLevel, PoolingLimits, Store,
+    Strategy,
+};
+pub use crate::trap::{Trap, TrapCode};
+pub use crate::typed_func::{TypedFunc, WasmParams};
 pub use crate::types::*;
 pub use crate::values::*;
+pub use wasmtime_environ::{ProducersField, ProducersValue};
 
 cfg_if::cfg_if! {
     if #[cfg(unix)] {
@@ -5,22 +5,29 @@ use crate::types::{
     TableType, ValType,
 };
 use anyhow::{bail, Error, Result};
+use std::ops::Range;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
 use wasmparser::{
     validate, CustomSectionKind, ExternalKind, ImportSectionEntryType, ModuleReader, Name,
     SectionCode,
 };
-use wasmtime_jit::CompiledModule;
+use wasmtime_environ::wasm::FuncIndex;
+use wasmtime_environ::ProducersField;
+use wasmtime_jit::{CompiledModule, SerializedModule};
 
-fn into_memory_type(mt: wasmparser::MemoryType) -> Result<MemoryType> {
-    if mt.shared {
-        bail!("shared memories are not supported yet");
+fn into_memory_type(mt: wasmparser::MemoryType, threads: bool) -> Result<MemoryType> {
+    let limits = Limits::new(mt.limits.initial, mt.limits.maximum);
+    if !mt.shared {
+        return Ok(MemoryType::new(limits));
     }
-    Ok(MemoryType::new(Limits::new(
-        mt.limits.initial,
-        mt.limits.maximum,
-    )))
+    if !threads {
+        bail!("shared memories are not supported unless the threads proposal is enabled");
+    }
+    if mt.limits.maximum.is_none() {
+        bail!("shared memory must have maximum");
+    }
+    Ok(MemoryType::shared(limits))
 }
 
 fn into_global_type(gt: wasmparser::GlobalType) -> GlobalType {
@@ -88,6 +95,7 @@ struct ModuleInner {
     imports: Box<[ImportType]>,
     exports: Box<[ExportType]>,
     compiled: CompiledModule,
+    custom_sections: Vec<(String, Box<[u8]>)>,
     frame_info_registration: Mutex<Option<Option<GlobalFrameInfoRegistration>>>,
     names: Arc<Names>,
 }
@@ -248,28 +256,107 @@ impl Module {
         validate(binary, Some(config)).map_err(Error::new)
     }
 
-    unsafe fn compile(store: &Store, binary: &[u8]) -> Result<Self> {
-        let compiled = CompiledModule::new(
+    /// Serializes this module, including its compiled machine code, into a
+    /// list of bytes which can later be turned back into a [`Module`] with
+    /// [`Module::deserialize`].
+    ///
+    /// This is useful to compile modules ahead of time, for example as part
+    /// of a build, and then load them elsewhere without compiling them again.
+    /// The output is only loadable by the same version of Wasmtime, on the
+    /// same target, with the same compilation settings in its [`Config`].
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if [`Config::module_serialization`] wasn't enabled
+    /// when this module was created.
+    ///
+    /// [`Config`]: crate::Config
+    /// [`Config::module_serialization`]: crate::Config::module_serialization
+    pub fn serialize(&self) -> Result<Vec<u8>> {
+        let store = self.store();
+        let config = store.engine().config();
+        match self.inner.compiled.serialize(
+            &store.compiler(),
+            config.debug_info,
+            &config.validating_config.operator_config,
+        ) {
+            Some(bytes) => Ok(bytes),
+            None => bail!("module serialization is not enabled in this module's `Config`"),
+        }
+    }
+
+    /// Deserializes a module previously produced by [`Module::serialize`],
+    /// without compiling its code again.
+    ///
+    /// The module is loaded into `store`. It must have been serialized by the
+    /// same version of Wasmtime using the same target and compilation settings
+    /// as the [`Engine`](crate::Engine) of `store`, otherwise an error is
+    /// returned.
+    ///
+    /// # Unsafety
+    ///
+    /// This function is `unsafe` because the machine code contained in
+    /// `bytes` is not validated in any way: it's assumed to be the unmodified
+    /// output of [`Module::serialize`]. Passing in arbitrary bytes may result
+    /// in arbitrary code being executed, so only data from trusted sources
+    /// should ever be deserialized.
+    pub unsafe fn deserialize(store: &Store, bytes: &[u8]) -> Result<Module> {
+        let config = store.engine().config();
+        let serialized = SerializedModule::from_bytes(
+            &store.compiler(),
+            bytes,
+            config.debug_info,
+            &config.validating_config.operator_config,
+        )?;
+        let wasm = serialized.wasm().to_vec();
+        let compiled = CompiledModule::deserialize(
             &mut store.compiler_mut(),
-            binary,
-            store.engine().config().debug_info,
-            store.engine().config().profiler.as_ref(),
+            serialized,
+            config.debug_info,
+            config.module_serialization,
+            config.profiler.as_ref(),
         )?;
+        let mut ret = Module::from_compiled(store, compiled);
+        ret.read_imports_and_exports(&wasm)?;
+        Ok(ret)
+    }
 
+    unsafe fn compile(store: &Store, binary: &[u8]) -> Result<Self> {
+        let config = store.engine().config();
+        let compiled = if config.module_serialization {
+            CompiledModule::new_serializable(
+                &mut store.compiler_mut(),
+                binary,
+                config.debug_info,
+                config.profiler.as_ref(),
+            )?
+        } else {
+            CompiledModule::new(
+                &mut store.compiler_mut(),
+                binary,
+                config.debug_info,
+                config.profiler.as_ref(),
+            )?
+        };
+        Ok(Module::from_compiled(store, compiled))
+    }
+
+    fn from_compiled(store: &Store, compiled: CompiledModule) -> Self {
         let names = Arc::new(Names {
             module_name: None,
             module: compiled.module().clone(),
         });
-        Ok(Module {
+        Module {
             inner: Arc::new(ModuleInner {
                 store: store.clone(),
                 imports: Box::new([]),
                 exports: Box::new([]),
                 names,
                 compiled,
+                custom_sections: Vec::new(),
                 frame_info_registration: Mutex::new(None),
             }),
-        })
+        }
     }
 
     pub(crate) fn compiled_module(&self) -> &CompiledModule {
@@ -294,6 +381,71 @@ impl Module {
         &self.inner.exports
     }
 
+    /// Returns the contents of the custom sections called `name` in this
+    /// [`Module`], in the order they appear in the binary.
+    ///
+    /// Custom sections are only kept if they were requested with
+    /// [`Config::retain_custom_section`](crate::Config::retain_custom_section)
+    /// before the module was created, otherwise nothing is returned.
+    pub fn custom_sections<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a [u8]> + 'a {
+        self.inner
+            .custom_sections
+            .iter()
+            .filter(move |(section, _)| section == name)
+            .map(|(_, data)| &data[..])
+    }
+
+    /// Returns the name the name section gives to the function at `index`,
+    /// if any.
+    ///
+    /// The index space includes imported functions, as in the `call`
+    /// instruction.
+    pub fn func_name(&self, index: u32) -> Option<&str> {
+        self.inner
+            .names
+            .module
+            .func_names
+            .get(&FuncIndex::from_u32(index))
+            .map(|name| name.as_str())
+    }
+
+    /// Returns the name the name section gives to local `local_index` of the
+    /// function at `func_index`, if any.
+    ///
+    /// Locals are indexed as in `local.get`, so parameters come first.
+    pub fn local_name(&self, func_index: u32, local_index: u32) -> Option<&str> {
+        self.inner
+            .names
+            .module
+            .local_names
+            .get(&FuncIndex::from_u32(func_index))?
+            .get(&local_index)
+            .map(|name| name.as_str())
+    }
+
+    /// Returns the fields of the `producers` section of this [`Module`],
+    /// which records the languages and tools used to produce it.
+    ///
+    /// An empty list is returned if the module has no `producers` section, or
+    /// if it's malformed, since invalid custom sections are ignored.
+    pub fn producers(&self) -> &[ProducersField] {
+        &self.inner.names.module.producers
+    }
+
+    /// Returns the function index and the byte range within the wasm binary
+    /// of the body of each function defined, rather than imported, by this
+    /// [`Module`].
+    ///
+    /// For modules created from the text format, offsets are into the binary
+    /// it was translated to.
+    pub fn func_body_ranges(&self) -> impl ExactSizeIterator<Item = (u32, Range<usize>)> + '_ {
+        let module = &self.inner.names.module;
+        module
+            .func_body_ranges
+            .iter()
+            .map(move |(index, range)| (module.local.func_index(index).as_u32(), range.clone()))
+    }
+
     /// Returns the [`Store`] that this [`Module`] was compiled into.
     pub fn store(&self) -> &Store {
         &self.inner.store
@@ -301,6 +453,9 @@ impl Module {
 
     fn read_imports_and_exports(&mut self, binary: &[u8]) -> Result<()> {
         let inner = Arc::get_mut(&mut self.inner).unwrap();
+        let engine = inner.store.engine().clone();
+        let config = engine.config();
+        let threads = config.validating_config.operator_config.enable_threads;
         let mut reader = ModuleReader::new(binary)?;
         let mut imports = Vec::new();
         let mut exports = Vec::new();
@@ -309,14 +464,21 @@ impl Module {
         let mut func_sig = Vec::new();
         let mut sigs = Vec::new();
         let mut globals = Vec::new();
+        let mut custom_sections = Vec::new();
         while !reader.eof() {
             let section = reader.read()?;
+            if let SectionCode::Custom { name, .. } = section.code {
+                if config.retained_custom_sections.contains(name) {
+                    let range = section.range();
+                    custom_sections.push((name.to_string(), binary[range.start..range.end].into()));
+                }
+            }
             match section.code {
                 SectionCode::Memory => {
                     let section = section.get_memory_section_reader()?;
                     memories.reserve_exact(section.get_count() as usize);
                     for entry in section {
-                        memories.push(into_memory_type(entry?)?);
+                        memories.push(into_memory_type(entry?, threads)?);
                     }
                 }
                 SectionCode::Type => {
@@ -364,7 +526,7 @@ impl Module {
                                 ExternType::Table(table)
                             }
                             ImportSectionEntryType::Memory(mt) => {
-                                let memory = into_memory_type(mt)?;
+                                let memory = into_memory_type(mt, threads)?;
                                 memories.push(memory.clone());
                                 ExternType::Memory(memory)
                             }
@@ -426,6 +588,7 @@ impl Module {
 
         inner.imports = imports.into();
         inner.exports = exports.into();
+        inner.custom_sections = custom_sections;
         Ok(())
     }
 
@@ -0,0 +1,356 @@
+use crate::{ExternType, Instance, Limits, Linker, Module, Store};
+use anyhow::{bail, Context as _, Result};
+use std::collections::HashMap;
+
+/// A graph of named modules whose imports are satisfied by each other's
+/// exports, instantiated all at once.
+///
+/// Each module added to a `ModuleGraph` is a node with a name. When a module
+/// imports from a module name which is also the name of a node in the graph,
+/// the import is satisfied by the export of the same name from that node's
+/// instance. Imports can also be directed to a node of a different name with
+/// [`ModuleGraph::link`]. All other imports are looked up in the [`Linker`]
+/// given to [`ModuleGraph::instantiate`], which is how host functions such as
+/// WASI are provided.
+///
+/// [`ModuleGraph::instantiate`] checks the whole graph before instantiating
+/// anything: every import satisfied by another node must name an export of
+/// that node with a matching [`ExternType`], and the graph must not contain a
+/// cycle. Modules are then instantiated so that each one comes after the
+/// modules it imports from.
+///
+/// # Examples
+///
+/// ```
+/// # use wasmtime::*;
+/// # fn main() -> anyhow::Result<()> {
+/// # let store = Store::default();
+/// let mut linker = Linker::new(&store);
+/// linker.define("host", "double", Func::wrap1(&store, |x: i32| x * 2))?;
+///
+/// let math = Module::new(
+///     &store,
+///     r#"
+///         (module
+///             (import "host" "double" (func $double (param i32) (result i32)))
+///             (func (export "quadruple") (param i32) (result i32)
+///                 (call $double (call $double (local.get 0))))
+///         )
+///     "#,
+/// )?;
+/// let plugin = Module::new(
+///     &store,
+///     r#"
+///         (module
+///             (import "math" "quadruple" (func $quadruple (param i32) (result i32)))
+///             (func (export "run") (result i32)
+///                 (call $quadruple (i32.const 10)))
+///         )
+///     "#,
+/// )?;
+///
+/// let mut graph = ModuleGraph::new(&store);
+/// graph.module("plugin", &plugin)?;
+/// graph.module("math", &math)?;
+/// let instances = graph.instantiate(&mut linker)?;
+///
+/// let run = instances["plugin"].get_export("run").unwrap().func().unwrap();
+/// assert_eq!(run.get0::<i32>()?()?, 40);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ModuleGraph {
+    store: Store,
+    nodes: Vec<Node>,
+    node_indices: HashMap<String, usize>,
+}
+
+struct Node {
+    name: String,
+    module: Module,
+    links: HashMap<String, usize>,
+    reexports: Vec<String>,
+}
+
+impl ModuleGraph {
+    /// Creates a new, empty [`ModuleGraph`] for modules in `store`.
+    pub fn new(store: &Store) -> ModuleGraph {
+        ModuleGraph {
+            store: store.clone(),
+            nodes: Vec::new(),
+            node_indices: HashMap::new(),
+        }
+    }
+
+    /// Adds `module` to this graph as a node called `name`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if a node called `name` already exists, or if
+    /// `module` comes from a different [`Store`] than this graph was created
+    /// with.
+    pub fn module(&mut self, name: &str, module: &Module) -> Result<&mut Self> {
+        if !Store::same(&self.store, module.store()) {
+            bail!("all modules in a module graph must be from the same store");
+        }
+        if self.node_indices.contains_key(name) {
+            bail!("module `{}` is defined twice in the module graph", name);
+        }
+        self.node_indices.insert(name.to_string(), self.nodes.len());
+        self.nodes.push(Node {
+            name: name.to_string(),
+            module: module.clone(),
+            links: HashMap::new(),
+            reexports: Vec::new(),
+        });
+        Ok(self)
+    }
+
+    /// Satisfies the imports of node `importer` from module `import_module`
+    /// with the exports of node `provider`.
+    ///
+    /// Without a link, imports are only satisfied by a node with the same
+    /// name as the module they import from.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `importer` or `provider` aren't nodes of this
+    /// graph, or if `import_module` was already linked for `importer`.
+    pub fn link(
+        &mut self,
+        importer: &str,
+        import_module: &str,
+        provider: &str,
+    ) -> Result<&mut Self> {
+        let importer = self.node_index(importer)?;
+        let provider = self.node_index(provider)?;
+        let node = &mut self.nodes[importer];
+        if node.links.contains_key(import_module) {
+            bail!(
+                "imports of `{}` from `{}` are linked twice",
+                node.name,
+                import_module
+            );
+        }
+        node.links.insert(import_module.to_string(), provider);
+        Ok(self)
+    }
+
+    /// Re-exports the instance of node `name` as `module_name` in the
+    /// [`Linker`] the graph is instantiated with, as if by
+    /// [`Linker::instance`].
+    ///
+    /// This makes the exports of the graph's instances available to modules
+    /// instantiated with the linker afterwards.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `name` isn't a node of this graph.
+    pub fn reexport(&mut self, name: &str, module_name: &str) -> Result<&mut Self> {
+        let index = self.node_index(name)?;
+        self.nodes[index].reexports.push(module_name.to_string());
+        Ok(self)
+    }
+
+    fn node_index(&self, name: &str) -> Result<usize> {
+        match self.node_indices.get(name) {
+            Some(index) => Ok(*index),
+            None => bail!("module `{}` is not defined in the module graph", name),
+        }
+    }
+
+    /// Returns the node providing the imports of node `index` from
+    /// `import_module`, if there is one.
+    fn provider(&self, index: usize, import_module: &str) -> Option<usize> {
+        self.nodes[index]
+            .links
+            .get(import_module)
+            .or_else(|| self.node_indices.get(import_module))
+            .cloned()
+    }
+
+    /// Instantiates every module of this graph, returning the instances by
+    /// node name.
+    ///
+    /// Imports which aren't satisfied by another node are looked up in
+    /// `linker`. Once every module is instantiated, the instances registered
+    /// with [`ModuleGraph::reexport`] are defined in `linker`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error without instantiating anything if:
+    ///
+    /// * an import satisfied by a node names an export that node doesn't
+    ///   have, or whose type doesn't match the import;
+    /// * the graph contains a cycle.
+    ///
+    /// Otherwise errors from resolving imports in `linker` and from
+    /// [`Instance::new`] are returned, with the name of the module being
+    /// instantiated. Instances created before the failure are dropped, but
+    /// any side effects of their `start` functions remain.
+    pub fn instantiate(&self, linker: &mut Linker) -> Result<HashMap<String, Instance>> {
+        if !Store::same(&self.store, linker.store()) {
+            bail!("the linker for a module graph must be from the same store");
+        }
+        self.type_check()?;
+        let order = self.instantiation_order()?;
+
+        let mut instances: Vec<Option<Instance>> = vec![None; self.nodes.len()];
+        for index in order {
+            let node = &self.nodes[index];
+            let mut imports = Vec::with_capacity(node.module.imports().len());
+            for import in node.module.imports() {
+                let item = match self.provider(index, import.module()) {
+                    Some(provider) => instances[provider]
+                        .as_ref()
+                        .expect("providers are instantiated first")
+                        .get_export(import.name())
+                        .expect("exports are type checked")
+                        .clone(),
+                    None => linker
+                        .resolve(import)
+                        .with_context(|| format!("failed to link module `{}`", node.name))?,
+                };
+                imports.push(item);
+            }
+            let instance = Instance::new(&node.module, &imports)
+                .with_context(|| format!("failed to instantiate module `{}`", node.name))?;
+            instances[index] = Some(instance);
+        }
+
+        let mut ret = HashMap::with_capacity(self.nodes.len());
+        for (node, instance) in self.nodes.iter().zip(instances) {
+            let instance = instance.expect("every module is instantiated");
+            for module_name in node.reexports.iter() {
+                linker.instance(module_name, &instance)?;
+            }
+            ret.insert(node.name.clone(), instance);
+        }
+        Ok(ret)
+    }
+
+    /// Checks that the imports satisfied by each edge of the graph match the
+    /// exports they're satisfied with.
+    fn type_check(&self) -> Result<()> {
+        for (index, node) in self.nodes.iter().enumerate() {
+            for import in node.module.imports() {
+                let provider = match self.provider(index, import.module()) {
+                    Some(provider) => &self.nodes[provider],
+                    None => continue,
+                };
+                let export = provider
+                    .module
+                    .exports()
+                    .iter()
+                    .find(|export| export.name() == import.name());
+                let export = match export {
+                    Some(export) => export,
+                    None => bail!(
+                        "module `{}` imports `{}::{}`, but module `{}` has no such export",
+                        node.name,
+                        import.module(),
+                        import.name(),
+                        provider.name,
+                    ),
+                };
+                if !extern_type_matches(import.ty(), export.ty()) {
+                    bail!(
+                        "incompatible import type for `{}::{}` in module `{}`\n\
+                         desired type was: {:?}\n\
+                         module `{}` exports: {:?}",
+                        import.module(),
+                        import.name(),
+                        node.name,
+                        import.ty(),
+                        provider.name,
+                        export.ty(),
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the node indices in an order where every node comes after the
+    /// nodes it imports from, or an error describing a cycle.
+    fn instantiation_order(&self) -> Result<Vec<usize>> {
+        #[derive(Clone, Copy, PartialEq)]
+        enum State {
+            Unvisited,
+            Visiting,
+            Done,
+        }
+
+        fn visit(
+            graph: &ModuleGraph,
+            index: usize,
+            states: &mut [State],
+            path: &mut Vec<usize>,
+            order: &mut Vec<usize>,
+        ) -> Result<()> {
+            match states[index] {
+                State::Done => return Ok(()),
+                State::Visiting => {
+                    let start = path.iter().position(|i| *i == index).unwrap();
+                    let cycle = path[start..]
+                        .iter()
+                        .chain(Some(&index))
+                        .map(|i| format!("`{}`", graph.nodes[*i].name))
+                        .collect::<Vec<_>>();
+                    bail!("module graph has a cycle: {}", cycle.join(" -> "));
+                }
+                State::Unvisited => {}
+            }
+            states[index] = State::Visiting;
+            path.push(index);
+            for import in graph.nodes[index].module.imports() {
+                if let Some(provider) = graph.provider(index, import.module()) {
+                    visit(graph, provider, states, path, order)?;
+                }
+            }
+            path.pop();
+            states[index] = State::Done;
+            order.push(index);
+            Ok(())
+        }
+
+        let mut states = vec![State::Unvisited; self.nodes.len()];
+        let mut path = Vec::new();
+        let mut order = Vec::with_capacity(self.nodes.len());
+        for index in 0..self.nodes.len() {
+            visit(self, index, &mut states, &mut path, &mut order)?;
+        }
+        Ok(order)
+    }
+
+    /// Returns the [`Store`] that this graph is connected to.
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+}
+
+/// Returns whether an export of type `actual` can satisfy an import of type
+/// `expected`.
+fn extern_type_matches(expected: &ExternType, actual: &ExternType) -> bool {
+    match (expected, actual) {
+        (ExternType::Func(expected), ExternType::Func(actual)) => expected == actual,
+        (ExternType::Global(expected), ExternType::Global(actual)) => expected == actual,
+        (ExternType::Table(expected), ExternType::Table(actual)) => {
+            expected.element() == actual.element()
+                && limits_match(expected.limits(), actual.limits())
+        }
+        (ExternType::Memory(expected), ExternType::Memory(actual)) => {
+            expected.is_shared() == actual.is_shared()
+                && limits_match(expected.limits(), actual.limits())
+        }
+        _ => false,
+    }
+}
+
+fn limits_match(expected: &Limits, actual: &Limits) -> bool {
+    actual.min() >= expected.min()
+        && match expected.max() {
+            Some(expected_max) => actual.max().map_or(false, |max| max <= expected_max),
+            None => true,
+        }
+}
@@ -1,14 +1,31 @@
-use anyhow::Result;
-use std::cell::RefCell;
+use crate::externals::MemoryCreator;
+use crate::trampoline::MemoryCreatorProxy;
+use crate::Trap;
+use anyhow::{anyhow, bail, Result};
+use std::any::Any;
+use std::cell::{Cell, Ref, RefCell, RefMut};
+use std::collections::HashSet;
+use std::convert::TryFrom;
 use std::fmt;
+use std::future::Future;
 use std::path::Path;
-use std::rc::Rc;
+use std::pin::Pin;
+use std::ptr;
+use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use target_lexicon::Triple;
 use wasmparser::{OperatorValidatorConfig, ValidatingParserConfig};
 use wasmtime_environ::settings::{self, Configurable};
-use wasmtime_environ::CacheConfig;
-use wasmtime_jit::{native, CompilationStrategy, Compiler};
+use wasmtime_environ::{CacheConfig, Tunables};
+use wasmtime_jit::{native, target_tunables, CompilationStrategy, Compiler};
 use wasmtime_profiling::{JitDumpAgent, ProfilingAgent, ProfilingStrategy};
+use wasmtime_runtime::{
+    Fiber, InstancePool, ResourceLimiter, RuntimeMemoryCreator, Suspend,
+    VMExternRefActivationsTable, VMInterrupts,
+};
+
+pub use wasmtime_runtime::PoolingLimits;
 
 // Runtime Environment
 
@@ -24,9 +41,17 @@ pub struct Config {
     pub(crate) flags: settings::Builder,
     pub(crate) validating_config: ValidatingParserConfig,
     pub(crate) debug_info: bool,
+    pub(crate) interruptable: bool,
+    pub(crate) consume_fuel: bool,
+    pub(crate) async_support: bool,
+    pub(crate) module_serialization: bool,
+    pub(crate) retained_custom_sections: HashSet<String>,
+    pub(crate) async_stack_size: usize,
     pub(crate) strategy: CompilationStrategy,
     pub(crate) cache_config: CacheConfig,
     pub(crate) profiler: Option<Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
+    pub(crate) memory_creator: Option<MemoryCreatorProxy>,
+    pub(crate) instance_pool: Option<InstancePool>,
 }
 
 impl Config {
@@ -53,6 +78,12 @@ impl Config {
 
         Config {
             debug_info: false,
+            interruptable: false,
+            consume_fuel: false,
+            async_support: false,
+            module_serialization: false,
+            retained_custom_sections: HashSet::new(),
+            async_stack_size: 2 << 20,
             validating_config: ValidatingParserConfig {
                 operator_config: OperatorValidatorConfig {
                     enable_threads: false,
@@ -66,6 +97,8 @@ impl Config {
             strategy: CompilationStrategy::Auto,
             cache_config: CacheConfig::new_cache_disabled(),
             profiler: None,
+            memory_creator: None,
+            instance_pool: None,
         }
     }
 
@@ -78,6 +111,108 @@ impl Config {
         self
     }
 
+    /// Configures whether functions and loops will be interruptable via the
+    /// [`Store::interrupt_handle`] method.
+    ///
+    /// For more information see the documentation on
+    /// [`Store::interrupt_handle`].
+    ///
+    /// Code generated by [`Strategy::Lightbeam`] can't be interrupted, so
+    /// modules fail to compile when this is combined with that strategy.
+    ///
+    /// By default this option is `false`.
+    pub fn interruptable(&mut self, enable: bool) -> &mut Self {
+        self.interruptable = enable;
+        self
+    }
+
+    /// Configures whether modules can be written out with
+    /// [`Module::serialize`](crate::Module::serialize).
+    ///
+    /// Serializable modules keep a copy of their wasm binary and of their
+    /// compiled code, relocations, and address maps for as long as they're
+    /// alive, which otherwise isn't needed after compilation.
+    ///
+    /// By default this option is `false`.
+    pub fn module_serialization(&mut self, enable: bool) -> &mut Self {
+        self.module_serialization = enable;
+        self
+    }
+
+    /// Configures modules to keep a copy of the custom sections called
+    /// `name`, which can then be read with
+    /// [`Module::custom_sections`](crate::Module::custom_sections).
+    ///
+    /// Custom sections such as DWARF debug info can be large, so only those
+    /// requested here are kept after compilation.
+    ///
+    /// By default no custom sections are kept.
+    pub fn retain_custom_section(&mut self, name: &str) -> &mut Self {
+        self.retained_custom_sections.insert(name.to_string());
+        self
+    }
+
+    /// Configures whether execution of WebAssembly will "consume fuel" and
+    /// halt once it has run out.
+    ///
+    /// This option is similar in purpose to [`Config::interruptable`] where
+    /// you can prevent infinitely-executing WebAssembly code. The difference
+    /// is that this option allows deterministic execution of WebAssembly code
+    /// by instrumenting generated code to consume fuel as it executes. When
+    /// fuel runs out the wasm traps.
+    ///
+    /// Every store starts out with no fuel, so when this option is enabled
+    /// fuel must be supplied with [`Store::add_fuel`] before any wasm can run.
+    /// The amount of fuel used so far can be read back with
+    /// [`Store::fuel_consumed`].
+    ///
+    /// Fuel is currently charged as one unit per wasm operator, with control
+    /// flow markers such as `block`, `end` and `nop` being free. This is
+    /// stable for a given module and configuration, but may change between
+    /// versions of wasmtime.
+    ///
+    /// By default this option is `false`.
+    pub fn consume_fuel(&mut self, enable: bool) -> &mut Self {
+        self.consume_fuel = enable;
+        self
+    }
+
+    /// Configures whether wasm may be run asynchronously, allowing host
+    /// functions defined with [`Func::new_async`](crate::Func::new_async) to
+    /// wait on futures.
+    ///
+    /// When enabled, wasm called with [`Func::call_async`](crate::Func::call_async)
+    /// runs on a separately allocated native stack. Whenever an async host
+    /// function's future isn't ready yet, execution of wasm is suspended and
+    /// the future returned by `call_async` yields back to its executor
+    /// without blocking the thread. Polling it again resumes wasm where it
+    /// left off.
+    ///
+    /// Async host functions can only be called from within `call_async`;
+    /// calling them from a synchronous call such as [`Func::call`](crate::Func::call)
+    /// traps instead.
+    ///
+    /// This is currently only supported on Unix platforms.
+    ///
+    /// By default this option is `false`.
+    pub fn async_support(&mut self, enable: bool) -> &mut Self {
+        self.async_support = enable;
+        self
+    }
+
+    /// Configures the size, in bytes, of the native stacks allocated to run
+    /// wasm on for [`Func::call_async`](crate::Func::call_async).
+    ///
+    /// All wasm, and any host functions it calls, run on this stack, so it
+    /// needs to be large enough for both. Overflowing it from wasm results in
+    /// a stack overflow trap.
+    ///
+    /// By default this is 2 MiB.
+    pub fn async_stack_size(&mut self, size: usize) -> &mut Self {
+        self.async_stack_size = size;
+        self
+    }
+
     /// Configures whether the WebAssembly threads proposal will be enabled for
     /// compilation.
     ///
@@ -90,6 +225,11 @@ impl Config {
     /// instructions. Note that enabling the threads feature will
     /// also enable the bulk memory feature.
     ///
+    /// Shared memories can be used from multiple threads through
+    /// [`SharedMemory`](crate::SharedMemory), and `memory.atomic.wait`
+    /// blocks the calling thread until another thread notifies it or the
+    /// timeout expires.
+    ///
     /// This is `false` by default.
     ///
     /// [threads]: https://github.com/webassembly/threads
@@ -115,6 +255,9 @@ impl Config {
     /// being in a module. Note that enabling the reference types feature will
     /// also enable the bulk memory feature.
     ///
+    /// Enabling this also makes compiled code record which stack slots hold
+    /// references at each call, so that [`Store::gc`] can find them.
+    ///
     /// This is `false` by default.
     ///
     /// [proposal]: https://github.com/webassembly/reference-types
@@ -122,6 +265,10 @@ impl Config {
         self.validating_config
             .operator_config
             .enable_reference_types = enable;
+        let val = if enable { "true" } else { "false" };
+        self.flags
+            .set("enable_safepoints", val)
+            .expect("should be valid flag");
         // The reference types proposal depends on the bulk memory proposal
         if enable {
             self.wasm_bulk_memory(true);
@@ -234,6 +381,58 @@ impl Config {
         Ok(self)
     }
 
+    /// Sets a custom memory creator.
+    ///
+    /// Custom memory creators are used for every linear memory created within
+    /// stores using this configuration, both host [`Memory`](crate::Memory)
+    /// objects and the memories defined by instantiated modules. See
+    /// [`MemoryCreator`] for the requirements placed on the memory it
+    /// returns.
+    pub fn with_host_memory(&mut self, mem_creator: Arc<dyn MemoryCreator>) -> &mut Self {
+        self.memory_creator = Some(MemoryCreatorProxy { mem_creator });
+        self
+    }
+
+    /// Configures how instances, along with the memories and tables they
+    /// define, are allocated.
+    ///
+    /// With [`InstanceAllocationStrategy::Pooling`] the space for a fixed
+    /// number of instances is reserved up front, when this is called, and
+    /// shared by every store using this configuration. Instantiating a module
+    /// only needs to claim a free slot of the pool, and the slot's memories
+    /// are reset for reuse when the instance is dropped, which makes
+    /// instantiation much cheaper when lots of short-lived instances are
+    /// created.
+    ///
+    /// Instantiation fails once the pool's limits are exceeded, for example
+    /// when all of its instance slots are in use or a module's memory is
+    /// larger than a memory slot. Memories can't grow beyond a slot either.
+    /// Memories, tables and functions created through the API, such as with
+    /// [`Memory::new`](crate::Memory::new), aren't allocated from the pool,
+    /// while the memories of instances are allocated from it even if a
+    /// custom memory creator has been set with [`Config::with_host_memory`].
+    ///
+    /// The default value for this is `InstanceAllocationStrategy::OnDemand`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the space for the pool couldn't be reserved.
+    pub fn allocation_strategy(
+        &mut self,
+        strategy: InstanceAllocationStrategy,
+    ) -> Result<&mut Self> {
+        self.instance_pool = match strategy {
+            InstanceAllocationStrategy::OnDemand => None,
+            InstanceAllocationStrategy::Pooling(limits) => {
+                let tunables = target_tunables(&Triple::host());
+                let pool = InstancePool::new(limits, &tunables)
+                    .map_err(|e| anyhow!("failed to create instance pool: {}", e))?;
+                Some(pool)
+            }
+        };
+        Ok(self)
+    }
+
     /// Configures whether the debug verifier of Cranelift is enabled or not.
     ///
     /// When Cranelift is used as a code generation backend this will configure
@@ -324,7 +523,17 @@ impl fmt::Debug for Config {
         let features = &self.validating_config.operator_config;
         f.debug_struct("Config")
             .field("debug_info", &self.debug_info)
+            .field("interruptable", &self.interruptable)
+            .field("consume_fuel", &self.consume_fuel)
+            .field("async_support", &self.async_support)
+            .field("module_serialization", &self.module_serialization)
+            .field("retained_custom_sections", &self.retained_custom_sections)
+            .field("async_stack_size", &self.async_stack_size)
             .field("strategy", &self.strategy)
+            .field(
+                "pooling_limits",
+                &self.instance_pool.as_ref().map(InstancePool::limits),
+            )
             .field("wasm_threads", &features.enable_threads)
             .field("wasm_reference_types", &features.enable_reference_types)
             .field("wasm_bulk_memory", &features.enable_bulk_memory)
@@ -367,6 +576,23 @@ pub enum Strategy {
     Lightbeam,
 }
 
+/// Possible allocation strategies for instances.
+///
+/// This is used as an argument to the [`Config::allocation_strategy`] method.
+#[non_exhaustive]
+#[derive(Clone, Debug)]
+pub enum InstanceAllocationStrategy {
+    /// Allocate each instance, along with its memories and tables, when it's
+    /// created and free it once it's dropped.
+    ///
+    /// This is the default.
+    OnDemand,
+
+    /// Allocate instances from a pool reserved up front, with the given
+    /// limits on the number and size of instances it holds.
+    Pooling(PoolingLimits),
+}
+
 /// Possible optimization levels for the Cranelift codegen backend.
 #[non_exhaustive]
 #[derive(Clone, Debug)]
@@ -423,6 +649,18 @@ impl Engine {
     pub fn config(&self) -> &Config {
         &self.config
     }
+
+    /// Returns the tunables used for code compiled for `triple` and for the
+    /// memories and tables created with this engine.
+    pub(crate) fn tunables(&self, triple: &Triple) -> Tunables {
+        let mut tunables = target_tunables(triple);
+        tunables.interruptable = self.config.interruptable;
+        tunables.consume_fuel = self.config.consume_fuel;
+        if let Some(pool) = &self.config.instance_pool {
+            pool.adjust_tunables(&mut tunables);
+        }
+        tunables
+    }
 }
 
 // Store
@@ -453,21 +691,100 @@ pub struct Store {
 struct StoreInner {
     engine: Engine,
     compiler: RefCell<Compiler>,
+    /// Total amount of fuel added to this store, which is used to translate
+    /// the counter in `VMInterrupts` into an amount of fuel consumed.
+    fuel_adj: Cell<i64>,
+    /// Limiter consulted when instances, memories and tables are created or
+    /// grown, if one was provided.
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+    instance_count: Cell<usize>,
+    memory_count: Cell<usize>,
+    table_count: Cell<usize>,
+    /// The fiber wasm is currently running on within `Func::call_async`, if
+    /// any, used by async host functions to suspend it.
+    current_suspend: Cell<*const Suspend>,
+    /// The task context of the `Func::call_async` future currently being
+    /// polled, if any.
+    current_poll_cx: Cell<*mut Context<'static>>,
+    /// Set while a dropped `Func::call_async` future unwinds its fiber, which
+    /// makes async host functions fail rather than suspend.
+    cancelling_fiber: Cell<bool>,
+    /// The `externref`s which wasm running within this store may be holding.
+    externref_activations_table: Rc<VMExternRefActivationsTable>,
+    /// Data attached by the embedder with `Store::set_data`.
+    data: RefCell<Option<Box<dyn Any>>>,
 }
 
 impl Store {
     /// Creates a new store to be associated with the given [`Engine`].
     pub fn new(engine: &Engine) -> Store {
+        Store::new_with_limiter(engine, None)
+    }
+
+    /// Creates a new store to be associated with the given [`Engine`], whose
+    /// resource usage is restricted by `limiter`.
+    ///
+    /// The limiter is consulted whenever a linear memory or table within this
+    /// store is created or grown, whether from wasm or from the host, and
+    /// bounds the number of instances, memories and tables instantiated into
+    /// this store.
+    ///
+    /// A [`StoreLimits`](crate::StoreLimits) built with a
+    /// [`StoreLimitsBuilder`](crate::StoreLimitsBuilder) covers the common
+    /// cases, and any other [`ResourceLimiter`] implementation may be used as
+    /// well.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use wasmtime::*;
+    /// # fn main() -> Result<()> {
+    /// let limits = StoreLimitsBuilder::new().memory_pages(1).build();
+    /// let store = Store::new_with_limits(&Engine::default(), limits);
+    ///
+    /// // A memory can't be created larger than the limit...
+    /// let module = Module::new(&store, "(module (memory 2))")?;
+    /// assert!(Instance::new(&module, &[]).is_err());
+    ///
+    /// // ...nor grow beyond it.
+    /// let module = Module::new(&store, "(module (memory (export \"m\") 1))")?;
+    /// let instance = Instance::new(&module, &[])?;
+    /// let memory = instance.get_export("m").unwrap().memory().unwrap();
+    /// assert!(memory.grow(1).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_with_limits(engine: &Engine, limiter: impl ResourceLimiter + 'static) -> Store {
+        Store::new_with_limiter(engine, Some(Rc::new(limiter)))
+    }
+
+    fn new_with_limiter(engine: &Engine, limiter: Option<Rc<dyn ResourceLimiter>>) -> Store {
         let isa = native::builder().finish(settings::Flags::new(engine.config.flags.clone()));
+        let tunables = engine.tunables(isa.triple());
         let compiler = Compiler::new(
             isa,
             engine.config.strategy,
             engine.config.cache_config.clone(),
+            tunables,
         );
+        let externref_activations_table = Rc::new(VMExternRefActivationsTable::new(
+            compiler.stack_map_registry().clone(),
+        ));
         Store {
             inner: Rc::new(StoreInner {
                 engine: engine.clone(),
                 compiler: RefCell::new(compiler),
+                fuel_adj: Cell::new(0),
+                limiter,
+                instance_count: Cell::new(0),
+                memory_count: Cell::new(0),
+                table_count: Cell::new(0),
+                current_suspend: Cell::new(ptr::null()),
+                current_poll_cx: Cell::new(ptr::null_mut()),
+                cancelling_fiber: Cell::new(false),
+                externref_activations_table,
+                data: RefCell::new(None),
             }),
         }
     }
@@ -485,6 +802,289 @@ impl Store {
         self.inner.compiler.borrow_mut()
     }
 
+    pub(crate) fn externref_activations_table(&self) -> &Rc<VMExternRefActivationsTable> {
+        &self.inner.externref_activations_table
+    }
+
+    /// Releases the `anyref` values which were passed to wasm within this
+    /// store and are no longer referenced from it.
+    ///
+    /// References held by wasm are tracked separately from the reference
+    /// counts of [`AnyRef`](crate::AnyRef)s, and are only released once a
+    /// collection finds that no wasm frame or global holds them anymore.
+    /// Collections happen automatically as references are passed to wasm, and
+    /// this method can be used to force one, for example to promptly drop host
+    /// values wasm is done with.
+    ///
+    /// Collections are skipped while any `Func::call_async` future of this
+    /// store is alive, since the stacks of suspended wasm can't be inspected.
+    pub fn gc(&self) {
+        self.inner.externref_activations_table.gc()
+    }
+
+    /// Runs `func` on a new fiber, returning a future which resolves to its
+    /// result once it has finished.
+    ///
+    /// Each poll of the future resumes the fiber, which runs until either
+    /// `func` finishes or an async host function waits on a pending future
+    /// with [`Store::block_on`].
+    ///
+    /// Dropping the future while the fiber is suspended resumes it one last
+    /// time with every async host function failing, so that `func` unwinds
+    /// and finishes before the fiber's stack is freed.
+    pub(crate) fn on_fiber<'a, R: 'a>(
+        &self,
+        func: impl FnOnce() -> R + 'a,
+    ) -> impl Future<Output = Result<R, Trap>> + 'a {
+        struct FiberFuture<'a, R> {
+            store: Store,
+            fiber: Option<Fiber<'a, R>>,
+            started: bool,
+            error: Option<Trap>,
+        }
+
+        impl<R> Future for FiberFuture<'_, R> {
+            type Output = Result<R, Trap>;
+
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+                if let Some(error) = self.error.take() {
+                    return Poll::Ready(Err(error));
+                }
+                let inner = self.store.inner.clone();
+                let prev = inner
+                    .current_poll_cx
+                    .replace(cx as *mut Context<'_> as *mut Context<'static>);
+                let _reset = Reset(&inner.current_poll_cx, prev);
+                // The fiber records itself as the current one whenever it
+                // runs, so put back whichever was current before once it
+                // suspends or finishes.
+                let _reset_suspend = Reset(&inner.current_suspend, inner.current_suspend.get());
+                self.started = true;
+                let fiber = self
+                    .fiber
+                    .as_mut()
+                    .expect("`call_async` future polled after completion");
+                match fiber.resume() {
+                    Some(result) => {
+                        self.fiber = None;
+                        self.store.externref_activations_table().resume_gc();
+                        Poll::Ready(Ok(result))
+                    }
+                    None => Poll::Pending,
+                }
+            }
+        }
+
+        impl<R> Drop for FiberFuture<'_, R> {
+            fn drop(&mut self) {
+                let mut fiber = match self.fiber.take() {
+                    Some(fiber) => fiber,
+                    None => return,
+                };
+                // The suspended wasm and host frames may be holding borrows
+                // and other resources, so rather than leaking its stack let
+                // the fiber run to completion, with the pending host
+                // function returning a trap.
+                if self.started && !fiber.done() {
+                    let inner = self.store.inner.clone();
+                    let _reset = Reset(
+                        &inner.cancelling_fiber,
+                        inner.cancelling_fiber.replace(true),
+                    );
+                    let _reset_suspend = Reset(&inner.current_suspend, inner.current_suspend.get());
+                    let finished = fiber.resume().is_some();
+                    debug_assert!(finished, "cancelled fiber suspended again");
+                }
+                drop(fiber);
+                self.store.externref_activations_table().resume_gc();
+            }
+        }
+
+        let store = self.clone();
+        let fiber = Fiber::new(self.engine().config().async_stack_size, move |suspend| {
+            store.inner.current_suspend.set(suspend);
+            func()
+        });
+        let (fiber, error) = match fiber {
+            // Wasm frames on the fiber's stack can't be found by collections,
+            // so defer them for as long as the fiber is alive.
+            Ok(fiber) => {
+                self.externref_activations_table().defer_gc();
+                (Some(fiber), None)
+            }
+            Err(e) => (
+                None,
+                Some(Trap::new(format!("failed to create fiber: {}", e))),
+            ),
+        };
+        FiberFuture {
+            store: self.clone(),
+            fiber,
+            started: false,
+            error,
+        }
+    }
+
+    /// Blocks the fiber wasm is running on until `future` is ready, returning
+    /// its output.
+    ///
+    /// While the future is pending the fiber is suspended, which causes the
+    /// `Func::call_async` future running it to return `Poll::Pending`. Fails
+    /// if wasm isn't running within `Func::call_async`, or if that future was
+    /// dropped before completing.
+    pub(crate) fn block_on<T>(
+        &self,
+        mut future: Pin<&mut dyn Future<Output = T>>,
+    ) -> Result<T, Trap> {
+        let suspend = self.inner.current_suspend.get();
+        if suspend.is_null() {
+            return Err(Trap::new(
+                "async host functions can only be called from within `Func::call_async`",
+            ));
+        }
+        loop {
+            if self.inner.cancelling_fiber.get() {
+                return Err(Trap::new(
+                    "`call_async` future was dropped before it completed",
+                ));
+            }
+            let cx = self.inner.current_poll_cx.get();
+            debug_assert!(!cx.is_null());
+            if let Poll::Ready(output) = future.as_mut().poll(unsafe { &mut *cx }) {
+                return Ok(output);
+            }
+            unsafe {
+                (*suspend).suspend();
+            }
+            self.inner.current_suspend.set(suspend);
+        }
+    }
+
+    pub(crate) fn memory_creator(&self) -> Option<&dyn RuntimeMemoryCreator> {
+        self.engine()
+            .config()
+            .memory_creator
+            .as_ref()
+            .map(|creator| creator as &dyn RuntimeMemoryCreator)
+    }
+
+    pub(crate) fn instance_pool(&self) -> Option<&InstancePool> {
+        self.engine().config().instance_pool.as_ref()
+    }
+
+    pub(crate) fn limiter(&self) -> Option<Rc<dyn ResourceLimiter>> {
+        self.inner.limiter.clone()
+    }
+
+    /// Records that an instance of `module` is about to be created in this
+    /// store, failing if that would exceed the limiter's instance, memory or
+    /// table counts.
+    pub(crate) fn bump_resource_counts(&self, module: &wasmtime_environ::Module) -> Result<()> {
+        fn check(slot: &Cell<usize>, max: usize, amt: usize, desc: &str) -> Result<usize> {
+            let new = slot.get().saturating_add(amt);
+            if new > max {
+                bail!(
+                    "resource limit exceeded: {} count too high at {}",
+                    desc,
+                    new
+                );
+            }
+            Ok(new)
+        }
+
+        let limiter = match &self.inner.limiter {
+            Some(limiter) => limiter,
+            None => return Ok(()),
+        };
+        let memories = module.local.memory_plans.len() - module.imported_memories.len();
+        let tables = module.local.table_plans.len() - module.imported_tables.len();
+
+        // Check every limit before recording anything so a failed
+        // instantiation doesn't count against the store.
+        let instance_count = check(
+            &self.inner.instance_count,
+            limiter.instances(),
+            1,
+            "instance",
+        )?;
+        let memory_count = check(
+            &self.inner.memory_count,
+            limiter.memories(),
+            memories,
+            "memory",
+        )?;
+        let table_count = check(&self.inner.table_count, limiter.tables(), tables, "table")?;
+        self.inner.instance_count.set(instance_count);
+        self.inner.memory_count.set(memory_count);
+        self.inner.table_count.set(table_count);
+
+        Ok(())
+    }
+
+    /// Undoes `bump_resource_counts` for an instance of `module` which failed
+    /// to be created.
+    pub(crate) fn release_resource_counts(&self, module: &wasmtime_environ::Module) {
+        if self.inner.limiter.is_none() {
+            return;
+        }
+        let memories = module.local.memory_plans.len() - module.imported_memories.len();
+        let tables = module.local.table_plans.len() - module.imported_tables.len();
+        let release = |slot: &Cell<usize>, amt: usize| slot.set(slot.get() - amt);
+        release(&self.inner.instance_count, 1);
+        release(&self.inner.memory_count, memories);
+        release(&self.inner.table_count, tables);
+    }
+
+    /// Attaches `data` to this store, replacing any data attached previously.
+    ///
+    /// This is typically used to hold state of the embedder which host
+    /// functions need, which they can reach through
+    /// [`Caller::store`](crate::Caller::store).
+    pub fn set_data<T: Any>(&self, data: T) {
+        // The previous data is dropped only once the new data is in place, in
+        // case its destructor uses the store.
+        let prev = self.inner.data.replace(Some(Box::new(data)));
+        drop(prev);
+    }
+
+    /// Returns the data attached to this store with [`Store::set_data`].
+    ///
+    /// Returns `None` if no data has been attached or if it isn't of type `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is currently borrowed mutably through
+    /// [`Store::data_mut`].
+    pub fn data<T: Any>(&self) -> Option<Ref<'_, T>> {
+        let data = self.inner.data.borrow();
+        if !data.as_ref().map_or(false, |data| data.is::<T>()) {
+            return None;
+        }
+        Some(Ref::map(data, |data| {
+            data.as_ref().unwrap().downcast_ref().unwrap()
+        }))
+    }
+
+    /// Returns the data attached to this store with [`Store::set_data`],
+    /// borrowed mutably.
+    ///
+    /// Returns `None` if no data has been attached or if it isn't of type `T`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the data is currently borrowed through [`Store::data`] or
+    /// [`Store::data_mut`], so borrows shouldn't be held while calling into
+    /// wasm, which may call host functions borrowing the data themselves.
+    pub fn data_mut<T: Any>(&self) -> Option<RefMut<'_, T>> {
+        let data = self.inner.data.borrow_mut();
+        if !data.as_ref().map_or(false, |data| data.is::<T>()) {
+            return None;
+        }
+        Some(RefMut::map(data, |data| {
+            data.as_mut().unwrap().downcast_mut().unwrap()
+        }))
+    }
+
     /// Returns whether the stores `a` and `b` refer to the same underlying
     /// `Store`.
     ///
@@ -494,6 +1094,173 @@ impl Store {
     pub fn same(a: &Store, b: &Store) -> bool {
         Rc::ptr_eq(&a.inner, &b.inner)
     }
+
+    /// Returns a reference to this store which doesn't keep it alive, for
+    /// state owned by the store itself, such as host functions, which would
+    /// otherwise form a reference cycle.
+    pub(crate) fn weak(&self) -> WeakStore {
+        WeakStore(Rc::downgrade(&self.inner))
+    }
+
+    /// Creates an [`InterruptHandle`] which can be used to interrupt the
+    /// execution of instances within this `Store`.
+    ///
+    /// An [`InterruptHandle`] handle is a mechanism of ensuring that guest code
+    /// doesn't execute for too long. For example it's used to prevent wasm
+    /// programs for executing infinitely in infinite loops or recursive call
+    /// chains.
+    ///
+    /// The [`InterruptHandle`] type is sendable to other threads so you can
+    /// interact with it even while the thread with this `Store` is executing
+    /// wasm code.
+    ///
+    /// There's one method on an interrupt handle:
+    /// [`InterruptHandle::interrupt`]. This method is used to generate an
+    /// interrupt and cause wasm code to exit "soon".
+    ///
+    /// ## When are interrupts delivered?
+    ///
+    /// The term "interrupt" here refers to one of two different behaviors that
+    /// are interrupted in wasm:
+    ///
+    /// * The head of every loop in wasm has a check to see if it's interrupted.
+    /// * The prologue of every function has a check to see if it's interrupted.
+    ///
+    /// This interrupt mechanism makes no attempt to signal interrupts to
+    /// native code. For example if a host function is blocked, then sending
+    /// an interrupt will not interrupt that operation.
+    ///
+    /// Interrupts are consumed as soon as possible when wasm itself starts
+    /// executing. This means that if you interrupt wasm code then it basically
+    /// guarantees that the next time wasm is executing on the target thread it
+    /// will return quickly (either normally if it were already in the process
+    /// of returning or with a trap from the interrupt). Once an interrupt
+    /// trap is generated then an interrupt is consumed, and further execution
+    /// will not be interrupted (unless another interrupt is set).
+    ///
+    /// When implementing interrupts you'll want to ensure that the delivery of
+    /// interrupts into wasm code is also handled in your host imports and
+    /// functionality. Host functions need to either execute for bounded
+    /// amounts of time or you'll need to arrange for them to be interrupted as
+    /// well.
+    ///
+    /// ## Return Value
+    ///
+    /// This function returns a `Result` since interrupts are not always
+    /// enabled. Interrupts are enabled via the [`Config::interruptable`]
+    /// method, and if this store's [`Config`] hasn't been configured to enable
+    /// interrupts then an error is returned.
+    ///
+    /// ## Examples
+    ///
+    /// ```
+    /// # use anyhow::Result;
+    /// # use wasmtime::*;
+    /// # fn main() -> Result<()> {
+    /// // Enable interruptable code via `Config` and then create an interrupt
+    /// // handle which we'll use later to interrupt running code.
+    /// let engine = Engine::new(Config::new().interruptable(true));
+    /// let store = Store::new(&engine);
+    /// let interrupt_handle = store.interrupt_handle()?;
+    ///
+    /// // Compile and instantiate a small example with an infinite loop.
+    /// let module = Module::new(&store, r#"
+    ///     (module (func (export "run") (loop br 0)))
+    /// "#)?;
+    /// let instance = Instance::new(&module, &[])?;
+    /// let run = instance
+    ///     .get_export("run")
+    ///     .and_then(|e| e.func())
+    ///     .ok_or(anyhow::format_err!("failed to find `run` function export"))?
+    ///     .get0::<()>()?;
+    ///
+    /// // Spin up a thread to send us an interrupt in a second
+    /// std::thread::spawn(move || {
+    ///     std::thread::sleep(std::time::Duration::from_secs(1));
+    ///     interrupt_handle.interrupt();
+    /// });
+    ///
+    /// let trap = run().unwrap_err();
+    /// assert!(trap.message().contains("wasm trap: interrupt"));
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn interrupt_handle(&self) -> Result<InterruptHandle> {
+        if self.engine().config.interruptable {
+            Ok(InterruptHandle {
+                interrupts: self.compiler().interrupts().clone(),
+            })
+        } else {
+            bail!("interrupts aren't enabled for this `Store`")
+        }
+    }
+
+    /// Returns the amount of fuel consumed by this store's execution so far.
+    ///
+    /// If fuel consumption is not enabled via [`Config::consume_fuel`] then
+    /// this function will return `None`. Also note that fuel, if enabled, must
+    /// be originally configured via [`Store::add_fuel`].
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        if !self.engine().config.consume_fuel {
+            return None;
+        }
+        let consumed = unsafe { *self.compiler().interrupts().fuel_consumed.get() };
+        Some(u64::try_from(self.inner.fuel_adj.get() + consumed).unwrap())
+    }
+
+    /// Adds fuel to this [`Store`] for wasm to consume while executing.
+    ///
+    /// For this method to work fuel consumption must be enabled via
+    /// [`Config::consume_fuel`]. By default a [`Store`] starts with 0 fuel
+    /// for wasm to execute with (meaning it will immediately trap).
+    /// This function must be called for the store to have
+    /// some fuel to allow WebAssembly to execute.
+    ///
+    /// Most WebAssembly instructions consume 1 unit of fuel. Some
+    /// instructions, such as `nop`, `drop`, `block`, and `loop`, consume 0
+    /// units, as any execution cost associated with them involves other
+    /// instructions which do consume fuel.
+    ///
+    /// Note that at this time when fuel is entirely consumed it will cause
+    /// wasm to trap. More usages of fuel are planned for the future.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if fuel consumption is not enabled
+    /// via [`Config::consume_fuel`].
+    pub fn add_fuel(&self, fuel: u64) -> Result<()> {
+        if !self.engine().config.consume_fuel {
+            bail!("fuel is not configured in this store");
+        }
+
+        // Fuel is stored as an i64, so we need to cast it. If the provided fuel
+        // value overflows that just assume that i64::max will suffice. Wasm
+        // execution isn't fast enough to burn through i64::max fuel in any
+        // reasonable amount of time anyway.
+        let fuel = i64::try_from(fuel).unwrap_or(i64::max_value());
+        let adj = self.inner.fuel_adj.get();
+        let compiler = self.compiler();
+        let consumed_ptr = unsafe { &mut *compiler.interrupts().fuel_consumed.get() };
+
+        match (consumed_ptr.checked_sub(fuel), adj.checked_add(fuel)) {
+            // If we successfully did arithmetic without overflowing then we can
+            // just update our fields.
+            (Some(consumed), Some(adj)) => {
+                self.inner.fuel_adj.set(adj);
+                *consumed_ptr = consumed;
+            }
+
+            // Otherwise something overflowed. Make sure that we preserve the
+            // amount of fuel that's already consumed, but otherwise assume that
+            // we were given infinite fuel.
+            _ => {
+                self.inner.fuel_adj.set(i64::max_value());
+                *consumed_ptr = (*consumed_ptr + adj) - i64::max_value();
+            }
+        }
+
+        Ok(())
+    }
 }
 
 impl Default for Store {
@@ -502,10 +1269,52 @@ impl Default for Store {
     }
 }
 
+/// A reference to a [`Store`] which doesn't keep it alive, created with
+/// [`Store::weak`].
+pub(crate) struct WeakStore(Weak<StoreInner>);
+
+impl WeakStore {
+    /// Returns the store, or `None` if it has already been dropped.
+    pub(crate) fn upgrade(&self) -> Option<Store> {
+        self.0.upgrade().map(|inner| Store { inner })
+    }
+}
+
+/// A threadsafe handle used to interrupt instances executing within a
+/// particular `Store`.
+///
+/// This structure is created by the [`Store::interrupt_handle`] method.
+pub struct InterruptHandle {
+    interrupts: Arc<VMInterrupts>,
+}
+
+impl InterruptHandle {
+    /// Flags that execution within this handle's original [`Store`] should be
+    /// interrupted.
+    ///
+    /// This will not immediately interrupt execution of wasm modules, but
+    /// rather it will interrupt wasm execution of loop headers and wasm
+    /// execution of function entries. For more information see
+    /// [`Store::interrupt_handle`].
+    pub fn interrupt(&self) {
+        self.interrupts.interrupt()
+    }
+}
+
 fn _assert_send_sync() {
     fn _assert<T: Send + Sync>() {}
     _assert::<Engine>();
     _assert::<Config>();
+    _assert::<InterruptHandle>();
+}
+
+/// Restores a `Cell` to a previous value when dropped.
+struct Reset<'a, T: Copy>(&'a Cell<T>, T);
+
+impl<T: Copy> Drop for Reset<'_, T> {
+    fn drop(&mut self) {
+        self.0.set(self.1);
+    }
 }
 
 #[cfg(test)]
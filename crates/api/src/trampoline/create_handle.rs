@@ -8,13 +8,31 @@ use std::sync::Arc;
 use wasmtime_environ::entity::PrimaryMap;
 use wasmtime_environ::wasm::DefinedFuncIndex;
 use wasmtime_environ::Module;
-use wasmtime_runtime::{Imports, InstanceHandle, VMFunctionBody};
+use wasmtime_runtime::{Imports, InstanceHandle, RuntimeMemoryCreator, VMFunctionBody};
 
 pub(crate) fn create_handle(
     module: Module,
     store: &Store,
     finished_functions: PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
     state: Box<dyn Any>,
+) -> Result<InstanceHandle> {
+    create_handle_with_memory_creator(
+        module,
+        store,
+        finished_functions,
+        state,
+        store.memory_creator(),
+    )
+}
+
+/// Like `create_handle`, but creating the instance's memories with
+/// `mem_creator` rather than the store's memory creator.
+pub(crate) fn create_handle_with_memory_creator(
+    module: Module,
+    store: &Store,
+    finished_functions: PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
+    state: Box<dyn Any>,
+    mem_creator: Option<&dyn RuntimeMemoryCreator>,
 ) -> Result<InstanceHandle> {
     let imports = Imports::new(
         HashSet::new(),
@@ -40,6 +58,7 @@ pub(crate) fn create_handle(
             finished_functions.into_boxed_slice(),
             imports,
             &data_initializers,
+            None,
             signatures.into_boxed_slice(),
             None,
             store
@@ -49,6 +68,11 @@ pub(crate) fn create_handle(
                 .operator_config
                 .enable_bulk_memory,
             state,
+            store.compiler().interrupts().clone(),
+            store.externref_activations_table().clone(),
+            store.limiter(),
+            mem_creator,
+            None,
         )?)
     }
 }
@@ -1,6 +1,7 @@
 //! Support for a calling of an imported function.
 
 use super::create_handle::create_handle;
+use crate::runtime::WeakStore;
 use crate::{Callable, FuncType, Store, Trap, Val};
 use anyhow::{bail, Result};
 use std::any::Any;
@@ -10,9 +11,10 @@ use std::rc::Rc;
 use wasmtime_environ::entity::{EntityRef, PrimaryMap};
 use wasmtime_environ::ir::types;
 use wasmtime_environ::isa::TargetIsa;
-use wasmtime_environ::wasm::{DefinedFuncIndex, FuncIndex};
+use wasmtime_environ::wasm::DefinedFuncIndex;
 use wasmtime_environ::{
-    ir, settings, CompiledFunction, CompiledFunctionUnwindInfo, Export, Module,
+    ir, settings, settings::Configurable, CompiledFunction, CompiledFunctionUnwindInfo, Export,
+    Module,
 };
 use wasmtime_jit::trampoline::ir::{
     ExternalName, Function, InstBuilder, MemFlags, StackSlotData, StackSlotKind,
@@ -25,13 +27,27 @@ use wasmtime_runtime::{InstanceHandle, VMContext, VMFunctionBody};
 
 struct TrampolineState {
     func: Rc<dyn Callable + 'static>,
+    ty: FuncType,
+    // Held weakly so the function doesn't keep its store alive by itself; the
+    // `Func` handles referring to it do.
+    store: WeakStore,
     #[allow(dead_code)]
     code_memory: CodeMemory,
 }
 
 impl TrampolineState {
-    fn new(func: Rc<dyn Callable + 'static>, code_memory: CodeMemory) -> Self {
-        TrampolineState { func, code_memory }
+    fn new(
+        func: Rc<dyn Callable + 'static>,
+        ty: FuncType,
+        store: WeakStore,
+        code_memory: CodeMemory,
+    ) -> Self {
+        TrampolineState {
+            func,
+            ty,
+            store,
+            code_memory,
+        }
     }
 }
 
@@ -74,43 +90,34 @@ unsafe extern "C" fn stub_fn(
 
     unsafe fn call_stub(
         vmctx: *mut VMContext,
-        call_id: u32,
+        _call_id: u32,
         values_vec: *mut i128,
     ) -> Result<(), Trap> {
         let instance = InstanceHandle::from_vmctx(vmctx);
-
-        let (args, returns_len) = {
-            let module = instance.module_ref();
-            let signature =
-                &module.local.signatures[module.local.functions[FuncIndex::new(call_id as usize)]];
-
-            let mut args = Vec::new();
-            for i in 2..signature.params.len() {
-                args.push(Val::read_value_from(
-                    values_vec.offset(i as isize - 2),
-                    signature.params[i].value_type,
-                ))
-            }
-            (args, signature.returns.len())
-        };
-
-        let mut returns = vec![Val::null(); returns_len];
         let state = &instance
             .host_state()
             .downcast_ref::<TrampolineState>()
             .expect("state");
+        let store = state
+            .store
+            .upgrade()
+            .expect("host function called after its store was dropped");
+
+        let mut args = Vec::new();
+        for (i, ty) in state.ty.params().iter().enumerate() {
+            args.push(Val::read_value_from(&store, values_vec.add(i), ty));
+        }
+
+        let mut returns = vec![Val::null(); state.ty.results().len()];
         state.func.call(&args, &mut returns)?;
 
-        let module = instance.module_ref();
-        let signature =
-            &module.local.signatures[module.local.functions[FuncIndex::new(call_id as usize)]];
-        for (i, ret) in returns.iter_mut().enumerate() {
-            if ret.ty().get_wasmtime_type() != Some(signature.returns[i].value_type) {
+        for (i, (ret, ty)) in returns.iter().zip(state.ty.results()).enumerate() {
+            if !ret.matches(ty) {
                 return Err(Trap::new(
                     "`Callable` attempted to return an incompatible value",
                 ));
             }
-            ret.write_value_to(values_vec.add(i));
+            ret.write_value_to(&store, values_vec.add(i));
         }
         Ok(())
     }
@@ -245,6 +252,92 @@ fn make_trampoline(
         .expect("allocate_for_function")
 }
 
+/// Create a trampoline with the wasm signature `signature` for invoking the
+/// raw function `func`, which takes a pointer to a values vector to store its
+/// results in as its last argument rather than returning them.
+fn make_retptr_trampoline(
+    isa: &dyn TargetIsa,
+    code_memory: &mut CodeMemory,
+    fn_builder_ctx: &mut FunctionBuilderContext,
+    func: *const VMFunctionBody,
+    signature: &ir::Signature,
+) -> *mut [VMFunctionBody] {
+    let pointer_type = isa.pointer_type();
+    let mut func_sig = ir::Signature::new(isa.frontend_config().default_call_conv);
+    func_sig.params.extend_from_slice(&signature.params);
+
+    // Add the `retptr` parameter.
+    func_sig.params.push(ir::AbiParam::new(pointer_type));
+
+    let value_size = 16;
+    let values_vec_len = (value_size * signature.returns.len()) as u32;
+
+    let mut context = Context::new();
+    context.func = Function::with_name_signature(ExternalName::user(0, 0), signature.clone());
+    context.func.collect_frame_layout_info();
+
+    let ss = context.func.create_stack_slot(StackSlotData::new(
+        StackSlotKind::ExplicitSlot,
+        values_vec_len,
+    ));
+
+    {
+        let mut builder = FunctionBuilder::new(&mut context.func, fn_builder_ctx);
+        let block0 = builder.create_block();
+
+        builder.append_block_params_for_function_params(block0);
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+
+        let retptr_val = builder.ins().stack_addr(pointer_type, ss, 0);
+        let mut callee_args = builder.func.dfg.block_params(block0).to_vec();
+        callee_args.push(retptr_val);
+
+        let new_sig = builder.import_signature(func_sig);
+        let callee_value = builder.ins().iconst(pointer_type, func as i64);
+        builder
+            .ins()
+            .call_indirect(new_sig, callee_value, &callee_args);
+
+        let mflags = MemFlags::trusted();
+        let mut results = Vec::new();
+        for (i, r) in signature.returns.iter().enumerate() {
+            let load =
+                builder
+                    .ins()
+                    .load(r.value_type, mflags, retptr_val, (i * value_size) as i32);
+            results.push(load);
+        }
+        builder.ins().return_(&results);
+        builder.finalize()
+    }
+
+    let mut code_buf: Vec<u8> = Vec::new();
+    let mut reloc_sink = binemit::TrampolineRelocSink {};
+    let mut trap_sink = binemit::NullTrapSink {};
+    let mut stackmap_sink = binemit::NullStackmapSink {};
+    context
+        .compile_and_emit(
+            isa,
+            &mut code_buf,
+            &mut reloc_sink,
+            &mut trap_sink,
+            &mut stackmap_sink,
+        )
+        .map_err(|error| pretty_error(&context.func, Some(isa), error))
+        .expect("compile_and_emit");
+
+    let unwind_info = CompiledFunctionUnwindInfo::new(isa, &context);
+
+    code_memory
+        .allocate_for_function(&CompiledFunction {
+            body: code_buf,
+            jt_offsets: context.func.jt_offsets,
+            unwind_info,
+        })
+        .expect("allocate_for_function")
+}
+
 pub fn create_handle_with_function(
     ft: &FuncType,
     func: &Rc<dyn Callable + 'static>,
@@ -252,7 +345,12 @@ pub fn create_handle_with_function(
 ) -> Result<InstanceHandle> {
     let isa = {
         let isa_builder = native::builder();
-        let flag_builder = settings::builder();
+        let mut flag_builder = settings::builder();
+        // Signatures may include reference types, which Cranelift only
+        // supports along with safepoints.
+        flag_builder
+            .set("enable_safepoints", "true")
+            .expect("should be valid flag");
         isa_builder.finish(settings::Flags::new(flag_builder))
     };
 
@@ -284,7 +382,8 @@ pub fn create_handle_with_function(
 
     finished_functions.push(trampoline);
 
-    let trampoline_state = TrampolineState::new(func.clone(), code_memory);
+    let trampoline_state =
+        TrampolineState::new(func.clone(), ft.clone(), store.weak(), code_memory);
 
     create_handle(
         module,
@@ -302,7 +401,10 @@ pub unsafe fn create_handle_with_raw_function(
 ) -> Result<InstanceHandle> {
     let isa = {
         let isa_builder = native::builder();
-        let flag_builder = settings::builder();
+        let mut flag_builder = settings::builder();
+        flag_builder
+            .set("enable_safepoints", "true")
+            .expect("should be valid flag");
         isa_builder.finish(settings::Flags::new(flag_builder))
     };
 
@@ -320,7 +422,24 @@ pub unsafe fn create_handle_with_raw_function(
     module
         .exports
         .insert("trampoline".to_string(), Export::Function(func_id));
-    finished_functions.push(func);
+
+    // Functions with multiple results store them through a pointer passed as
+    // their last argument, so wasm calls them through a trampoline which
+    // returns them instead. It's allocated in the store's code memory to keep
+    // it alive for as long as the function may be called, and shared by all
+    // the functions wrapping the same host function.
+    if sig.returns.len() > 1 {
+        let func = func as *const VMFunctionBody;
+        let trampoline =
+            store
+                .compiler_mut()
+                .get_host_trampoline(&sig, func, |code_memory, fn_builder_ctx| {
+                    make_retptr_trampoline(isa.as_ref(), code_memory, fn_builder_ctx, func, &sig)
+                });
+        finished_functions.push(trampoline);
+    } else {
+        finished_functions.push(func);
+    }
 
     create_handle(module, store, finished_functions, state)
 }
@@ -9,8 +9,8 @@ use wasmtime_runtime::InstanceHandle;
 pub fn create_global(store: &Store, gt: &GlobalType, val: Val) -> Result<InstanceHandle> {
     let global = wasm::Global {
         ty: match gt.content().get_wasmtime_type() {
-            Some(t) => t,
-            None => bail!("cannot support {:?} as a wasm global type", gt.content()),
+            Some(t) if !gt.content().is_ref() => t,
+            _ => bail!("cannot support {:?} as a wasm global type", gt.content()),
         },
         mutability: match gt.mutability() {
             Mutability::Const => false,
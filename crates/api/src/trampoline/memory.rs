@@ -1,10 +1,14 @@
-use super::create_handle::create_handle;
-use crate::MemoryType;
+use super::create_handle::{create_handle, create_handle_with_memory_creator};
+use crate::externals::{LinearMemory, MemoryCreator};
 use crate::Store;
+use crate::{Limits, MemoryType};
 use anyhow::Result;
+use std::sync::Arc;
 use wasmtime_environ::entity::PrimaryMap;
-use wasmtime_environ::{wasm, Module};
-use wasmtime_runtime::InstanceHandle;
+use wasmtime_environ::{wasm, MemoryPlan, MemoryStyle, Module, WASM_MAX_PAGES, WASM_PAGE_SIZE};
+use wasmtime_runtime::{
+    InstanceHandle, RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory, VMMemoryDefinition,
+};
 
 pub fn create_handle_with_memory(store: &Store, memory: &MemoryType) -> Result<InstanceHandle> {
     let mut module = Module::new();
@@ -12,7 +16,7 @@ pub fn create_handle_with_memory(store: &Store, memory: &MemoryType) -> Result<I
     let memory = wasm::Memory {
         minimum: memory.limits().min(),
         maximum: memory.limits().max(),
-        shared: false, // TODO
+        shared: memory.is_shared(),
     };
     let tunable = Default::default();
 
@@ -25,3 +29,95 @@ pub fn create_handle_with_memory(store: &Store, memory: &MemoryType) -> Result<I
 
     create_handle(module, store, PrimaryMap::new(), Box::new(()))
 }
+
+/// Creates an instance exporting `memory`, so that an existing shared memory
+/// can be used from `store`.
+pub fn create_handle_with_shared_memory(
+    store: &Store,
+    memory: &SharedMemory,
+) -> Result<InstanceHandle> {
+    let mut module = Module::new();
+
+    let memory_id = module.local.memory_plans.push(memory.plan().clone());
+    module.exports.insert(
+        "memory".to_string(),
+        wasmtime_environ::Export::Memory(memory_id),
+    );
+
+    let creator = ExistingSharedMemory(memory.clone());
+    create_handle_with_memory_creator(
+        module,
+        store,
+        PrimaryMap::new(),
+        Box::new(()),
+        Some(&creator),
+    )
+}
+
+/// Hands out an existing shared memory in place of creating a new one.
+struct ExistingSharedMemory(SharedMemory);
+
+impl RuntimeMemoryCreator for ExistingSharedMemory {
+    fn new_memory(&self, _plan: &MemoryPlan) -> Result<Box<dyn RuntimeLinearMemory>, String> {
+        Err("only the existing shared memory can be created".to_string())
+    }
+
+    fn new_shared_memory(&self, _plan: &MemoryPlan) -> Result<SharedMemory, String> {
+        Ok(self.0.clone())
+    }
+}
+
+struct LinearMemoryProxy {
+    mem: Box<dyn LinearMemory>,
+    maximum: Option<u32>,
+}
+
+impl RuntimeLinearMemory for LinearMemoryProxy {
+    fn size(&self) -> u32 {
+        self.mem.size()
+    }
+
+    fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
+    fn grow(&self, delta: u32) -> Option<u32> {
+        // Enforce the limits of the wasm memory here rather than trusting the
+        // embedder's implementation to do so.
+        let new_pages = self.mem.size().checked_add(delta)?;
+        if new_pages > self.maximum.unwrap_or(WASM_MAX_PAGES) {
+            return None;
+        }
+        self.mem.grow(delta)
+    }
+
+    fn vmmemory(&self) -> VMMemoryDefinition {
+        VMMemoryDefinition {
+            base: self.mem.as_ptr(),
+            current_length: self.mem.size() as usize * WASM_PAGE_SIZE as usize,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct MemoryCreatorProxy {
+    pub(crate) mem_creator: Arc<dyn MemoryCreator>,
+}
+
+impl RuntimeMemoryCreator for MemoryCreatorProxy {
+    fn new_memory(&self, plan: &MemoryPlan) -> Result<Box<dyn RuntimeLinearMemory>, String> {
+        let ty = MemoryType::new(Limits::new(plan.memory.minimum, plan.memory.maximum));
+        let reserved_size_in_bytes = match plan.style {
+            MemoryStyle::Static { bound } => Some(u64::from(bound) * u64::from(WASM_PAGE_SIZE)),
+            MemoryStyle::Dynamic => None,
+        };
+        self.mem_creator
+            .new_memory(ty, reserved_size_in_bytes, plan.offset_guard_size)
+            .map(|mem| {
+                Box::new(LinearMemoryProxy {
+                    mem,
+                    maximum: plan.memory.maximum,
+                }) as Box<dyn RuntimeLinearMemory>
+            })
+    }
+}
@@ -8,7 +8,8 @@ mod table;
 
 use self::func::create_handle_with_function;
 use self::global::create_global;
-use self::memory::create_handle_with_memory;
+pub(crate) use self::memory::MemoryCreatorProxy;
+use self::memory::{create_handle_with_memory, create_handle_with_shared_memory};
 use self::table::create_handle_with_table;
 use super::{Callable, FuncType, GlobalType, MemoryType, Store, TableType, Val};
 use anyhow::Result;
@@ -59,6 +60,15 @@ pub fn generate_memory_export(
     Ok((instance, export))
 }
 
+pub fn generate_shared_memory_export(
+    store: &Store,
+    m: &wasmtime_runtime::SharedMemory,
+) -> Result<(wasmtime_runtime::InstanceHandle, wasmtime_runtime::Export)> {
+    let instance = create_handle_with_shared_memory(store, m)?;
+    let export = instance.lookup("memory").expect("memory export");
+    Ok((instance, export))
+}
+
 pub fn generate_table_export(
     store: &Store,
     t: &TableType,
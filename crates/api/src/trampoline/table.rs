@@ -14,6 +14,7 @@ pub fn create_handle_with_table(store: &Store, table: &TableType) -> Result<Inst
         maximum: table.limits().max(),
         ty: match table.element() {
             ValType::FuncRef => wasm::TableElementType::Func,
+            ValType::AnyRef => wasm::TableElementType::Val(crate::types::reference_type()),
             _ => bail!("cannot support {:?} as a table element", table.element()),
         },
     };
@@ -3,6 +3,7 @@ use crate::FrameInfo;
 use backtrace::Backtrace;
 use std::fmt;
 use std::sync::Arc;
+use wasmtime_environ::ir;
 
 /// A struct representing an aborted instruction execution, with a message
 /// indicating the cause.
@@ -11,7 +12,116 @@ pub struct Trap {
     inner: Arc<TrapInner>,
 }
 
+/// State describing the occasion which evoked a trap.
+#[derive(Debug)]
+enum TrapReason {
+    /// A trap described only by its message.
+    Message,
+
+    /// An `i32` exit status describing an explicit program exit.
+    I32Exit(i32),
+
+    /// A structured error describing a trap.
+    Error(Box<dyn std::error::Error + Send + Sync>),
+
+    /// A specific code for a trap triggered while executing WASM.
+    InstructionTrap(TrapCode),
+}
+
+/// A trap code describing the reason for a trap.
+///
+/// All trap instructions have an explicit trap code.
+#[non_exhaustive]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum TrapCode {
+    /// The current stack space was exhausted.
+    StackOverflow,
+
+    /// An out-of-bounds memory access.
+    MemoryOutOfBounds,
+
+    /// A wasm atomic operation was presented with a misaligned address.
+    HeapMisaligned,
+
+    /// A wasm `atomic.wait` was executed on a memory which isn't shared.
+    AtomicWaitNonSharedMemory,
+
+    /// An out-of-bounds access to a table.
+    TableOutOfBounds,
+
+    /// Indirect call to a null table entry.
+    IndirectCallToNull,
+
+    /// Signature mismatch on indirect call.
+    BadSignature,
+
+    /// An integer arithmetic operation caused an overflow.
+    IntegerOverflow,
+
+    /// An integer division by zero.
+    IntegerDivisionByZero,
+
+    /// Failed float-to-int conversion.
+    BadConversionToInteger,
+
+    /// Code that was supposed to have been unreachable was reached.
+    UnreachableCodeReached,
+
+    /// Execution has potentially run too long and may be interrupted.
+    Interrupt,
+
+    /// Execution has consumed all of the fuel it was given.
+    OutOfFuel,
+}
+
+impl TrapCode {
+    /// Panics if `code` is `ir::TrapCode::User`.
+    fn from_non_user(code: ir::TrapCode) -> Self {
+        match code {
+            ir::TrapCode::StackOverflow => TrapCode::StackOverflow,
+            ir::TrapCode::HeapOutOfBounds | ir::TrapCode::OutOfBounds => {
+                TrapCode::MemoryOutOfBounds
+            }
+            ir::TrapCode::HeapMisaligned => TrapCode::HeapMisaligned,
+            ir::TrapCode::HeapUnshared => TrapCode::AtomicWaitNonSharedMemory,
+            ir::TrapCode::TableOutOfBounds => TrapCode::TableOutOfBounds,
+            ir::TrapCode::IndirectCallToNull => TrapCode::IndirectCallToNull,
+            ir::TrapCode::BadSignature => TrapCode::BadSignature,
+            ir::TrapCode::IntegerOverflow => TrapCode::IntegerOverflow,
+            ir::TrapCode::IntegerDivisionByZero => TrapCode::IntegerDivisionByZero,
+            ir::TrapCode::BadConversionToInteger => TrapCode::BadConversionToInteger,
+            ir::TrapCode::UnreachableCodeReached => TrapCode::UnreachableCodeReached,
+            ir::TrapCode::Interrupt => TrapCode::Interrupt,
+            ir::TrapCode::OutOfFuel => TrapCode::OutOfFuel,
+            ir::TrapCode::User(_) => panic!("Called `TrapCode::from_non_user` with user code"),
+        }
+    }
+}
+
+impl fmt::Display for TrapCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TrapCode::*;
+        let desc = match self {
+            StackOverflow => "call stack exhausted",
+            MemoryOutOfBounds => "out of bounds memory access",
+            HeapMisaligned => "unaligned atomic",
+            AtomicWaitNonSharedMemory => "expected shared memory",
+            TableOutOfBounds => "undefined element: out of bounds",
+            IndirectCallToNull => "uninitialized element",
+            BadSignature => "indirect call type mismatch",
+            IntegerOverflow => "integer overflow",
+            IntegerDivisionByZero => "integer divide by zero",
+            BadConversionToInteger => "invalid conversion to integer",
+            UnreachableCodeReached => "unreachable",
+            Interrupt => "interrupt",
+            OutOfFuel => "all fuel consumed by WebAssembly",
+        };
+        write!(f, "{}", desc)
+    }
+}
+
 struct TrapInner {
+    reason: TrapReason,
     message: String,
     wasm_trace: Vec<FrameInfo>,
     native_trace: Backtrace,
@@ -29,31 +139,41 @@ impl Trap {
     /// assert_eq!("unexpected error", trap.message());
     /// ```
     pub fn new<I: Into<String>>(message: I) -> Self {
-        Trap::new_with_trace(message.into(), Backtrace::new_unresolved())
+        let message = message.into();
+        Trap::new_with_trace(message, TrapReason::Message, Backtrace::new_unresolved())
+    }
+
+    /// Creates a new `Trap` representing an explicit program exit with a classic `i32`
+    /// exit status value.
+    ///
+    /// This is typically raised by host functions such as WASI's `proc_exit`
+    /// to unwind out of wasm without terminating the host process.
+    ///
+    /// # Example
+    /// ```
+    /// let trap = wasmtime::Trap::i32_exit(1);
+    /// assert_eq!(Some(1), trap.i32_exit_status());
+    /// ```
+    pub fn i32_exit(status: i32) -> Self {
+        let message = format!("Exited with i32 exit status {}", status);
+        let reason = TrapReason::I32Exit(status);
+        Trap::new_with_trace(message, reason, Backtrace::new_unresolved())
     }
 
     pub(crate) fn from_jit(jit: wasmtime_runtime::Trap) -> Self {
         match jit {
-            wasmtime_runtime::Trap::User(error) => {
-                // Since we're the only one using the wasmtime internals (in
-                // theory) we should only see user errors which were originally
-                // created from our own `Trap` type (see the trampoline module
-                // with functions).
-                //
-                // If this unwrap trips for someone we'll need to tweak the
-                // return type of this function to probably be `anyhow::Error`
-                // or something like that.
-                *error
-                    .downcast()
-                    .expect("only `Trap` user errors are supported")
-            }
+            wasmtime_runtime::Trap::User(error) => Trap::from(error),
             wasmtime_runtime::Trap::Wasm { desc, backtrace } => {
-                Trap::new_with_trace(desc.to_string(), backtrace)
+                let reason = match desc.trap_code {
+                    ir::TrapCode::User(_) => TrapReason::Message,
+                    code => TrapReason::InstructionTrap(TrapCode::from_non_user(code)),
+                };
+                Trap::new_with_trace(desc.to_string(), reason, backtrace)
             }
         }
     }
 
-    fn new_with_trace(message: String, native_trace: Backtrace) -> Self {
+    fn new_with_trace(message: String, reason: TrapReason, native_trace: Backtrace) -> Self {
         let mut wasm_trace = Vec::new();
         for frame in native_trace.frames() {
             let pc = frame.ip() as usize;
@@ -63,6 +183,7 @@ impl Trap {
         }
         Trap {
             inner: Arc::new(TrapInner {
+                reason,
                 message,
                 wasm_trace,
                 native_trace,
@@ -75,6 +196,25 @@ impl Trap {
         &self.inner.message
     }
 
+    /// If the trap was the result of an explicit program exit with a classic
+    /// `i32` exit status value, return the value, otherwise return `None`.
+    pub fn i32_exit_status(&self) -> Option<i32> {
+        match self.inner.reason {
+            TrapReason::I32Exit(status) => Some(status),
+            _ => None,
+        }
+    }
+
+    /// Returns the code of the wasm instruction which trapped, or `None` if
+    /// this trap wasn't raised by wasm code itself (for example it was created
+    /// by a host function).
+    pub fn trap_code(&self) -> Option<TrapCode> {
+        match self.inner.reason {
+            TrapReason::InstructionTrap(code) => Some(code),
+            _ => None,
+        }
+    }
+
     /// Returns a list of function frames in WebAssembly code that led to this
     /// trap happening.
     pub fn trace(&self) -> &[FrameInfo] {
@@ -86,6 +226,7 @@ impl fmt::Debug for Trap {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Trap")
             .field("message", &self.inner.message)
+            .field("reason", &self.inner.reason)
             .field("wasm_trace", &self.inner.wasm_trace)
             .field("native_trace", &self.inner.native_trace)
             .finish()
@@ -116,4 +257,33 @@ impl fmt::Display for Trap {
     }
 }
 
-impl std::error::Error for Trap {}
+impl std::error::Error for Trap {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match &self.inner.reason {
+            TrapReason::Error(e) => Some(&**e),
+            TrapReason::I32Exit(_) | TrapReason::Message | TrapReason::InstructionTrap(_) => None,
+        }
+    }
+}
+
+impl From<anyhow::Error> for Trap {
+    fn from(e: anyhow::Error) -> Trap {
+        match e.downcast::<Trap>() {
+            Ok(trap) => trap,
+            Err(e) => Box::<dyn std::error::Error + Send + Sync>::from(e).into(),
+        }
+    }
+}
+
+impl From<Box<dyn std::error::Error + Send + Sync>> for Trap {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Trap {
+        // If the top-level error is already a trap, don't be redundant and just return it.
+        if let Some(trap) = e.downcast_ref::<Trap>() {
+            trap.clone()
+        } else {
+            let message = e.to_string();
+            let reason = TrapReason::Error(e);
+            Trap::new_with_trace(message, reason, Backtrace::new_unresolved())
+        }
+    }
+}
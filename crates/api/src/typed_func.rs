@@ -0,0 +1,255 @@
+use crate::callable::call_trampoline;
+use crate::func::WasmResults;
+use crate::{AnyRef, Func, Store, Trap, Val, ValType};
+use anyhow::{ensure, Context as _, Result};
+use std::cmp;
+use std::marker;
+use std::mem;
+use std::ptr;
+use wasmtime_runtime::VMFunctionBody;
+
+/// The number of values which can be passed to or returned from a
+/// [`TypedFunc`] call without allocating.
+const INLINE_VALUES: usize = 8;
+
+/// A statically typed WebAssembly function, created with [`Func::typed`].
+///
+/// The signature of the function is checked once when the `TypedFunc` is
+/// created, so calls through it only convert the Rust values given to the
+/// representation wasm uses, without boxing them into [`Val`]s or allocating
+/// vectors of them like [`Func::call`] does.
+///
+/// `Params` is the type of the parameters, which is either a single value,
+/// `()` for no parameters, or a tuple of values. The supported parameter
+/// types are:
+///
+/// | Rust Parameter Type | WebAssembly Type |
+/// |---------------------|------------------|
+/// | `i32`               | `i32`            |
+/// | `i64`               | `i64`            |
+/// | `f32`               | `f32`            |
+/// | `f64`               | `f64`            |
+/// | `AnyRef`            | `anyref`         |
+/// | `Option<Func>`      | `funcref`        |
+///
+/// `Results` is the type of the results, which is one of the numeric types
+/// above, `()` for no results, or a tuple of them.
+pub struct TypedFunc<Params, Results> {
+    func: Func,
+    trampoline: *const VMFunctionBody,
+    values_vec_len: usize,
+    _marker: marker::PhantomData<fn(Params) -> Results>,
+}
+
+impl<Params, Results> Clone for TypedFunc<Params, Results> {
+    fn clone(&self) -> Self {
+        TypedFunc {
+            func: self.func.clone(),
+            trampoline: self.trampoline,
+            values_vec_len: self.values_vec_len,
+            _marker: marker::PhantomData,
+        }
+    }
+}
+
+impl<Params, Results> TypedFunc<Params, Results>
+where
+    Params: WasmParams,
+    Results: WasmResults,
+{
+    pub(crate) fn new(func: &Func) -> Result<Self> {
+        let mut params = func.ty().params().iter().cloned();
+        Params::matches(&mut params).context("Type mismatch in parameters")?;
+        ensure!(
+            params.next().is_none(),
+            "Type mismatch: too many parameters"
+        );
+
+        let mut results = func.ty().results().iter().cloned();
+        Results::matches(&mut results).context("Type mismatch in results")?;
+        ensure!(results.next().is_none(), "Type mismatch: too many results");
+
+        let signature = match func.wasmtime_export() {
+            wasmtime_runtime::Export::Function { signature, .. } => signature,
+            _ => panic!("expected function export"),
+        };
+        let trampoline = func
+            .store()
+            .compiler_mut()
+            .get_published_trampoline(signature, mem::size_of::<u128>())?;
+
+        Ok(TypedFunc {
+            func: func.clone(),
+            trampoline,
+            values_vec_len: cmp::max(func.param_arity(), func.result_arity()),
+            _marker: marker::PhantomData,
+        })
+    }
+
+    /// Returns the underlying [`Func`] that this is wrapping.
+    pub fn func(&self) -> &Func {
+        &self.func
+    }
+
+    /// Invokes this function with the `params` given, returning the results
+    /// or a trap, if one occurs.
+    pub fn call(&self, params: Params) -> Result<Results, Trap> {
+        let store = self.func.store();
+        let (vmctx, address) = match self.func.wasmtime_export() {
+            wasmtime_runtime::Export::Function { vmctx, address, .. } => (*vmctx, *address),
+            _ => panic!("expected function export"),
+        };
+
+        // Small numbers of values are passed on the stack rather than in a
+        // vector allocated for every call.
+        let mut inline = [0i128; INLINE_VALUES];
+        let mut heap = Vec::new();
+        let values_vec = if self.values_vec_len <= INLINE_VALUES {
+            &mut inline[..]
+        } else {
+            heap.resize(self.values_vec_len, 0);
+            &mut heap[..]
+        };
+
+        unsafe {
+            call_trampoline(
+                store,
+                vmctx,
+                self.trampoline,
+                address,
+                values_vec,
+                |values_vec| {
+                    params.store_to(store, values_vec.as_mut_ptr());
+                    Ok(())
+                },
+            )?;
+            Ok(Results::load_from(vmctx, values_vec.as_ptr()))
+        }
+    }
+}
+
+/// A trait implemented for types which can be passed as the parameters of a
+/// [`TypedFunc`].
+///
+/// This trait should not be implemented by user types. This trait may change at
+/// any time internally. The types which implement this trait, however, are
+/// stable over time.
+///
+/// For more information see [`TypedFunc`]
+pub trait WasmParams {
+    #[doc(hidden)]
+    fn push(dst: &mut Vec<ValType>);
+    #[doc(hidden)]
+    fn matches(tys: impl Iterator<Item = ValType>) -> Result<()>;
+    #[doc(hidden)]
+    unsafe fn store_to(self, store: &Store, slot: *mut i128) -> *mut i128;
+}
+
+impl WasmParams for () {
+    fn push(_dst: &mut Vec<ValType>) {}
+
+    fn matches(_tys: impl Iterator<Item = ValType>) -> Result<()> {
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn store_to(self, _store: &Store, slot: *mut i128) -> *mut i128 {
+        slot
+    }
+}
+
+macro_rules! params {
+    ($($t:ty => $valtype:ident,)*) => ($(
+        impl WasmParams for $t {
+            fn push(dst: &mut Vec<ValType>) {
+                dst.push(ValType::$valtype);
+            }
+
+            fn matches(mut tys: impl Iterator<Item = ValType>) -> Result<()> {
+                let next = tys.next();
+                ensure!(
+                    next == Some(ValType::$valtype),
+                    "Type mismatch, expected {}, got {:?}",
+                    stringify!($t),
+                    next
+                );
+                Ok(())
+            }
+
+            #[inline]
+            unsafe fn store_to(self, store: &Store, slot: *mut i128) -> *mut i128 {
+                Val::from(self).write_value_to(store, slot);
+                slot.add(1)
+            }
+        }
+    )*)
+}
+
+params! {
+    i32 => I32,
+    i64 => I64,
+    f32 => F32,
+    f64 => F64,
+    AnyRef => AnyRef,
+}
+
+impl WasmParams for Option<Func> {
+    fn push(dst: &mut Vec<ValType>) {
+        dst.push(ValType::FuncRef);
+    }
+
+    fn matches(mut tys: impl Iterator<Item = ValType>) -> Result<()> {
+        let next = tys.next();
+        ensure!(
+            next == Some(ValType::FuncRef),
+            "Type mismatch, expected funcref, got {:?}",
+            next
+        );
+        Ok(())
+    }
+
+    #[inline]
+    unsafe fn store_to(self, store: &Store, slot: *mut i128) -> *mut i128 {
+        match self {
+            Some(func) => Val::FuncRef(func).write_value_to(store, slot),
+            None => ptr::write(slot as *mut *mut u8, ptr::null_mut()),
+        }
+        slot.add(1)
+    }
+}
+
+macro_rules! tuples {
+    ($(($($t:ident),*))*) => ($(
+        #[allow(non_snake_case)]
+        impl<$($t: WasmParams,)*> WasmParams for ($($t,)*) {
+            fn push(dst: &mut Vec<ValType>) {
+                $($t::push(dst);)*
+            }
+
+            fn matches(mut tys: impl Iterator<Item = ValType>) -> Result<()> {
+                $($t::matches(&mut tys)?;)*
+                Ok(())
+            }
+
+            #[inline]
+            unsafe fn store_to(self, store: &Store, slot: *mut i128) -> *mut i128 {
+                let ($($t,)*) = self;
+                $(let slot = $t.store_to(store, slot);)*
+                slot
+            }
+        }
+    )*)
+}
+
+tuples! {
+    (A1)
+    (A1, A2)
+    (A1, A2, A3)
+    (A1, A2, A3, A4)
+    (A1, A2, A3, A4, A5)
+    (A1, A2, A3, A4, A5, A6)
+    (A1, A2, A3, A4, A5, A6, A7)
+    (A1, A2, A3, A4, A5, A6, A7, A8)
+    (A1, A2, A3, A4, A5, A6, A7, A8, A9)
+    (A1, A2, A3, A4, A5, A6, A7, A8, A9, A10)
+}
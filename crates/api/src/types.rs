@@ -5,7 +5,7 @@ use wasmtime_environ::{ir, wasm};
 // Type attributes
 
 /// Indicator of whether a global is mutable or not
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Mutability {
     /// The global is constant and its value does not change
     Const,
@@ -48,7 +48,7 @@ impl Limits {
 // Value Types
 
 /// A list of all possible value types in WebAssembly.
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum ValType {
     /// Signed 32 bit integer.
     I32,
@@ -91,7 +91,7 @@ impl ValType {
             ValType::F32 => Some(ir::types::F32),
             ValType::F64 => Some(ir::types::F64),
             ValType::V128 => Some(ir::types::I8X16),
-            _ => None,
+            ValType::AnyRef | ValType::FuncRef => Some(reference_type()),
         }
     }
 
@@ -102,11 +102,23 @@ impl ValType {
             ir::types::F32 => Some(ValType::F32),
             ir::types::F64 => Some(ValType::F64),
             ir::types::I8X16 => Some(ValType::V128),
+            // Both `anyref` and `funcref` lower to the same native type, so
+            // only the more general of the two can be recovered here.
+            ir::types::R32 | ir::types::R64 => Some(ValType::AnyRef),
             _ => None,
         }
     }
 }
 
+/// Returns the native type compiled code uses for reference values.
+pub(crate) fn reference_type() -> ir::Type {
+    if cfg!(target_pointer_width = "32") {
+        ir::types::R32
+    } else {
+        ir::types::R64
+    }
+}
+
 // External Types
 
 /// A list of all possible types which can be externally referenced from a
@@ -168,7 +180,7 @@ fn from_wasmtime_abiparam(param: &ir::AbiParam) -> Option<ValType> {
 /// A descriptor for a function in a WebAssembly module.
 ///
 /// WebAssembly functions can have 0 or more parameters and results.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct FuncType {
     params: Box<[ValType]>,
     results: Box<[ValType]>,
@@ -249,7 +261,7 @@ impl FuncType {
 /// This type describes an instance of a global in a WebAssembly module. Globals
 /// are local to an [`Instance`](crate::Instance) and are either immutable or
 /// mutable.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct GlobalType {
     content: ValType,
     mutability: Mutability,
@@ -319,12 +331,10 @@ impl TableType {
     }
 
     pub(crate) fn from_wasmtime_table(table: &wasm::Table) -> TableType {
-        assert!(if let wasm::TableElementType::Func = table.ty {
-            true
-        } else {
-            false
-        });
-        let ty = ValType::FuncRef;
+        let ty = match table.ty {
+            wasm::TableElementType::Func => ValType::FuncRef,
+            wasm::TableElementType::Val(_) => ValType::AnyRef,
+        };
         let limits = Limits::new(table.minimum, table.maximum);
         TableType::new(ty, limits)
     }
@@ -339,13 +349,29 @@ impl TableType {
 #[derive(Debug, Clone)]
 pub struct MemoryType {
     limits: Limits,
+    shared: bool,
 }
 
 impl MemoryType {
     /// Creates a new descriptor for a WebAssembly memory given the specified
     /// limits of the memory.
     pub fn new(limits: Limits) -> MemoryType {
-        MemoryType { limits }
+        MemoryType {
+            limits,
+            shared: false,
+        }
+    }
+
+    /// Creates a new descriptor for a shared WebAssembly memory, as
+    /// introduced by the threads proposal, given the specified limits of the
+    /// memory.
+    ///
+    /// Shared memories must declare a maximum size.
+    pub fn shared(limits: Limits) -> MemoryType {
+        MemoryType {
+            limits,
+            shared: true,
+        }
     }
 
     /// Returns the limits (in pages) that are configured for this memory.
@@ -353,8 +379,16 @@ impl MemoryType {
         &self.limits
     }
 
+    /// Returns whether this is a shared memory.
+    pub fn is_shared(&self) -> bool {
+        self.shared
+    }
+
     pub(crate) fn from_wasmtime_memory(memory: &wasm::Memory) -> MemoryType {
-        MemoryType::new(Limits::new(memory.minimum, memory.maximum))
+        MemoryType {
+            limits: Limits::new(memory.minimum, memory.maximum),
+            shared: memory.shared,
+        }
     }
 }
 
@@ -1,8 +1,10 @@
 use crate::r#ref::AnyRef;
+use crate::types::reference_type;
 use crate::{Func, Store, ValType};
 use anyhow::{bail, Result};
 use std::ptr;
-use wasmtime_environ::ir;
+use wasmtime_environ::wasm;
+use wasmtime_runtime::{TableElement, VMExternRef};
 
 /// Possible runtime values that a WebAssembly module can either consume or
 /// produce.
@@ -81,25 +83,46 @@ impl Val {
         }
     }
 
-    pub(crate) unsafe fn write_value_to(&self, p: *mut i128) {
+    pub(crate) unsafe fn write_value_to(&self, store: &Store, p: *mut i128) {
         match self {
             Val::I32(i) => ptr::write(p as *mut i32, *i),
             Val::I64(i) => ptr::write(p as *mut i64, *i),
             Val::F32(u) => ptr::write(p as *mut u32, *u),
             Val::F64(u) => ptr::write(p as *mut u64, *u),
             Val::V128(b) => ptr::write(p as *mut u128, *b),
-            _ => unimplemented!("Val::write_value_to"),
+            Val::AnyRef(AnyRef::Null) => ptr::write(p as *mut *mut u8, ptr::null_mut()),
+            Val::AnyRef(_) | Val::FuncRef(_) => {
+                let elem = into_table_element(store, &self.ty(), self.clone())
+                    .expect("value matches its own type");
+                ptr::write(
+                    p as *mut *mut u8,
+                    elem.into_raw(store.externref_activations_table()),
+                )
+            }
         }
     }
 
-    pub(crate) unsafe fn read_value_from(p: *const i128, ty: ir::Type) -> Val {
+    pub(crate) unsafe fn read_value_from(store: &Store, p: *const i128, ty: &ValType) -> Val {
         match ty {
-            ir::types::I32 => Val::I32(ptr::read(p as *const i32)),
-            ir::types::I64 => Val::I64(ptr::read(p as *const i64)),
-            ir::types::F32 => Val::F32(ptr::read(p as *const u32)),
-            ir::types::F64 => Val::F64(ptr::read(p as *const u64)),
-            ir::types::I8X16 => Val::V128(ptr::read(p as *const u128)),
-            _ => unimplemented!("Val::read_value_from"),
+            ValType::I32 => Val::I32(ptr::read(p as *const i32)),
+            ValType::I64 => Val::I64(ptr::read(p as *const i64)),
+            ValType::F32 => Val::F32(ptr::read(p as *const u32)),
+            ValType::F64 => Val::F64(ptr::read(p as *const u64)),
+            ValType::V128 => Val::V128(ptr::read(p as *const u128)),
+            ValType::AnyRef | ValType::FuncRef => {
+                let raw = ptr::read(p as *const *mut u8);
+                let elem = TableElement::from_raw(table_element_type(ty), raw);
+                from_table_element(store, elem)
+            }
+        }
+    }
+
+    /// Returns whether this value can be used where a value of type `ty` is
+    /// expected, which for a null reference is any reference type.
+    pub(crate) fn matches(&self, ty: &ValType) -> bool {
+        match self {
+            Val::AnyRef(AnyRef::Null) => ty.is_ref(),
+            _ => self.ty() == *ty,
         }
     }
 
@@ -201,6 +224,38 @@ pub(crate) fn into_checked_anyfunc(
     })
 }
 
+fn table_element_type(ty: &ValType) -> wasm::TableElementType {
+    match ty {
+        ValType::FuncRef => wasm::TableElementType::Func,
+        _ => wasm::TableElementType::Val(reference_type()),
+    }
+}
+
+/// Converts `val` into an element of a table whose elements are of type `ty`.
+pub(crate) fn into_table_element(store: &Store, ty: &ValType, val: Val) -> Result<TableElement> {
+    Ok(match (ty, val) {
+        (ValType::FuncRef, val) => TableElement::FuncRef(into_checked_anyfunc(val, store)?),
+        (ValType::AnyRef, Val::AnyRef(AnyRef::Null)) => TableElement::ExternRef(None),
+        (ValType::AnyRef, Val::AnyRef(r)) => {
+            TableElement::ExternRef(Some(VMExternRef::new(Box::new(r))))
+        }
+        (ty, val) => bail!("value of type {:?} is not a {:?}", val.ty(), ty),
+    })
+}
+
+/// Converts an element of a table back into a `Val`.
+pub(crate) fn from_table_element(store: &Store, elem: TableElement) -> Val {
+    match elem {
+        TableElement::FuncRef(anyfunc) => from_checked_anyfunc(anyfunc, store),
+        TableElement::ExternRef(None) => Val::AnyRef(AnyRef::Null),
+        TableElement::ExternRef(Some(r)) => Val::AnyRef(
+            r.downcast_ref::<AnyRef>()
+                .expect("externref created by the embedding API")
+                .clone(),
+        ),
+    }
+}
+
 pub(crate) fn from_checked_anyfunc(
     item: wasmtime_runtime::VMCallerCheckedAnyfunc,
     store: &Store,
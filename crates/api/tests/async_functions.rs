@@ -0,0 +1,291 @@
+#[cfg(not(target_os = "windows"))]
+mod common;
+
+#[cfg(not(target_os = "windows"))]
+mod not_for_windows {
+    use crate::common::store_with;
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::rc::Rc;
+    use std::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+    use wasmtime::*;
+
+    fn dummy_waker() -> Waker {
+        return unsafe { Waker::from_raw(clone(5 as *const _)) };
+
+        unsafe fn clone(ptr: *const ()) -> RawWaker {
+            assert_eq!(ptr as usize, 5);
+            const VTABLE: RawWakerVTable = RawWakerVTable::new(clone, wake, wake_by_ref, drop);
+            RawWaker::new(ptr, &VTABLE)
+        }
+
+        unsafe fn wake(ptr: *const ()) {
+            assert_eq!(ptr as usize, 5);
+        }
+
+        unsafe fn wake_by_ref(ptr: *const ()) {
+            assert_eq!(ptr as usize, 5);
+        }
+
+        unsafe fn drop(ptr: *const ()) {
+            assert_eq!(ptr as usize, 5);
+        }
+    }
+
+    /// Polls `future` to completion, returning its output along with how many
+    /// times it returned `Poll::Pending`.
+    fn run<F: Future>(future: F) -> (F::Output, usize) {
+        let mut future = Box::pin(future);
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut pending = 0;
+        loop {
+            match future.as_mut().poll(&mut cx) {
+                Poll::Ready(output) => return (output, pending),
+                Poll::Pending => pending += 1,
+            }
+        }
+    }
+
+    /// A future which is pending `n` times before becoming ready.
+    struct PendingN(usize);
+
+    impl Future for PendingN {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                Poll::Ready(())
+            } else {
+                self.0 -= 1;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn pending_host(store: &Store, n: usize, polls: Rc<Cell<usize>>) -> Func {
+        let ty = FuncType::new(Box::new([ValType::I32]), Box::new([ValType::I32]));
+        Func::new_async(store, ty, move |params, results| {
+            let polls = polls.clone();
+            Box::new(async move {
+                PendingN(n).await;
+                polls.set(polls.get() + 1);
+                results[0] = Val::I32(params[0].unwrap_i32() + 1);
+                Ok(())
+            })
+        })
+    }
+
+    #[test]
+    fn smoke() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let module = Module::new(
+            &store,
+            r#"(func (export "add") (param i32 i32) (result i32)
+                local.get 0
+                local.get 1
+                i32.add)"#,
+        )?;
+        let instance = Instance::new(&module, &[])?;
+        let add = instance.get_export("add").unwrap().func().unwrap().clone();
+        let (results, pending) = run(add.call_async(&[Val::I32(1), Val::I32(2)]));
+        assert_eq!(results?[0].unwrap_i32(), 3);
+        assert_eq!(pending, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn host_future_suspends_wasm() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let polls = Rc::new(Cell::new(0));
+        let host = pending_host(&store, 3, polls.clone());
+        let module = Module::new(
+            &store,
+            r#"
+                (module
+                    (import "" "" (func $host (param i32) (result i32)))
+                    (func (export "run") (param i32) (result i32)
+                        local.get 0
+                        call $host
+                        call $host)
+                )
+            "#,
+        )?;
+        let instance = Instance::new(&module, &[host.into()])?;
+        let run_func = instance.get_export("run").unwrap().func().unwrap().clone();
+        let (results, pending) = run(run_func.call_async(&[Val::I32(10)]));
+        assert_eq!(results?[0].unwrap_i32(), 12);
+        assert_eq!(pending, 6);
+        assert_eq!(polls.get(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn async_host_requires_call_async() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let host = pending_host(&store, 1, Rc::new(Cell::new(0)));
+        let trap = host.call(&[Val::I32(0)]).unwrap_err();
+        assert!(
+            trap.message()
+                .contains("only be called from within `Func::call_async`"),
+            "bad message: {}",
+            trap.message()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn traps_on_fiber() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let module = Module::new(
+            &store,
+            r#"
+                (module
+                    (func (export "unreachable") unreachable)
+                    (func $recurse (export "recurse") (call $recurse))
+                )
+            "#,
+        )?;
+        let instance = Instance::new(&module, &[])?;
+        let unreachable = instance.get_export("unreachable").unwrap().func().unwrap();
+        let trap = run(unreachable.call_async(&[])).0.unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::UnreachableCodeReached));
+
+        // Overflowing the fiber's stack is caught just like overflowing the
+        // main stack.
+        let recurse = instance.get_export("recurse").unwrap().func().unwrap();
+        let trap = run(recurse.call_async(&[])).0.unwrap_err();
+        assert_eq!(trap.trap_code(), Some(TrapCode::StackOverflow));
+
+        // Synchronous calls still work afterwards.
+        assert!(unreachable.call(&[]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn interleaved_calls() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let polls = Rc::new(Cell::new(0));
+        let host = pending_host(&store, 2, polls.clone());
+        let module = Module::new(
+            &store,
+            r#"
+                (module
+                    (import "" "" (func $host (param i32) (result i32)))
+                    (func (export "run") (param i32) (result i32)
+                        local.get 0
+                        call $host)
+                    (func (export "trap") unreachable)
+                )
+            "#,
+        )?;
+        let instance = Instance::new(&module, &[host.into()])?;
+        let run_func = instance.get_export("run").unwrap().func().unwrap().clone();
+        let trap_func = instance.get_export("trap").unwrap().func().unwrap().clone();
+
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut a = Box::pin(run_func.call_async(&[Val::I32(1)]));
+        let mut b = Box::pin(run_func.call_async(&[Val::I32(2)]));
+
+        // Both calls are suspended inside the host function at the same
+        // time, and traps in between them are still handled properly.
+        assert!(a.as_mut().poll(&mut cx).is_pending());
+        assert!(b.as_mut().poll(&mut cx).is_pending());
+        assert!(trap_func.call(&[]).is_err());
+        assert!(a.as_mut().poll(&mut cx).is_pending());
+        assert!(b.as_mut().poll(&mut cx).is_pending());
+
+        match b.as_mut().poll(&mut cx) {
+            Poll::Ready(results) => assert_eq!(results?[0].unwrap_i32(), 3),
+            Poll::Pending => panic!("expected call to be finished"),
+        }
+        match a.as_mut().poll(&mut cx) {
+            Poll::Ready(results) => assert_eq!(results?[0].unwrap_i32(), 2),
+            Poll::Pending => panic!("expected call to be finished"),
+        }
+        assert_eq!(polls.get(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn drop_pending_call() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let state = Rc::new(RefCell::new(0));
+        let finished = Rc::new(Cell::new(false));
+        let ty = FuncType::new(Box::new([]), Box::new([]));
+        let host = {
+            let (state, finished) = (state.clone(), finished.clone());
+            Func::new_async(&store, ty, move |_, _| {
+                let (state, finished) = (state.clone(), finished.clone());
+                Box::new(async move {
+                    // The borrow is held for as long as the call is pending.
+                    let mut state = state.borrow_mut();
+                    PendingN(1).await;
+                    *state += 1;
+                    finished.set(true);
+                    Ok(())
+                })
+            })
+        };
+        let module = Module::new(
+            &store,
+            r#"
+                (module
+                    (import "" "" (func $host))
+                    (func (export "run") call $host)
+                )
+            "#,
+        )?;
+        let instance = Instance::new(&module, &[host.into()])?;
+        let run_func = instance.get_export("run").unwrap().func().unwrap().clone();
+
+        let waker = dummy_waker();
+        let mut cx = Context::from_waker(&waker);
+        let mut call = Box::pin(run_func.call_async(&[]));
+        assert!(call.as_mut().poll(&mut cx).is_pending());
+        assert!(state.try_borrow_mut().is_err());
+        drop(call);
+
+        // The host future was dropped without finishing, along with its
+        // borrow, and the store can still be used.
+        assert!(state.try_borrow_mut().is_ok());
+        assert!(!finished.get());
+        let (result, pending) = run(run_func.call_async(&[]));
+        result?;
+        assert_eq!(pending, 1);
+        assert_eq!(*state.borrow(), 1);
+
+        // Calls which were never polled have nothing to unwind.
+        drop(run_func.call_async(&[]));
+        assert_eq!(*state.borrow(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn host_trap_and_panic() -> anyhow::Result<()> {
+        let store = store_with(|config| config.async_support(true));
+        let ty = FuncType::new(Box::new([]), Box::new([]));
+        let trap = Func::new_async(&store, ty.clone(), |_, _| {
+            Box::new(async {
+                PendingN(1).await;
+                Err(Trap::new("async trap"))
+            })
+        });
+        let (result, _) = run(trap.call_async(&[]));
+        assert_eq!(result.unwrap_err().message(), "async trap");
+
+        async fn panics() -> Result<(), Trap> {
+            PendingN(1).await;
+            panic!("async panic")
+        }
+        let panic = Func::new_async(&store, ty, |_, _| Box::new(panics()));
+        let err =
+            std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| run(panic.call_async(&[]))))
+                .unwrap_err();
+        assert_eq!(err.downcast_ref::<&'static str>(), Some(&"async panic"));
+        Ok(())
+    }
+}
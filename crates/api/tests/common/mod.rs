@@ -0,0 +1,10 @@
+//! Helpers shared by the integration tests.
+
+use wasmtime::{Config, Engine, Store};
+
+/// Creates a store whose engine uses the configuration set up by `configure`.
+pub fn store_with(configure: impl FnOnce(&mut Config) -> &mut Config) -> Store {
+    let mut config = Config::new();
+    configure(&mut config);
+    Store::new(&Engine::new(&config))
+}
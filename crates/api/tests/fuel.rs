@@ -0,0 +1,159 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+const SUM: &str = r#"
+    (module
+        (func (export "sum") (param i32) (result i32) (local i32)
+            block
+                loop
+                    local.get 0
+                    i32.eqz
+                    br_if 1
+                    local.get 1
+                    local.get 0
+                    i32.add
+                    local.set 1
+                    local.get 0
+                    i32.const 1
+                    i32.sub
+                    local.set 0
+                    br 0
+                end
+            end
+            local.get 1
+        )
+    )
+"#;
+
+/// Runs `sum(n)` in a fresh store with plenty of fuel, returning how much fuel
+/// it consumed.
+fn sum_consumption(n: i32) -> Result<u64> {
+    let store = store_with(|config| config.consume_fuel(true));
+    store.add_fuel(u64::max_value())?;
+    let module = Module::new(&store, SUM)?;
+    let instance = Instance::new(&module, &[])?;
+    let sum = instance
+        .get_export("sum")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+    assert_eq!(sum(n)?, n * (n + 1) / 2);
+    Ok(store.fuel_consumed().unwrap())
+}
+
+#[test]
+fn fuel_disabled_by_default() {
+    let store = Store::default();
+    assert!(store.add_fuel(1).is_err());
+    assert_eq!(store.fuel_consumed(), None);
+}
+
+#[test]
+fn no_fuel_traps() -> Result<()> {
+    let store = store_with(|config| config.consume_fuel(true));
+    assert_eq!(store.fuel_consumed(), Some(0));
+    let module = Module::new(&store, r#"(module (func (export "run")))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<()>()?;
+    let trap = run().unwrap_err();
+    assert!(
+        trap.message().contains("all fuel consumed"),
+        "bad message: {}",
+        trap.message()
+    );
+    Ok(())
+}
+
+#[test]
+fn consumption_is_deterministic() -> Result<()> {
+    // Each iteration of the loop costs 12 units, leaving the loop costs 3 and
+    // returning the result costs 1.
+    for &n in &[0, 1, 10, 100] {
+        let first = sum_consumption(n)?;
+        assert_eq!(first, 12 * n as u64 + 4);
+        for _ in 0..3 {
+            assert_eq!(sum_consumption(n)?, first);
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn exact_fuel_budget() -> Result<()> {
+    let store = store_with(|config| config.consume_fuel(true));
+    let module = Module::new(&store, SUM)?;
+    let instance = Instance::new(&module, &[])?;
+    let sum = instance
+        .get_export("sum")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+
+    store.add_fuel(124)?;
+    assert_eq!(sum(10)?, 55);
+    assert_eq!(store.fuel_consumed(), Some(124));
+
+    // Calling again consumes the same amount, and there's none left.
+    assert!(sum(10).is_err());
+    store.add_fuel(124)?;
+    assert_eq!(sum(10)?, 55);
+    assert_eq!(store.fuel_consumed(), Some(248));
+    Ok(())
+}
+
+#[test]
+fn infinite_loop_runs_out_of_fuel() -> Result<()> {
+    let store = store_with(|config| config.consume_fuel(true));
+    let module = Module::new(&store, r#"(module (func (export "run") (loop br 0)))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<()>()?;
+    store.add_fuel(10_000)?;
+    let trap = run().unwrap_err();
+    assert!(trap.message().contains("all fuel consumed"));
+    assert!(store.fuel_consumed().unwrap() >= 10_000);
+    Ok(())
+}
+
+#[test]
+fn calls_share_fuel() -> Result<()> {
+    let store = store_with(|config| config.consume_fuel(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (func $f (result i32) i32.const 1)
+                (func (export "run") (result i32)
+                    call $f
+                    call $f
+                    i32.add)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    store.add_fuel(1_000)?;
+    assert_eq!(run()?, 2);
+    // Two calls and an add in `run`, plus a constant in each call to `$f`.
+    assert_eq!(store.fuel_consumed(), Some(5));
+    Ok(())
+}
@@ -1,7 +1,12 @@
 use anyhow::Result;
 use std::rc::Rc;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
-use wasmtime::{Callable, Func, FuncType, Instance, Module, Store, Trap, Val, ValType};
+use wasmtime::{
+    Callable, Caller, Extern, Func, FuncType, Instance, Module, Store, Trap, Val, ValType,
+};
+
+mod common;
+use common::store_with;
 
 #[test]
 fn func_constructors() {
@@ -20,6 +25,10 @@ fn func_constructors() {
     Func::wrap0(&store, || -> Result<i64, Trap> { loop {} });
     Func::wrap0(&store, || -> Result<f32, Trap> { loop {} });
     Func::wrap0(&store, || -> Result<f64, Trap> { loop {} });
+
+    Func::wrap0(&store, || -> (i32, i64) { (0, 0) });
+    Func::wrap0(&store, || -> (f32, f64, i32) { (0.0, 0.0, 0) });
+    Func::wrap0(&store, || -> Result<(i32, i64), Trap> { loop {} });
 }
 
 #[test]
@@ -289,3 +298,218 @@ fn get_from_module() -> anyhow::Result<()> {
     assert!(f2.get1::<i32, f32>().is_err());
     Ok(())
 }
+
+#[test]
+fn wrap_multi_value() -> Result<()> {
+    let store = store_with(|config| config.wasm_multi_value(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "" (func $f (param i32) (result i32 i64 f32 f64)))
+                (func (export "run") (param i32) (result f64)
+                    (local f64)
+                    local.get 0
+                    call $f
+                    local.set 1
+                    f64.promote_f32
+                    local.get 1
+                    f64.add
+                    local.set 1
+                    f64.convert_i64_s
+                    local.get 1
+                    f64.add
+                    local.set 1
+                    f64.convert_i32_s
+                    local.get 1
+                    f64.add)
+            )
+        "#,
+    )?;
+    let f = Func::wrap1(&store, |x: i32| {
+        (x, x as i64 * 2, x as f32 * 3.0, x as f64 * 4.0)
+    });
+    assert_eq!(f.ty().results().len(), 4);
+    let instance = Instance::new(&module, &[f.clone().into()])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, f64>()?;
+    assert_eq!(run(1)?, 10.0);
+    assert_eq!(run(5)?, 50.0);
+
+    // The function can be called dynamically as well.
+    let results = f.call(&[Val::I32(2)])?;
+    assert_eq!(results[0].unwrap_i32(), 2);
+    assert_eq!(results[1].unwrap_i64(), 4);
+    assert_eq!(results[2].unwrap_f32(), 6.0);
+    assert_eq!(results[3].unwrap_f64(), 8.0);
+    Ok(())
+}
+
+#[test]
+fn wrap_multi_value_shared_trampoline() -> Result<()> {
+    let store = store_with(|config| config.wasm_multi_value(true));
+    // Both functions wrap the same host function, so they share a trampoline
+    // but still see their own state.
+    let wrap = |n: i32| Func::wrap0(&store, move || (n, n + 1));
+    let (f, g) = (wrap(1), wrap(10));
+    let results = f.call(&[])?;
+    assert_eq!(results[0].unwrap_i32(), 1);
+    assert_eq!(results[1].unwrap_i32(), 2);
+    let results = g.call(&[])?;
+    assert_eq!(results[0].unwrap_i32(), 10);
+    assert_eq!(results[1].unwrap_i32(), 11);
+    Ok(())
+}
+
+#[test]
+fn wrap_multi_value_trap() -> Result<()> {
+    let store = store_with(|config| config.wasm_multi_value(true));
+    let f = Func::wrap0(&store, || -> Result<(i32, i32), Trap> {
+        Err(Trap::new("multi"))
+    });
+    let err = f.call(&[]).unwrap_err();
+    assert_eq!(err.message(), "multi");
+
+    let f = Func::wrap1(&store, |x: i64| -> Result<(i64, i64), Trap> { Ok((x, -x)) });
+    let results = f.call(&[Val::I64(7)])?;
+    assert_eq!(results[0].unwrap_i64(), 7);
+    assert_eq!(results[1].unwrap_i64(), -7);
+    Ok(())
+}
+
+#[test]
+fn get_multi_value() -> Result<()> {
+    let store = store_with(|config| config.wasm_multi_value(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (func (export "swap") (param i32 i64) (result i64 i32)
+                    (local.get 1)
+                    (local.get 0))
+                (func (export "many") (result i32 i64 f32 f64 i32)
+                    (i32.const 1)
+                    (i64.const 2)
+                    (f32.const 3)
+                    (f64.const 4)
+                    (i32.const 5))
+                (func (export "trap") (result i32 i32)
+                    unreachable)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let swap = instance.get_export("swap").unwrap().func().unwrap();
+    assert!(swap.get2::<i32, i64, i64>().is_err());
+    assert!(swap.get2::<i32, i64, (i32, i64)>().is_err());
+    assert!(swap.get2::<i32, i64, (i64, i32, i32)>().is_err());
+    let swap = swap.get2::<i32, i64, (i64, i32)>()?;
+    assert_eq!(swap(1, 2)?, (2, 1));
+
+    let many = instance.get_export("many").unwrap().func().unwrap();
+    let many = many.get0::<(i32, i64, f32, f64, i32)>()?;
+    assert_eq!(many()?, (1, 2, 3.0, 4.0, 5));
+
+    let trap = instance.get_export("trap").unwrap().func().unwrap();
+    let trap = trap.get0::<(i32, i32)>()?;
+    assert!(trap().is_err());
+
+    // Host functions returning multiple values can be called through typed
+    // calls too.
+    let f = Func::wrap2(&store, |a: i32, b: i32| (b, a));
+    let f = f.get2::<i32, i32, (i32, i32)>()?;
+    assert_eq!(f(3, 4)?, (4, 3));
+    Ok(())
+}
+
+#[test]
+fn wrap_inferred_arity() -> Result<()> {
+    let store = Store::default();
+    let f = Func::wrap(&store, |a: i32, b: i64, c: f32| {
+        a as f64 + b as f64 + c as f64
+    });
+    assert_eq!(f.param_arity(), 3);
+    assert_eq!(f.get3::<i32, i64, f32, f64>()?(1, 2, 3.0)?, 6.0);
+    let f = Func::wrap(&store, || {});
+    assert_eq!(f.param_arity(), 0);
+    assert_eq!(f.result_arity(), 0);
+    Ok(())
+}
+
+#[test]
+fn caller_memory() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "sum" (func $sum (param i32 i32) (result i32)))
+                (memory (export "memory") 1)
+                (data (i32.const 8) "\01\02\03\04")
+                (func (export "run") (result i32)
+                    (call $sum (i32.const 8) (i32.const 4)))
+            )
+        "#,
+    )?;
+    let sum = Func::wrap(&store, |caller: Caller<'_>, ptr: i32, len: i32| {
+        let memory = match caller.get_export("memory") {
+            Some(Extern::Memory(memory)) => memory,
+            _ => return Err(Trap::new("missing memory")),
+        };
+        assert!(caller.get_export("missing").is_none());
+        assert!(caller.get_export("run").unwrap().func().is_some());
+        let bytes = unsafe { &memory.data_unchecked()[ptr as usize..][..len as usize] };
+        Ok(bytes.iter().map(|b| *b as i32).sum::<i32>())
+    });
+    let instance = Instance::new(&module, &[sum.clone().into()])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    assert_eq!(run()?, 10);
+
+    // Without a calling instance there are no exports to get.
+    let err = sum.call(&[Val::I32(0), Val::I32(0)]).unwrap_err();
+    assert_eq!(err.message(), "missing memory");
+    Ok(())
+}
+
+#[test]
+fn store_data() -> Result<()> {
+    let store = Store::default();
+    assert!(store.data::<u32>().is_none());
+    store.set_data(1u32);
+    assert!(store.data::<i32>().is_none());
+    assert_eq!(*store.data::<u32>().unwrap(), 1);
+
+    let increment = Func::wrap(&store, |caller: Caller<'_>, n: i32| {
+        *caller.store().data_mut::<u32>().unwrap() += n as u32;
+    });
+    increment.get1::<i32, ()>()?(2)?;
+    assert_eq!(*store.data::<u32>().unwrap(), 3);
+
+    store.set_data("replaced");
+    assert!(store.data::<u32>().is_none());
+    assert_eq!(*store.data::<&str>().unwrap(), "replaced");
+    Ok(())
+}
+
+#[test]
+fn caller_store_outlives_original_handle() -> Result<()> {
+    let store = Store::default();
+    store.set_data(1i32);
+    let get = Func::wrap(&store, |caller: Caller<'_>| -> i32 {
+        *caller.store().data::<i32>().unwrap()
+    });
+    // The function keeps its store alive, so it can still be reached through
+    // the caller once every other handle is gone.
+    drop(store);
+    assert_eq!(get.get0::<i32>()?()?, 1);
+    Ok(())
+}
@@ -0,0 +1,103 @@
+use anyhow::Result;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+struct SetFlagOnDrop(Rc<Cell<bool>>);
+
+impl Drop for SetFlagOnDrop {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+fn new_anyref(dropped: &Rc<Cell<bool>>) -> AnyRef {
+    AnyRef::new(Box::new(SetFlagOnDrop(dropped.clone())))
+}
+
+#[test]
+fn anyrefs_passed_to_wasm_are_collected() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true));
+    let wat = r#"
+        (module
+            (table $t (export "t") 1 anyref)
+            (func (export "id") (param anyref) (result anyref)
+                (local.get 0))
+            (func (export "set") (param anyref)
+                (table.set $t (i32.const 0) (local.get 0)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &[])?;
+    let id = instance.get_export("id").unwrap().func().unwrap().clone();
+    let set = instance.get_export("set").unwrap().func().unwrap().clone();
+
+    // Once the host drops its handles the value is only kept alive until the
+    // next collection.
+    let dropped = Rc::new(Cell::new(false));
+    id.call(&[new_anyref(&dropped).into()])?;
+    assert!(!dropped.get());
+    store.gc();
+    assert!(dropped.get());
+
+    // Values stored in a table survive collections.
+    let dropped = Rc::new(Cell::new(false));
+    set.call(&[new_anyref(&dropped).into()])?;
+    store.gc();
+    assert!(!dropped.get());
+    let table = instance.get_export("t").unwrap().table().unwrap();
+    table.set(0, Val::AnyRef(AnyRef::Null))?;
+    assert!(dropped.get());
+    Ok(())
+}
+
+#[test]
+fn anyrefs_on_the_stack_survive_collections() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true));
+    let wat = r#"
+        (module
+            (import "" "gc" (func $gc))
+            (global $g (mut anyref) (ref.null))
+            (func (export "run") (param anyref) (result anyref)
+                (call $gc)
+                (local.get 0))
+            (func (export "set-global") (param anyref)
+                (global.set $g (local.get 0)))
+            (func (export "clear-global")
+                (global.set $g (ref.null)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let gc = {
+        let store = store.clone();
+        Func::wrap0(&store.clone(), move || store.gc())
+    };
+    let instance = Instance::new(&module, &[gc.into()])?;
+    let func = |name: &str| instance.get_export(name).unwrap().func().unwrap().clone();
+
+    // The argument is only held by the wasm frame while `$gc` runs.
+    let dropped = Rc::new(Cell::new(false));
+    let result = func("run").call(&[new_anyref(&dropped).into()])?;
+    assert!(!dropped.get());
+    assert!(result[0]
+        .unwrap_anyref()
+        .data()
+        .downcast_ref::<SetFlagOnDrop>()
+        .is_some());
+    drop(result);
+    store.gc();
+    assert!(dropped.get());
+
+    // Values stored in a global survive collections until overwritten.
+    let dropped = Rc::new(Cell::new(false));
+    func("set-global").call(&[new_anyref(&dropped).into()])?;
+    store.gc();
+    assert!(!dropped.get());
+    func("clear-global").call(&[])?;
+    store.gc();
+    assert!(dropped.get());
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use anyhow::Result;
+use std::time::Duration;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+#[test]
+fn interrupts_disabled_by_default() {
+    let store = Store::default();
+    assert!(store.interrupt_handle().is_err());
+}
+
+#[test]
+fn loops_interruptable() -> Result<()> {
+    let store = store_with(|config| config.interruptable(true));
+    let module = Module::new(&store, r#"(func (export "loop") (loop br 0))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    let iloop = instance
+        .get_export("loop")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<()>()?;
+    store.interrupt_handle()?.interrupt();
+    let trap = iloop().unwrap_err();
+    assert!(trap.message().contains("wasm trap: interrupt"));
+    Ok(())
+}
+
+#[test]
+fn functions_interruptable() -> Result<()> {
+    let store = store_with(|config| config.interruptable(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "" (func $host))
+                (func (export "run") call $host)
+            )
+        "#,
+    )?;
+    let handle = store.interrupt_handle()?;
+    // The host function requests an interrupt which is then delivered by the
+    // entry check of the next wasm function to run.
+    let host = Func::wrap0(&store, move || handle.interrupt());
+    let instance = Instance::new(&module, &[host.into()])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<()>()?;
+    run()?;
+    let trap = run().unwrap_err();
+    assert!(trap.message().contains("wasm trap: interrupt"));
+    Ok(())
+}
+
+#[test]
+fn interrupt_is_consumed() -> Result<()> {
+    let store = store_with(|config| config.interruptable(true));
+    let module = Module::new(
+        &store,
+        r#"(func (export "run") (result i32) (loop) i32.const 1)"#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    store.interrupt_handle()?.interrupt();
+    assert!(run().is_err());
+    // Once delivered, the interrupt no longer affects later calls.
+    assert_eq!(run()?, 1);
+    Ok(())
+}
+
+#[test]
+fn loop_interrupt_from_afar() -> Result<()> {
+    let store = store_with(|config| config.interruptable(true));
+    let module = Module::new(&store, r#"(func (export "loop") (loop br 0))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    let iloop = instance
+        .get_export("loop")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<()>()?;
+
+    // Interrupt the infinite loop from another thread once it has had a
+    // chance to start running.
+    let handle = store.interrupt_handle()?;
+    let thread = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(100));
+        handle.interrupt();
+    });
+
+    let trap = iloop().unwrap_err();
+    thread.join().unwrap();
+    assert!(trap.message().contains("wasm trap: interrupt"));
+    Ok(())
+}
+
+#[test]
+#[cfg(feature = "lightbeam")]
+fn lightbeam_not_interruptable() -> Result<()> {
+    let store = store_with(|config| {
+        config
+            .interruptable(true)
+            .strategy(Strategy::Lightbeam)
+            .unwrap()
+    });
+    assert!(Module::new(&store, "(module (func (loop br 0)))").is_err());
+    Ok(())
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasmtime::*;
+
+#[test]
+fn test_limits_memory() -> Result<()> {
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new().memory_pages(10).build(),
+    );
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (export "m") 0)
+                (func (export "grow") (param i32) (result i32)
+                    local.get 0
+                    memory.grow)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let memory = instance.get_export("m").unwrap().memory().unwrap();
+    let grow = instance
+        .get_export("grow")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+
+    assert_eq!(memory.grow(3)?, 0);
+    assert_eq!(grow(5)?, 3);
+    assert_eq!(memory.size(), 8);
+
+    // Growing past the limit fails both from the host and from wasm, and
+    // leaves the memory untouched.
+    assert!(memory.grow(3).is_err());
+    assert_eq!(grow(3)?, -1);
+    assert_eq!(memory.size(), 8);
+
+    // Growing right up to the limit is fine.
+    assert_eq!(grow(2)?, 8);
+    assert_eq!(memory.size(), 10);
+    assert_eq!(grow(0)?, 10);
+    assert!(memory.grow(1).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_limits_memory_minimum() -> Result<()> {
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new().memory_pages(10).build(),
+    );
+
+    let module = Module::new(&store, "(module (memory 11))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("memory minimum size of 11 pages exceeds memory limits"),
+        "bad error: {}",
+        err
+    );
+
+    let module = Module::new(&store, "(module (memory 10))")?;
+    Instance::new(&module, &[])?;
+    Ok(())
+}
+
+#[test]
+fn test_limits_table() -> Result<()> {
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new().table_elements(5).build(),
+    );
+    let module = Module::new(&store, "(module (table (export \"t\") 0 anyfunc))")?;
+    let instance = Instance::new(&module, &[])?;
+    let table = instance.get_export("t").unwrap().table().unwrap();
+
+    assert_eq!(table.grow(2, Val::AnyRef(AnyRef::Null))?, 0);
+    assert_eq!(table.grow(3, Val::AnyRef(AnyRef::Null))?, 2);
+    assert!(table.grow(1, Val::AnyRef(AnyRef::Null)).is_err());
+    assert_eq!(table.size(), 5);
+
+    let module = Module::new(&store, "(module (table 6 anyfunc))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("table minimum size of 6 elements exceeds table limits"),
+        "bad error: {}",
+        err
+    );
+    Ok(())
+}
+
+#[test]
+fn test_limits_counts() -> Result<()> {
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new()
+            .instances(3)
+            .memories(2)
+            .tables(1)
+            .build(),
+    );
+    let memory = Module::new(&store, "(module (memory 0))")?;
+    let table = Module::new(&store, "(module (table 0 anyfunc))")?;
+
+    Instance::new(&memory, &[])?;
+    Instance::new(&table, &[])?;
+    let err = Instance::new(&table, &[]).err().unwrap();
+    assert!(
+        err.to_string().contains("table count too high"),
+        "bad error: {}",
+        err
+    );
+
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new().instances(2).memories(1).build(),
+    );
+    let empty = Module::new(&store, "(module)")?;
+    let memory = Module::new(&store, "(module (memory 0))")?;
+    Instance::new(&memory, &[])?;
+    let err = Instance::new(&memory, &[]).err().unwrap();
+    assert!(
+        err.to_string().contains("memory count too high"),
+        "bad error: {}",
+        err
+    );
+    Instance::new(&empty, &[])?;
+    let err = Instance::new(&empty, &[]).err().unwrap();
+    assert!(
+        err.to_string().contains("instance count too high"),
+        "bad error: {}",
+        err
+    );
+
+    // Instances which fail to be created don't count against the limits.
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        StoreLimitsBuilder::new().instances(1).memories(1).build(),
+    );
+    let trapping = Module::new(
+        &store,
+        r#"(module (memory 0) (func $start unreachable) (start $start))"#,
+    )?;
+    let memory = Module::new(&store, "(module (memory 0))")?;
+    for _ in 0..2 {
+        let err = Instance::new(&trapping, &[]).err().unwrap();
+        assert!(err.downcast_ref::<Trap>().is_some(), "bad error: {}", err);
+    }
+    Instance::new(&memory, &[])?;
+    Ok(())
+}
+
+struct CountingLimiter {
+    memory_requests: Rc<Cell<usize>>,
+}
+
+impl ResourceLimiter for CountingLimiter {
+    fn memory_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool {
+        self.memory_requests.set(self.memory_requests.get() + 1);
+        assert_eq!(maximum, Some(4));
+        // Only allow growing one page at a time.
+        desired <= current + 1
+    }
+
+    fn table_growing(&self, _current: u32, _desired: u32, _maximum: Option<u32>) -> bool {
+        true
+    }
+}
+
+#[test]
+fn test_custom_limiter() -> Result<()> {
+    let memory_requests = Rc::new(Cell::new(0));
+    let store = Store::new_with_limits(
+        &Engine::default(),
+        CountingLimiter {
+            memory_requests: memory_requests.clone(),
+        },
+    );
+    let module = Module::new(&store, "(module (memory (export \"m\") 0 4))")?;
+    let instance = Instance::new(&module, &[])?;
+    let memory = instance.get_export("m").unwrap().memory().unwrap();
+    assert_eq!(memory_requests.get(), 1);
+
+    assert_eq!(memory.grow(1)?, 0);
+    assert!(memory.grow(2).is_err());
+    assert_eq!(memory.grow(1)?, 1);
+    assert_eq!(memory_requests.get(), 4);
+
+    // The module's own maximum still applies when the limiter allows growth.
+    assert_eq!(memory.grow(1)?, 2);
+    assert_eq!(memory.grow(1)?, 3);
+    assert!(memory.grow(1).is_err());
+    assert_eq!(memory.size(), 4);
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn link_undefined() -> Result<()> {
+    let store = Store::default();
+    let linker = Linker::new(&store);
+    let module = Module::new(&store, r#"(module (import "" "" (func)))"#)?;
+    let err = linker.instantiate(&module).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("unknown import: `::` has not been defined"));
+    let module = Module::new(&store, r#"(module (import "" "" (global i32)))"#)?;
+    assert!(linker.instantiate(&module).is_err());
+    let module = Module::new(&store, r#"(module (import "" "" (memory 1)))"#)?;
+    assert!(linker.instantiate(&module).is_err());
+    let module = Module::new(&store, r#"(module (import "" "" (table 1 funcref)))"#)?;
+    assert!(linker.instantiate(&module).is_err());
+    Ok(())
+}
+
+#[test]
+fn link_mismatched_type() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+    linker.define("host", "f", Func::wrap1(&store, |x: i32| x))?;
+    let module = Module::new(
+        &store,
+        r#"(module (import "host" "f" (func (param i64) (result i64))))"#,
+    )?;
+    let err = linker.instantiate(&module).err().unwrap();
+    assert!(err
+        .to_string()
+        .contains("incompatible import type for `host::f` specified"));
+    Ok(())
+}
+
+#[test]
+fn link_twice_bad() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+
+    // functions
+    linker.define("", "", Func::wrap0(&store, || {}))?;
+    assert!(linker.define("", "", Func::wrap0(&store, || {})).is_err());
+    assert!(linker
+        .define("", "", Func::wrap0(&store, || -> i32 { 0 }))
+        .is_ok());
+
+    // globals
+    let ty = GlobalType::new(ValType::I32, Mutability::Const);
+    let global = Global::new(&store, ty, Val::I32(0))?;
+    linker.define("g", "1", global.clone())?;
+    assert!(linker.define("g", "1", global.clone()).is_err());
+
+    let ty = GlobalType::new(ValType::I32, Mutability::Var);
+    let global = Global::new(&store, ty, Val::I32(0))?;
+    linker.define("g", "2", global.clone())?;
+    assert!(linker.define("g", "2", global.clone()).is_err());
+
+    let ty = GlobalType::new(ValType::I64, Mutability::Const);
+    let global = Global::new(&store, ty, Val::I64(0))?;
+    linker.define("g", "3", global.clone())?;
+    assert!(linker.define("g", "3", global.clone()).is_err());
+
+    // memories
+    let ty = MemoryType::new(Limits::new(1, None));
+    let memory = Memory::new(&store, ty);
+    linker.define("m", "", memory.clone())?;
+    assert!(linker.define("m", "", memory.clone()).is_err());
+    let ty = MemoryType::new(Limits::new(2, None));
+    let memory = Memory::new(&store, ty);
+    assert!(linker.define("m", "", memory.clone()).is_err());
+
+    // tables
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let table = Table::new(&store, ty, Val::AnyRef(AnyRef::Null))?;
+    linker.define("t", "", table.clone())?;
+    assert!(linker.define("t", "", table.clone()).is_err());
+    let ty = TableType::new(ValType::FuncRef, Limits::new(2, None));
+    let table = Table::new(&store, ty, Val::AnyRef(AnyRef::Null))?;
+    assert!(linker.define("t", "", table.clone()).is_err());
+    Ok(())
+}
+
+#[test]
+fn link_shadowing() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+    linker.allow_shadowing(true);
+    linker.define("host", "f", Func::wrap0(&store, || 1i32))?;
+    linker.define("host", "f", Func::wrap0(&store, || 2i32))?;
+
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "host" "f" (func $f (result i32)))
+                (func (export "run") (result i32) call $f)
+            )
+        "#,
+    )?;
+    let instance = linker.instantiate(&module)?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    assert_eq!(run()?, 2);
+    Ok(())
+}
+
+#[test]
+fn link_instance() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+
+    let module = Module::new(
+        &store,
+        r#"(module (func (export "answer") (result i32) i32.const 42))"#,
+    )?;
+    let instance = linker.instantiate(&module)?;
+    linker.instance("first", &instance)?;
+    assert!(linker.instance("first", &instance).is_err());
+
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "first" "answer" (func $answer (result i32)))
+                (func (export "run") (result i32) call $answer)
+            )
+        "#,
+    )?;
+    let instance = linker.instantiate(&module)?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    assert_eq!(run()?, 42);
+    assert!(linker.get_one_by_name("first", "answer").is_ok());
+    assert!(linker.get_one_by_name("first", "missing").is_err());
+    Ok(())
+}
+
+#[test]
+fn link_from_other_store() -> Result<()> {
+    let store = Store::default();
+    let other = Store::default();
+    let mut linker = Linker::new(&store);
+    assert!(linker.define("", "", Func::wrap0(&other, || {})).is_err());
+    Ok(())
+}
@@ -0,0 +1,271 @@
+#[cfg(not(target_os = "windows"))]
+mod common;
+
+#[cfg(not(target_os = "windows"))]
+mod not_for_windows {
+    use crate::common::store_with;
+    use std::cell::RefCell;
+    use std::ptr::null_mut;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use wasmtime::*;
+
+    use libc::{c_void, mmap, mprotect, munmap};
+    use libc::{sysconf, _SC_PAGESIZE};
+    use libc::{MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_NONE, PROT_READ, PROT_WRITE};
+
+    const WASM_PAGE_SIZE: usize = 0x10000;
+
+    /// A memory which reserves its whole address range up front and never
+    /// moves, backed directly by `mmap`.
+    struct CustomMemory {
+        mem: *mut c_void,
+        size: usize,
+        guard_size: usize,
+        used_wasm_pages: RefCell<u32>,
+        glob_page_counter: Arc<AtomicUsize>,
+    }
+
+    impl CustomMemory {
+        unsafe fn new(
+            num_wasm_pages: u32,
+            max_wasm_pages: u32,
+            guard_size: usize,
+            glob_counter: Arc<AtomicUsize>,
+        ) -> Self {
+            let page_size = sysconf(_SC_PAGESIZE) as usize;
+            assert_eq!(guard_size % page_size, 0);
+            let size = max_wasm_pages as usize * WASM_PAGE_SIZE + guard_size;
+            let used_size = num_wasm_pages as usize * WASM_PAGE_SIZE;
+            assert_eq!(size % page_size, 0);
+
+            let mem = mmap(null_mut(), size, PROT_NONE, MAP_PRIVATE | MAP_ANON, -1, 0);
+            assert_ne!(
+                mem,
+                MAP_FAILED,
+                "mmap failed: {}",
+                std::io::Error::last_os_error()
+            );
+
+            let r = mprotect(mem, used_size, PROT_READ | PROT_WRITE);
+            assert_eq!(r, 0, "mprotect failed: {}", std::io::Error::last_os_error());
+            glob_counter.fetch_add(num_wasm_pages as usize, Ordering::SeqCst);
+
+            Self {
+                mem,
+                size,
+                guard_size,
+                used_wasm_pages: RefCell::new(num_wasm_pages),
+                glob_page_counter: glob_counter,
+            }
+        }
+    }
+
+    impl Drop for CustomMemory {
+        fn drop(&mut self) {
+            let n = *self.used_wasm_pages.borrow() as usize;
+            self.glob_page_counter.fetch_sub(n, Ordering::SeqCst);
+            let r = unsafe { munmap(self.mem, self.size) };
+            assert_eq!(r, 0, "munmap failed: {}", std::io::Error::last_os_error());
+        }
+    }
+
+    unsafe impl LinearMemory for CustomMemory {
+        fn size(&self) -> u32 {
+            *self.used_wasm_pages.borrow()
+        }
+
+        fn grow(&self, delta: u32) -> Option<u32> {
+            let delta_size = (delta as usize).checked_mul(WASM_PAGE_SIZE)?;
+
+            let prev_pages = *self.used_wasm_pages.borrow();
+            let prev_size = (prev_pages as usize).checked_mul(WASM_PAGE_SIZE)?;
+
+            let new_pages = prev_pages.checked_add(delta)?;
+            let new_size = (new_pages as usize).checked_mul(WASM_PAGE_SIZE)?;
+
+            if new_size > self.size - self.guard_size {
+                return None;
+            }
+            unsafe {
+                let start = (self.mem as *mut u8).add(prev_size) as _;
+                let r = mprotect(start, delta_size, PROT_READ | PROT_WRITE);
+                assert_eq!(r, 0, "mprotect failed: {}", std::io::Error::last_os_error());
+            }
+
+            self.glob_page_counter
+                .fetch_add(delta as usize, Ordering::SeqCst);
+            *self.used_wasm_pages.borrow_mut() = new_pages;
+            Some(prev_pages)
+        }
+
+        fn as_ptr(&self) -> *mut u8 {
+            self.mem as *mut u8
+        }
+    }
+
+    struct CustomMemoryCreator {
+        num_created_memories: AtomicUsize,
+        num_total_pages: Arc<AtomicUsize>,
+    }
+
+    impl CustomMemoryCreator {
+        fn new() -> Self {
+            Self {
+                num_created_memories: AtomicUsize::new(0),
+                num_total_pages: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    unsafe impl MemoryCreator for CustomMemoryCreator {
+        fn new_memory(
+            &self,
+            ty: MemoryType,
+            reserved_size: Option<u64>,
+            guard_size: u64,
+        ) -> Result<Box<dyn LinearMemory>, String> {
+            // Reserve either what the runtime asked for or, for memories
+            // which may move, the largest size the memory can grow to.
+            let max_pages = match reserved_size {
+                Some(size) => (size / WASM_PAGE_SIZE as u64) as u32,
+                None => ty.limits().max().unwrap_or(0x1_0000),
+            };
+            if let Some(max) = ty.limits().max() {
+                assert!(max <= max_pages);
+            }
+            let mem = unsafe {
+                CustomMemory::new(
+                    ty.limits().min(),
+                    max_pages,
+                    guard_size as usize,
+                    self.num_total_pages.clone(),
+                )
+            };
+            self.num_created_memories.fetch_add(1, Ordering::SeqCst);
+            Ok(Box::new(mem))
+        }
+    }
+
+    fn config() -> (Store, Arc<CustomMemoryCreator>) {
+        let mem_creator = Arc::new(CustomMemoryCreator::new());
+        let store = store_with(|config| config.with_host_memory(mem_creator.clone()));
+        (store, mem_creator)
+    }
+
+    #[test]
+    fn host_memory() -> anyhow::Result<()> {
+        let (store, mem_creator) = config();
+        let module = Module::new(
+            &store,
+            r#"
+            (module
+                (memory (export "memory") 1)
+            )
+        "#,
+        )?;
+        Instance::new(&module, &[])?;
+
+        assert_eq!(mem_creator.num_created_memories.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn host_memory_grow() -> anyhow::Result<()> {
+        let (store, mem_creator) = config();
+        let module = Module::new(
+            &store,
+            r#"
+            (module
+                (func $f (drop (memory.grow (i32.const 1))))
+                (memory (export "memory") 1 2)
+                (start $f)
+            )
+        "#,
+        )?;
+
+        let instance1 = Instance::new(&module, &[])?;
+        let instance2 = Instance::new(&module, &[])?;
+
+        assert_eq!(mem_creator.num_created_memories.load(Ordering::SeqCst), 2);
+
+        assert_eq!(
+            instance2
+                .get_export("memory")
+                .unwrap()
+                .memory()
+                .unwrap()
+                .size(),
+            2
+        );
+
+        assert_eq!(mem_creator.num_total_pages.load(Ordering::SeqCst), 4);
+
+        drop(instance1);
+        assert_eq!(mem_creator.num_total_pages.load(Ordering::SeqCst), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn host_memory_load_store() -> anyhow::Result<()> {
+        let (store, mem_creator) = config();
+        let module = Module::new(
+            &store,
+            r#"
+            (module
+                (memory (export "memory") 1)
+                (func (export "store") (param i32 i32)
+                    local.get 0
+                    local.get 1
+                    i32.store)
+                (func (export "load") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+            )
+        "#,
+        )?;
+        let instance = Instance::new(&module, &[])?;
+        let memory = instance.get_export("memory").unwrap().memory().unwrap();
+        let store_fn = instance
+            .get_export("store")
+            .unwrap()
+            .func()
+            .unwrap()
+            .get2::<i32, i32, ()>()?;
+        let load = instance
+            .get_export("load")
+            .unwrap()
+            .func()
+            .unwrap()
+            .get1::<i32, i32>()?;
+
+        store_fn(100, 0x1234)?;
+        assert_eq!(load(100)?, 0x1234);
+        assert_eq!(unsafe { memory.data_unchecked()[100] }, 0x34);
+
+        // Accesses beyond the end of the memory still trap, and start working
+        // once the memory has grown to cover them.
+        assert!(load(WASM_PAGE_SIZE as i32).is_err());
+        assert_eq!(memory.grow(1)?, 1);
+        assert_eq!(load(WASM_PAGE_SIZE as i32)?, 0);
+        assert_eq!(mem_creator.num_total_pages.load(Ordering::SeqCst), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn host_memory_from_api() -> anyhow::Result<()> {
+        let (store, mem_creator) = config();
+        let memory = Memory::new(&store, MemoryType::new(Limits::new(1, Some(3))));
+        assert_eq!(mem_creator.num_created_memories.load(Ordering::SeqCst), 1);
+        assert_eq!(memory.size(), 1);
+        assert_eq!(memory.grow(2)?, 1);
+        assert!(memory.grow(1).is_err());
+        assert_eq!(mem_creator.num_total_pages.load(Ordering::SeqCst), 3);
+
+        drop(memory);
+        assert_eq!(mem_creator.num_total_pages.load(Ordering::SeqCst), 0);
+        Ok(())
+    }
+}
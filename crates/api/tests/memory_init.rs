@@ -0,0 +1,149 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+fn memory(instance: &Instance) -> Memory {
+    instance.get_export("m").unwrap().memory().unwrap().clone()
+}
+
+#[test]
+fn instances_do_not_share_writes() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (export "m") 2)
+                (data (i32.const 70000) "hello")
+                (data (i32.const 10) "abcdef")
+                (data (i32.const 12) "XY")
+            )
+        "#,
+    )?;
+
+    let a = Instance::new(&module, &[])?;
+    let b = Instance::new(&module, &[])?;
+    let (memory_a, memory_b) = (memory(&a), memory(&b));
+    unsafe {
+        let data = memory_a.data_unchecked_mut();
+        assert_eq!(&data[10..16], b"abXYef");
+        assert_eq!(&data[70000..70005], b"hello");
+        assert_eq!(data[9], 0);
+        assert_eq!(data[70005], 0);
+        data[10] = b'z';
+        data[0] = 1;
+
+        let data = memory_b.data_unchecked();
+        assert_eq!(&data[10..16], b"abXYef");
+        assert_eq!(data[0], 0);
+    }
+
+    drop((a, b, memory_a, memory_b));
+    let c = Instance::new(&module, &[])?;
+    unsafe {
+        assert_eq!(&memory(&c).data_unchecked()[10..16], b"abXYef");
+    }
+    Ok(())
+}
+
+#[test]
+fn data_survives_growth() -> Result<()> {
+    let store = Store::default();
+    for wat in &[
+        // Memories which may move when grown, and ones which can't.
+        r#"(module (memory (export "m") 1) (data (i32.const 100) "data"))"#,
+        r#"(module (memory (export "m") 1 4) (data (i32.const 100) "data"))"#,
+    ] {
+        let module = Module::new(&store, wat)?;
+        let instance = Instance::new(&module, &[])?;
+        let memory = memory(&instance);
+        unsafe {
+            memory.data_unchecked_mut()[200] = 1;
+        }
+        assert_eq!(memory.grow(3)?, 1);
+        unsafe {
+            let data = memory.data_unchecked();
+            assert_eq!(&data[100..104], b"data");
+            assert_eq!(data[200], 1);
+            assert!(data[65536..].iter().all(|b| *b == 0));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn segments_not_in_an_image() -> Result<()> {
+    let store = Store::default();
+
+    // Segments at an offset given by a global are still copied in.
+    let global = Global::new(
+        &store,
+        GlobalType::new(ValType::I32, Mutability::Const),
+        Val::I32(20),
+    )?;
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "g" (global i32))
+                (memory (export "m") 1)
+                (data (i32.const 0) "first")
+                (data (global.get 0) "second")
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[global.into()])?;
+    let m = memory(&instance);
+    unsafe {
+        let data = m.data_unchecked();
+        assert_eq!(&data[0..5], b"first");
+        assert_eq!(&data[20..26], b"second");
+    }
+
+    // Segments which don't fit fail instantiation as before.
+    let module = Module::new(
+        &store,
+        r#"(module (memory 1) (data (i32.const 65534) "abc"))"#,
+    )?;
+    assert!(Instance::new(&module, &[]).is_err());
+    Ok(())
+}
+
+#[test]
+fn pooled_memories_are_reset() -> Result<()> {
+    let store = store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 1,
+                memory_pages: 2,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    });
+    let with_data = Module::new(
+        &store,
+        r#"(module (memory (export "m") 1) (data (i32.const 0) "abc"))"#,
+    )?;
+    let without_data = Module::new(&store, r#"(module (memory (export "m") 1))"#)?;
+
+    for _ in 0..3 {
+        let instance = Instance::new(&with_data, &[])?;
+        let m = memory(&instance);
+        unsafe {
+            let data = m.data_unchecked_mut();
+            assert_eq!(&data[0..4], b"abc\0");
+            data[3] = 1;
+        }
+        drop((instance, m));
+
+        // The next instance in the same slot sees none of the previous one's
+        // data.
+        let instance = Instance::new(&without_data, &[])?;
+        unsafe {
+            assert!(memory(&instance).data_unchecked().iter().all(|b| *b == 0));
+        }
+    }
+    Ok(())
+}
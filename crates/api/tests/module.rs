@@ -0,0 +1,103 @@
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+/// Encodes a custom section, for payloads short enough that each length fits
+/// in a single LEB128 byte.
+fn custom_section(name: &str, payload: &[u8]) -> Vec<u8> {
+    let size = 1 + name.len() + payload.len();
+    assert!(size < 0x80);
+    let mut section = vec![0x00, size as u8, name.len() as u8];
+    section.extend_from_slice(name.as_bytes());
+    section.extend_from_slice(payload);
+    section
+}
+
+#[test]
+fn custom_sections() -> anyhow::Result<()> {
+    let store = store_with(|config| config.retain_custom_section("a").retain_custom_section("b"));
+    let mut wasm = wat::parse_str("(module)")?;
+    wasm.extend(custom_section("a", b"first"));
+    wasm.extend(custom_section("b", b"other"));
+    wasm.extend(custom_section("a", b"second"));
+    wasm.extend(custom_section("c", b"unused"));
+
+    let module = Module::new(&store, &wasm)?;
+    let a = module.custom_sections("a").collect::<Vec<_>>();
+    assert_eq!(a, [&b"first"[..], &b"second"[..]]);
+    let b = module.custom_sections("b").collect::<Vec<_>>();
+    assert_eq!(b, [&b"other"[..]]);
+    assert_eq!(module.custom_sections("c").count(), 0);
+    assert_eq!(module.custom_sections("d").count(), 0);
+
+    // Nothing is kept unless it's requested.
+    let module = Module::new(&Store::default(), &wasm)?;
+    assert_eq!(module.custom_sections("a").count(), 0);
+    Ok(())
+}
+
+#[test]
+fn producers() -> anyhow::Result<()> {
+    let store = Store::default();
+    let module = Module::new(&store, "(module)")?;
+    assert!(module.producers().is_empty());
+
+    let mut payload = vec![0x02];
+    payload.extend(b"\x08language\x01\x04Rust\x031.0");
+    payload.extend(b"\x0cprocessed-by\x02\x05rustc\x041.41\x03wat\x00");
+    let mut wasm = wat::parse_str("(module)")?;
+    wasm.extend(custom_section("producers", &payload));
+
+    let module = Module::new(&store, &wasm)?;
+    let producers = module.producers();
+    assert_eq!(producers.len(), 2);
+    assert_eq!(producers[0].name(), "language");
+    assert_eq!(producers[0].values().len(), 1);
+    assert_eq!(producers[0].values()[0].name(), "Rust");
+    assert_eq!(producers[0].values()[0].version(), "1.0");
+    assert_eq!(producers[1].name(), "processed-by");
+    let tools = producers[1]
+        .values()
+        .iter()
+        .map(|v| (v.name(), v.version()))
+        .collect::<Vec<_>>();
+    assert_eq!(tools, [("rustc", "1.41"), ("wat", "")]);
+
+    // Malformed producers sections are ignored.
+    let mut wasm = wat::parse_str("(module)")?;
+    wasm.extend(custom_section("producers", b"\x01\x08lang"));
+    let module = Module::new(&store, &wasm)?;
+    assert!(module.producers().is_empty());
+    Ok(())
+}
+
+#[test]
+fn func_body_ranges() -> anyhow::Result<()> {
+    let store = store_with(|config| config.module_serialization(true));
+    let wasm = wat::parse_str(
+        r#"
+            (module
+                (import "" "" (func))
+                (func nop)
+                (func (local i32) i32.const 1 drop))
+        "#,
+    )?;
+
+    let module = Module::new(&store, &wasm)?;
+    let ranges = module.func_body_ranges().collect::<Vec<_>>();
+    assert_eq!(ranges.len(), 2);
+    assert_eq!(ranges[0].0, 1);
+    assert_eq!(&wasm[ranges[0].1.clone()], [0x00u8, 0x01, 0x0b]);
+    assert_eq!(ranges[1].0, 2);
+    assert_eq!(
+        &wasm[ranges[1].1.clone()],
+        [0x01u8, 0x01, 0x7f, 0x41, 0x01, 0x1a, 0x0b]
+    );
+
+    // The same ranges are available after a round trip through
+    // serialization.
+    let module = unsafe { Module::deserialize(&store, &module.serialize()?)? };
+    assert_eq!(module.func_body_ranges().collect::<Vec<_>>(), ranges);
+    Ok(())
+}
@@ -0,0 +1,196 @@
+use anyhow::Result;
+use wasmtime::*;
+
+#[test]
+fn instantiates_in_dependency_order() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+    linker.define("host", "double", Func::wrap1(&store, |x: i32| x * 2))?;
+
+    // `app` imports from `lib`, which imports a memory from `mem`; none of
+    // them are added in the order they need to be instantiated in.
+    let app = Module::new(
+        &store,
+        r#"
+            (module
+                (import "lib" "store" (func $store (param i32)))
+                (import "mem" "memory" (memory 1))
+                (func (export "run") (result i32)
+                    (call $store (i32.const 21))
+                    (i32.load (i32.const 0)))
+            )
+        "#,
+    )?;
+    let lib = Module::new(
+        &store,
+        r#"
+            (module
+                (import "host" "double" (func $double (param i32) (result i32)))
+                (import "mem" "memory" (memory 1))
+                (func (export "store") (param i32)
+                    (i32.store (i32.const 0) (call $double (local.get 0))))
+            )
+        "#,
+    )?;
+    let mem = Module::new(&store, r#"(module (memory (export "memory") 2))"#)?;
+
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("app", &app)?;
+    graph.module("lib", &lib)?;
+    graph.module("mem", &mem)?;
+    let instances = graph.instantiate(&mut linker)?;
+    assert_eq!(instances.len(), 3);
+
+    let run = instances["app"].get_export("run").unwrap().func().unwrap();
+    assert_eq!(run.get0::<i32>()?()?, 42);
+    Ok(())
+}
+
+#[test]
+fn links_and_reexports() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+
+    let provider = Module::new(
+        &store,
+        r#"(module (func (export "answer") (result i32) i32.const 42))"#,
+    )?;
+    let consumer = Module::new(
+        &store,
+        r#"
+            (module
+                (import "env" "answer" (func $answer (result i32)))
+                (func (export "run") (result i32) call $answer)
+            )
+        "#,
+    )?;
+
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("provider", &provider)?;
+    graph.module("consumer", &consumer)?;
+    assert!(graph.link("consumer", "env", "missing").is_err());
+    graph.link("consumer", "env", "provider")?;
+    assert!(graph.link("consumer", "env", "provider").is_err());
+    graph.reexport("consumer", "plugin")?;
+    let instances = graph.instantiate(&mut linker)?;
+
+    let run = instances["consumer"]
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap();
+    assert_eq!(run.get0::<i32>()?()?, 42);
+
+    // The re-exported instance can be imported from the linker.
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "plugin" "run" (func (result i32)))
+                (export "run" (func 0))
+            )
+        "#,
+    )?;
+    let instance = linker.instantiate(&module)?;
+    let run = instance.get_export("run").unwrap().func().unwrap();
+    assert_eq!(run.get0::<i32>()?()?, 42);
+    Ok(())
+}
+
+#[test]
+fn duplicate_modules() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(&store, "(module)")?;
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("a", &module)?;
+    assert!(graph.module("a", &module).is_err());
+
+    let other_store = Store::default();
+    let other = Module::new(&other_store, "(module)")?;
+    assert!(graph.module("b", &other).is_err());
+    Ok(())
+}
+
+#[test]
+fn cycles() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+    let a = Module::new(
+        &store,
+        r#"(module (import "b" "f" (func)) (func (export "f")))"#,
+    )?;
+    let b = Module::new(
+        &store,
+        r#"(module (import "c" "f" (func)) (func (export "f")))"#,
+    )?;
+    let c = Module::new(
+        &store,
+        r#"(module (import "a" "f" (func)) (func (export "f")))"#,
+    )?;
+
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("a", &a)?;
+    graph.module("b", &b)?;
+    graph.module("c", &c)?;
+    let err = graph.instantiate(&mut linker).unwrap_err().to_string();
+    assert_eq!(err, "module graph has a cycle: `a` -> `b` -> `c` -> `a`");
+
+    // A module importing from itself is a cycle too.
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("b", &a)?;
+    let err = graph.instantiate(&mut linker).unwrap_err().to_string();
+    assert_eq!(err, "module graph has a cycle: `b` -> `b`");
+    Ok(())
+}
+
+#[test]
+fn edge_type_checks() -> Result<()> {
+    let store = Store::default();
+    let mut linker = Linker::new(&store);
+    let provider = Module::new(
+        &store,
+        r#"
+            (module
+                (func (export "f") (param i32))
+                (global (export "g") (mut i32) (i32.const 0))
+                (memory (export "m") 1 2)
+            )
+        "#,
+    )?;
+
+    let check = |wat: &str| -> Result<()> {
+        let module = Module::new(&store, wat)?;
+        let mut graph = ModuleGraph::new(&store);
+        graph.module("provider", &provider)?;
+        graph.module("importer", &module)?;
+        graph.instantiate(&mut Linker::new(&store))?;
+        Ok(())
+    };
+
+    check(r#"(module (import "provider" "f" (func (param i32))))"#)?;
+    check(r#"(module (import "provider" "g" (global (mut i32))))"#)?;
+    check(r#"(module (import "provider" "m" (memory 1)))"#)?;
+    check(r#"(module (import "provider" "m" (memory 0 3)))"#)?;
+
+    let err = check(r#"(module (import "provider" "f" (func)))"#).unwrap_err();
+    assert!(err.to_string().contains("incompatible import type"));
+    assert!(check(r#"(module (import "provider" "g" (global i32)))"#).is_err());
+    assert!(check(r#"(module (import "provider" "m" (memory 2)))"#).is_err());
+    assert!(check(r#"(module (import "provider" "m" (memory 1 1)))"#).is_err());
+    assert!(check(r#"(module (import "provider" "f" (memory 1)))"#).is_err());
+
+    let err = check(r#"(module (import "provider" "h" (func)))"#).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "module `importer` imports `provider::h`, but module `provider` has no such export"
+    );
+
+    // Imports from modules outside the graph are resolved by the linker.
+    let module = Module::new(&store, r#"(module (import "host" "f" (func)))"#)?;
+    let mut graph = ModuleGraph::new(&store);
+    graph.module("importer", &module)?;
+    assert!(graph.instantiate(&mut linker).is_err());
+    linker.define("host", "f", Func::wrap0(&store, || {}))?;
+    graph.instantiate(&mut linker)?;
+    Ok(())
+}
@@ -0,0 +1,148 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+fn serializable_config() -> Config {
+    let mut config = Config::new();
+    config.module_serialization(true);
+    config
+}
+
+fn serialize(config: &Config, wat: &str) -> Result<Vec<u8>> {
+    let store = Store::new(&Engine::new(config));
+    let module = Module::new(&store, wat)?;
+    module.serialize()
+}
+
+#[test]
+fn test_module_serialize_simple() -> Result<()> {
+    let buffer = serialize(
+        &serializable_config(),
+        r#"
+            (module
+                (memory (export "memory") 1)
+                (data (i32.const 0) "\2a")
+                (func (export "run") (result i32)
+                    i32.const 0
+                    i32.load8_u)
+            )
+        "#,
+    )?;
+
+    let store = Store::default();
+    let module = unsafe { Module::deserialize(&store, &buffer)? };
+    assert_eq!(module.exports().len(), 2);
+    assert_eq!(module.exports()[1].name(), "run");
+
+    let instance = Instance::new(&module, &[])?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+    assert_eq!(run()?, 42);
+    Ok(())
+}
+
+#[test]
+fn test_module_serialize_imports() -> Result<()> {
+    let buffer = serialize(
+        &serializable_config(),
+        r#"
+            (module
+                (import "host" "double" (func $double (param i32) (result i32)))
+                (func (export "run") (param i32) (result i32)
+                    local.get 0
+                    call $double)
+            )
+        "#,
+    )?;
+
+    let store = Store::default();
+    let module = unsafe { Module::deserialize(&store, &buffer)? };
+    assert_eq!(module.imports().len(), 1);
+    assert_eq!(module.imports()[0].module(), "host");
+    assert_eq!(module.imports()[0].name(), "double");
+
+    let mut linker = Linker::new(&store);
+    linker.define("host", "double", Func::wrap1(&store, |x: i32| x * 2))?;
+    let instance = linker.instantiate(&module)?;
+    let run = instance
+        .get_export("run")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+    assert_eq!(run(21)?, 42);
+    Ok(())
+}
+
+#[test]
+fn test_module_serialize_incompatible_config() -> Result<()> {
+    let mut config = serializable_config();
+    config.cranelift_opt_level(OptLevel::None);
+    let buffer = serialize(&config, "(module)")?;
+
+    let store = store_with(|config| config.cranelift_opt_level(OptLevel::Speed));
+    assert!(unsafe { Module::deserialize(&store, &buffer) }.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_module_serialize_incompatible_features() -> Result<()> {
+    let mut config = serializable_config();
+    config.wasm_multi_value(true);
+    let buffer = serialize(&config, "(module)")?;
+
+    let store = store_with(|config| config.wasm_multi_value(false));
+    assert!(unsafe { Module::deserialize(&store, &buffer) }.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_module_serialize_incompatible_pooling_limits() -> Result<()> {
+    let buffer = serialize(&serializable_config(), "(module (memory 1))")?;
+
+    let store = store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 1,
+                memory_pages: 10,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    });
+    assert!(unsafe { Module::deserialize(&store, &buffer) }.is_err());
+    Ok(())
+}
+
+#[test]
+fn test_module_serialize_garbage() {
+    let store = Store::default();
+    assert!(unsafe { Module::deserialize(&store, b"not a module") }.is_err());
+
+    let mut buffer = serialize(&serializable_config(), "(module (func))").unwrap();
+    buffer.truncate(buffer.len() / 2);
+    assert!(unsafe { Module::deserialize(&store, &buffer) }.is_err());
+}
+
+#[test]
+fn test_module_serialize_opt_in() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(&store, "(module (func))")?;
+    assert!(module.serialize().is_err());
+
+    // Modules deserialized without the option can't be serialized again.
+    let buffer = serialize(&serializable_config(), "(module (func))")?;
+    let module = unsafe { Module::deserialize(&store, &buffer)? };
+    assert!(module.serialize().is_err());
+
+    let store = store_with(|config| config.module_serialization(true));
+    let module = unsafe { Module::deserialize(&store, &buffer)? };
+    let module = unsafe { Module::deserialize(&store, &module.serialize()?)? };
+    Instance::new(&module, &[])?;
+    Ok(())
+}
@@ -32,3 +32,36 @@ fn test_module_name() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_func_and_local_names() -> anyhow::Result<()> {
+    let store = Store::default();
+    let wat = r#"
+        (module
+        (import "" "" (func $imported))
+        (func $run (export "run") (nop))
+        (func (param i32))
+        )
+    "#;
+
+    let module = Module::new(&store, wat)?;
+    assert_eq!(module.func_name(0), Some("imported"));
+    assert_eq!(module.func_name(1), Some("run"));
+    assert_eq!(module.func_name(2), None);
+
+    // A name section naming the parameter and a local of function 1.
+    let mut wasm = wat::parse_str("(module (func (param i32) (local i64)))")?;
+    let name_section = [
+        0x00, 0x0d, 0x04, b'n', b'a', b'm', b'e', // custom section "name"
+        0x02, 0x06, // local names subsection
+        0x01, 0x00, // function 0
+        0x01, 0x01, 0x01, b'x', // local 1 is "x"
+    ];
+    wasm.extend_from_slice(&name_section);
+    let module = Module::new(&store, &wasm)?;
+    assert_eq!(module.local_name(0, 0), None);
+    assert_eq!(module.local_name(0, 1), Some("x"));
+    assert_eq!(module.local_name(1, 1), None);
+
+    Ok(())
+}
@@ -0,0 +1,216 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+fn pooling_store() -> Store {
+    store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 3,
+                memory_pages: 10,
+                table_elements: 10,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    })
+}
+
+#[test]
+fn memory_is_reset_for_reuse() -> Result<()> {
+    let store = pooling_store();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (export "m") 1 10)
+                (data (i32.const 0) "abc")
+            )
+        "#,
+    )?;
+
+    for _ in 0..10 {
+        let instance = Instance::new(&module, &[])?;
+        let memory = instance.get_export("m").unwrap().memory().unwrap();
+        assert_eq!(memory.size(), 1);
+        unsafe {
+            let data = memory.data_unchecked_mut();
+            assert_eq!(&data[..4], b"abc\0");
+            assert!(data[100..].iter().all(|b| *b == 0));
+            data[100] = 1;
+            data[0] = b'x';
+        }
+
+        // Memory grown by a previous instance is gone too.
+        assert_eq!(memory.grow(2)?, 1);
+        unsafe {
+            memory.data_unchecked_mut()[2 * 65536] = 1;
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn instance_limit() -> Result<()> {
+    let store = pooling_store();
+    let module = Module::new(&store, "(module (memory 1) (table 1 anyfunc))")?;
+
+    let mut instances = (0..3)
+        .map(|_| Instance::new(&module, &[]))
+        .collect::<Result<Vec<_>>>()?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("maximum concurrent instance limit of 3 reached"),
+        "bad error: {}",
+        err
+    );
+
+    // Dropping an instance frees up its slot.
+    instances.pop();
+    Instance::new(&module, &[])?;
+
+    // Host objects don't take up slots of the pool.
+    let memory = Memory::new(&store, MemoryType::new(Limits::new(1, None)));
+    assert_eq!(memory.grow(20)?, 1);
+    Ok(())
+}
+
+#[test]
+fn memory_limits() -> Result<()> {
+    let store = pooling_store();
+
+    let module = Module::new(&store, "(module (memory 11))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("memory minimum size of 11 pages exceeds the pooling limit of 10 pages"),
+        "bad error: {}",
+        err
+    );
+
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (export "m") 1)
+                (func (export "grow") (param i32) (result i32)
+                    local.get 0
+                    memory.grow)
+                (func (export "load") (param i32) (result i32)
+                    local.get 0
+                    i32.load)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let memory = instance.get_export("m").unwrap().memory().unwrap();
+    let grow = instance
+        .get_export("grow")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+    let load = instance
+        .get_export("load")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, i32>()?;
+
+    assert!(load(65536).is_err());
+    assert_eq!(grow(9)?, 1);
+    assert_eq!(load(65536)?, 0);
+    assert_eq!(load(10 * 65536 - 4)?, 0);
+    assert!(load(10 * 65536).is_err());
+
+    // Memories can't grow beyond their slot.
+    assert_eq!(grow(1)?, -1);
+    assert!(memory.grow(1).is_err());
+    assert_eq!(memory.size(), 10);
+    Ok(())
+}
+
+#[test]
+fn table_limits() -> Result<()> {
+    let store = pooling_store();
+
+    let module = Module::new(&store, "(module (table 11 anyfunc))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("table minimum size of 11 elements exceeds the pooling limit of 10 elements"),
+        "bad error: {}",
+        err
+    );
+
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (table (export "t") 1 anyfunc)
+                (func $f (result i32) i32.const 42)
+                (elem (i32.const 0) $f)
+                (type $t (func (result i32)))
+                (func (export "call") (param i32) (result i32)
+                    local.get 0
+                    call_indirect (type $t))
+            )
+        "#,
+    )?;
+    for _ in 0..5 {
+        let instance = Instance::new(&module, &[])?;
+        let table = instance.get_export("t").unwrap().table().unwrap();
+        let call = instance
+            .get_export("call")
+            .unwrap()
+            .func()
+            .unwrap()
+            .get1::<i32, i32>()?;
+        assert_eq!(call(0)?, 42);
+
+        assert_eq!(table.grow(9, Val::AnyRef(AnyRef::Null))?, 1);
+        assert!(table.grow(1, Val::AnyRef(AnyRef::Null)).is_err());
+        assert_eq!(table.size(), 10);
+        assert!(call(9).is_err());
+    }
+    Ok(())
+}
+
+#[test]
+fn too_many_memories_or_tables() -> Result<()> {
+    let store = store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 1,
+                memories: 0,
+                tables: 0,
+                memory_pages: 1,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    });
+
+    let module = Module::new(&store, "(module (memory 1))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("defined memories count of 1 exceeds the pooling limit of 0"),
+        "bad error: {}",
+        err
+    );
+
+    let module = Module::new(&store, "(module (table 1 anyfunc))")?;
+    let err = Instance::new(&module, &[]).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("defined tables count of 1 exceeds the pooling limit of 0"),
+        "bad error: {}",
+        err
+    );
+
+    // A failed instantiation doesn't leak its slot.
+    Instance::new(&Module::new(&store, "(module)")?, &[])?;
+    Ok(())
+}
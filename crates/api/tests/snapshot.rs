@@ -0,0 +1,217 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+const WAT: &str = r#"
+    (module
+        (memory (export "memory") 1 10)
+        (table (export "table") 1 10 funcref)
+        (global $counter (export "counter") (mut i32) (i32.const 0))
+        (global (export "constant") i64 (i64.const 7))
+        (data (i32.const 0) "hello")
+        (func $init (export "init")
+            (global.set $counter (i32.const 100))
+            (i32.store8 (i32.const 0) (i32.const 72)))
+        (func (export "bump") (result i32)
+            (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+            (global.get $counter))
+        (elem (i32.const 0) $init)
+    )
+"#;
+
+fn check_restored(instance: &Instance) -> Result<()> {
+    let memory = instance.get_export("memory").unwrap().memory().unwrap();
+    let table = instance.get_export("table").unwrap().table().unwrap();
+    let counter = instance.get_export("counter").unwrap().global().unwrap();
+    let constant = instance.get_export("constant").unwrap().global().unwrap();
+
+    assert_eq!(memory.size(), 1);
+    unsafe {
+        let data = memory.data_unchecked();
+        assert_eq!(&data[..6], b"Hello\0");
+        assert!(data[6..].iter().all(|b| *b == 0));
+    }
+    assert_eq!(table.size(), 1);
+    assert!(table.get(0).unwrap().funcref().is_some());
+    assert_eq!(counter.get().i32(), Some(100));
+    assert_eq!(constant.get().i64(), Some(7));
+    Ok(())
+}
+
+fn change_everything(instance: &Instance) -> Result<()> {
+    let memory = instance.get_export("memory").unwrap().memory().unwrap();
+    let table = instance.get_export("table").unwrap().table().unwrap();
+    let bump = instance.get_export("bump").unwrap().func().unwrap();
+
+    memory.grow(2)?;
+    unsafe {
+        let data = memory.data_unchecked_mut();
+        data[..5].copy_from_slice(b"world");
+        data[0x10000] = 1;
+        data[0x2ffff] = 1;
+    }
+    table.grow(3, Val::AnyRef(AnyRef::Null))?;
+    table.set(0, Val::AnyRef(AnyRef::Null))?;
+    bump.get0::<i32>()?()?;
+    Ok(())
+}
+
+#[test]
+fn restore_instance() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &[])?;
+    let init = instance.get_export("init").unwrap().func().unwrap();
+    init.get0::<()>()?()?;
+
+    let snapshot = instance.snapshot()?;
+    check_restored(&instance)?;
+
+    for _ in 0..3 {
+        change_everything(&instance)?;
+        instance.restore(&snapshot)?;
+        check_restored(&instance)?;
+    }
+
+    // Wasm sees the restored state too, including memory grown again after
+    // being shrunk.
+    let bump = instance.get_export("bump").unwrap().func().unwrap();
+    assert_eq!(bump.get0::<i32>()?()?, 101);
+    let memory = instance.get_export("memory").unwrap().memory().unwrap();
+    assert_eq!(memory.grow(1)?, 1);
+    unsafe {
+        assert!(memory.data_unchecked()[0x10000..].iter().all(|b| *b == 0));
+    }
+    Ok(())
+}
+
+#[test]
+fn restore_resizes_to_snapshot() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(&store, WAT)?;
+    let instance = Instance::new(&module, &[])?;
+    let memory = instance.get_export("memory").unwrap().memory().unwrap();
+    let table = instance.get_export("table").unwrap().table().unwrap();
+
+    memory.grow(2)?;
+    table.grow(2, Val::AnyRef(AnyRef::Null))?;
+    unsafe {
+        memory.data_unchecked_mut()[0x20000] = 42;
+    }
+    let snapshot = instance.snapshot()?;
+
+    // Snapshots can only be restored into the instance they were taken of.
+    let other = Instance::new(&module, &[])?;
+    assert!(other.restore(&snapshot).is_err());
+    assert!(instance.restore(&other.snapshot()?).is_err());
+
+    memory.grow(1)?;
+    instance.restore(&snapshot)?;
+    assert_eq!(memory.size(), 3);
+    assert_eq!(table.size(), 3);
+    unsafe {
+        assert_eq!(memory.data_unchecked()[0x20000], 42);
+    }
+    Ok(())
+}
+
+#[test]
+fn snapshot_with_pooling_allocator() -> Result<()> {
+    let store = store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 2,
+                memory_pages: 10,
+                table_elements: 10,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    });
+    let module = Module::new(&store, WAT)?;
+
+    for _ in 0..3 {
+        let instance = Instance::new(&module, &[])?;
+        let init = instance.get_export("init").unwrap().func().unwrap();
+        init.get0::<()>()?()?;
+        let snapshot = instance.snapshot()?;
+        change_everything(&instance)?;
+        instance.restore(&snapshot)?;
+        check_restored(&instance)?;
+        change_everything(&instance)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn unsupported_snapshots() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true));
+    let module = Module::new(&store, r#"(module (global (mut anyref) (ref.null)))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    assert!(instance.snapshot().is_err());
+
+    // Immutable reference-typed globals can't change, so aren't captured.
+    let module = Module::new(&store, r#"(module (global anyref (ref.null)))"#)?;
+    let instance = Instance::new(&module, &[])?;
+    instance.restore(&instance.snapshot()?)?;
+    Ok(())
+}
+
+#[test]
+fn restore_dropped_passive_elements() -> Result<()> {
+    let store = store_with(|config| config.wasm_bulk_memory(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (table (export "table") 1 funcref)
+                (func $f)
+                (elem $segment funcref (ref.func $f))
+                (func (export "init")
+                    (table.init $segment (i32.const 0) (i32.const 0) (i32.const 1)))
+                (func (export "drop")
+                    (elem.drop $segment)))
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let init = instance.get_export("init").unwrap().func().unwrap();
+    let init = init.get0::<()>()?;
+    let elem_drop = instance.get_export("drop").unwrap().func().unwrap();
+    let elem_drop = elem_drop.get0::<()>()?;
+    let table = instance.get_export("table").unwrap().table().unwrap();
+
+    // Dropping the segment is undone by restoring, so it can be used again.
+    let snapshot = instance.snapshot()?;
+    elem_drop()?;
+    assert!(init().is_err());
+
+    instance.restore(&snapshot)?;
+    init()?;
+    assert!(table.get(0).unwrap().funcref().is_some());
+    Ok(())
+}
+
+#[test]
+fn restore_into_reused_pool_slot() -> Result<()> {
+    let store = store_with(|config| {
+        config
+            .allocation_strategy(InstanceAllocationStrategy::Pooling(PoolingLimits {
+                instances: 1,
+                memory_pages: 10,
+                table_elements: 10,
+                ..PoolingLimits::default()
+            }))
+            .unwrap()
+    });
+    let module = Module::new(&store, WAT)?;
+
+    // The second instance lives at the same address as the first, but is
+    // still a different instance.
+    let instance = Instance::new(&module, &[])?;
+    let snapshot = instance.snapshot()?;
+    drop(instance);
+    let instance = Instance::new(&module, &[])?;
+    assert!(instance.restore(&snapshot).is_err());
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use anyhow::Result;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+#[test]
+fn anyref_table_api() -> Result<()> {
+    let store = Store::default();
+    let ty = TableType::new(ValType::AnyRef, Limits::new(2, Some(4)));
+    let table = Table::new(&store, ty, Val::AnyRef(AnyRef::Null))?;
+    assert!(table.get(0).unwrap().unwrap_anyref().ptr_eq(&AnyRef::Null));
+
+    let value = AnyRef::new(Box::new(42u32));
+    table.set(1, value.clone().into())?;
+    assert!(table.get(1).unwrap().unwrap_anyref().ptr_eq(&value));
+
+    // Previous size is returned when growing.
+    assert_eq!(table.grow(2, value.clone().into())?, 2);
+    assert_eq!(table.size(), 4);
+    assert!(table.get(3).unwrap().unwrap_anyref().ptr_eq(&value));
+    assert!(table.grow(1, Val::AnyRef(AnyRef::Null)).is_err());
+
+    // Functions can't be stored in `anyref` tables.
+    let f = Func::wrap0(&store, || {});
+    assert!(table.set(0, f.clone().into()).is_err());
+
+    // And tables of different element types can't be copied between.
+    let ty = TableType::new(ValType::FuncRef, Limits::new(1, None));
+    let funcs = Table::new(&store, ty, f.into())?;
+    assert!(Table::copy(&table, 0, &funcs, 0, 1).is_err());
+    Ok(())
+}
+
+#[test]
+fn table_ops_from_wasm() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true));
+    let wat = r#"
+        (module
+            (table $t (export "t") 1 anyref)
+            (func (export "get") (param i32) (result anyref)
+                (table.get $t (local.get 0)))
+            (func (export "set") (param i32 anyref)
+                (table.set $t (local.get 0) (local.get 1)))
+            (func (export "grow") (param i32 anyref) (result i32)
+                (table.grow $t (local.get 1) (local.get 0)))
+            (func (export "size") (result i32)
+                (table.size $t))
+            (func (export "fill") (param i32 anyref i32)
+                (table.fill $t (local.get 0) (local.get 1) (local.get 2)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &[])?;
+    let func = |name: &str| instance.get_export(name).unwrap().func().unwrap().clone();
+    let (get, set, grow, size, fill) = (
+        func("get"),
+        func("set"),
+        func("grow"),
+        func("size"),
+        func("fill"),
+    );
+
+    let value = AnyRef::new(Box::new("hello"));
+    set.call(&[0.into(), value.clone().into()])?;
+    let result = get.call(&[0.into()])?;
+    assert!(result[0].unwrap_anyref().ptr_eq(&value));
+
+    let result = grow.call(&[3.into(), Val::AnyRef(AnyRef::Null)])?;
+    assert_eq!(result[0].unwrap_i32(), 1);
+    assert_eq!(size.call(&[])?[0].unwrap_i32(), 4);
+    let result = get.call(&[2.into()])?;
+    assert!(result[0].unwrap_anyref().ptr_eq(&AnyRef::Null));
+
+    fill.call(&[1.into(), value.clone().into(), 3.into()])?;
+    let table = instance.get_export("t").unwrap().table().unwrap();
+    for i in 0..4 {
+        assert!(table.get(i).unwrap().unwrap_anyref().ptr_eq(&value));
+    }
+
+    // Out of bounds accesses trap.
+    assert!(get.call(&[4.into()]).is_err());
+    assert!(set.call(&[4.into(), value.clone().into()]).is_err());
+    assert!(fill.call(&[2.into(), value.into(), 3.into()]).is_err());
+    Ok(())
+}
+
+#[test]
+fn ref_func() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true));
+    let wat = r#"
+        (module
+            (table $t (export "t") 2 funcref)
+            (func $f (result i32) (i32.const 42))
+            (elem (i32.const 1) $f)
+            (func (export "store")
+                (table.set $t (i32.const 0) (ref.func $f)))
+        )
+    "#;
+    let module = Module::new(&store, wat)?;
+    let instance = Instance::new(&module, &[])?;
+    let table = instance.get_export("t").unwrap().table().unwrap();
+    assert!(table.get(0).unwrap().funcref().is_none());
+
+    instance
+        .get_export("store")
+        .unwrap()
+        .func()
+        .unwrap()
+        .call(&[])?;
+    let f = table.get(0).unwrap().unwrap_funcref().clone();
+    assert_eq!(f.call(&[])?[0].unwrap_i32(), 42);
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use anyhow::Result;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use wasmtime::*;
+
+fn threads_engine() -> Engine {
+    let mut config = Config::new();
+    config.wasm_threads(true);
+    Engine::new(&config)
+}
+
+#[test]
+fn shared_memory_requires_threads() -> Result<()> {
+    let ty = MemoryType::shared(Limits::new(1, Some(1)));
+    assert!(SharedMemory::new(&Engine::default(), ty.clone()).is_err());
+    assert!(SharedMemory::new(&threads_engine(), ty).is_ok());
+
+    let store = Store::default();
+    let err = Module::new(&store, "(module (memory 1 1 shared))")
+        .err()
+        .unwrap();
+    assert!(err.to_string().contains("not supported"), "{}", err);
+    Ok(())
+}
+
+#[test]
+fn shared_memory_type_checks() -> Result<()> {
+    let engine = threads_engine();
+    let ty = MemoryType::new(Limits::new(1, Some(1)));
+    assert!(SharedMemory::new(&engine, ty).is_err());
+    let ty = MemoryType::shared(Limits::new(1, None));
+    assert!(SharedMemory::new(&engine, ty).is_err());
+    Ok(())
+}
+
+#[test]
+fn exported_shared_memory() -> Result<()> {
+    let store = Store::new(&threads_engine());
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (export "shared") 1 2 shared)
+                (func (export "store") (param i32 i32)
+                    (i32.atomic.store (local.get 0) (local.get 1)))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let memory = instance.get_export("shared").unwrap().memory().unwrap();
+    assert!(memory.ty().is_shared());
+    let shared = memory.shared().unwrap();
+    assert!(shared.ty().is_shared());
+
+    let store_fn = instance
+        .get_export("store")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get2::<i32, i32, ()>()?;
+    store_fn(4, 42)?;
+    assert_eq!(unsafe { *(shared.data_ptr().add(4) as *const u32) }, 42);
+
+    // Growth through another store is subject to that store's limits, and
+    // visible to all the stores using the memory.
+    let limited = Store::new_with_limits(
+        store.engine(),
+        StoreLimitsBuilder::new().memory_pages(1).build(),
+    );
+    assert!(Memory::from_shared(&limited, &shared).grow(1).is_err());
+    let other = Store::new(store.engine());
+    assert_eq!(Memory::from_shared(&other, &shared).grow(1)?, 1);
+    assert_eq!(memory.size(), 2);
+    assert_eq!(memory.data_size(), 2 * 0x10000);
+    assert_eq!(shared.size(), 2);
+    assert!(memory.grow(1).is_err());
+
+    let plain = Memory::new(&store, MemoryType::new(Limits::new(1, None)));
+    assert!(plain.shared().is_none());
+    Ok(())
+}
+
+#[test]
+fn importers_see_growth_of_shared_memory() -> Result<()> {
+    let engine = threads_engine();
+    let shared = SharedMemory::new(&engine, MemoryType::shared(Limits::new(1, Some(2))))?;
+
+    let store = Store::new(&engine);
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory (import "" "memory") 1 2 shared)
+                (export "memory" (memory 0))
+                (func (export "fill") (param i32)
+                    (memory.fill (local.get 0) (i32.const 1) (i32.const 4)))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[Memory::from_shared(&store, &shared).into()])?;
+    let memory = instance.get_export("memory").unwrap().memory().unwrap();
+    let fill = instance
+        .get_export("fill")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get1::<i32, ()>()?;
+    assert!(fill(0x10000).is_err());
+
+    let other = Store::new(&engine);
+    assert_eq!(Memory::from_shared(&other, &shared).grow(1)?, 1);
+    assert_eq!(memory.data_size(), 2 * 0x10000);
+    fill(0x10000)?;
+    assert_eq!(unsafe { *shared.data_ptr().add(0x10000) }, 1);
+    Ok(())
+}
+
+#[test]
+fn atomic_add_from_many_threads() -> Result<()> {
+    const THREADS: u32 = 4;
+    const ITERATIONS: i32 = 1000;
+
+    let engine = threads_engine();
+    let shared = SharedMemory::new(&engine, MemoryType::shared(Limits::new(1, Some(1))))?;
+
+    let threads = (0..THREADS)
+        .map(|_| {
+            let engine = engine.clone();
+            let shared = shared.clone();
+            thread::spawn(move || -> Result<()> {
+                let store = Store::new(&engine);
+                let module = Module::new(
+                    &store,
+                    r#"
+                        (module
+                            (memory (import "" "memory") 1 1 shared)
+                            (func (export "run") (param i32)
+                                (loop $l
+                                    (drop (i32.atomic.rmw.add (i32.const 8) (i32.const 1)))
+                                    (br_if $l (local.tee 0 (i32.sub (local.get 0) (i32.const 1))))))
+                        )
+                    "#,
+                )?;
+                let memory = Memory::from_shared(&store, &shared);
+                let instance = Instance::new(&module, &[memory.into()])?;
+                let run = instance
+                    .get_export("run")
+                    .unwrap()
+                    .func()
+                    .unwrap()
+                    .get1::<i32, ()>()?;
+                run(ITERATIONS)?;
+                Ok(())
+            })
+        })
+        .collect::<Vec<_>>();
+    for thread in threads {
+        thread.join().unwrap()?;
+    }
+
+    let counter = unsafe { &*(shared.data_ptr().add(8) as *const AtomicU32) };
+    assert_eq!(counter.load(Ordering::SeqCst), THREADS * ITERATIONS as u32);
+    Ok(())
+}
+
+#[test]
+fn wait_and_notify() -> Result<()> {
+    const WAT: &str = r#"
+        (module
+            (memory (import "" "memory") 1 1 shared)
+            (func (export "wait") (result i32)
+                (i32.atomic.wait (i32.const 0) (i32.const 0) (i64.const -1)))
+            (func (export "notify") (result i32)
+                (atomic.notify (i32.const 0) (i32.const 1)))
+        )
+    "#;
+
+    let engine = threads_engine();
+    let shared = SharedMemory::new(&engine, MemoryType::shared(Limits::new(1, Some(1))))?;
+
+    let waiter = {
+        let engine = engine.clone();
+        let shared = shared.clone();
+        thread::spawn(move || -> Result<i32> {
+            let store = Store::new(&engine);
+            let module = Module::new(&store, WAT)?;
+            let memory = Memory::from_shared(&store, &shared);
+            let instance = Instance::new(&module, &[memory.into()])?;
+            let wait = instance
+                .get_export("wait")
+                .unwrap()
+                .func()
+                .unwrap()
+                .get0::<i32>()?;
+            Ok(wait()?)
+        })
+    };
+
+    let store = Store::new(&engine);
+    let module = Module::new(&store, WAT)?;
+    let memory = Memory::from_shared(&store, &shared);
+    let instance = Instance::new(&module, &[memory.into()])?;
+    let notify = instance
+        .get_export("notify")
+        .unwrap()
+        .func()
+        .unwrap()
+        .get0::<i32>()?;
+
+    // The waiter may not have started waiting yet, so keep notifying until it
+    // has been woken.
+    while notify()? == 0 {
+        thread::yield_now();
+    }
+    assert_eq!(waiter.join().unwrap()?, 0);
+    Ok(())
+}
@@ -402,3 +402,105 @@ fn call_signature_mismatch() -> Result<()> {
     );
     Ok(())
 }
+
+#[test]
+fn trap_codes() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (memory 1)
+                (table 1 anyfunc)
+                (func (export "unreachable") unreachable)
+                (func (export "oob") (drop (i32.load (i32.const 0x10000))))
+                (func (export "div") (drop (i32.div_s (i32.const 1) (i32.const 0))))
+                (func (export "overflow")
+                    (drop (i32.div_s (i32.const 0x80000000) (i32.const -1))))
+                (func (export "null") (call_indirect (i32.const 0)))
+                (func $recurse (export "recurse") (call $recurse))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let code = |name: &str| {
+        let f = instance.get_export(name).unwrap().func().unwrap();
+        f.call(&[]).unwrap_err().trap_code()
+    };
+    assert_eq!(code("unreachable"), Some(TrapCode::UnreachableCodeReached));
+    assert_eq!(code("oob"), Some(TrapCode::MemoryOutOfBounds));
+    assert_eq!(code("div"), Some(TrapCode::IntegerDivisionByZero));
+    assert_eq!(code("overflow"), Some(TrapCode::IntegerOverflow));
+    assert_eq!(code("null"), Some(TrapCode::IndirectCallToNull));
+    assert_eq!(code("recurse"), Some(TrapCode::StackOverflow));
+
+    assert_eq!(Trap::new("host").trap_code(), None);
+    Ok(())
+}
+
+#[test]
+fn i32_exit_from_host() -> Result<()> {
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "exit" (func $exit (param i32)))
+                (func (export "run")
+                    (call $exit (i32.const 42))
+                    unreachable)
+            )
+        "#,
+    )?;
+    let exit = Func::wrap1(&store, |status: i32| -> Result<(), Trap> {
+        Err(Trap::i32_exit(status))
+    });
+    let instance = Instance::new(&module, &[exit.into()])?;
+    let run = instance.get_export("run").unwrap().func().unwrap();
+    let trap = run.call(&[]).unwrap_err();
+    assert_eq!(trap.i32_exit_status(), Some(42));
+    assert_eq!(trap.trap_code(), None);
+    assert_eq!(Trap::new("exit").i32_exit_status(), None);
+    Ok(())
+}
+
+#[test]
+fn host_error_is_source() -> Result<()> {
+    #[derive(Debug)]
+    struct MyError;
+
+    impl std::fmt::Display for MyError {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "my error")
+        }
+    }
+
+    impl std::error::Error for MyError {}
+
+    let store = Store::default();
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (import "" "fail" (func $fail))
+                (func (export "run") (call $fail))
+            )
+        "#,
+    )?;
+    let fail = Func::wrap0(&store, || -> Result<(), Trap> {
+        let error: Box<dyn std::error::Error + Send + Sync> = Box::new(MyError);
+        Err(Trap::from(error))
+    });
+    let instance = Instance::new(&module, &[fail.into()])?;
+    let run = instance.get_export("run").unwrap().func().unwrap();
+    let trap = run.call(&[]).unwrap_err();
+    assert_eq!(trap.message(), "my error");
+    let source = std::error::Error::source(&trap).expect("trap should have a source");
+    assert!(source.downcast_ref::<MyError>().is_some());
+
+    // Converting a trap into an error and back doesn't wrap it again.
+    let trap = Trap::from(anyhow::Error::new(Trap::i32_exit(3)));
+    assert_eq!(trap.i32_exit_status(), Some(3));
+    assert!(std::error::Error::source(&trap).is_none());
+    Ok(())
+}
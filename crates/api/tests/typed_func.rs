@@ -0,0 +1,132 @@
+use anyhow::Result;
+use std::cell::Cell;
+use std::rc::Rc;
+use wasmtime::*;
+
+mod common;
+use common::store_with;
+
+#[test]
+fn typed_signature_checks() -> Result<()> {
+    let store = Store::default();
+    let f = Func::wrap2(&store, |_: i32, _: f64| -> i64 { 0 });
+    assert!(f.typed::<(i32, f64), i64>().is_ok());
+    assert!(f.typed::<(i32, f64), ()>().is_err());
+    assert!(f.typed::<(i32, f64), (i64, i64)>().is_err());
+    assert!(f.typed::<(i32, f32), i64>().is_err());
+    assert!(f.typed::<i32, i64>().is_err());
+    assert!(f.typed::<(i32, f64, i32), i64>().is_err());
+
+    let f = Func::wrap0(&store, || {});
+    assert!(f.typed::<(), ()>().is_ok());
+    assert!(f.typed::<i32, ()>().is_err());
+    assert!(f.typed::<(), i32>().is_err());
+
+    let f = Func::wrap1(&store, |_: i32| {});
+    assert!(f.typed::<i32, ()>().is_ok());
+    assert!(f.typed::<(i32,), ()>().is_ok());
+    Ok(())
+}
+
+#[test]
+fn typed_calls() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true).wasm_multi_value(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (func (export "add") (param i32 i64) (result i64)
+                    (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1)))
+                (func (export "swap") (param f32 f64) (result f64 f32)
+                    (local.get 1)
+                    (local.get 0))
+                (func (export "many") (param i32 i32 i32 i32 i32 i32 i32 i32 i32 i32)
+                    (result i32)
+                    (i32.add (local.get 0) (local.get 9)))
+                (func (export "trap")
+                    unreachable)
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+
+    let add = instance.get_export("add").unwrap().func().unwrap();
+    let add = add.typed::<(i32, i64), i64>()?;
+    assert_eq!(add.call((1, 2))?, 3);
+    assert_eq!(add.call((-1, 10))?, 9);
+
+    let swap = instance.get_export("swap").unwrap().func().unwrap();
+    let swap = swap.typed::<(f32, f64), (f64, f32)>()?;
+    assert_eq!(swap.call((1.0, 2.0))?, (2.0, 1.0));
+
+    // More values than fit inline in a call.
+    let many = instance.get_export("many").unwrap().func().unwrap();
+    let many = many.typed::<(i32, i32, i32, i32, i32, i32, i32, i32, i32, i32), i32>()?;
+    assert_eq!(many.call((1, 0, 0, 0, 0, 0, 0, 0, 0, 2))?, 3);
+
+    let trap = instance.get_export("trap").unwrap().func().unwrap();
+    let trap = trap.typed::<(), ()>()?;
+    let err = trap.call(()).unwrap_err();
+    assert_eq!(err.trap_code(), Some(TrapCode::UnreachableCodeReached));
+    Ok(())
+}
+
+#[test]
+fn typed_host_calls() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true).wasm_multi_value(true));
+    let f = Func::wrap2(&store, |a: i32, b: i32| (b, a));
+    let f = f.typed::<(i32, i32), (i32, i32)>()?;
+    assert_eq!(f.call((1, 2))?, (2, 1));
+    assert!(f.func().typed::<(i32, i32), i32>().is_err());
+    Ok(())
+}
+
+#[test]
+fn typed_reference_params() -> Result<()> {
+    let store = store_with(|config| config.wasm_reference_types(true).wasm_multi_value(true));
+    let module = Module::new(
+        &store,
+        r#"
+            (module
+                (table $t (export "t") 1 anyref)
+                (table $f (export "f") 1 funcref)
+                (func (export "set") (param anyref funcref)
+                    (table.set $t (i32.const 0) (local.get 0))
+                    (table.set $f (i32.const 0) (local.get 1)))
+            )
+        "#,
+    )?;
+    let instance = Instance::new(&module, &[])?;
+    let set = instance.get_export("set").unwrap().func().unwrap();
+    let set = set.typed::<(AnyRef, Option<Func>), ()>()?;
+    let table = instance.get_export("t").unwrap().table().unwrap().clone();
+    let funcs = instance.get_export("f").unwrap().table().unwrap().clone();
+
+    let dropped = Rc::new(Cell::new(false));
+    struct SetFlagOnDrop(Rc<Cell<bool>>);
+    impl Drop for SetFlagOnDrop {
+        fn drop(&mut self) {
+            self.0.set(true);
+        }
+    }
+
+    let r = AnyRef::new(Box::new(SetFlagOnDrop(dropped.clone())));
+    let answer = Func::wrap0(&store, || 42);
+    set.call((r.clone(), Some(answer.clone())))?;
+    assert!(table.get(0).unwrap().unwrap_anyref().ptr_eq(&r));
+    let answer = funcs.get(0).unwrap().unwrap_funcref().get0::<i32>()?;
+    assert_eq!(answer()?, 42);
+
+    set.call((AnyRef::null(), None))?;
+    assert!(table
+        .get(0)
+        .unwrap()
+        .unwrap_anyref()
+        .ptr_eq(&AnyRef::null()));
+    assert!(funcs.get(0).unwrap().funcref().is_none());
+
+    drop(r);
+    store.gc();
+    assert!(dropped.get());
+    Ok(())
+}
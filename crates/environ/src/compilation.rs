@@ -4,7 +4,7 @@
 use crate::cache::ModuleCacheDataTupleType;
 use crate::module;
 use crate::module_environ::FunctionBodyData;
-use crate::CacheConfig;
+use crate::{CacheConfig, Tunables};
 use cranelift_codegen::{binemit, ir, isa, Context};
 use cranelift_entity::PrimaryMap;
 use cranelift_wasm::{DefinedFuncIndex, FuncIndex, ModuleTranslationState, WasmError};
@@ -268,6 +268,41 @@ pub struct TrapInformation {
 /// Information about traps associated with the functions where the traps are placed.
 pub type Traps = PrimaryMap<DefinedFuncIndex, Vec<TrapInformation>>;
 
+/// The stack map of a GC safepoint: which words of a function's stack frame
+/// hold live references when it's stopped at the safepoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct StackMapInformation {
+    /// The offset of the safepoint in native code. It is relative to the beginning of the function.
+    pub code_offset: binemit::CodeOffset,
+    /// The number of words of the stack frame described by `bitmap`.
+    pub mapped_words: u32,
+    /// One bit per word of the stack frame, starting from the lowest address,
+    /// which is set if that word holds a live reference.
+    pub bitmap: Vec<u32>,
+}
+
+impl StackMapInformation {
+    /// Creates the stack map information for a stack map Cranelift emitted
+    /// at `code_offset`.
+    pub fn new(code_offset: binemit::CodeOffset, stack_map: &binemit::Stackmap) -> Self {
+        Self {
+            code_offset,
+            mapped_words: stack_map.mapped_words(),
+            bitmap: stack_map.as_slice().iter().map(|bits| bits.0).collect(),
+        }
+    }
+
+    /// Returns whether the word at `index` in the stack frame holds a live
+    /// reference.
+    pub fn get_bit(&self, index: usize) -> bool {
+        assert!(index < self.mapped_words as usize);
+        self.bitmap[index / 32] & (1 << (index % 32)) != 0
+    }
+}
+
+/// Stack maps for the GC safepoints of all the functions of a module.
+pub type StackMaps = PrimaryMap<DefinedFuncIndex, Vec<StackMapInformation>>;
+
 /// An error while compiling WebAssembly to machine code.
 #[derive(Error, Debug)]
 pub enum CompileError {
@@ -282,6 +317,10 @@ pub enum CompileError {
     /// A compilation error occured.
     #[error("Debug info is not supported with this configuration")]
     DebugInfoNotSupported,
+
+    /// Interruptable code was requested from a compiler which can't generate it.
+    #[error("Interrupts are not supported with this configuration")]
+    InterruptsNotSupported,
 }
 
 /// An implementation of a compiler from parsed WebAssembly module to native code.
@@ -293,6 +332,7 @@ pub trait Compiler {
         function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
         isa: &dyn isa::TargetIsa,
         generate_debug_info: bool,
+        tunables: &Tunables,
         cache_config: &CacheConfig,
     ) -> Result<ModuleCacheDataTupleType, CompileError>;
 }
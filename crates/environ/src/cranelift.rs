@@ -4,12 +4,12 @@ use crate::address_map::{FunctionAddressMap, InstructionAddressMap};
 use crate::cache::{ModuleCacheDataTupleType, ModuleCacheEntry};
 use crate::compilation::{
     Compilation, CompileError, CompiledFunction, CompiledFunctionUnwindInfo, Relocation,
-    RelocationTarget, TrapInformation,
+    RelocationTarget, StackMapInformation, TrapInformation,
 };
 use crate::func_environ::{get_func_name, FuncEnvironment};
 use crate::module::{Module, ModuleLocal};
 use crate::module_environ::FunctionBodyData;
-use crate::CacheConfig;
+use crate::{CacheConfig, Tunables};
 use cranelift_codegen::ir::{self, ExternalName};
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::{binemit, isa, Context};
@@ -115,6 +115,25 @@ impl binemit::TrapSink for TrapSink {
     }
 }
 
+struct StackMapSink {
+    pub stack_maps: Vec<StackMapInformation>,
+}
+
+impl StackMapSink {
+    fn new() -> Self {
+        Self {
+            stack_maps: Vec::new(),
+        }
+    }
+}
+
+impl binemit::StackmapSink for StackMapSink {
+    fn add_stackmap(&mut self, code_offset: binemit::CodeOffset, stack_map: binemit::Stackmap) {
+        self.stack_maps
+            .push(StackMapInformation::new(code_offset, &stack_map));
+    }
+}
+
 fn get_function_address_map<'data>(
     context: &Context,
     data: &FunctionBodyData<'data>,
@@ -167,6 +186,7 @@ impl crate::compilation::Compiler for Cranelift {
         function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'_>>,
         isa: &dyn isa::TargetIsa,
         generate_debug_info: bool,
+        tunables: &Tunables,
         cache_config: &CacheConfig,
     ) -> Result<ModuleCacheDataTupleType, CompileError> {
         let cache_entry = ModuleCacheEntry::new("cranelift", cache_config);
@@ -178,6 +198,7 @@ impl crate::compilation::Compiler for Cranelift {
                 function_body_inputs,
                 Isa(isa),
                 generate_debug_info,
+                tunables,
             ),
             compile,
         )?;
@@ -192,12 +213,14 @@ fn compile(
         function_body_inputs,
         Isa(isa),
         generate_debug_info,
+        tunables,
     ): (
         &ModuleLocal,
         HashedModuleTranslationState<'_>,
         PrimaryMap<DefinedFuncIndex, FunctionBodyData<'_>>,
         Isa<'_, '_>,
         bool,
+        &Tunables,
     ),
 ) -> Result<ModuleCacheDataTupleType, CompileError> {
     let mut functions = PrimaryMap::with_capacity(function_body_inputs.len());
@@ -206,6 +229,7 @@ fn compile(
     let mut value_ranges = PrimaryMap::with_capacity(function_body_inputs.len());
     let mut stack_slots = PrimaryMap::with_capacity(function_body_inputs.len());
     let mut traps = PrimaryMap::with_capacity(function_body_inputs.len());
+    let mut stack_maps = PrimaryMap::with_capacity(function_body_inputs.len());
 
     function_body_inputs
         .into_iter()
@@ -226,20 +250,20 @@ fn compile(
                 input.data,
                 input.module_offset,
                 &mut context.func,
-                &mut FuncEnvironment::new(isa.frontend_config(), module),
+                &mut FuncEnvironment::new(isa.frontend_config(), module, tunables),
             )?;
 
             let mut code_buf: Vec<u8> = Vec::new();
             let mut reloc_sink = RelocSink::new(func_index);
             let mut trap_sink = TrapSink::new();
-            let mut stackmap_sink = binemit::NullStackmapSink {};
+            let mut stack_map_sink = StackMapSink::new();
             context
                 .compile_and_emit(
                     isa,
                     &mut code_buf,
                     &mut reloc_sink,
                     &mut trap_sink,
-                    &mut stackmap_sink,
+                    &mut stack_map_sink,
                 )
                 .map_err(|error| {
                     CompileError::Codegen(pretty_error(&context.func, Some(isa), error))
@@ -271,6 +295,7 @@ fn compile(
                 ranges,
                 context.func.stack_slots,
                 trap_sink.traps,
+                stack_map_sink.stack_maps,
                 unwind_info,
             ))
         })
@@ -285,6 +310,7 @@ fn compile(
                 ranges,
                 sss,
                 function_traps,
+                function_stack_maps,
                 unwind_info,
             )| {
                 functions.push(CompiledFunction {
@@ -299,6 +325,7 @@ fn compile(
                 value_ranges.push(ranges.unwrap_or_default());
                 stack_slots.push(sss);
                 traps.push(function_traps);
+                stack_maps.push(function_stack_maps);
             },
         );
 
@@ -311,6 +338,7 @@ fn compile(
         value_ranges,
         stack_slots,
         traps,
+        stack_maps,
     ))
 }
 
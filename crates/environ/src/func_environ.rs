@@ -1,4 +1,5 @@
 use crate::module::{MemoryPlan, MemoryStyle, ModuleLocal, TableStyle};
+use crate::tunables::Tunables;
 use crate::vmoffsets::VMOffsets;
 use crate::WASM_PAGE_SIZE;
 use cranelift_codegen::cursor::{Cursor, FuncCursor};
@@ -9,13 +10,16 @@ use cranelift_codegen::ir::types::*;
 use cranelift_codegen::ir::{AbiParam, ArgumentPurpose, Function, InstBuilder, Signature};
 use cranelift_codegen::isa::TargetFrontendConfig;
 use cranelift_entity::EntityRef;
+use cranelift_frontend::{FunctionBuilder, Variable};
 use cranelift_wasm::{
-    self, FuncIndex, GlobalIndex, GlobalVariable, MemoryIndex, SignatureIndex, TableIndex,
-    TargetEnvironment, WasmError, WasmResult,
+    self, FuncIndex, FuncTranslationState, GlobalIndex, GlobalVariable, MemoryIndex,
+    SignatureIndex, TableIndex, TargetEnvironment, WasmError, WasmResult,
 };
 #[cfg(feature = "lightbeam")]
 use cranelift_wasm::{DefinedFuncIndex, DefinedGlobalIndex, DefinedMemoryIndex, DefinedTableIndex};
 use std::convert::TryFrom;
+use std::mem;
+use wasmparser::Operator;
 
 /// Compute an `ir::ExternalName` for a given wasm function index.
 pub fn get_func_name(func_index: FuncIndex) -> ir::ExternalName {
@@ -72,9 +76,41 @@ impl BuiltinFunctionIndex {
     pub const fn get_imported_memory_fill_index() -> Self {
         Self(10)
     }
+    /// Returns an index for wasm's `table.grow`.
+    pub const fn get_table_grow_index() -> Self {
+        Self(11)
+    }
+    /// Returns an index for wasm's `table.get`.
+    pub const fn get_table_get_index() -> Self {
+        Self(12)
+    }
+    /// Returns an index for wasm's `table.set`.
+    pub const fn get_table_set_index() -> Self {
+        Self(13)
+    }
+    /// Returns an index for wasm's `table.fill`.
+    pub const fn get_table_fill_index() -> Self {
+        Self(14)
+    }
+    /// Returns an index for wasm's `ref.func`.
+    pub const fn get_ref_func_index() -> Self {
+        Self(15)
+    }
+    /// Returns an index for wasm's `memory.atomic.wait32`.
+    pub const fn get_memory_atomic_wait32_index() -> Self {
+        Self(16)
+    }
+    /// Returns an index for wasm's `memory.atomic.wait64`.
+    pub const fn get_memory_atomic_wait64_index() -> Self {
+        Self(17)
+    }
+    /// Returns an index for wasm's `memory.atomic.notify`.
+    pub const fn get_memory_atomic_notify_index() -> Self {
+        Self(18)
+    }
     /// Returns the total number of builtin functions.
     pub const fn builtin_functions_total_number() -> u32 {
-        11
+        19
     }
 
     /// Return the index as an u32 number.
@@ -120,14 +156,50 @@ pub struct FuncEnvironment<'module_environment> {
     /// (it's the same for both local and imported memories).
     memory_fill_sig: Option<ir::SigRef>,
 
+    /// The external function signature for implementing wasm's `table.grow`.
+    table_grow_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.get`.
+    table_get_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.set`.
+    table_set_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `table.fill`.
+    table_fill_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's `ref.func`.
+    ref_func_sig: Option<ir::SigRef>,
+
+    /// The external function signatures for implementing wasm's
+    /// `memory.atomic.wait32` and `memory.atomic.wait64`.
+    memory_atomic_wait32_sig: Option<ir::SigRef>,
+    memory_atomic_wait64_sig: Option<ir::SigRef>,
+
+    /// The external function signature for implementing wasm's
+    /// `memory.atomic.notify`.
+    memory_atomic_notify_sig: Option<ir::SigRef>,
+
     /// Offsets to struct fields accessed by JIT code.
     offsets: VMOffsets,
+
+    /// Tunable parameters for the code being generated.
+    tunables: Tunables,
+
+    /// The variable holding the function's view of the store's fuel counter,
+    /// used when `tunables.consume_fuel` is enabled.
+    fuel_var: Variable,
+
+    /// Fuel consumed by operators translated since the last time it was
+    /// added to `fuel_var`.
+    fuel_consumed: i64,
 }
 
 impl<'module_environment> FuncEnvironment<'module_environment> {
     pub fn new(
         target_config: TargetFrontendConfig,
         module: &'module_environment ModuleLocal,
+        tunables: &Tunables,
     ) -> Self {
         Self {
             target_config,
@@ -140,7 +212,19 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
             elem_drop_sig: None,
             memory_copy_sig: None,
             memory_fill_sig: None,
+            table_grow_sig: None,
+            table_get_sig: None,
+            table_set_sig: None,
+            table_fill_sig: None,
+            ref_func_sig: None,
+            memory_atomic_wait32_sig: None,
+            memory_atomic_wait64_sig: None,
+            memory_atomic_notify_sig: None,
             offsets: VMOffsets::new(target_config.pointer_bytes(), module),
+            tunables: tunables.clone(),
+            // Replaced in `after_locals` once the number of locals is known.
+            fuel_var: Variable::new(0),
+            fuel_consumed: 0,
         }
     }
 
@@ -423,6 +507,359 @@ impl<'module_environment> FuncEnvironment<'module_environment> {
         }
     }
 
+    fn get_table_grow_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_grow_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Number of elements to grow by.
+                    AbiParam::new(I32),
+                    // Initial value of the new elements.
+                    AbiParam::new(self.reference_type()),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_grow_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_grow_func(&mut self, func: &mut Function) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_table_grow_sig(func);
+        (sig, BuiltinFunctionIndex::get_table_grow_index())
+    }
+
+    fn get_table_get_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_get_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Index within the table.
+                    AbiParam::new(I32),
+                    // Source location.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(self.reference_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_get_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_get_func(&mut self, func: &mut Function) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_table_get_sig(func);
+        (sig, BuiltinFunctionIndex::get_table_get_index())
+    }
+
+    fn get_table_set_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_set_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Index within the table.
+                    AbiParam::new(I32),
+                    // Value.
+                    AbiParam::new(self.reference_type()),
+                    // Source location.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_set_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_set_func(&mut self, func: &mut Function) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_table_set_sig(func);
+        (sig, BuiltinFunctionIndex::get_table_set_index())
+    }
+
+    fn get_table_fill_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.table_fill_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Table index.
+                    AbiParam::new(I32),
+                    // Destination index within the table.
+                    AbiParam::new(I32),
+                    // Value.
+                    AbiParam::new(self.reference_type()),
+                    // Number of elements to fill.
+                    AbiParam::new(I32),
+                    // Source location.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.table_fill_sig = Some(sig);
+        sig
+    }
+
+    fn get_table_fill_func(&mut self, func: &mut Function) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_table_fill_sig(func);
+        (sig, BuiltinFunctionIndex::get_table_fill_index())
+    }
+
+    fn get_ref_func_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.ref_func_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Function index.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(self.reference_type())],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.ref_func_sig = Some(sig);
+        sig
+    }
+
+    fn get_ref_func_func(&mut self, func: &mut Function) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_ref_func_sig(func);
+        (sig, BuiltinFunctionIndex::get_ref_func_index())
+    }
+
+    fn get_memory_atomic_wait_sig(&mut self, func: &mut Function, ty: ir::Type) -> ir::SigRef {
+        let cached = if ty == I32 {
+            self.memory_atomic_wait32_sig
+        } else {
+            self.memory_atomic_wait64_sig
+        };
+        let sig = cached.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Native address of the awaited value.
+                    AbiParam::new(self.pointer_type()),
+                    // Expected value.
+                    AbiParam::new(ty),
+                    // Timeout in nanoseconds.
+                    AbiParam::new(I64),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        if ty == I32 {
+            self.memory_atomic_wait32_sig = Some(sig);
+        } else {
+            self.memory_atomic_wait64_sig = Some(sig);
+        }
+        sig
+    }
+
+    fn get_memory_atomic_wait_func(
+        &mut self,
+        func: &mut Function,
+        ty: ir::Type,
+    ) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_memory_atomic_wait_sig(func, ty);
+        if ty == I32 {
+            (sig, BuiltinFunctionIndex::get_memory_atomic_wait32_index())
+        } else {
+            (sig, BuiltinFunctionIndex::get_memory_atomic_wait64_index())
+        }
+    }
+
+    fn get_memory_atomic_notify_sig(&mut self, func: &mut Function) -> ir::SigRef {
+        let sig = self.memory_atomic_notify_sig.unwrap_or_else(|| {
+            func.import_signature(Signature {
+                params: vec![
+                    AbiParam::special(self.pointer_type(), ArgumentPurpose::VMContext),
+                    // Native address of the notified location.
+                    AbiParam::new(self.pointer_type()),
+                    // Maximum number of waiters to wake.
+                    AbiParam::new(I32),
+                ],
+                returns: vec![AbiParam::new(I32)],
+                call_conv: self.target_config.default_call_conv,
+            })
+        });
+        self.memory_atomic_notify_sig = Some(sig);
+        sig
+    }
+
+    fn get_memory_atomic_notify_func(
+        &mut self,
+        func: &mut Function,
+    ) -> (ir::SigRef, BuiltinFunctionIndex) {
+        let sig = self.get_memory_atomic_notify_sig(func);
+        (sig, BuiltinFunctionIndex::get_memory_atomic_notify_index())
+    }
+
+    /// Emits a check of the `VMInterrupts` flag shared with this instance's
+    /// store, trapping with `TrapCode::Interrupt` if it has been set.
+    fn translate_interrupt_check(&mut self, pos: &mut FuncCursor<'_>) {
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(&mut pos.func);
+        let base = pos.ins().global_value(pointer_type, vmctx);
+
+        let mut mem_flags = ir::MemFlags::trusted();
+        mem_flags.set_readonly();
+        let interrupts_offset = i32::try_from(self.offsets.vmctx_interrupts()).unwrap();
+        let interrupts = pos
+            .ins()
+            .load(pointer_type, mem_flags, base, interrupts_offset);
+
+        // The flag itself is written by other threads, so it must not be
+        // treated as readonly.
+        let interrupted_offset = i32::from(self.offsets.vminterrupts_interrupted());
+        let interrupted = pos.ins().load(
+            pointer_type,
+            ir::MemFlags::trusted(),
+            interrupts,
+            interrupted_offset,
+        );
+        pos.ins().trapnz(interrupted, ir::TrapCode::Interrupt);
+    }
+
+    /// Returns the address of the store's `VMInterrupts`.
+    fn interrupts_ptr(&mut self, builder: &mut FunctionBuilder) -> ir::Value {
+        let pointer_type = self.pointer_type();
+        let vmctx = self.vmctx(&mut builder.func);
+        let base = builder.ins().global_value(pointer_type, vmctx);
+        let mut mem_flags = ir::MemFlags::trusted();
+        mem_flags.set_readonly();
+        let offset = i32::try_from(self.offsets.vmctx_interrupts()).unwrap();
+        builder.ins().load(pointer_type, mem_flags, base, offset)
+    }
+
+    /// Charges fuel for `op`, flushing the fuel counted so far into
+    /// `fuel_var` at control flow boundaries and writing it back to memory
+    /// before control leaves this function.
+    fn fuel_before_op(&mut self, op: &Operator, builder: &mut FunctionBuilder, reachable: bool) {
+        if !reachable {
+            debug_assert_eq!(self.fuel_consumed, 0);
+            return;
+        }
+
+        self.fuel_consumed += match op {
+            // Operators which don't do any work of their own are free.
+            Operator::Nop | Operator::Drop => 0,
+            Operator::Block { .. }
+            | Operator::Loop { .. }
+            | Operator::Unreachable
+            | Operator::Return
+            | Operator::Else
+            | Operator::End => 0,
+            _ => 1,
+        };
+
+        match op {
+            // Calls, returns and traps hand control to other code which may
+            // consume or inspect fuel, so the in-memory counter must be up to
+            // date beforehand.
+            Operator::Unreachable
+            | Operator::Return
+            | Operator::Call { .. }
+            | Operator::CallIndirect { .. } => {
+                self.fuel_increment_var(builder);
+                self.fuel_save_from_var(builder);
+            }
+
+            // Anything which ends a basic block flushes the fuel counted so
+            // far, so that each block is charged exactly once.
+            Operator::Loop { .. }
+            | Operator::If { .. }
+            | Operator::Br { .. }
+            | Operator::BrIf { .. }
+            | Operator::BrTable { .. }
+            | Operator::End
+            | Operator::Else => {
+                self.fuel_increment_var(builder);
+            }
+
+            _ => {}
+        }
+    }
+
+    fn fuel_after_op(&mut self, op: &Operator, builder: &mut FunctionBuilder) {
+        match op {
+            // The callee may have consumed fuel, so pick up its changes.
+            Operator::Call { .. } | Operator::CallIndirect { .. } => {
+                self.fuel_load_into_var(builder);
+            }
+
+            // Every iteration of a loop is checked so that loops can't run
+            // forever without running out of fuel.
+            Operator::Loop { .. } => {
+                self.fuel_check(builder);
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Adds the fuel consumed since the last flush to `fuel_var`.
+    fn fuel_increment_var(&mut self, builder: &mut FunctionBuilder) {
+        let consumption = mem::replace(&mut self.fuel_consumed, 0);
+        if consumption == 0 {
+            return;
+        }
+
+        let fuel = builder.use_var(self.fuel_var);
+        let fuel = builder.ins().iadd_imm(fuel, consumption);
+        builder.def_var(self.fuel_var, fuel);
+    }
+
+    /// Loads the store's fuel counter into `fuel_var`.
+    fn fuel_load_into_var(&mut self, builder: &mut FunctionBuilder) {
+        let interrupts = self.interrupts_ptr(builder);
+        let offset = i32::from(self.offsets.vminterrupts_fuel_consumed());
+        let fuel = builder
+            .ins()
+            .load(I64, ir::MemFlags::trusted(), interrupts, offset);
+        builder.def_var(self.fuel_var, fuel);
+    }
+
+    /// Stores `fuel_var` back into the store's fuel counter.
+    fn fuel_save_from_var(&mut self, builder: &mut FunctionBuilder) {
+        let interrupts = self.interrupts_ptr(builder);
+        let offset = i32::from(self.offsets.vminterrupts_fuel_consumed());
+        let fuel = builder.use_var(self.fuel_var);
+        builder
+            .ins()
+            .store(ir::MemFlags::trusted(), fuel, interrupts, offset);
+    }
+
+    /// Traps with `TrapCode::OutOfFuel` if `fuel_var` shows that all fuel has
+    /// been consumed, saving it to memory first so the store can observe it.
+    fn fuel_check(&mut self, builder: &mut FunctionBuilder) {
+        self.fuel_increment_var(builder);
+        let out_of_fuel = builder.create_block();
+        let continuation = builder.create_block();
+
+        let fuel = builder.use_var(self.fuel_var);
+        let exhausted = builder
+            .ins()
+            .icmp_imm(IntCC::SignedGreaterThanOrEqual, fuel, 0);
+        builder.ins().brnz(exhausted, out_of_fuel, &[]);
+        builder.ins().jump(continuation, &[]);
+        builder.seal_block(out_of_fuel);
+
+        builder.switch_to_block(out_of_fuel);
+        self.fuel_save_from_var(builder);
+        builder.ins().trap(ir::TrapCode::OutOfFuel);
+
+        builder.switch_to_block(continuation);
+        builder.seal_block(continuation);
+    }
+
     /// Translates load of builtin function and returns a pair of values `vmctx`
     /// and address of the loaded function.
     fn translate_load_builtin_function_address(
@@ -651,60 +1088,117 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
 
     fn translate_table_grow(
         &mut self,
-        _: cranelift_codegen::cursor::FuncCursor<'_>,
-        _: u32,
-        _: ir::Value,
-        _: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: u32,
+        delta: ir::Value,
+        init_value: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `table.grow` instruction is not supported yet".into(),
-        ))
+        let (func_sig, func_idx) = self.get_table_grow_func(&mut pos.func);
+
+        let table_index_arg = pos.ins().iconst(I32, table_index as i64);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        let call_inst = pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, delta, init_value],
+        );
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_table_get(
         &mut self,
-        _: cranelift_codegen::cursor::FuncCursor<'_>,
-        _: u32,
-        _: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: u32,
+        index: ir::Value,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `table.get` instruction is not supported yet".into(),
-        ))
+        let (func_sig, func_idx) = self.get_table_get_func(&mut pos.func);
+
+        let table_index_arg = pos.ins().iconst(I32, table_index as i64);
+
+        let src_loc = pos.srcloc();
+        let src_loc_arg = pos.ins().iconst(I32, src_loc.bits() as i64);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        let call_inst = pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, index, src_loc_arg],
+        );
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_table_set(
         &mut self,
-        _: cranelift_codegen::cursor::FuncCursor<'_>,
-        _: u32,
-        _: ir::Value,
-        _: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: u32,
+        value: ir::Value,
+        index: ir::Value,
     ) -> WasmResult<()> {
-        Err(WasmError::Unsupported(
-            "the `table.set` instruction is not supported yet".into(),
-        ))
+        let (func_sig, func_idx) = self.get_table_set_func(&mut pos.func);
+
+        let table_index_arg = pos.ins().iconst(I32, table_index as i64);
+
+        let src_loc = pos.srcloc();
+        let src_loc_arg = pos.ins().iconst(I32, src_loc.bits() as i64);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, index, value, src_loc_arg],
+        );
+
+        Ok(())
     }
 
     fn translate_table_fill(
         &mut self,
-        _: cranelift_codegen::cursor::FuncCursor<'_>,
-        _: u32,
-        _: ir::Value,
-        _: ir::Value,
-        _: ir::Value,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        table_index: u32,
+        dst: ir::Value,
+        val: ir::Value,
+        len: ir::Value,
     ) -> WasmResult<()> {
-        Err(WasmError::Unsupported(
-            "the `table.fill` instruction is not supported yet".into(),
-        ))
+        let (func_sig, func_idx) = self.get_table_fill_func(&mut pos.func);
+
+        let table_index_arg = pos.ins().iconst(I32, table_index as i64);
+
+        let src_loc = pos.srcloc();
+        let src_loc_arg = pos.ins().iconst(I32, src_loc.bits() as i64);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        pos.ins().call_indirect(
+            func_sig,
+            func_addr,
+            &[vmctx, table_index_arg, dst, val, len, src_loc_arg],
+        );
+
+        Ok(())
     }
 
     fn translate_ref_func(
         &mut self,
-        _: cranelift_codegen::cursor::FuncCursor<'_>,
-        _: u32,
+        mut pos: cranelift_codegen::cursor::FuncCursor<'_>,
+        func_index: u32,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "the `ref.func` instruction is not supported yet".into(),
-        ))
+        let (func_sig, func_idx) = self.get_ref_func_func(&mut pos.func);
+
+        let func_index_arg = pos.ins().iconst(I32, func_index as i64);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, func_index_arg]);
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
     }
 
     fn translate_custom_global_get(
@@ -1095,15 +1589,65 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
         ))
     }
 
+    fn translate_atomic_wait(
+        &mut self,
+        mut pos: FuncCursor,
+        index: MemoryIndex,
+        _heap: ir::Heap,
+        addr: ir::Value,
+        expected: ir::Value,
+        timeout: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        // Nothing could ever notify a thread waiting on a memory which isn't
+        // shared, so such waits always trap. A conditional trap on a constant
+        // is used since the translator still expects a result to follow.
+        if !self.module.memory_plans[index].memory.shared {
+            let zero = pos.ins().iconst(I32, 0);
+            pos.ins().trapz(zero, ir::TrapCode::HeapUnshared);
+            return Ok(pos.ins().iconst(I32, 0));
+        }
+
+        let ty = pos.func.dfg.value_type(expected);
+        let (func_sig, func_idx) = self.get_memory_atomic_wait_func(&mut pos.func, ty);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        let call_inst =
+            pos.ins()
+                .call_indirect(func_sig, func_addr, &[vmctx, addr, expected, timeout]);
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
+    fn translate_atomic_notify(
+        &mut self,
+        mut pos: FuncCursor,
+        _index: MemoryIndex,
+        _heap: ir::Heap,
+        addr: ir::Value,
+        count: ir::Value,
+    ) -> WasmResult<ir::Value> {
+        let (func_sig, func_idx) = self.get_memory_atomic_notify_func(&mut pos.func);
+
+        let (vmctx, func_addr) = self.translate_load_builtin_function_address(&mut pos, func_idx);
+
+        let call_inst = pos
+            .ins()
+            .call_indirect(func_sig, func_addr, &[vmctx, addr, count]);
+
+        Ok(*pos.func.dfg.inst_results(call_inst).first().unwrap())
+    }
+
     fn translate_table_size(
         &mut self,
-        _pos: FuncCursor,
+        mut pos: FuncCursor,
         _index: TableIndex,
-        _table: ir::Table,
+        table: ir::Table,
     ) -> WasmResult<ir::Value> {
-        Err(WasmError::Unsupported(
-            "bulk memory: `table.size`".to_string(),
-        ))
+        // The table's bound is kept current in its `VMTableDefinition`, so
+        // its size can be read directly.
+        let bound_gv = pos.func.tables[table].bound_gv;
+        Ok(pos.ins().global_value(I32, bound_gv))
     }
 
     fn translate_table_copy(
@@ -1195,4 +1739,66 @@ impl<'module_environment> cranelift_wasm::FuncEnvironment for FuncEnvironment<'m
 
         Ok(())
     }
+
+    fn translate_loop_header(&mut self, mut pos: FuncCursor) -> WasmResult<()> {
+        if self.tunables.interruptable {
+            self.translate_interrupt_check(&mut pos);
+        }
+        Ok(())
+    }
+
+    fn after_locals(&mut self, num_locals_defined: usize) {
+        self.fuel_var = Variable::new(num_locals_defined);
+    }
+
+    fn before_translate_function(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        _state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        if self.tunables.interruptable {
+            self.translate_interrupt_check(&mut builder.cursor());
+        }
+        if self.tunables.consume_fuel {
+            builder.declare_var(self.fuel_var, I64);
+            self.fuel_load_into_var(builder);
+            self.fuel_check(builder);
+        }
+        Ok(())
+    }
+
+    fn before_translate_operator(
+        &mut self,
+        op: &Operator,
+        builder: &mut FunctionBuilder,
+        state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        if self.tunables.consume_fuel {
+            self.fuel_before_op(op, builder, state.reachable());
+        }
+        Ok(())
+    }
+
+    fn after_translate_operator(
+        &mut self,
+        op: &Operator,
+        builder: &mut FunctionBuilder,
+        state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        if self.tunables.consume_fuel && state.reachable() {
+            self.fuel_after_op(op, builder);
+        }
+        Ok(())
+    }
+
+    fn after_translate_function(
+        &mut self,
+        builder: &mut FunctionBuilder,
+        state: &FuncTranslationState,
+    ) -> WasmResult<()> {
+        if self.tunables.consume_fuel && state.reachable() {
+            self.fuel_save_from_var(builder);
+        }
+        Ok(())
+    }
 }
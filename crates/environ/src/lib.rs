@@ -48,7 +48,7 @@ pub use crate::cache::CacheConfig;
 pub use crate::compilation::{
     Compilation, CompileError, CompiledFunction, CompiledFunctionUnwindInfo,
     CompiledFunctionUnwindInfoReloc, Compiler, Relocation, RelocationTarget, Relocations,
-    TrapInformation, Traps,
+    StackMapInformation, StackMaps, TrapInformation, Traps,
 };
 pub use crate::cranelift::Cranelift;
 pub use crate::data_structures::*;
@@ -56,7 +56,8 @@ pub use crate::func_environ::BuiltinFunctionIndex;
 #[cfg(feature = "lightbeam")]
 pub use crate::lightbeam::Lightbeam;
 pub use crate::module::{
-    Export, MemoryPlan, MemoryStyle, Module, ModuleLocal, TableElements, TablePlan, TableStyle,
+    Export, MemoryPlan, MemoryStyle, Module, ModuleLocal, ProducersField, ProducersValue,
+    TableElements, TablePlan, TableStyle,
 };
 pub use crate::module_environ::{
     translate_signature, DataInitializer, DataInitializerLocation, FunctionBodyData,
@@ -1,14 +1,14 @@
 //! Support for compiling with Lightbeam.
 
 use crate::cache::ModuleCacheDataTupleType;
-use crate::compilation::{Compilation, CompileError, Traps};
+use crate::compilation::{Compilation, CompileError, StackMaps, Traps};
 use crate::func_environ::FuncEnvironment;
 use crate::module::Module;
 use crate::module_environ::FunctionBodyData;
 // TODO: Put this in `compilation`
 use crate::address_map::{ModuleAddressMap, ValueLabelsRanges};
 use crate::cranelift::RelocSink;
-use crate::CacheConfig;
+use crate::{CacheConfig, Tunables};
 use cranelift_codegen::isa;
 use cranelift_entity::{PrimaryMap, SecondaryMap};
 use cranelift_wasm::{DefinedFuncIndex, ModuleTranslationState};
@@ -26,13 +26,19 @@ impl crate::compilation::Compiler for Lightbeam {
         isa: &dyn isa::TargetIsa,
         // TODO
         generate_debug_info: bool,
+        tunables: &Tunables,
         _cache_config: &CacheConfig,
     ) -> Result<ModuleCacheDataTupleType, CompileError> {
         if generate_debug_info {
             return Err(CompileError::DebugInfoNotSupported);
         }
+        // Lightbeam doesn't check the interrupt flag in loop headers and
+        // function prologues, so its code couldn't be interrupted.
+        if tunables.interruptable {
+            return Err(CompileError::InterruptsNotSupported);
+        }
 
-        let env = FuncEnvironment::new(isa.frontend_config(), &module.local);
+        let env = FuncEnvironment::new(isa.frontend_config(), &module.local, tunables);
         let mut relocations = PrimaryMap::new();
         let mut codegen_session: lightbeam::CodeGenSession<_> =
             lightbeam::CodeGenSession::new(function_body_inputs.len() as u32, &env);
@@ -70,6 +76,7 @@ impl crate::compilation::Compiler for Lightbeam {
             ValueLabelsRanges::new(),
             PrimaryMap::new(),
             Traps::new(),
+            StackMaps::new(),
         ))
     }
 }
@@ -11,7 +11,9 @@ use cranelift_wasm::{
 use indexmap::IndexMap;
 use more_asserts::assert_ge;
 use std::collections::HashMap;
+use std::ops::Range;
 use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use wasmparser::ProducersSectionReader;
 
 /// A WebAssembly table initializer.
 #[derive(Clone, Debug, Hash)]
@@ -69,6 +71,16 @@ impl MemoryStyle {
             );
         }
 
+        // Shared memories can't be moved while other threads may be accessing
+        // them, so they always reserve enough address space for their
+        // (mandatory) declared maximum.
+        if memory.shared {
+            return (
+                Self::Static { bound: maximum },
+                tunables.static_memory_offset_guard_size,
+            );
+        }
+
         // Otherwise, make it dynamic.
         (Self::Dynamic, tunables.dynamic_memory_offset_guard_size)
     }
@@ -168,8 +180,19 @@ pub struct Module {
     /// WebAssembly passive elements.
     pub passive_elements: HashMap<PassiveElemIndex, Box<[FuncIndex]>>,
 
-    /// WebAssembly table initializers.
+    /// Function names from the name section.
     pub func_names: HashMap<FuncIndex, String>,
+
+    /// Names of function locals from the name section, keyed by function
+    /// and then by local index.
+    pub local_names: HashMap<FuncIndex, HashMap<u32, String>>,
+
+    /// The fields of the `producers` section, which are empty if there's no
+    /// such section or it's malformed.
+    pub producers: Vec<ProducersField>,
+
+    /// Byte ranges of the bodies of defined functions within the module.
+    pub func_body_ranges: PrimaryMap<DefinedFuncIndex, Range<usize>>,
 }
 
 /// Local information known about a wasm module, the bare minimum necessary to
@@ -224,6 +247,9 @@ impl Module {
             table_elements: Vec::new(),
             passive_elements: HashMap::new(),
             func_names: HashMap::new(),
+            local_names: HashMap::new(),
+            producers: Vec::new(),
+            func_body_ranges: PrimaryMap::new(),
             local: ModuleLocal {
                 num_imported_funcs: 0,
                 num_imported_tables: 0,
@@ -333,3 +359,66 @@ impl ModuleLocal {
         index.index() < self.num_imported_globals
     }
 }
+
+/// Reads the fields of a `producers` section.
+pub(crate) fn read_producers(data: &[u8]) -> wasmparser::Result<Vec<ProducersField>> {
+    let mut reader = ProducersSectionReader::new(data, 0)?;
+    let mut fields = Vec::with_capacity(reader.get_count() as usize);
+    for _ in 0..reader.get_count() {
+        let field = reader.read()?;
+        let values = field
+            .get_producer_field_values_reader()?
+            .into_iter()
+            .map(|value| {
+                value.map(|value| ProducersValue {
+                    name: value.name.to_string(),
+                    version: value.version.to_string(),
+                })
+            })
+            .collect::<wasmparser::Result<_>>()?;
+        fields.push(ProducersField {
+            name: field.name.to_string(),
+            values,
+        });
+    }
+    Ok(fields)
+}
+
+/// A field of the `producers` section of a [`Module`], such as `language`
+/// or `processed-by`, along with the tools it lists.
+#[derive(Debug, Clone)]
+pub struct ProducersField {
+    name: String,
+    values: Vec<ProducersValue>,
+}
+
+impl ProducersField {
+    /// Returns the name of this field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the values listed under this field.
+    pub fn values(&self) -> &[ProducersValue] {
+        &self.values
+    }
+}
+
+/// A tool or language, and its version, listed in a [`ProducersField`].
+#[derive(Debug, Clone)]
+pub struct ProducersValue {
+    name: String,
+    version: String,
+}
+
+impl ProducersValue {
+    /// Returns the name of the tool or language.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the version of the tool or language.
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+}
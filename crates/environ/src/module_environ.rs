@@ -1,5 +1,5 @@
 use crate::func_environ::FuncEnvironment;
-use crate::module::{Export, MemoryPlan, Module, TableElements, TablePlan};
+use crate::module::{read_producers, Export, MemoryPlan, Module, TableElements, TablePlan};
 use crate::tunables::Tunables;
 use cranelift_codegen::ir;
 use cranelift_codegen::ir::{AbiParam, ArgumentPurpose};
@@ -10,6 +10,7 @@ use cranelift_wasm::{
     ModuleTranslationState, PassiveDataIndex, PassiveElemIndex, SignatureIndex, Table, TableIndex,
     TargetEnvironment, WasmError, WasmResult,
 };
+use std::collections::HashMap;
 use std::convert::TryFrom;
 
 /// Contains function data: byte code and its offset in the module.
@@ -48,7 +49,7 @@ pub struct ModuleTranslation<'data> {
 impl<'data> ModuleTranslation<'data> {
     /// Return a new `FuncEnvironment` for translating a function.
     pub fn func_env(&self) -> FuncEnvironment<'_> {
-        FuncEnvironment::new(self.target_config, &self.module.local)
+        FuncEnvironment::new(self.target_config, &self.module.local, &self.tunables)
     }
 }
 
@@ -358,6 +359,10 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
         body_bytes: &'data [u8],
         body_offset: usize,
     ) -> WasmResult<()> {
+        self.result
+            .module
+            .func_body_ranges
+            .push(body_offset..body_offset + body_bytes.len());
         self.result.function_body_inputs.push(FunctionBodyData {
             data: body_bytes,
             module_offset: body_offset,
@@ -412,6 +417,29 @@ impl<'data> cranelift_wasm::ModuleEnvironment<'data> for ModuleEnvironment<'data
             .insert(func_index, name.to_string());
         Ok(())
     }
+
+    fn declare_local_name(
+        &mut self,
+        func_index: FuncIndex,
+        local_index: u32,
+        name: &'data str,
+    ) -> WasmResult<()> {
+        self.result
+            .module
+            .local_names
+            .entry(func_index)
+            .or_insert_with(HashMap::new)
+            .insert(local_index, name.to_string());
+        Ok(())
+    }
+
+    fn custom_section(&mut self, name: &'data str, data: &'data [u8]) -> WasmResult<()> {
+        // Per spec, invalid custom sections are ignored.
+        if name == "producers" && self.result.module.producers.is_empty() {
+            self.result.module.producers = read_producers(data).unwrap_or_default();
+        }
+        Ok(())
+    }
 }
 
 /// Add environment-specific function parameters.
@@ -1,5 +1,5 @@
 /// Tunable parameters for WebAssembly compilation.
-#[derive(Clone)]
+#[derive(Clone, Debug, Hash)]
 pub struct Tunables {
     /// For static heaps, the size in wasm pages of the heap protected by bounds checking.
     pub static_memory_bound: u32,
@@ -9,6 +9,14 @@ pub struct Tunables {
 
     /// The size in bytes of the offset guard for dynamic heaps.
     pub dynamic_memory_offset_guard_size: u64,
+
+    /// Whether or not to emit checks at function entries and loop headers
+    /// which allow running code to be interrupted from another thread.
+    pub interruptable: bool,
+
+    /// Whether or not fuel is consumed by the generated code, trapping once
+    /// all of it has been used up.
+    pub consume_fuel: bool,
 }
 
 impl Default for Tunables {
@@ -39,6 +47,9 @@ impl Default for Tunables {
             /// Allocate a small guard to optimize common cases but without
             /// wasting too much memor.
             dynamic_memory_offset_guard_size: 0x1_0000,
+
+            interruptable: false,
+            consume_fuel: false,
         }
     }
 }
@@ -251,11 +251,39 @@ impl VMOffsets {
     }
 }
 
+/// Offsets for `VMInterrupts`.
+impl VMOffsets {
+    /// The offset of the `interrupted` field.
+    #[allow(clippy::erasing_op)]
+    pub fn vminterrupts_interrupted(&self) -> u8 {
+        0 * self.pointer_size
+    }
+
+    /// The offset of the `fuel_consumed` field, which is kept 8-byte aligned
+    /// even on targets with 4-byte pointers.
+    pub fn vminterrupts_fuel_consumed(&self) -> u8 {
+        align(u32::from(self.pointer_size), 8) as u8
+    }
+
+    /// Return the size of `VMInterrupts`.
+    pub fn size_of_vminterrupts(&self) -> u8 {
+        self.vminterrupts_fuel_consumed() + 8
+    }
+}
+
 /// Offsets for `VMContext`.
 impl VMOffsets {
+    /// The offset of the pointer to the `VMInterrupts` shared by this
+    /// instance's store.
+    pub fn vmctx_interrupts(&self) -> u32 {
+        0
+    }
+
     /// The offset of the `signature_ids` array.
     pub fn vmctx_signature_ids_begin(&self) -> u32 {
-        0
+        self.vmctx_interrupts()
+            .checked_add(u32::from(self.pointer_size))
+            .unwrap()
     }
 
     /// The offset of the `tables` array.
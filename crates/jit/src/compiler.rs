@@ -2,28 +2,29 @@
 
 use crate::code_memory::CodeMemory;
 use crate::instantiate::SetupError;
-use crate::target_tunables::target_tunables;
 use cranelift_codegen::ir::InstBuilder;
 use cranelift_codegen::print_errors::pretty_error;
 use cranelift_codegen::Context;
 use cranelift_codegen::{binemit, ir};
 use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
 use cranelift_wasm::ModuleTranslationState;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::convert::TryFrom;
+use std::sync::Arc;
 use wasmtime_debug::{emit_debugsections_image, DebugInfoData};
 use wasmtime_environ::entity::{EntityRef, PrimaryMap};
 use wasmtime_environ::isa::{TargetFrontendConfig, TargetIsa};
 use wasmtime_environ::wasm::{DefinedFuncIndex, DefinedMemoryIndex, MemoryIndex};
 use wasmtime_environ::{
     CacheConfig, Compilation, CompileError, CompiledFunction, CompiledFunctionUnwindInfo,
-    Compiler as _C, FunctionBodyData, Module, ModuleMemoryOffset, ModuleVmctxInfo, Relocations,
-    Traps, Tunables, VMOffsets,
+    Compiler as _C, FunctionBodyData, Module, ModuleAddressMap, ModuleMemoryOffset,
+    ModuleVmctxInfo, Relocations, StackMaps, Traps, Tunables, VMOffsets, ValueLabelsRanges,
 };
 use wasmtime_profiling::ProfilingAgent;
 use wasmtime_runtime::{
-    InstantiationError, SignatureRegistry, TrapRegistration, TrapRegistry, VMFunctionBody,
-    VMSharedSignatureIndex,
+    InstantiationError, SignatureRegistry, StackMapRegistration, StackMapRegistry,
+    TrapRegistration, TrapRegistry, VMFunctionBody, VMInterrupts, VMSharedSignatureIndex,
 };
 
 /// Select which kind of compilation to use.
@@ -40,6 +41,23 @@ pub enum CompilationStrategy {
     Lightbeam,
 }
 
+/// Everything produced by compiling the function bodies of a module: the
+/// machine code along with the relocations, traps, stack maps, and debug
+/// metadata that accompany it.
+///
+/// This is all that's needed to load the module's code again later without
+/// compiling it anew.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct CompilationArtifacts {
+    pub compilation: Compilation,
+    pub relocations: Relocations,
+    pub address_transform: ModuleAddressMap,
+    pub value_ranges: ValueLabelsRanges,
+    pub stack_slots: PrimaryMap<DefinedFuncIndex, ir::StackSlots>,
+    pub traps: Traps,
+    pub stack_maps: StackMaps,
+}
+
 /// A WebAssembly code JIT compiler.
 ///
 /// A `Compiler` instance owns the executable memory that it allocates.
@@ -53,10 +71,15 @@ pub struct Compiler {
 
     code_memory: CodeMemory,
     trap_registry: TrapRegistry,
+    stack_map_registry: StackMapRegistry,
     trampoline_park: HashMap<VMSharedSignatureIndex, *const VMFunctionBody>,
+    host_trampoline_park:
+        HashMap<(VMSharedSignatureIndex, *const VMFunctionBody), *mut [VMFunctionBody]>,
     signatures: SignatureRegistry,
     strategy: CompilationStrategy,
     cache_config: CacheConfig,
+    tunables: Tunables,
+    interrupts: Arc<VMInterrupts>,
 
     /// The `FunctionBuilderContext`, shared between trampline function compilations.
     fn_builder_ctx: FunctionBuilderContext,
@@ -68,16 +91,21 @@ impl Compiler {
         isa: Box<dyn TargetIsa>,
         strategy: CompilationStrategy,
         cache_config: CacheConfig,
+        tunables: Tunables,
     ) -> Self {
         Self {
             isa,
             code_memory: CodeMemory::new(),
             trampoline_park: HashMap::new(),
+            host_trampoline_park: HashMap::new(),
             signatures: SignatureRegistry::new(),
             fn_builder_ctx: FunctionBuilderContext::new(),
             strategy,
             trap_registry: TrapRegistry::default(),
+            stack_map_registry: StackMapRegistry::default(),
             cache_config,
+            tunables,
+            interrupts: Arc::new(VMInterrupts::default()),
         }
     }
 }
@@ -90,63 +118,99 @@ impl Compiler {
 
     /// Return the tunables in use by this engine.
     pub fn tunables(&self) -> Tunables {
-        target_tunables(self.isa.triple())
+        self.tunables.clone()
     }
 
     /// Compile the given function bodies.
     pub(crate) fn compile<'data>(
-        &mut self,
+        &self,
         module: &Module,
         module_translation: &ModuleTranslationState,
         function_body_inputs: PrimaryMap<DefinedFuncIndex, FunctionBodyData<'data>>,
+        generate_debug_info: bool,
+    ) -> Result<CompilationArtifacts, SetupError> {
+        let (
+            compilation,
+            relocations,
+            address_transform,
+            value_ranges,
+            stack_slots,
+            traps,
+            stack_maps,
+        ) = match self.strategy {
+            // For now, interpret `Auto` as `Cranelift` since that's the most stable
+            // implementation.
+            CompilationStrategy::Auto | CompilationStrategy::Cranelift => {
+                wasmtime_environ::cranelift::Cranelift::compile_module(
+                    module,
+                    module_translation,
+                    function_body_inputs,
+                    &*self.isa,
+                    generate_debug_info,
+                    &self.tunables,
+                    &self.cache_config,
+                )
+            }
+            #[cfg(feature = "lightbeam")]
+            CompilationStrategy::Lightbeam => {
+                wasmtime_environ::lightbeam::Lightbeam::compile_module(
+                    module,
+                    module_translation,
+                    function_body_inputs,
+                    &*self.isa,
+                    generate_debug_info,
+                    &self.tunables,
+                    &self.cache_config,
+                )
+            }
+        }
+        .map_err(SetupError::Compile)?;
+
+        Ok(CompilationArtifacts {
+            compilation,
+            relocations,
+            address_transform,
+            value_ranges,
+            stack_slots,
+            traps,
+            stack_maps,
+        })
+    }
+
+    /// Copy the compiled function bodies in `artifacts` into executable
+    /// memory, register their traps and stack maps, and emit debug info if
+    /// `debug_data` is provided.
+    pub(crate) fn load(
+        &mut self,
+        module: &Module,
+        artifacts: &CompilationArtifacts,
         debug_data: Option<DebugInfoData>,
     ) -> Result<
         (
             PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
             PrimaryMap<DefinedFuncIndex, ir::JumpTableOffsets>,
-            Relocations,
             Option<Vec<u8>>,
             TrapRegistration,
+            StackMapRegistration,
         ),
         SetupError,
     > {
-        let (compilation, relocations, address_transform, value_ranges, stack_slots, traps) =
-            match self.strategy {
-                // For now, interpret `Auto` as `Cranelift` since that's the most stable
-                // implementation.
-                CompilationStrategy::Auto | CompilationStrategy::Cranelift => {
-                    wasmtime_environ::cranelift::Cranelift::compile_module(
-                        module,
-                        module_translation,
-                        function_body_inputs,
-                        &*self.isa,
-                        debug_data.is_some(),
-                        &self.cache_config,
-                    )
-                }
-                #[cfg(feature = "lightbeam")]
-                CompilationStrategy::Lightbeam => {
-                    wasmtime_environ::lightbeam::Lightbeam::compile_module(
-                        module,
-                        module_translation,
-                        function_body_inputs,
-                        &*self.isa,
-                        debug_data.is_some(),
-                        &self.cache_config,
-                    )
-                }
-            }
-            .map_err(SetupError::Compile)?;
-
+        let compilation = &artifacts.compilation;
         let allocated_functions =
-            allocate_functions(&mut self.code_memory, &compilation).map_err(|message| {
+            allocate_functions(&mut self.code_memory, compilation).map_err(|message| {
                 SetupError::Instantiate(InstantiationError::Resource(format!(
                     "failed to allocate memory for functions: {}",
                     message
                 )))
             })?;
 
-        let trap_registration = register_traps(&allocated_functions, &traps, &self.trap_registry);
+        let trap_registration =
+            register_traps(&allocated_functions, &artifacts.traps, &self.trap_registry);
+        let stack_map_registration = register_stack_maps(
+            &allocated_functions,
+            &artifacts.stack_maps,
+            &self.stack_map_registry,
+        );
 
         // Translate debug info (DWARF) only if at least one function is present.
         let dbg = if debug_data.is_some() && !allocated_functions.is_empty() {
@@ -170,7 +234,7 @@ impl Compiler {
                     } else {
                         ModuleMemoryOffset::None
                     },
-                    stack_slots,
+                    stack_slots: artifacts.stack_slots.clone(),
                 }
             };
             let bytes = emit_debugsections_image(
@@ -178,8 +242,8 @@ impl Compiler {
                 target_config,
                 debug_data.as_ref().unwrap(),
                 &module_vmctx_info,
-                &address_transform,
-                &value_ranges,
+                &artifacts.address_transform,
+                &artifacts.value_ranges,
                 &funcs,
             )
             .map_err(SetupError::DebugInfo)?;
@@ -193,12 +257,29 @@ impl Compiler {
         Ok((
             allocated_functions,
             jt_offsets,
-            relocations,
             dbg,
             trap_registration,
+            stack_map_registration,
         ))
     }
 
+    /// Returns a description of everything about this compiler that affects
+    /// the code it produces: the crate version, target, flags, strategy, and
+    /// tunables, including any adjustments made for the pooling allocator.
+    ///
+    /// Compiled code may only be loaded by a compiler with the same
+    /// description as the one that produced it.
+    pub(crate) fn compatibility_key(&self) -> String {
+        format!(
+            "wasmtime-jit {}\n{}\n{}\n{:?}\n{:?}",
+            crate::VERSION,
+            self.isa.triple(),
+            self.isa,
+            self.strategy,
+            self.tunables,
+        )
+    }
+
     /// Create a trampoline for invoking a function.
     pub(crate) fn get_trampoline(
         &mut self,
@@ -231,6 +312,25 @@ impl Compiler {
         Ok(result)
     }
 
+    /// Returns the trampoline through which wasm calls the host function
+    /// `func` with `signature`, creating it with `make` the first time it's
+    /// requested. The trampoline is published before it's returned.
+    pub fn get_host_trampoline(
+        &mut self,
+        signature: &ir::Signature,
+        func: *const VMFunctionBody,
+        make: impl FnOnce(&mut CodeMemory, &mut FunctionBuilderContext) -> *mut [VMFunctionBody],
+    ) -> *mut [VMFunctionBody] {
+        let index = self.signatures.register(signature);
+        if let Some(trampoline) = self.host_trampoline_park.get(&(index, func)) {
+            return *trampoline;
+        }
+        let body = make(&mut self.code_memory, &mut self.fn_builder_ctx);
+        self.publish_compiled_code();
+        self.host_trampoline_park.insert((index, func), body);
+        body
+    }
+
     /// Make memory containing compiled code executable.
     pub(crate) fn publish_compiled_code(&mut self) {
         self.code_memory.publish();
@@ -255,6 +355,17 @@ impl Compiler {
     pub fn trap_registry(&self) -> &TrapRegistry {
         &self.trap_registry
     }
+
+    /// Shared registration of the stack maps of compiled functions
+    pub fn stack_map_registry(&self) -> &StackMapRegistry {
+        &self.stack_map_registry
+    }
+
+    /// Shared flag used to interrupt code running in instances created with
+    /// this compiler.
+    pub fn interrupts(&self) -> &Arc<VMInterrupts> {
+        &self.interrupts
+    }
 }
 
 /// Create a trampoline for invoking a function.
@@ -411,6 +522,23 @@ fn register_traps(
     registry.register_traps(traps)
 }
 
+fn register_stack_maps(
+    allocated_functions: &PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
+    stack_maps: &StackMaps,
+    registry: &StackMapRegistry,
+) -> StackMapRegistration {
+    let stack_maps =
+        allocated_functions
+            .values()
+            .zip(stack_maps.values())
+            .map(|(func, func_stack_maps)| {
+                let func = unsafe { &**func };
+                let start = func.as_ptr() as usize;
+                (start..start + func.len(), func_stack_maps.as_slice())
+            });
+    registry.register_stack_maps(stack_maps)
+}
+
 /// We don't expect trampoline compilation to produce any relocations, so
 /// this `RelocSink` just asserts that it doesn't recieve any.
 struct RelocSink {}
@@ -3,14 +3,16 @@
 //! `CompiledModule` to allow compiling and instantiating to be done as separate
 //! steps.
 
-use crate::compiler::Compiler;
+use crate::compiler::{CompilationArtifacts, Compiler};
 use crate::imports::resolve_imports;
 use crate::link::link_module;
 use crate::resolver::Resolver;
+use serde::{Deserialize, Serialize};
 use std::io::Write;
 use std::rc::Rc;
 use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use wasmparser::OperatorValidatorConfig;
 use wasmtime_debug::read_debuginfo;
 use wasmtime_environ::entity::{BoxedSlice, PrimaryMap};
 use wasmtime_environ::wasm::{DefinedFuncIndex, SignatureIndex};
@@ -19,7 +21,9 @@ use wasmtime_environ::{
 };
 use wasmtime_profiling::ProfilingAgent;
 use wasmtime_runtime::{
-    GdbJitImageRegistration, InstanceHandle, InstantiationError, TrapRegistration, VMFunctionBody,
+    GdbJitImageRegistration, InstanceHandle, InstancePool, InstantiationError, ModuleMemoryImages,
+    ResourceLimiter, RuntimeMemoryCreator, StackMapRegistration, StackMapRegistry,
+    TrapRegistration, VMExternRefActivationsTable, VMFunctionBody, VMInterrupts,
     VMSharedSignatureIndex,
 };
 
@@ -43,6 +47,73 @@ pub enum SetupError {
     /// Debug information generation error occured.
     #[error("Debug information error")]
     DebugInfo(#[from] anyhow::Error),
+
+    /// A serialized module is malformed, or was produced by an incompatible
+    /// compiler configuration.
+    #[error("Failed to deserialize module: {0}")]
+    Deserialize(String),
+}
+
+/// Leading bytes of every serialized `CompiledModule`.
+const SERIALIZED_MAGIC: &[u8] = b"\0wasmtime-module";
+
+/// A `CompiledModule` in serialized form: the wasm binary it was compiled
+/// from along with its compiled code, relocations, traps, and address maps.
+#[derive(Serialize, Deserialize)]
+pub struct SerializedModule {
+    wasm: Vec<u8>,
+    artifacts: CompilationArtifacts,
+}
+
+impl SerializedModule {
+    /// Read a module previously written by
+    /// [`CompiledModule::serialize`].
+    ///
+    /// `compiler` must be configured identically to the compiler which
+    /// produced `bytes`, otherwise an error is returned.
+    pub fn from_bytes(
+        compiler: &Compiler,
+        bytes: &[u8],
+        debug_info: bool,
+        features: &OperatorValidatorConfig,
+    ) -> Result<Self, SetupError> {
+        if !bytes.starts_with(SERIALIZED_MAGIC) {
+            return Err(SetupError::Deserialize(
+                "not a serialized wasmtime module".to_string(),
+            ));
+        }
+        let mut reader = &bytes[SERIALIZED_MAGIC.len()..];
+        let key: String = bincode::deserialize_from(&mut reader)
+            .map_err(|e| SetupError::Deserialize(e.to_string()))?;
+        if key != compatibility_key(compiler, debug_info, features) {
+            return Err(SetupError::Deserialize(format!(
+                "module was compiled with an incompatible configuration:\n{}",
+                key
+            )));
+        }
+        bincode::deserialize_from(&mut reader).map_err(|e| SetupError::Deserialize(e.to_string()))
+    }
+
+    /// Return the wasm binary the module was compiled from.
+    pub fn wasm(&self) -> &[u8] {
+        &self.wasm
+    }
+}
+
+/// Returns the key recording everything which must match between the
+/// compiler that serialized a module and the one deserializing it, including
+/// the wasm features the module was validated with.
+fn compatibility_key(
+    compiler: &Compiler,
+    debug_info: bool,
+    features: &OperatorValidatorConfig,
+) -> String {
+    format!(
+        "{}\ndebug_info: {}\n{:?}",
+        compiler.compatibility_key(),
+        debug_info,
+        features
+    )
 }
 
 /// This is similar to `CompiledModule`, but references the data initializers
@@ -54,13 +125,19 @@ struct RawCompiledModule<'data> {
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     dbg_jit_registration: Option<GdbJitImageRegistration>,
     trap_registration: TrapRegistration,
+    stack_map_registration: StackMapRegistration,
+    artifacts: CompilationArtifacts,
 }
 
 impl<'data> RawCompiledModule<'data> {
     /// Create a new `RawCompiledModule` by compiling the wasm module in `data` and instatiating it.
+    ///
+    /// If `artifacts` is provided, it's used as the module's compiled code
+    /// instead of compiling the function bodies in `data`.
     fn new(
         compiler: &mut Compiler,
         data: &'data [u8],
+        artifacts: Option<CompilationArtifacts>,
         debug_info: bool,
         profiler: Option<&Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
     ) -> Result<Self, SetupError> {
@@ -76,19 +153,31 @@ impl<'data> RawCompiledModule<'data> {
             None
         };
 
-        let (finished_functions, jt_offsets, relocations, dbg_image, trap_registration) = compiler
-            .compile(
+        let artifacts = match artifacts {
+            Some(artifacts) => {
+                if artifacts.compilation.len() != translation.function_body_inputs.len() {
+                    return Err(SetupError::Deserialize(
+                        "compiled code doesn't match the module's functions".to_string(),
+                    ));
+                }
+                artifacts
+            }
+            None => compiler.compile(
                 &translation.module,
                 translation.module_translation.as_ref().unwrap(),
                 translation.function_body_inputs,
-                debug_data,
-            )?;
+                debug_data.is_some(),
+            )?,
+        };
+
+        let (finished_functions, jt_offsets, dbg_image, trap_registration, stack_map_registration) =
+            compiler.load(&translation.module, &artifacts, debug_data)?;
 
         link_module(
             &translation.module,
             &finished_functions,
             &jt_offsets,
-            relocations,
+            &artifacts.relocations,
         );
 
         // Compute indices into the shared signature table.
@@ -137,6 +226,8 @@ impl<'data> RawCompiledModule<'data> {
             signatures: signatures.into_boxed_slice(),
             dbg_jit_registration,
             trap_registration,
+            stack_map_registration,
+            artifacts,
         })
     }
 }
@@ -146,9 +237,14 @@ pub struct CompiledModule {
     module: Arc<Module>,
     finished_functions: BoxedSlice<DefinedFuncIndex, *mut [VMFunctionBody]>,
     data_initializers: Box<[OwnedDataInitializer]>,
+    memory_images: Option<ModuleMemoryImages>,
     signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
     dbg_jit_registration: Option<Rc<GdbJitImageRegistration>>,
     trap_registration: TrapRegistration,
+    stack_map_registration: StackMapRegistration,
+    /// Only kept for modules which may be serialized, since the compiled code
+    /// is otherwise only needed while it's being loaded.
+    serialized: Option<SerializedModule>,
 }
 
 impl CompiledModule {
@@ -159,23 +255,77 @@ impl CompiledModule {
         debug_info: bool,
         profiler: Option<&Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
     ) -> Result<Self, SetupError> {
-        let raw = RawCompiledModule::<'data>::new(compiler, data, debug_info, profiler)?;
+        let raw = RawCompiledModule::<'data>::new(compiler, data, None, debug_info, profiler)?;
+        Ok(Self::from_raw(raw, None))
+    }
 
-        Ok(Self::from_parts(
-            raw.module,
-            raw.finished_functions,
-            raw.data_initializers
-                .iter()
-                .map(OwnedDataInitializer::new)
-                .collect::<Vec<_>>()
-                .into_boxed_slice(),
-            raw.signatures.clone(),
-            raw.dbg_jit_registration,
-            raw.trap_registration,
+    /// Like [`new`](CompiledModule::new), but keeps a copy of `data` and of
+    /// the compiled code so that the module can later be passed to
+    /// [`serialize`](CompiledModule::serialize).
+    pub fn new_serializable<'data>(
+        compiler: &mut Compiler,
+        data: &'data [u8],
+        debug_info: bool,
+        profiler: Option<&Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
+    ) -> Result<Self, SetupError> {
+        let raw = RawCompiledModule::<'data>::new(compiler, data, None, debug_info, profiler)?;
+        Ok(Self::from_raw(raw, Some(data)))
+    }
+
+    /// Load a module read by [`SerializedModule::from_bytes`], without
+    /// compiling it again.
+    ///
+    /// If `serializable` is true, the module keeps `serialized` so that it
+    /// can be serialized again.
+    ///
+    /// # Unsafety
+    ///
+    /// The machine code in `serialized` is trusted to be what `serialize`
+    /// wrote; loading arbitrary bytes may result in executing arbitrary code.
+    pub unsafe fn deserialize(
+        compiler: &mut Compiler,
+        serialized: SerializedModule,
+        debug_info: bool,
+        serializable: bool,
+        profiler: Option<&Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
+    ) -> Result<Self, SetupError> {
+        let SerializedModule { wasm, artifacts } = serialized;
+        let raw = RawCompiledModule::new(compiler, &wasm, Some(artifacts), debug_info, profiler)?;
+        Ok(Self::from_raw(
+            raw,
+            if serializable { Some(&wasm) } else { None },
         ))
     }
 
+    /// Serialize this module so that it can later be loaded by
+    /// [`deserialize`](CompiledModule::deserialize) without recompiling it.
+    ///
+    /// The output records the wasm binary along with its compiled code,
+    /// relocations, traps, and address maps, prefixed by a key describing
+    /// `compiler`'s configuration, which must match at load time.
+    ///
+    /// Returns `None` if this module wasn't created by
+    /// [`new_serializable`](CompiledModule::new_serializable), or
+    /// deserialized as serializable.
+    pub fn serialize(
+        &self,
+        compiler: &Compiler,
+        debug_info: bool,
+        features: &OperatorValidatorConfig,
+    ) -> Option<Vec<u8>> {
+        let serialized = self.serialized.as_ref()?;
+        let mut bytes = SERIALIZED_MAGIC.to_vec();
+        bincode::serialize_into(
+            &mut bytes,
+            &compatibility_key(compiler, debug_info, features),
+        )
+        .expect("failed to serialize compatibility key");
+        bincode::serialize_into(&mut bytes, serialized).expect("failed to serialize module");
+        Some(bytes)
+    }
+
     /// Construct a `CompiledModule` from component parts.
+    #[deprecated(note = "use `CompiledModule::new` or `CompiledModule::deserialize` instead")]
     pub fn from_parts(
         module: Module,
         finished_functions: BoxedSlice<DefinedFuncIndex, *mut [VMFunctionBody]>,
@@ -188,9 +338,39 @@ impl CompiledModule {
             module: Arc::new(module),
             finished_functions,
             data_initializers,
+            memory_images: None,
             signatures,
             dbg_jit_registration: dbg_jit_registration.map(Rc::new),
             trap_registration,
+            stack_map_registration: StackMapRegistry::default()
+                .register_stack_maps(std::iter::empty()),
+            serialized: None,
+        }
+    }
+
+    /// Keeps a copy of `wasm` along with the compiled code if `wasm` is given.
+    fn from_raw(raw: RawCompiledModule<'_>, wasm: Option<&[u8]>) -> Self {
+        // Prepare the initial contents of the module's memories up front, so
+        // that they can be mapped into each instance rather than copied.
+        let memory_images = ModuleMemoryImages::new(&raw.module, &raw.data_initializers);
+        Self {
+            module: Arc::new(raw.module),
+            finished_functions: raw.finished_functions,
+            data_initializers: raw
+                .data_initializers
+                .iter()
+                .map(OwnedDataInitializer::new)
+                .collect::<Vec<_>>()
+                .into_boxed_slice(),
+            memory_images,
+            signatures: raw.signatures,
+            dbg_jit_registration: raw.dbg_jit_registration.map(Rc::new),
+            trap_registration: raw.trap_registration,
+            stack_map_registration: raw.stack_map_registration,
+            serialized: wasm.map(|wasm| SerializedModule {
+                wasm: wasm.to_vec(),
+                artifacts: raw.artifacts,
+            }),
         }
     }
 
@@ -207,6 +387,11 @@ impl CompiledModule {
         &self,
         is_bulk_memory: bool,
         resolver: &mut dyn Resolver,
+        interrupts: Arc<VMInterrupts>,
+        externref_activations_table: Rc<VMExternRefActivationsTable>,
+        limiter: Option<Rc<dyn ResourceLimiter>>,
+        mem_creator: Option<&dyn RuntimeMemoryCreator>,
+        pool: Option<&InstancePool>,
     ) -> Result<InstanceHandle, InstantiationError> {
         let data_initializers = self
             .data_initializers
@@ -223,10 +408,16 @@ impl CompiledModule {
             self.finished_functions.clone(),
             imports,
             &data_initializers,
+            self.memory_images.as_ref(),
             self.signatures.clone(),
             self.dbg_jit_registration.as_ref().map(|r| Rc::clone(&r)),
             is_bulk_memory,
             Box::new(()),
+            interrupts,
+            externref_activations_table,
+            limiter,
+            mem_creator,
+            pool,
         )
     }
 
@@ -282,7 +473,16 @@ pub unsafe fn instantiate(
     is_bulk_memory: bool,
     profiler: Option<&Arc<Mutex<Box<dyn ProfilingAgent + Send>>>>,
 ) -> Result<InstanceHandle, SetupError> {
-    let instance = CompiledModule::new(compiler, data, debug_info, profiler)?
-        .instantiate(is_bulk_memory, resolver)?;
+    let instance = CompiledModule::new(compiler, data, debug_info, profiler)?.instantiate(
+        is_bulk_memory,
+        resolver,
+        compiler.interrupts().clone(),
+        Rc::new(VMExternRefActivationsTable::new(
+            compiler.stack_map_registry().clone(),
+        )),
+        None,
+        None,
+        None,
+    )?;
     Ok(instance)
 }
@@ -16,9 +16,9 @@ pub fn link_module(
     module: &Module,
     allocated_functions: &PrimaryMap<DefinedFuncIndex, *mut [VMFunctionBody]>,
     jt_offsets: &PrimaryMap<DefinedFuncIndex, JumpTableOffsets>,
-    relocations: Relocations,
+    relocations: &Relocations,
 ) {
-    for (i, function_relocs) in relocations.into_iter() {
+    for (i, function_relocs) in relocations.iter() {
         for r in function_relocs {
             use self::libcalls::*;
             let target_func_address: usize = match r.reloc_target {
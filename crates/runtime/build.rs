@@ -17,4 +17,12 @@ fn main() {
     }
 
     build.compile("signalhandlers");
+
+    if !target.contains("windows") {
+        println!("cargo:rerun-if-changed=fiber/Fiber.c");
+        cc::Build::new()
+            .warnings(false)
+            .file("fiber/Fiber.c")
+            .compile("fiber");
+    }
 }
@@ -0,0 +1,68 @@
+// Minimal stack switching support built on top of `ucontext`, used by the
+// runtime to run wasm on a separately allocated stack which can be suspended
+// and later resumed.
+
+#if defined(__APPLE__)
+// `ucontext.h` is only available on macOS when this is defined.
+#define _XOPEN_SOURCE 600
+#endif
+
+#include <stdint.h>
+#include <stdlib.h>
+#include <ucontext.h>
+
+typedef struct {
+  ucontext_t fiber;
+  ucontext_t caller;
+  void (*entry)(void *);
+  void *arg;
+} WasmtimeFiber;
+
+// `makecontext` only portably passes `int` arguments, so the fiber pointer is
+// split into two halves.
+static void wasmtime_fiber_start(unsigned int hi, unsigned int lo) {
+  uintptr_t ptr = (uintptr_t)lo;
+#if UINTPTR_MAX > 0xffffffff
+  ptr |= ((uintptr_t)hi) << 32;
+#else
+  (void)hi;
+#endif
+  WasmtimeFiber *fiber = (WasmtimeFiber *)ptr;
+  fiber->entry(fiber->arg);
+  // Returning switches back to `caller` through `uc_link`.
+}
+
+WasmtimeFiber *wasmtime_fiber_new(void *stack, size_t stack_size,
+                                  void (*entry)(void *), void *arg) {
+  WasmtimeFiber *fiber = calloc(1, sizeof(WasmtimeFiber));
+  if (fiber == NULL)
+    return NULL;
+  if (getcontext(&fiber->fiber) != 0) {
+    free(fiber);
+    return NULL;
+  }
+  fiber->fiber.uc_stack.ss_sp = stack;
+  fiber->fiber.uc_stack.ss_size = stack_size;
+  fiber->fiber.uc_link = &fiber->caller;
+  fiber->entry = entry;
+  fiber->arg = arg;
+
+  uintptr_t ptr = (uintptr_t)fiber;
+  unsigned int hi = 0;
+#if UINTPTR_MAX > 0xffffffff
+  hi = (unsigned int)(ptr >> 32);
+#endif
+  makecontext(&fiber->fiber, (void (*)(void))wasmtime_fiber_start, 2, hi,
+              (unsigned int)ptr);
+  return fiber;
+}
+
+int wasmtime_fiber_switch_to(WasmtimeFiber *fiber) {
+  return swapcontext(&fiber->caller, &fiber->fiber);
+}
+
+int wasmtime_fiber_switch_back(WasmtimeFiber *fiber) {
+  return swapcontext(&fiber->fiber, &fiber->caller);
+}
+
+void wasmtime_fiber_free(WasmtimeFiber *fiber) { free(fiber); }
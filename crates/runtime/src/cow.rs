@@ -0,0 +1,265 @@
+//! Copy-on-write images of the initial contents of linear memories.
+//!
+//! When every data segment of a module's memory is at a constant offset, the
+//! memory's initial contents are the same for every instance. They can then be
+//! written once into an anonymous file, which is mapped copy-on-write into the
+//! memory of each new instance instead of copying the data segments in.
+
+use std::convert::TryFrom;
+use std::fs::File;
+use wasmtime_environ::entity::PrimaryMap;
+use wasmtime_environ::wasm::DefinedMemoryIndex;
+use wasmtime_environ::{DataInitializer, Module, WASM_PAGE_SIZE};
+
+/// The initial contents of one linear memory, which can be mapped into it.
+#[derive(Debug)]
+pub struct MemoryImage {
+    /// The file holding the image.
+    fd: File,
+
+    /// The offset in bytes into the memory the image is mapped at, which is
+    /// page-aligned.
+    offset: usize,
+
+    /// The length of the image in bytes, which is page-aligned.
+    len: usize,
+}
+
+/// Images of the initial contents of all of the memories a module defines.
+#[derive(Debug)]
+pub struct ModuleMemoryImages {
+    memories: PrimaryMap<DefinedMemoryIndex, Option<MemoryImage>>,
+}
+
+impl ModuleMemoryImages {
+    /// Creates images of the memories of `module` as initialized by
+    /// `data_initializers`.
+    ///
+    /// Returns `None` if the memories can't be initialized from images, for
+    /// example because a data segment is at an offset given by a global or
+    /// would be out of bounds, in which case the data segments have to be
+    /// copied into each instance instead.
+    pub fn new(module: &Module, data_initializers: &[DataInitializer<'_>]) -> Option<Self> {
+        if !cfg!(target_os = "linux") {
+            return None;
+        }
+
+        let num_defined = module.local.memory_plans.len() - module.imported_memories.len();
+        let mut segments = (0..num_defined)
+            .map(|_| Vec::new())
+            .collect::<PrimaryMap<DefinedMemoryIndex, _>>();
+        for init in data_initializers {
+            if init.location.base.is_some() {
+                return None;
+            }
+            let index = module
+                .local
+                .defined_memory_index(init.location.memory_index)?;
+            let minimum_bytes = (module.local.memory_plans[init.location.memory_index]
+                .memory
+                .minimum as usize)
+                .checked_mul(WASM_PAGE_SIZE as usize)?;
+            let end = init.location.offset.checked_add(init.data.len())?;
+            if end > minimum_bytes {
+                return None;
+            }
+            if !init.data.is_empty() {
+                segments[index].push((init.location.offset, init.data));
+            }
+        }
+
+        let mut memories = PrimaryMap::with_capacity(num_defined);
+        for segments in segments.values() {
+            memories.push(MemoryImage::new(segments).ok()?);
+        }
+        Some(Self { memories })
+    }
+
+    /// Returns the image of the memory at `index`, if it has any initial
+    /// contents.
+    pub(crate) fn get(&self, index: DefinedMemoryIndex) -> Option<&MemoryImage> {
+        self.memories[index].as_ref()
+    }
+}
+
+impl MemoryImage {
+    /// Creates an image holding each of the non-empty `segments`, written in
+    /// order at their offsets, or `None` if there aren't any.
+    fn new(segments: &[(usize, &[u8])]) -> Result<Option<Self>, String> {
+        let start = segments.iter().map(|(offset, _)| *offset).min();
+        let end = segments
+            .iter()
+            .map(|(offset, data)| offset + data.len())
+            .max();
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return Ok(None),
+        };
+
+        // The memory's minimum size is a multiple of the wasm page size, and
+        // so of the host page size too, so rounding up here never reaches
+        // beyond it.
+        let page_size = region::page::size();
+        let offset = start & !(page_size - 1);
+        let len = ((end + page_size - 1) & !(page_size - 1)) - offset;
+
+        let fd = create_file(len)?;
+        for (segment_offset, data) in segments {
+            write_at(&fd, data, (segment_offset - offset) as u64)?;
+        }
+        Ok(Some(Self { fd, offset, len }))
+    }
+
+    /// Creates an image of the current contents of a memory, `data`, to be
+    /// mapped at its start, or `None` if it's empty.
+    ///
+    /// Only pages holding something other than zeros are written, so the
+    /// image takes no more space than the memory does.
+    pub(crate) fn from_memory(data: &[u8]) -> Result<Option<Self>, String> {
+        if data.is_empty() {
+            return Ok(None);
+        }
+        let page_size = region::page::size();
+        debug_assert_eq!(data.len() % page_size, 0);
+
+        let fd = create_file(data.len())?;
+        let mut run_start = None;
+        for (i, page) in data.chunks(page_size).enumerate() {
+            let offset = i * page_size;
+            match (run_start, page.iter().any(|b| *b != 0)) {
+                (None, true) => run_start = Some(offset),
+                (Some(start), false) => {
+                    write_at(&fd, &data[start..offset], start as u64)?;
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = run_start {
+            write_at(&fd, &data[start..], start as u64)?;
+        }
+        Ok(Some(Self {
+            fd,
+            offset: 0,
+            len: data.len(),
+        }))
+    }
+
+    /// Copies the contents of this image into `memory`, for memories which
+    /// can't have it mapped in.
+    pub(crate) fn copy_to(&self, memory: &mut [u8]) -> Result<(), String> {
+        let dst = &mut memory[self.offset..self.offset + self.len];
+        read_at(&self.fd, dst, 0)
+    }
+
+    /// Maps this image copy-on-write into the memory at `base`, replacing
+    /// whatever was mapped there.
+    ///
+    /// # Safety
+    ///
+    /// The memory at `base` must be at least as large as the image, and must
+    /// not move while it's mapped.
+    #[cfg(target_os = "linux")]
+    pub(crate) unsafe fn map_at(&self, base: *mut u8) -> Result<(), String> {
+        use std::os::unix::io::AsRawFd;
+        let ptr = libc::mmap(
+            base.add(self.offset) as *mut libc::c_void,
+            self.len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_PRIVATE | libc::MAP_FIXED,
+            self.fd.as_raw_fd(),
+            0,
+        );
+        if ptr as isize == -1_isize {
+            return Err(std::io::Error::last_os_error().to_string());
+        }
+        Ok(())
+    }
+
+    /// Maps this image copy-on-write into the memory at `base`, replacing
+    /// whatever was mapped there.
+    ///
+    /// # Safety
+    ///
+    /// The memory at `base` must be at least as large as the image, and must
+    /// not move while it's mapped.
+    #[cfg(not(target_os = "linux"))]
+    pub(crate) unsafe fn map_at(&self, _base: *mut u8) -> Result<(), String> {
+        unreachable!("memory images are only created on Linux")
+    }
+}
+
+/// Creates an anonymous file of `len` bytes.
+#[cfg(target_os = "linux")]
+fn create_file(len: usize) -> Result<File, String> {
+    use std::os::unix::io::FromRawFd;
+    let fd = unsafe {
+        libc::syscall(
+            libc::SYS_memfd_create,
+            b"wasm-memory-image\0".as_ptr(),
+            libc::MFD_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    let file = unsafe { File::from_raw_fd(fd as libc::c_int) };
+    file.set_len(u64::try_from(len).unwrap())
+        .map_err(|e| e.to_string())?;
+    Ok(file)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn create_file(_len: usize) -> Result<File, String> {
+    Err("memory images are only supported on Linux".to_string())
+}
+
+#[cfg(unix)]
+fn write_at(file: &File, data: &[u8], offset: u64) -> Result<(), String> {
+    use std::os::unix::fs::FileExt;
+    file.write_all_at(data, offset).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn write_at(_file: &File, _data: &[u8], _offset: u64) -> Result<(), String> {
+    unreachable!("memory images are only created on Linux")
+}
+
+#[cfg(unix)]
+fn read_at(file: &File, data: &mut [u8], offset: u64) -> Result<(), String> {
+    use std::os::unix::fs::FileExt;
+    file.read_exact_at(data, offset).map_err(|e| e.to_string())
+}
+
+#[cfg(not(unix))]
+fn read_at(_file: &File, _data: &mut [u8], _offset: u64) -> Result<(), String> {
+    unreachable!("memory images are only created on Linux")
+}
+
+/// Removes an image mapped with [`MemoryImage::map_at`], leaving the `len`
+/// bytes at `addr` zeroed and inaccessible.
+#[cfg(unix)]
+pub(crate) unsafe fn unmap_image(addr: *mut u8, len: usize) -> Result<(), String> {
+    if len == 0 {
+        return Ok(());
+    }
+    let ptr = libc::mmap(
+        addr as *mut libc::c_void,
+        len,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+        -1,
+        0,
+    );
+    if ptr as isize == -1_isize {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Removes an image mapped with [`MemoryImage::map_at`], leaving the `len`
+/// bytes at `addr` zeroed and inaccessible.
+#[cfg(not(unix))]
+pub(crate) unsafe fn unmap_image(_addr: *mut u8, _len: usize) -> Result<(), String> {
+    unreachable!("memory images are only created on Linux")
+}
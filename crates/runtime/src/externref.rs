@@ -0,0 +1,255 @@
+//! The runtime representation of `externref` values.
+//!
+//! An `externref` is a pointer to a reference-counted, heap-allocated box
+//! holding an arbitrary host value. Tables of `externref`s own a reference to
+//! each of their elements, and compiled wasm code passes the raw pointers
+//! around.
+//!
+//! Compiled code doesn't maintain reference counts itself. Instead every
+//! `externref` handed to wasm is first inserted into a
+//! `VMExternRefActivationsTable`, which keeps it alive until a garbage
+//! collection finds it's no longer held by any wasm frame or global. Frames
+//! are found by walking the stack, using the stack maps Cranelift emits at
+//! each safepoint to tell which stack slots hold live references.
+
+use crate::stack_map_registry::StackMapRegistry;
+use crate::vmcontext::VMGlobalDefinition;
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::mem;
+use std::ops::Deref;
+use std::ptr::NonNull;
+
+/// An owned reference to a host value which can be stored in an `externref`
+/// table or passed to compiled wasm code.
+pub struct VMExternRef(NonNull<VMExternData>);
+
+struct VMExternData {
+    ref_count: Cell<usize>,
+    value: Box<dyn Any>,
+}
+
+impl VMExternRef {
+    /// Wraps `value` in a new `externref`.
+    pub fn new(value: Box<dyn Any>) -> Self {
+        let data = Box::new(VMExternData {
+            ref_count: Cell::new(1),
+            value,
+        });
+        Self(unsafe { NonNull::new_unchecked(Box::into_raw(data)) })
+    }
+
+    /// Returns the raw pointer which compiled wasm code uses for this
+    /// reference, without giving up ownership of it.
+    pub fn as_raw(&self) -> *mut u8 {
+        self.0.as_ptr() as *mut u8
+    }
+
+    /// Creates a new owned reference from a raw pointer handed out by
+    /// [`VMExternRef::as_raw`].
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be non-null and refer to a live `externref`.
+    pub unsafe fn clone_from_raw(ptr: *mut u8) -> Self {
+        debug_assert!(!ptr.is_null());
+        let r = Self(NonNull::new_unchecked(ptr as *mut VMExternData));
+        r.data().ref_count.set(r.data().ref_count.get() + 1);
+        r
+    }
+
+    /// Returns whether `a` and `b` refer to the same value.
+    pub fn ptr_eq(a: &Self, b: &Self) -> bool {
+        a.0 == b.0
+    }
+
+    fn data(&self) -> &VMExternData {
+        unsafe { self.0.as_ref() }
+    }
+}
+
+impl Clone for VMExternRef {
+    fn clone(&self) -> Self {
+        unsafe { Self::clone_from_raw(self.as_raw()) }
+    }
+}
+
+impl Drop for VMExternRef {
+    fn drop(&mut self) {
+        let count = self.data().ref_count.get() - 1;
+        self.data().ref_count.set(count);
+        if count == 0 {
+            drop(unsafe { Box::from_raw(self.0.as_ptr()) });
+        }
+    }
+}
+
+impl Deref for VMExternRef {
+    type Target = dyn Any;
+
+    fn deref(&self) -> &dyn Any {
+        &*self.data().value
+    }
+}
+
+impl fmt::Debug for VMExternRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "VMExternRef({:p})", self.0)
+    }
+}
+
+/// The number of references a `VMExternRefActivationsTable` holds before it
+/// first collects garbage.
+const INITIAL_GC_THRESHOLD: usize = 512;
+
+/// A table of the `externref`s which compiled wasm code may be holding.
+///
+/// Each reference handed to wasm is inserted here, and is released by the
+/// first garbage collection which doesn't find it in a wasm stack frame or in
+/// one of the reference-typed globals registered with the table.
+pub struct VMExternRefActivationsTable {
+    /// The references inserted since the last collection, along with the
+    /// ones the last collection found to still be live.
+    refs: RefCell<Vec<VMExternRef>>,
+
+    /// Collections are triggered by `maybe_gc` once `refs` holds this many
+    /// references.
+    gc_threshold: Cell<usize>,
+
+    /// The number of outstanding requests to defer collections, see
+    /// `defer_gc`.
+    gc_deferrals: Cell<usize>,
+
+    /// Reference-typed globals of the instances using this table, keyed by
+    /// the instance which defines them.
+    global_roots: RefCell<HashMap<usize, Vec<*const VMGlobalDefinition>>>,
+
+    /// The stack maps used to find references on the stack.
+    stack_map_registry: StackMapRegistry,
+}
+
+impl VMExternRefActivationsTable {
+    /// Creates a new, empty table which finds references on the stack using
+    /// the stack maps in `stack_map_registry`.
+    pub fn new(stack_map_registry: StackMapRegistry) -> Self {
+        Self {
+            refs: RefCell::new(Vec::new()),
+            gc_threshold: Cell::new(INITIAL_GC_THRESHOLD),
+            gc_deferrals: Cell::new(0),
+            global_roots: RefCell::new(HashMap::new()),
+            stack_map_registry,
+        }
+    }
+
+    /// Inserts `externref` into this table, returning the raw pointer which
+    /// compiled wasm code uses for it.
+    ///
+    /// This never collects garbage, so pointers previously returned here
+    /// which haven't yet made it into a wasm frame remain valid.
+    pub fn insert(&self, externref: VMExternRef) -> *mut u8 {
+        let ptr = externref.as_raw();
+        self.refs.borrow_mut().push(externref);
+        ptr
+    }
+
+    /// Collects garbage if enough references have been inserted since the
+    /// last collection.
+    pub fn maybe_gc(&self) {
+        if self.refs.borrow().len() >= self.gc_threshold.get() {
+            self.gc();
+        }
+    }
+
+    /// Releases all the references in this table which aren't held by any
+    /// wasm frame on the current stack or any registered global.
+    ///
+    /// Does nothing while collections are deferred.
+    pub fn gc(&self) {
+        if self.gc_deferrals.get() > 0 {
+            return;
+        }
+
+        // Roots are only ever compared against the references in this table,
+        // never dereferenced, since a stack map found for a frame without a
+        // safepoint of its own may describe words which don't hold references.
+        let mut roots = HashSet::new();
+        backtrace::trace(|frame| {
+            let pc = frame.ip() as usize;
+            if let Some(stack_map) = self.stack_map_registry.lookup_stack_map(pc) {
+                let sp = frame.sp() as usize;
+                for i in 0..stack_map.mapped_words as usize {
+                    if stack_map.get_bit(i) {
+                        let slot = (sp + i * mem::size_of::<usize>()) as *const usize;
+                        roots.insert(unsafe { *slot });
+                    }
+                }
+            }
+            true
+        });
+        for globals in self.global_roots.borrow().values() {
+            for global in globals {
+                roots.insert(unsafe { *(**global).as_reference() } as usize);
+            }
+        }
+
+        // Keep one copy of each live reference, and drop the rest only once
+        // the table is no longer borrowed since dropping a host value may run
+        // arbitrary code.
+        let refs = self.refs.replace(Vec::new());
+        let mut kept = HashSet::new();
+        let (live, dead): (Vec<_>, Vec<_>) = refs.into_iter().partition(|r| {
+            let ptr = r.as_raw() as usize;
+            roots.contains(&ptr) && kept.insert(ptr)
+        });
+        self.gc_threshold
+            .set(INITIAL_GC_THRESHOLD.max(live.len() * 2));
+        self.refs.borrow_mut().extend(live);
+        drop(dead);
+    }
+
+    /// Defers collections until a matching call to `resume_gc`.
+    ///
+    /// This is needed while wasm frames live on a stack other than the
+    /// current one, such as that of a suspended fiber, since collections
+    /// can't find the references those frames hold.
+    pub fn defer_gc(&self) {
+        self.gc_deferrals.set(self.gc_deferrals.get() + 1);
+    }
+
+    /// Undoes a previous call to `defer_gc`.
+    pub fn resume_gc(&self) {
+        debug_assert!(self.gc_deferrals.get() > 0);
+        self.gc_deferrals.set(self.gc_deferrals.get() - 1);
+    }
+
+    /// Registers reference-typed globals as roots for collections, until
+    /// `unregister_global_roots` is called with the same `key`.
+    ///
+    /// # Safety
+    ///
+    /// The globals must remain valid until they're unregistered.
+    pub unsafe fn register_global_roots(
+        &self,
+        key: usize,
+        globals: Vec<*const VMGlobalDefinition>,
+    ) {
+        if !globals.is_empty() {
+            self.global_roots.borrow_mut().insert(key, globals);
+        }
+    }
+
+    /// Unregisters the globals registered with `key`, if any.
+    pub fn unregister_global_roots(&self, key: usize) {
+        self.global_roots.borrow_mut().remove(&key);
+    }
+}
+
+impl fmt::Debug for VMExternRefActivationsTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VMExternRefActivationsTable")
+            .field("len", &self.refs.borrow().len())
+            .finish()
+    }
+}
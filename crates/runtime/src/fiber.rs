@@ -0,0 +1,180 @@
+//! Fibers: functions run on a separately allocated native stack which can
+//! suspend themselves and later be resumed.
+//!
+//! These are used to run wasm in a way that can be paused whenever a host
+//! function is waiting on a future, without blocking the thread.
+
+use crate::mmap::Mmap;
+use crate::traphandlers::TlsRestore;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::thread;
+
+#[repr(C)]
+struct RawFiber {
+    _private: [u8; 0],
+}
+
+#[cfg(not(target_os = "windows"))]
+extern "C" {
+    fn wasmtime_fiber_new(
+        stack: *mut u8,
+        stack_size: usize,
+        entry: extern "C" fn(*mut u8),
+        arg: *mut u8,
+    ) -> *mut RawFiber;
+    fn wasmtime_fiber_switch_to(fiber: *mut RawFiber) -> i32;
+    fn wasmtime_fiber_switch_back(fiber: *mut RawFiber) -> i32;
+    fn wasmtime_fiber_free(fiber: *mut RawFiber);
+}
+
+// Stack switching isn't implemented on Windows yet, so creating a fiber always
+// fails there and these are never called.
+#[cfg(target_os = "windows")]
+unsafe fn wasmtime_fiber_new(
+    _stack: *mut u8,
+    _stack_size: usize,
+    _entry: extern "C" fn(*mut u8),
+    _arg: *mut u8,
+) -> *mut RawFiber {
+    ptr::null_mut()
+}
+#[cfg(target_os = "windows")]
+unsafe fn wasmtime_fiber_switch_to(_fiber: *mut RawFiber) -> i32 {
+    unreachable!()
+}
+#[cfg(target_os = "windows")]
+unsafe fn wasmtime_fiber_switch_back(_fiber: *mut RawFiber) -> i32 {
+    unreachable!()
+}
+#[cfg(target_os = "windows")]
+unsafe fn wasmtime_fiber_free(_fiber: *mut RawFiber) {}
+
+/// A function running on its own native stack.
+///
+/// The function is started by the first call to [`Fiber::resume`] and runs
+/// until it either finishes or calls [`Suspend::suspend`], at which point
+/// `resume` returns to its caller. The next call to `resume` continues where
+/// the function left off.
+///
+/// If a fiber is dropped while its function is suspended the function never
+/// finishes, and anything living on its stack is leaked rather than dropped.
+pub struct Fiber<'a, R> {
+    inner: Box<FiberInner<'a, R>>,
+}
+
+struct FiberInner<'a, R> {
+    raw: *mut RawFiber,
+    func: Option<Box<dyn FnOnce(&Suspend) -> R + 'a>>,
+    result: Option<thread::Result<R>>,
+    done: bool,
+    // The stack is unmapped only after `raw` has been freed.
+    _stack: Mmap,
+}
+
+/// Handle passed to the function running on a [`Fiber`] with which it can
+/// suspend itself.
+pub struct Suspend {
+    raw: *mut RawFiber,
+}
+
+impl<'a, R> Fiber<'a, R> {
+    /// Creates a new fiber which will run `func` on a stack of `stack_size`
+    /// bytes.
+    ///
+    /// The stack is followed by a guard page, so overflowing it faults rather
+    /// than corrupting other memory.
+    pub fn new(stack_size: usize, func: impl FnOnce(&Suspend) -> R + 'a) -> io::Result<Self> {
+        let page_size = region::page::size();
+        let stack_size = (stack_size.max(1) + page_size - 1) & !(page_size - 1);
+        let mut stack = Mmap::accessible_reserved(0, stack_size + page_size)
+            .and_then(|mut stack| {
+                stack.make_accessible(page_size, stack_size)?;
+                Ok(stack)
+            })
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        let stack_ptr = unsafe { stack.as_mut_ptr().add(page_size) };
+
+        let mut inner = Box::new(FiberInner {
+            raw: ptr::null_mut(),
+            func: Some(Box::new(func)),
+            result: None,
+            done: false,
+            _stack: stack,
+        });
+        let arg = &mut *inner as *mut FiberInner<'a, R> as *mut u8;
+        inner.raw = unsafe { wasmtime_fiber_new(stack_ptr, stack_size, fiber_start::<R>, arg) };
+        if inner.raw.is_null() {
+            if cfg!(target_os = "windows") {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "fibers are not supported on this platform",
+                ));
+            }
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Fiber { inner })
+    }
+
+    /// Runs this fiber's function until it either finishes or suspends.
+    ///
+    /// Returns `Some` with the function's result once it has finished and
+    /// `None` if it suspended itself. Panics raised by the function are
+    /// propagated from here.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the function has already finished.
+    pub fn resume(&mut self) -> Option<R> {
+        assert!(!self.inner.done, "cannot resume a finished fiber");
+        unsafe {
+            wasmtime_fiber_switch_to(self.inner.raw);
+        }
+        match self.inner.result.take() {
+            Some(Ok(result)) => Some(result),
+            Some(Err(panic)) => panic::resume_unwind(panic),
+            None => None,
+        }
+    }
+
+    /// Returns whether this fiber's function has finished.
+    pub fn done(&self) -> bool {
+        self.inner.done
+    }
+}
+
+impl<R> Drop for FiberInner<'_, R> {
+    fn drop(&mut self) {
+        unsafe {
+            wasmtime_fiber_free(self.raw);
+        }
+    }
+}
+
+extern "C" fn fiber_start<R>(arg: *mut u8) {
+    unsafe {
+        let inner = &mut *(arg as *mut FiberInner<'_, R>);
+        let suspend = Suspend { raw: inner.raw };
+        let func = inner.func.take().unwrap();
+        // Unwinding must not cross the stack switch, so panics are carried
+        // over to `resume` instead.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| func(&suspend)));
+        inner.result = Some(result);
+        inner.done = true;
+    }
+}
+
+impl Suspend {
+    /// Suspends the running fiber, returning from the [`Fiber::resume`] call
+    /// which resumed it. This returns once the fiber is resumed again.
+    pub fn suspend(&self) {
+        // Any calls into wasm on this fiber's stack must not be visible to
+        // traps or calls made while it's suspended.
+        let tls = TlsRestore::take();
+        unsafe {
+            wasmtime_fiber_switch_back(self.raw);
+        }
+        tls.replace();
+    }
+}
@@ -2,17 +2,24 @@
 //! wasm module (except its callstack and register state). An
 //! `InstanceHandle` is a reference-counting handle for an `Instance`.
 
+use crate::cow::ModuleMemoryImages;
 use crate::export::Export;
+use crate::externref::VMExternRefActivationsTable;
 use crate::imports::Imports;
 use crate::jit_int::GdbJitImageRegistration;
-use crate::memory::LinearMemory;
+use crate::limits::ResourceLimiter;
+use crate::memory::{
+    DefaultMemoryCreator, RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory,
+};
+use crate::pooling::{InstancePool, InstanceSlot};
 use crate::signalhandlers;
-use crate::table::Table;
+use crate::snapshot::{InstanceSnapshot, MemoryContents, MemorySnapshot};
+use crate::table::{Table, TableElement};
 use crate::traphandlers::{catch_traps, Trap};
 use crate::vmcontext::{
     VMBuiltinFunctionsArray, VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport,
-    VMGlobalDefinition, VMGlobalImport, VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex,
-    VMTableDefinition, VMTableImport,
+    VMGlobalDefinition, VMGlobalImport, VMInterrupts, VMMemoryDefinition, VMMemoryImport,
+    VMSharedSignatureIndex, VMTableDefinition, VMTableImport,
 };
 use crate::TrapRegistration;
 use memoffset::offset_of;
@@ -24,13 +31,15 @@ use std::collections::HashMap;
 use std::collections::HashSet;
 use std::convert::TryFrom;
 use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::sync::Arc;
 use std::{mem, ptr, slice};
 use thiserror::Error;
 use wasmtime_environ::entity::{packed_option::ReservedValue, BoxedSlice, EntityRef, PrimaryMap};
 use wasmtime_environ::wasm::{
     DefinedFuncIndex, DefinedGlobalIndex, DefinedMemoryIndex, DefinedTableIndex, FuncIndex,
-    GlobalIndex, GlobalInit, MemoryIndex, PassiveElemIndex, SignatureIndex, TableIndex,
+    GlobalIndex, GlobalInit, MemoryIndex, PassiveElemIndex, SignatureIndex, TableElementType,
+    TableIndex,
 };
 use wasmtime_environ::{ir, DataInitializer, Module, TableElements, VMOffsets};
 
@@ -67,6 +76,10 @@ cfg_if::cfg_if! {
 /// This is repr(C) to ensure that the vmctx field is last.
 #[repr(C)]
 pub(crate) struct Instance {
+    /// A unique identifier (within this process) for this instance, which
+    /// unlike its address isn't reused once the instance is deallocated.
+    id: usize,
+
     /// The number of references to this `Instance`.
     refcount: Cell<usize>,
 
@@ -82,7 +95,7 @@ pub(crate) struct Instance {
     offsets: VMOffsets,
 
     /// WebAssembly linear memory data.
-    memories: BoxedSlice<DefinedMemoryIndex, LinearMemory>,
+    memories: BoxedSlice<DefinedMemoryIndex, Box<dyn RuntimeLinearMemory>>,
 
     /// WebAssembly table data.
     tables: BoxedSlice<DefinedTableIndex, Table>,
@@ -92,6 +105,11 @@ pub(crate) struct Instance {
     /// empty slice.
     passive_elements: RefCell<HashMap<PassiveElemIndex, Box<[VMCallerCheckedAnyfunc]>>>,
 
+    /// Copies of the `VMCallerCheckedAnyfunc`s of this instance's functions
+    /// which have been used as `funcref` values, keyed by function body.
+    /// They're boxed so that compiled wasm code can keep pointers to them.
+    funcrefs: RefCell<HashMap<usize, Box<VMCallerCheckedAnyfunc>>>,
+
     /// Pointers to functions in executable memory.
     finished_functions: BoxedSlice<DefinedFuncIndex, *mut [VMFunctionBody]>,
 
@@ -108,6 +126,21 @@ pub(crate) struct Instance {
     /// when a segfault/sigill happens.
     pub(crate) trap_registration: TrapRegistration,
 
+    /// Interrupt flag shared with the other instances of this instance's
+    /// store. The `vmctx` holds a raw pointer to it.
+    pub(crate) interrupts: Arc<VMInterrupts>,
+
+    /// Limiter consulted when this instance's memories and tables grow.
+    limiter: Option<Rc<dyn ResourceLimiter>>,
+
+    /// Table keeping alive the `externref`s handed to compiled wasm code,
+    /// shared with the other instances of this instance's store.
+    externref_activations_table: Rc<VMExternRefActivationsTable>,
+
+    /// The slot of an `InstancePool` this instance, along with its memories
+    /// and tables, lives in, if it was allocated from one.
+    slot: Option<InstanceSlot>,
+
     /// Additional context used by compiled wasm code. This field is last, and
     /// represents a dynamically-sized array that extends beyond the nominal
     /// end of the struct (similar to a flexible array member).
@@ -124,6 +157,11 @@ impl Instance {
             .cast()
     }
 
+    /// Return a pointer to the `VMInterrupts` pointer.
+    fn interrupts_ptr(&self) -> *mut *const VMInterrupts {
+        unsafe { self.vmctx_plus_offset(self.offsets.vmctx_interrupts()) }
+    }
+
     /// Return the indexed `VMSharedSignatureIndex`.
     fn signature_id(&self, index: SignatureIndex) -> VMSharedSignatureIndex {
         let index = usize::try_from(index.as_u32()).unwrap();
@@ -214,10 +252,30 @@ impl Instance {
     /// Get a locally defined or imported memory.
     pub(crate) fn get_memory(&self, index: MemoryIndex) -> VMMemoryDefinition {
         if let Some(defined_index) = self.module.local.defined_memory_index(index) {
-            self.memory(defined_index)
+            self.defined_memory(defined_index)
         } else {
             let import = self.imported_memory(index);
-            *unsafe { import.from.as_ref().unwrap() }
+            unsafe {
+                let foreign_instance = (&*import.vmctx).instance();
+                let foreign_index = foreign_instance.memory_index(&*import.from);
+                foreign_instance.defined_memory(foreign_index)
+            }
+        }
+    }
+
+    /// Return the `VMMemoryDefinition` of a locally defined memory.
+    ///
+    /// Shared memories may be grown through other instances, which doesn't
+    /// update this instance's `VMContext`, so their definition is read from
+    /// the memory itself.
+    fn defined_memory(&self, index: DefinedMemoryIndex) -> VMMemoryDefinition {
+        if self.module.local.memory_plans[self.module.local.memory_index(index)]
+            .memory
+            .shared
+        {
+            self.memories[index].vmmemory()
+        } else {
+            self.memory(index)
         }
     }
 
@@ -250,7 +308,6 @@ impl Instance {
     }
 
     /// Set the indexed global to `VMGlobalDefinition`.
-    #[allow(dead_code)]
     fn set_global(&self, index: DefinedGlobalIndex, global: VMGlobalDefinition) {
         unsafe {
             *self.global_ptr(index) = global;
@@ -332,7 +389,13 @@ impl Instance {
             wasmtime_environ::Export::Memory(index) => {
                 let (definition, vmctx) =
                     if let Some(def_index) = self.module.local.defined_memory_index(*index) {
-                        (self.memory_ptr(def_index), self.vmctx_ptr())
+                        // Instances importing a shared memory refer to its own
+                        // definition, which is kept current when it's grown.
+                        let definition = match self.memories[def_index].shared() {
+                            Some(memory) => memory.vmmemory_ptr(),
+                            None => self.memory_ptr(def_index),
+                        };
+                        (definition, self.vmctx_ptr())
                     } else {
                         let import = self.imported_memory(*index);
                         (import.from, import.vmctx)
@@ -428,6 +491,13 @@ impl Instance {
 
     /// Return the memory index for the given `VMMemoryDefinition`.
     pub(crate) fn memory_index(&self, memory: &VMMemoryDefinition) -> DefinedMemoryIndex {
+        let shared = self.memories.iter().find(|(_, m)| {
+            m.shared()
+                .map_or(false, |m| ptr::eq(m.vmmemory_ptr(), memory))
+        });
+        if let Some((index, _)) = shared {
+            return index;
+        }
         let offsets = &self.offsets;
         let begin = unsafe {
             (&self.vmctx as *const VMContext as *const u8)
@@ -447,11 +517,20 @@ impl Instance {
     /// Returns `None` if memory can't be grown by the specified amount
     /// of pages.
     pub(crate) fn memory_grow(&self, memory_index: DefinedMemoryIndex, delta: u32) -> Option<u32> {
-        let result = self
+        let memory = self
             .memories
             .get(memory_index)
-            .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()))
-            .grow(delta);
+            .unwrap_or_else(|| panic!("no memory for index {}", memory_index.index()));
+
+        if let Some(limiter) = &self.limiter {
+            let current = memory.size();
+            let desired = current.checked_add(delta)?;
+            if delta != 0 && !limiter.memory_growing(current, desired, memory.maximum()) {
+                return None;
+            }
+        }
+
+        let result = memory.grow(delta);
 
         // Keep current the VMContext pointers used by compiled wasm code.
         self.set_memory(memory_index, self.memories[memory_index].vmmemory());
@@ -502,16 +581,31 @@ impl Instance {
         foreign_instance.memory_size(foreign_index)
     }
 
-    /// Grow table by the specified amount of elements.
+    /// Grow table by the specified amount of elements, filling the new ones
+    /// with `init`.
     ///
-    /// Returns `None` if table can't be grown by the specified amount
-    /// of elements.
-    pub(crate) fn table_grow(&self, table_index: DefinedTableIndex, delta: u32) -> Option<u32> {
-        let result = self
+    /// Returns the previous size of the table, or `None` if table can't be
+    /// grown by the specified amount of elements.
+    pub(crate) fn table_grow(
+        &self,
+        table_index: DefinedTableIndex,
+        delta: u32,
+        init: TableElement,
+    ) -> Option<u32> {
+        let table = self
             .tables
             .get(table_index)
-            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()))
-            .grow(delta);
+            .unwrap_or_else(|| panic!("no table for index {}", table_index.index()));
+
+        if let Some(limiter) = &self.limiter {
+            let current = table.size();
+            let desired = current.checked_add(delta)?;
+            if delta != 0 && !limiter.table_growing(current, desired, table.maximum()) {
+                return None;
+            }
+        }
+
+        let result = table.grow(delta, init);
 
         // Keep current the VMContext pointers used by compiled wasm code.
         self.set_table(table_index, self.tables[table_index].vmtable());
@@ -519,12 +613,27 @@ impl Instance {
         result
     }
 
-    // Get table element by index.
-    fn table_get(
+    /// Grow a table by index regardless of whether it is locally-defined or
+    /// an imported, foreign table.
+    pub(crate) fn defined_or_imported_table_grow(
         &self,
-        table_index: DefinedTableIndex,
-        index: u32,
-    ) -> Option<VMCallerCheckedAnyfunc> {
+        table_index: TableIndex,
+        delta: u32,
+        init: TableElement,
+    ) -> Option<u32> {
+        if let Some(defined_table_index) = self.module.local.defined_table_index(table_index) {
+            self.table_grow(defined_table_index, delta, init)
+        } else {
+            let import = self.imported_table(table_index);
+            let foreign_instance = unsafe { (&mut *import.vmctx).instance() };
+            let foreign_table = unsafe { &*import.from };
+            let foreign_index = foreign_instance.table_index(foreign_table);
+            foreign_instance.table_grow(foreign_index, delta, init)
+        }
+    }
+
+    // Get table element by index.
+    fn table_get(&self, table_index: DefinedTableIndex, index: u32) -> Option<TableElement> {
         self.tables
             .get(table_index)
             .unwrap_or_else(|| panic!("no table for index {}", table_index.index()))
@@ -535,7 +644,7 @@ impl Instance {
         &self,
         table_index: DefinedTableIndex,
         index: u32,
-        val: VMCallerCheckedAnyfunc,
+        val: TableElement,
     ) -> Result<(), ()> {
         self.tables
             .get(table_index)
@@ -543,11 +652,11 @@ impl Instance {
             .set(index, val)
     }
 
-    fn alloc_layout(&self) -> Layout {
-        let size = mem::size_of_val(self)
-            .checked_add(usize::try_from(self.offsets.size_of_vmctx()).unwrap())
+    fn alloc_layout(offsets: &VMOffsets) -> Layout {
+        let size = mem::size_of::<Self>()
+            .checked_add(usize::try_from(offsets.size_of_vmctx()).unwrap())
             .unwrap();
-        let align = mem::align_of_val(self);
+        let align = mem::align_of::<Self>();
         Layout::from_size_align(size, align).unwrap()
     }
 
@@ -577,6 +686,38 @@ impl Instance {
         }
     }
 
+    /// Returns a pointer to a copy of `anyfunc`, for use as a `funcref`
+    /// value by compiled wasm code, which lives as long as the instance the
+    /// function belongs to.
+    ///
+    /// # Safety
+    ///
+    /// `anyfunc` must be non-null and its `vmctx` must belong to a live
+    /// instance.
+    pub(crate) unsafe fn anyfunc_ptr(
+        anyfunc: VMCallerCheckedAnyfunc,
+    ) -> *mut VMCallerCheckedAnyfunc {
+        let instance = (&mut *anyfunc.vmctx).instance();
+        let mut funcrefs = instance.funcrefs.borrow_mut();
+        let anyfunc = funcrefs
+            .entry(anyfunc.func_ptr as usize)
+            .or_insert_with(|| Box::new(anyfunc));
+        &mut **anyfunc
+    }
+
+    /// Returns the table keeping alive the `externref`s handed to compiled
+    /// wasm code.
+    pub(crate) fn externref_activations_table(&self) -> &VMExternRefActivationsTable {
+        &self.externref_activations_table
+    }
+
+    /// The `ref.func` operation: returns the `funcref` value for the
+    /// function at `index`.
+    pub(crate) fn ref_func(&self, index: FuncIndex) -> *mut u8 {
+        let anyfunc = self.get_caller_checked_anyfunc(index);
+        unsafe { TableElement::FuncRef(anyfunc).into_raw(&self.externref_activations_table) }
+    }
+
     /// The `table.init` operation: initializes a portion of a table with a
     /// passive element.
     ///
@@ -613,7 +754,7 @@ impl Instance {
         // TODO(#983): investigate replacing this get/set loop with a `memcpy`.
         for (dst, src) in (dst..dst + len).zip(src..src + len) {
             table
-                .set(dst, elem[src as usize].clone())
+                .set(dst, table_element(table, elem[src as usize].clone()))
                 .expect("should never panic because we already did the bounds check above");
         }
 
@@ -646,7 +787,7 @@ impl Instance {
     ) -> Result<(), Trap> {
         // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-memory-copy
 
-        let memory = self.memory(memory_index);
+        let memory = self.defined_memory(memory_index);
 
         if src
             .checked_add(len)
@@ -703,7 +844,7 @@ impl Instance {
         len: u32,
         source_loc: ir::SourceLoc,
     ) -> Result<(), Trap> {
-        let memory = self.memory(memory_index);
+        let memory = self.defined_memory(memory_index);
 
         if dst
             .checked_add(len)
@@ -770,6 +911,114 @@ impl Instance {
         let foreign_index = foreign_instance.table_index(foreign_table);
         &foreign_instance.tables[foreign_index]
     }
+
+    /// Captures the state of the memories, mutable globals, tables and passive
+    /// elements this instance defines.
+    ///
+    /// Returns an error if this instance defines a shared memory, which other
+    /// threads may be changing, or a mutable reference-typed global, whose
+    /// reference the snapshot couldn't keep alive.
+    pub(crate) fn snapshot(&self) -> Result<InstanceSnapshot, String> {
+        let module = &self.module;
+
+        let mut memories = PrimaryMap::with_capacity(self.memories.len());
+        for (index, memory) in self.memories.iter() {
+            if memory.shared().is_some() {
+                return Err("snapshots of shared memories are not supported".to_string());
+            }
+            let definition = self.memory(index);
+            let data = unsafe { slice::from_raw_parts(definition.base, definition.current_length) };
+            memories.push(MemorySnapshot::new(memory.size(), data));
+        }
+
+        let mut globals = Vec::new();
+        for (index, global) in module.local.globals.iter() {
+            let defined_index = match module.local.defined_global_index(index) {
+                Some(defined_index) if global.mutability => defined_index,
+                _ => continue,
+            };
+            if global.ty.is_ref() {
+                return Err(
+                    "snapshots of mutable reference-typed globals are not supported".to_string(),
+                );
+            }
+            globals.push((defined_index, self.global(defined_index)));
+        }
+
+        let tables = self
+            .tables
+            .values()
+            .map(|table| {
+                (0..table.size())
+                    .map(|i| table.get(i).unwrap())
+                    .collect::<Box<[_]>>()
+            })
+            .collect();
+
+        Ok(InstanceSnapshot {
+            module: module.clone(),
+            instance_id: self.id,
+            memories,
+            globals,
+            tables,
+            passive_elements: self.passive_elements.borrow().clone(),
+        })
+    }
+
+    /// Restores the memories, mutable globals, tables and passive elements of
+    /// this instance to the state captured in `snapshot`, which must have been
+    /// taken of this instance.
+    ///
+    /// Memories and tables are grown or shrunk back to their size at the time
+    /// of the snapshot. The contents of memories are mapped back in
+    /// copy-on-write if possible, and copied otherwise.
+    pub(crate) fn restore(&self, snapshot: &InstanceSnapshot) -> Result<(), String> {
+        if !Arc::ptr_eq(&self.module, &snapshot.module) || snapshot.instance_id != self.id {
+            return Err("snapshot was taken of a different instance".to_string());
+        }
+
+        for (index, memory_snapshot) in snapshot.memories.iter() {
+            let memory = &self.memories[index];
+            let size = memory.size();
+            if size > memory_snapshot.pages && !memory.shrink(memory_snapshot.pages) {
+                return Err("memory has grown and can't be shrunk".to_string());
+            }
+            if size < memory_snapshot.pages {
+                memory
+                    .grow(memory_snapshot.pages - size)
+                    .ok_or_else(|| "failed to grow memory".to_string())?;
+            }
+            // Keep current the VMContext pointers used by compiled wasm code.
+            self.set_memory(index, memory.vmmemory());
+
+            let mapped = match &memory_snapshot.contents {
+                MemoryContents::Image(image) => memory.map_image(image)?,
+                MemoryContents::Bytes(_) => false,
+            };
+            if !mapped {
+                let definition = self.memory(index);
+                let data = unsafe {
+                    slice::from_raw_parts_mut(definition.base, definition.current_length)
+                };
+                memory_snapshot.contents.copy_to(data)?;
+            }
+        }
+
+        for (index, elements) in snapshot.tables.iter() {
+            self.tables[index]
+                .reset(elements)
+                .map_err(|()| "failed to restore table".to_string())?;
+            self.set_table(index, self.tables[index].vmtable());
+        }
+
+        for (index, global) in snapshot.globals.iter() {
+            self.set_global(*index, *global);
+        }
+
+        *self.passive_elements.borrow_mut() = snapshot.passive_elements.clone();
+
+        Ok(())
+    }
 }
 
 /// A handle holding an `Instance` of a WebAssembly module.
@@ -800,13 +1049,28 @@ impl InstanceHandle {
         finished_functions: BoxedSlice<DefinedFuncIndex, *mut [VMFunctionBody]>,
         imports: Imports,
         data_initializers: &[DataInitializer<'_>],
+        memory_images: Option<&ModuleMemoryImages>,
         vmshared_signatures: BoxedSlice<SignatureIndex, VMSharedSignatureIndex>,
         dbg_jit_registration: Option<Rc<GdbJitImageRegistration>>,
         is_bulk_memory: bool,
         host_state: Box<dyn Any>,
+        interrupts: Arc<VMInterrupts>,
+        externref_activations_table: Rc<VMExternRefActivationsTable>,
+        limiter: Option<Rc<dyn ResourceLimiter>>,
+        mem_creator: Option<&dyn RuntimeMemoryCreator>,
+        pool: Option<&InstancePool>,
     ) -> Result<Self, InstantiationError> {
-        let tables = create_tables(&module);
-        let memories = create_memories(&module)?;
+        let slot = match pool {
+            Some(pool) => Some(pool.allocate(&module)?),
+            None => None,
+        };
+        let tables = create_tables(&module, limiter.as_deref(), slot.as_ref())?;
+        let memories = create_memories(
+            &module,
+            limiter.as_deref(),
+            mem_creator.unwrap_or(&DefaultMemoryCreator),
+            slot.as_ref(),
+        )?;
 
         let vmctx_tables = tables
             .values()
@@ -816,16 +1080,29 @@ impl InstanceHandle {
 
         let vmctx_memories = memories
             .values()
-            .map(LinearMemory::vmmemory)
+            .map(|memory| memory.vmmemory())
             .collect::<PrimaryMap<DefinedMemoryIndex, _>>()
             .into_boxed_slice();
 
         let vmctx_globals = create_globals(&module);
 
         let offsets = VMOffsets::new(mem::size_of::<*const u8>() as u8, &module.local);
+        let layout = Instance::alloc_layout(&offsets);
+        let instance_ptr = match &slot {
+            Some(slot) => slot.instance_ptr(layout)?,
+            None => {
+                let ptr = alloc::alloc(layout);
+                if ptr.is_null() {
+                    alloc::handle_alloc_error(layout);
+                }
+                ptr
+            }
+        } as *mut Instance;
 
         let handle = {
+            static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
             let instance = Instance {
+                id: NEXT_ID.fetch_add(1, SeqCst),
                 refcount: Cell::new(1),
                 dependencies: imports.dependencies,
                 module,
@@ -833,18 +1110,18 @@ impl InstanceHandle {
                 memories,
                 tables,
                 passive_elements: Default::default(),
+                funcrefs: Default::default(),
                 finished_functions,
                 dbg_jit_registration,
                 host_state,
                 signal_handler: Cell::new(None),
                 trap_registration,
+                interrupts,
+                limiter,
+                externref_activations_table,
+                slot,
                 vmctx: VMContext {},
             };
-            let layout = instance.alloc_layout();
-            let instance_ptr = alloc::alloc(layout) as *mut Instance;
-            if instance_ptr.is_null() {
-                alloc::handle_alloc_error(layout);
-            }
             ptr::write(instance_ptr, instance);
             InstanceHandle {
                 instance: instance_ptr,
@@ -852,6 +1129,10 @@ impl InstanceHandle {
         };
         let instance = handle.instance();
 
+        ptr::write(
+            instance.interrupts_ptr(),
+            &*instance.interrupts as *const VMInterrupts,
+        );
         ptr::copy(
             vmshared_signatures.values().as_slice().as_ptr(),
             instance.signature_ids_ptr() as *mut VMSharedSignatureIndex,
@@ -909,8 +1190,9 @@ impl InstanceHandle {
         // Apply the initializers.
         initialize_tables(instance)?;
         initialize_passive_elements(instance);
-        initialize_memories(instance, data_initializers)?;
+        initialize_memories(instance, data_initializers, memory_images)?;
         initialize_globals(instance);
+        register_global_roots(instance);
 
         // Ensure that our signal handlers are ready for action.
         // TODO: Move these calls out of `InstanceHandle`.
@@ -994,38 +1276,47 @@ impl InstanceHandle {
         self.instance().memory_grow(memory_index, delta)
     }
 
+    /// Return the memory at the given index in this instance if it's a shared
+    /// memory, which can then be used from other threads.
+    pub fn shared_memory(&self, memory_index: DefinedMemoryIndex) -> Option<SharedMemory> {
+        self.instance().memories[memory_index].shared()
+    }
+
     /// Return the table index for the given `VMTableDefinition` in this instance.
     pub fn table_index(&self, table: &VMTableDefinition) -> DefinedTableIndex {
         self.instance().table_index(table)
     }
 
-    /// Grow table in this instance by the specified amount of pages.
+    /// Grow table in this instance by the specified amount of elements,
+    /// filling the new ones with `init`.
     ///
-    /// Returns `None` if memory can't be grown by the specified amount
-    /// of pages.
-    pub fn table_grow(&self, table_index: DefinedTableIndex, delta: u32) -> Option<u32> {
-        self.instance().table_grow(table_index, delta)
+    /// Returns the previous size of the table, or `None` if it can't be
+    /// grown by the specified amount of elements.
+    pub fn table_grow(
+        &self,
+        table_index: DefinedTableIndex,
+        delta: u32,
+        init: TableElement,
+    ) -> Option<u32> {
+        self.instance().table_grow(table_index, delta, init)
     }
 
     /// Get table element reference.
     ///
     /// Returns `None` if index is out of bounds.
-    pub fn table_get(
-        &self,
-        table_index: DefinedTableIndex,
-        index: u32,
-    ) -> Option<VMCallerCheckedAnyfunc> {
+    pub fn table_get(&self, table_index: DefinedTableIndex, index: u32) -> Option<TableElement> {
         self.instance().table_get(table_index, index)
     }
 
     /// Set table element reference.
     ///
-    /// Returns an error if the index is out of bounds
+    /// Returns an error if the index is out of bounds or the element is of
+    /// the wrong type for the table.
     pub fn table_set(
         &self,
         table_index: DefinedTableIndex,
         index: u32,
-        val: VMCallerCheckedAnyfunc,
+        val: TableElement,
     ) -> Result<(), ()> {
         self.instance().table_set(table_index, index, val)
     }
@@ -1035,6 +1326,22 @@ impl InstanceHandle {
         self.instance().get_defined_table(index)
     }
 
+    /// Captures the state of the memories, mutable globals, tables and passive
+    /// elements this instance defines, which it can later be restored to with
+    /// `restore`.
+    pub fn snapshot(&self) -> Result<InstanceSnapshot, String> {
+        self.instance().snapshot()
+    }
+
+    /// Restores this instance to the state captured in `snapshot`, which must
+    /// have been taken of this instance.
+    ///
+    /// If an error is returned, the instance may have been partially
+    /// restored.
+    pub fn restore(&self, snapshot: &InstanceSnapshot) -> Result<(), String> {
+        self.instance().restore(snapshot)
+    }
+
     /// Return a reference to the contained `Instance`.
     pub(crate) fn instance(&self) -> &Instance {
         unsafe { &*(self.instance as *const Instance) }
@@ -1057,15 +1364,46 @@ impl Drop for InstanceHandle {
         let count = instance.refcount.get();
         instance.refcount.set(count - 1);
         if count == 1 {
-            let layout = instance.alloc_layout();
+            let layout = Instance::alloc_layout(&instance.offsets);
             unsafe {
+                // The slot is only given back to the pool once everything
+                // living in it has been dropped.
+                let slot = (*self.instance).slot.take();
+                instance
+                    .externref_activations_table
+                    .unregister_global_roots(instance.vmctx_ptr() as usize);
                 ptr::drop_in_place(self.instance);
-                alloc::dealloc(self.instance.cast(), layout);
+                match slot {
+                    Some(slot) => drop(slot),
+                    None => alloc::dealloc(self.instance.cast(), layout),
+                }
             }
         }
     }
 }
 
+/// Registers this instance's reference-typed globals as roots for garbage
+/// collection of `externref`s, since compiled code stores to them directly.
+///
+/// `funcref` and `externref` globals can't be told apart here, but registering
+/// `funcref`s too is harmless: roots are only matched against `externref`s.
+fn register_global_roots(instance: &Instance) {
+    let module = &instance.module;
+    let globals = module
+        .local
+        .globals
+        .iter()
+        .filter(|(_, global)| global.ty.is_ref())
+        .filter_map(|(index, _)| module.local.defined_global_index(index))
+        .map(|index| instance.global_ptr(index) as *const VMGlobalDefinition)
+        .collect();
+    unsafe {
+        instance
+            .externref_activations_table
+            .register_global_roots(instance.vmctx_ptr() as usize, globals);
+    }
+}
+
 fn check_table_init_bounds(instance: &Instance) -> Result<(), InstantiationError> {
     let module = Arc::clone(&instance.module);
     for init in &module.table_elements {
@@ -1111,13 +1449,13 @@ unsafe fn get_memory_slice<'instance>(
         .local
         .defined_memory_index(init.location.memory_index)
     {
-        instance.memory(defined_memory_index)
+        instance.defined_memory(defined_memory_index)
     } else {
         let import = instance.imported_memory(init.location.memory_index);
         let foreign_instance = (&mut *(import).vmctx).instance();
         let foreign_memory = &mut *(import).from;
         let foreign_index = foreign_instance.memory_index(foreign_memory);
-        foreign_instance.memory(foreign_index)
+        foreign_instance.defined_memory(foreign_index)
     };
     slice::from_raw_parts_mut(memory.base, memory.current_length)
 }
@@ -1142,14 +1480,30 @@ fn check_memory_init_bounds(
 }
 
 /// Allocate memory for just the tables of the current module.
-fn create_tables(module: &Module) -> BoxedSlice<DefinedTableIndex, Table> {
+fn create_tables(
+    module: &Module,
+    limiter: Option<&dyn ResourceLimiter>,
+    slot: Option<&InstanceSlot>,
+) -> Result<BoxedSlice<DefinedTableIndex, Table>, InstantiationError> {
     let num_imports = module.imported_tables.len();
     let mut tables: PrimaryMap<DefinedTableIndex, _> =
         PrimaryMap::with_capacity(module.local.table_plans.len() - num_imports);
-    for table in &module.local.table_plans.values().as_slice()[num_imports..] {
-        tables.push(Table::new(table));
+    for plan in &module.local.table_plans.values().as_slice()[num_imports..] {
+        if let Some(limiter) = limiter {
+            if !limiter.table_growing(0, plan.table.minimum, plan.table.maximum) {
+                return Err(InstantiationError::Resource(format!(
+                    "table minimum size of {} elements exceeds table limits",
+                    plan.table.minimum
+                )));
+            }
+        }
+        let table = match slot {
+            Some(slot) => slot.table(DefinedTableIndex::new(tables.len()), plan)?,
+            None => Table::new(plan),
+        };
+        tables.push(table);
     }
-    tables.into_boxed_slice()
+    Ok(tables.into_boxed_slice())
 }
 
 /// Compute the offset for a table element initializer.
@@ -1190,7 +1544,10 @@ fn initialize_tables(instance: &Instance) -> Result<(), InstantiationError> {
         for (i, func_idx) in init.elements.iter().enumerate() {
             let anyfunc = instance.get_caller_checked_anyfunc(*func_idx);
             table
-                .set(u32::try_from(start + i).unwrap(), anyfunc)
+                .set(
+                    u32::try_from(start + i).unwrap(),
+                    table_element(table, anyfunc),
+                )
                 .unwrap();
         }
     }
@@ -1198,6 +1555,20 @@ fn initialize_tables(instance: &Instance) -> Result<(), InstantiationError> {
     Ok(())
 }
 
+/// Converts an element of an element segment, resolved into a
+/// `VMCallerCheckedAnyfunc`, into an element of `table`.
+///
+/// Element segments of `externref` tables can only hold null references.
+fn table_element(table: &Table, anyfunc: VMCallerCheckedAnyfunc) -> TableElement {
+    match table.element_type() {
+        TableElementType::Func => TableElement::FuncRef(anyfunc),
+        TableElementType::Val(_) => {
+            debug_assert!(anyfunc.func_ptr.is_null());
+            TableElement::ExternRef(None)
+        }
+    }
+}
+
 /// Initialize the `Instance::passive_elements` map by resolving the
 /// `Module::passive_elements`'s `FuncIndex`s into `VMCallerCheckedAnyfunc`s for
 /// this instance.
@@ -1229,22 +1600,75 @@ fn initialize_passive_elements(instance: &Instance) {
 /// Allocate memory for just the memories of the current module.
 fn create_memories(
     module: &Module,
-) -> Result<BoxedSlice<DefinedMemoryIndex, LinearMemory>, InstantiationError> {
+    limiter: Option<&dyn ResourceLimiter>,
+    mem_creator: &dyn RuntimeMemoryCreator,
+    slot: Option<&InstanceSlot>,
+) -> Result<BoxedSlice<DefinedMemoryIndex, Box<dyn RuntimeLinearMemory>>, InstantiationError> {
     let num_imports = module.imported_memories.len();
     let mut memories: PrimaryMap<DefinedMemoryIndex, _> =
         PrimaryMap::with_capacity(module.local.memory_plans.len() - num_imports);
     for plan in &module.local.memory_plans.values().as_slice()[num_imports..] {
-        memories.push(LinearMemory::new(plan).map_err(InstantiationError::Resource)?);
+        if let Some(limiter) = limiter {
+            if !limiter.memory_growing(0, plan.memory.minimum, plan.memory.maximum) {
+                return Err(InstantiationError::Resource(format!(
+                    "memory minimum size of {} pages exceeds memory limits",
+                    plan.memory.minimum
+                )));
+            }
+        }
+        let memory = if plan.memory.shared {
+            // Shared memories may outlive the instance, so they're never
+            // allocated from a pool slot.
+            Box::new(
+                mem_creator
+                    .new_shared_memory(plan)
+                    .map_err(InstantiationError::Resource)?,
+            ) as Box<dyn RuntimeLinearMemory>
+        } else {
+            match slot {
+                Some(slot) => slot.memory(DefinedMemoryIndex::new(memories.len()), plan)?,
+                None => mem_creator
+                    .new_memory(plan)
+                    .map_err(InstantiationError::Resource)?,
+            }
+        };
+        memories.push(memory);
     }
     Ok(memories.into_boxed_slice())
 }
 
 /// Initialize the table memory from the provided initializers.
+///
+/// Memories which `memory_images` has an image of are initialized by mapping
+/// it in, if they support that, rather than copying their data segments in.
 fn initialize_memories(
     instance: &Instance,
     data_initializers: &[DataInitializer<'_>],
+    memory_images: Option<&ModuleMemoryImages>,
 ) -> Result<(), InstantiationError> {
+    let mut from_image: PrimaryMap<DefinedMemoryIndex, bool> =
+        PrimaryMap::with_capacity(instance.memories.len());
+    for (index, memory) in instance.memories.iter() {
+        let mapped = match memory_images.and_then(|images| images.get(index)) {
+            Some(image) => memory
+                .map_image(image)
+                .map_err(InstantiationError::Resource)?,
+            None => false,
+        };
+        from_image.push(mapped);
+    }
+
     for init in data_initializers {
+        if let Some(index) = instance
+            .module
+            .local
+            .defined_memory_index(init.location.memory_index)
+        {
+            if from_image[index] {
+                continue;
+            }
+        }
+
         let memory = instance.get_memory(init.location.memory_index);
 
         let start = get_memory_init_start(init, instance);
@@ -1304,7 +1728,8 @@ fn initialize_globals(instance: &Instance) {
                     *to = from;
                 }
                 GlobalInit::Import => panic!("locally-defined global initialized as import"),
-                GlobalInit::RefNullConst | GlobalInit::RefFunc(_) => unimplemented!(),
+                GlobalInit::RefNullConst => *(*to).as_reference_mut() = ptr::null_mut(),
+                GlobalInit::RefFunc(f) => *(*to).as_reference_mut() = instance.ref_func(f),
             }
         }
     }
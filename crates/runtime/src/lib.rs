@@ -21,14 +21,22 @@
     )
 )]
 
+mod cow;
 mod export;
+mod externref;
+mod fiber;
 mod imports;
 mod instance;
 mod jit_int;
+mod limits;
 mod memory;
 mod mmap;
+mod parking_spot;
+mod pooling;
 mod sig_registry;
 mod signalhandlers;
+mod snapshot;
+mod stack_map_registry;
 mod table;
 mod trap_registry;
 mod traphandlers;
@@ -36,13 +44,23 @@ mod vmcontext;
 
 pub mod libcalls;
 
+pub use crate::cow::{MemoryImage, ModuleMemoryImages};
 pub use crate::export::Export;
+pub use crate::externref::{VMExternRef, VMExternRefActivationsTable};
+pub use crate::fiber::{Fiber, Suspend};
 pub use crate::imports::Imports;
 pub use crate::instance::{InstanceHandle, InstantiationError, LinkError};
 pub use crate::jit_int::GdbJitImageRegistration;
+pub use crate::limits::{
+    ResourceLimiter, DEFAULT_INSTANCE_LIMIT, DEFAULT_MEMORY_LIMIT, DEFAULT_TABLE_LIMIT,
+};
+pub use crate::memory::{RuntimeLinearMemory, RuntimeMemoryCreator, SharedMemory};
 pub use crate::mmap::Mmap;
+pub use crate::pooling::{InstancePool, PoolingLimits};
 pub use crate::sig_registry::SignatureRegistry;
-pub use crate::table::Table;
+pub use crate::snapshot::InstanceSnapshot;
+pub use crate::stack_map_registry::{StackMapRegistration, StackMapRegistry};
+pub use crate::table::{Table, TableElement};
 pub use crate::trap_registry::{TrapDescription, TrapRegistration, TrapRegistry};
 pub use crate::traphandlers::resume_panic;
 pub use crate::traphandlers::{
@@ -50,8 +68,8 @@ pub use crate::traphandlers::{
 };
 pub use crate::vmcontext::{
     VMCallerCheckedAnyfunc, VMContext, VMFunctionBody, VMFunctionImport, VMGlobalDefinition,
-    VMGlobalImport, VMInvokeArgument, VMMemoryDefinition, VMMemoryImport, VMSharedSignatureIndex,
-    VMTableDefinition, VMTableImport,
+    VMGlobalImport, VMInterrupts, VMInvokeArgument, VMMemoryDefinition, VMMemoryImport,
+    VMSharedSignatureIndex, VMTableDefinition, VMTableImport,
 };
 
 /// Version number of this crate.
@@ -32,11 +32,15 @@
 //!   }
 //!   ```
 
-use crate::table::Table;
-use crate::traphandlers::raise_lib_trap;
+use crate::parking_spot;
+use crate::table::{Table, TableElement};
+use crate::traphandlers::{raise_lib_trap, Trap};
 use crate::vmcontext::VMContext;
+use std::time::Duration;
 use wasmtime_environ::ir;
-use wasmtime_environ::wasm::{DefinedMemoryIndex, MemoryIndex, PassiveElemIndex, TableIndex};
+use wasmtime_environ::wasm::{
+    DefinedMemoryIndex, FuncIndex, MemoryIndex, PassiveElemIndex, TableIndex,
+};
 
 /// Implementation of f32.ceil
 pub extern "C" fn wasmtime_f32_ceil(x: f32) -> f32 {
@@ -299,3 +303,145 @@ pub unsafe extern "C" fn wasmtime_imported_memory_fill(
         raise_lib_trap(trap);
     }
 }
+
+/// Implementation of `table.grow`.
+///
+/// References to table elements, here and in the other table libcalls, are
+/// pointers whose meaning depends on the table's element type, see
+/// `TableElement::from_raw`.
+pub unsafe extern "C" fn wasmtime_table_grow(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    delta: u32,
+    init_value: *mut u8,
+) -> u32 {
+    let table_index = TableIndex::from_u32(table_index);
+    let instance = (&mut *vmctx).instance();
+    let ty = instance.get_table(table_index).element_type();
+    let init_value = TableElement::from_raw(ty, init_value);
+
+    instance
+        .defined_or_imported_table_grow(table_index, delta, init_value)
+        .unwrap_or(u32::max_value())
+}
+
+/// Implementation of `table.get`.
+pub unsafe extern "C" fn wasmtime_table_get(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    index: u32,
+    source_loc: u32,
+) -> *mut u8 {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let instance = (&mut *vmctx).instance();
+        let activations = instance.externref_activations_table();
+        // All the references live in wasm frames are in stack maps at this
+        // point, making it a good time to collect garbage.
+        activations.maybe_gc();
+        match instance.get_table(table_index).get(index) {
+            Some(elem) => Ok(elem.into_raw(activations)),
+            None => Err(Trap::wasm(
+                ir::SourceLoc::new(source_loc),
+                ir::TrapCode::TableOutOfBounds,
+            )),
+        }
+    };
+    match result {
+        Ok(value) => value,
+        Err(trap) => raise_lib_trap(trap),
+    }
+}
+
+/// Implementation of `table.set`.
+pub unsafe extern "C" fn wasmtime_table_set(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    index: u32,
+    value: *mut u8,
+    source_loc: u32,
+) {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let instance = (&mut *vmctx).instance();
+        let table = instance.get_table(table_index);
+        let value = TableElement::from_raw(table.element_type(), value);
+        table.set(index, value).map_err(|()| {
+            Trap::wasm(
+                ir::SourceLoc::new(source_loc),
+                ir::TrapCode::TableOutOfBounds,
+            )
+        })
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `table.fill`.
+pub unsafe extern "C" fn wasmtime_table_fill(
+    vmctx: *mut VMContext,
+    table_index: u32,
+    dst: u32,
+    value: *mut u8,
+    len: u32,
+    source_loc: u32,
+) {
+    let result = {
+        let table_index = TableIndex::from_u32(table_index);
+        let source_loc = ir::SourceLoc::new(source_loc);
+        let instance = (&mut *vmctx).instance();
+        let table = instance.get_table(table_index);
+        let value = TableElement::from_raw(table.element_type(), value);
+        table.fill(dst, value, len, source_loc)
+    };
+    if let Err(trap) = result {
+        raise_lib_trap(trap);
+    }
+}
+
+/// Implementation of `ref.func`.
+pub unsafe extern "C" fn wasmtime_ref_func(vmctx: *mut VMContext, func_index: u32) -> *mut u8 {
+    let func_index = FuncIndex::from_u32(func_index);
+    let instance = (&mut *vmctx).instance();
+    instance.ref_func(func_index)
+}
+
+/// Implementation of `memory.atomic.wait32`.
+pub unsafe extern "C" fn wasmtime_memory_atomic_wait32(
+    _vmctx: *mut VMContext,
+    addr: *mut u8,
+    expected: u32,
+    timeout: i64,
+) -> u32 {
+    parking_spot::wait32(addr as *const u32, expected, wait_timeout(timeout)) as u32
+}
+
+/// Implementation of `memory.atomic.wait64`.
+pub unsafe extern "C" fn wasmtime_memory_atomic_wait64(
+    _vmctx: *mut VMContext,
+    addr: *mut u8,
+    expected: u64,
+    timeout: i64,
+) -> u32 {
+    parking_spot::wait64(addr as *const u64, expected, wait_timeout(timeout)) as u32
+}
+
+/// Converts the timeout operand of `memory.atomic.wait`, in nanoseconds,
+/// where negative values mean waiting forever.
+fn wait_timeout(timeout: i64) -> Option<Duration> {
+    if timeout < 0 {
+        None
+    } else {
+        Some(Duration::from_nanos(timeout as u64))
+    }
+}
+
+/// Implementation of `memory.atomic.notify`.
+pub unsafe extern "C" fn wasmtime_memory_atomic_notify(
+    _vmctx: *mut VMContext,
+    addr: *mut u8,
+    count: u32,
+) -> u32 {
+    parking_spot::notify(addr, count)
+}
@@ -0,0 +1,73 @@
+//! Embedder-provided limits on the resources wasm instances may use.
+
+/// The default number of instances a [`ResourceLimiter`] allows.
+pub const DEFAULT_INSTANCE_LIMIT: usize = 10000;
+/// The default number of tables a [`ResourceLimiter`] allows.
+pub const DEFAULT_TABLE_LIMIT: usize = 10000;
+/// The default number of memories a [`ResourceLimiter`] allows.
+pub const DEFAULT_MEMORY_LIMIT: usize = 10000;
+
+/// Used by hosts to limit resource consumption of instances.
+///
+/// A limiter is consulted whenever a linear memory or table is created or
+/// grown, in addition to the limits declared by the module itself, so hosts
+/// can take their own resource usage policies into account.
+pub trait ResourceLimiter {
+    /// Notifies the resource limiter that a linear memory is being created or
+    /// has been requested to grow.
+    ///
+    /// * `current` is the current size of the linear memory in WebAssembly
+    ///   page units, or 0 if the memory is being created.
+    /// * `desired` is the desired size of the linear memory in WebAssembly
+    ///   page units.
+    /// * `maximum` is the linear memory's declared maximum in WebAssembly page
+    ///   units. A value of `None` indicates that the linear memory is
+    ///   unbounded.
+    ///
+    /// This function should return `true` to indicate that the operation is
+    /// permitted or `false` if not. Returning `true` when a maximum has been
+    /// exceeded will have no effect as the linear memory will not grow.
+    fn memory_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// Notifies the resource limiter that a table is being created or has
+    /// been requested to grow.
+    ///
+    /// * `current` is the current number of elements in the table, or 0 if
+    ///   the table is being created.
+    /// * `desired` is the desired number of elements in the table.
+    /// * `maximum` is the table's declared maximum number of elements. A value
+    ///   of `None` indicates that the table is unbounded.
+    ///
+    /// This function should return `true` to indicate that the operation is
+    /// permitted or `false` if not. Returning `true` when a maximum has been
+    /// exceeded will have no effect as the table will not grow.
+    fn table_growing(&self, current: u32, desired: u32, maximum: Option<u32>) -> bool;
+
+    /// The maximum number of instances that can be created for a `Store`.
+    ///
+    /// Module instantiation will fail if this limit is exceeded.
+    ///
+    /// This value defaults to 10,000.
+    fn instances(&self) -> usize {
+        DEFAULT_INSTANCE_LIMIT
+    }
+
+    /// The maximum number of tables that can be created for a `Store`.
+    ///
+    /// Module instantiation will fail if this limit is exceeded.
+    ///
+    /// This value defaults to 10,000.
+    fn tables(&self) -> usize {
+        DEFAULT_TABLE_LIMIT
+    }
+
+    /// The maximum number of linear memories that can be created for a
+    /// `Store`.
+    ///
+    /// Module instantiation will fail if this limit is exceeded.
+    ///
+    /// This value defaults to 10,000.
+    fn memories(&self) -> usize {
+        DEFAULT_MEMORY_LIMIT
+    }
+}
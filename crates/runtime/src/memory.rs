@@ -1,17 +1,85 @@
 //! Memory management for linear memories.
 //!
-//! `LinearMemory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
+//! `RuntimeLinearMemory` is to WebAssembly linear memories what `Table` is to WebAssembly tables.
 
+use crate::cow::{unmap_image, MemoryImage};
 use crate::mmap::Mmap;
 use crate::vmcontext::VMMemoryDefinition;
 use more_asserts::{assert_ge, assert_le};
-use std::cell::RefCell;
+use std::cell::{RefCell, UnsafeCell};
 use std::convert::TryFrom;
+use std::sync::{Arc, Mutex};
 use wasmtime_environ::{MemoryPlan, MemoryStyle, WASM_MAX_PAGES, WASM_PAGE_SIZE};
 
+/// A memory allocator
+pub trait RuntimeMemoryCreator: Send + Sync {
+    /// Create new RuntimeLinearMemory
+    fn new_memory(&self, plan: &MemoryPlan) -> Result<Box<dyn RuntimeLinearMemory>, String>;
+
+    /// Create a new shared memory, which may be accessed from any thread.
+    fn new_shared_memory(&self, plan: &MemoryPlan) -> Result<SharedMemory, String> {
+        SharedMemory::new(plan)
+    }
+}
+
+/// A default memory allocator used by Wasmtime
+pub struct DefaultMemoryCreator;
+
+impl RuntimeMemoryCreator for DefaultMemoryCreator {
+    /// Create new MmapMemory
+    fn new_memory(&self, plan: &MemoryPlan) -> Result<Box<dyn RuntimeLinearMemory>, String> {
+        Ok(Box::new(MmapMemory::new(plan)?) as Box<dyn RuntimeLinearMemory>)
+    }
+}
+
+/// A linear memory
+pub trait RuntimeLinearMemory {
+    /// Returns the number of allocated wasm pages.
+    fn size(&self) -> u32;
+
+    /// Returns the maximum number of wasm pages this memory may grow to, if
+    /// one was declared.
+    fn maximum(&self) -> Option<u32>;
+
+    /// Grow memory by the specified amount of wasm pages.
+    ///
+    /// Returns `None` if memory can't be grown by the specified amount
+    /// of wasm pages.
+    fn grow(&self, delta: u32) -> Option<u32>;
+
+    /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm code.
+    fn vmmemory(&self) -> VMMemoryDefinition;
+
+    /// Maps `image` copy-on-write into this memory, replacing the contents
+    /// it covers. This is used to initialize newly created memories rather
+    /// than copying their data segments in, and to restore snapshots.
+    ///
+    /// Returns `Ok(false)` if this memory doesn't support images, in which
+    /// case the contents are copied in instead.
+    fn map_image(&self, image: &MemoryImage) -> Result<bool, String> {
+        let _ = image;
+        Ok(false)
+    }
+
+    /// Shrinks this memory to `pages` wasm pages, which must not be more than
+    /// its current size, discarding the contents beyond them.
+    ///
+    /// Wasm itself can never shrink a memory; this is only used to restore
+    /// snapshots. Returns `false` if this memory doesn't support shrinking.
+    fn shrink(&self, pages: u32) -> bool {
+        let _ = pages;
+        false
+    }
+
+    /// Returns the shared memory backing this memory, if it's shared.
+    fn shared(&self) -> Option<SharedMemory> {
+        None
+    }
+}
+
 /// A linear memory instance.
 #[derive(Debug)]
-pub struct LinearMemory {
+pub struct MmapMemory {
     // The underlying allocation.
     mmap: RefCell<WasmMmap>,
 
@@ -35,7 +103,7 @@ struct WasmMmap {
     size: u32,
 }
 
-impl LinearMemory {
+impl MmapMemory {
     /// Create a new linear memory instance with specified minimum and maximum number of wasm pages.
     pub fn new(plan: &MemoryPlan) -> Result<Self, String> {
         // `maximum` cannot be set to more than `65536` pages.
@@ -77,17 +145,25 @@ impl LinearMemory {
             needs_signal_handlers,
         })
     }
+}
 
+impl RuntimeLinearMemory for MmapMemory {
     /// Returns the number of allocated wasm pages.
-    pub fn size(&self) -> u32 {
+    fn size(&self) -> u32 {
         self.mmap.borrow().size
     }
 
+    /// Returns the maximum number of wasm pages this memory may grow to, if
+    /// one was declared.
+    fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
     /// Grow memory by the specified amount of wasm pages.
     ///
     /// Returns `None` if memory can't be grown by the specified amount
     /// of wasm pages.
-    pub fn grow(&self, delta: u32) -> Option<u32> {
+    fn grow(&self, delta: u32) -> Option<u32> {
         // Optimization of memory.grow 0 calls.
         let mut mmap = self.mmap.borrow_mut();
         if delta == 0 {
@@ -143,11 +219,140 @@ impl LinearMemory {
     }
 
     /// Return a `VMMemoryDefinition` for exposing the memory to compiled wasm code.
-    pub fn vmmemory(&self) -> VMMemoryDefinition {
+    fn vmmemory(&self) -> VMMemoryDefinition {
         let mut mmap = self.mmap.borrow_mut();
         VMMemoryDefinition {
             base: mmap.alloc.as_mut_ptr(),
             current_length: mmap.size as usize * WASM_PAGE_SIZE as usize,
         }
     }
+
+    /// Maps `image` copy-on-write into this memory.
+    fn map_image(&self, image: &MemoryImage) -> Result<bool, String> {
+        let mut mmap = self.mmap.borrow_mut();
+        unsafe {
+            image.map_at(mmap.alloc.as_mut_ptr())?;
+        }
+        Ok(true)
+    }
+
+    /// Shrinks this memory to `pages` wasm pages, leaving the pages beyond
+    /// them reserved but inaccessible, as if they had never been grown into.
+    fn shrink(&self, pages: u32) -> bool {
+        if !cfg!(unix) {
+            return false;
+        }
+        let mut mmap = self.mmap.borrow_mut();
+        assert_le!(pages, mmap.size);
+        let new_bytes = pages as usize * WASM_PAGE_SIZE as usize;
+        let old_bytes = mmap.size as usize * WASM_PAGE_SIZE as usize;
+        let result = unsafe {
+            unmap_image(
+                mmap.alloc.as_mut_ptr().add(new_bytes),
+                old_bytes - new_bytes,
+            )
+        };
+        if result.is_err() {
+            return false;
+        }
+        mmap.size = pages;
+        true
+    }
+}
+
+/// A linear memory which can be shared between threads, as declared with the
+/// `shared` attribute by the threads proposal.
+///
+/// Shared memories are always statically allocated, so growing them never
+/// moves them and every thread sees the same base address. Clones refer to
+/// the same memory.
+///
+/// The memory's `VMMemoryDefinition` lives alongside it rather than in the
+/// `VMContext` of an instance, so that instances importing it all see its
+/// current length whichever of them grew it.
+#[derive(Debug, Clone)]
+pub struct SharedMemory(Arc<SharedMemoryInner>);
+
+#[derive(Debug)]
+struct SharedMemoryInner {
+    plan: MemoryPlan,
+    memory: Mutex<MmapMemory>,
+    definition: UnsafeCell<VMMemoryDefinition>,
+}
+
+// `definition` is only written while `memory` is locked, and its base never
+// changes.
+unsafe impl Send for SharedMemoryInner {}
+unsafe impl Sync for SharedMemoryInner {}
+
+impl SharedMemory {
+    /// Create a new shared linear memory for `plan`, which must use the
+    /// static memory style.
+    pub fn new(plan: &MemoryPlan) -> Result<Self, String> {
+        if !plan.memory.shared {
+            return Err("shared memories must be declared as shared".to_string());
+        }
+        match plan.style {
+            MemoryStyle::Static { .. } => {}
+            MemoryStyle::Dynamic => {
+                return Err("shared memories must use the static memory style".to_string())
+            }
+        }
+        let memory = MmapMemory::new(plan)?;
+        let definition = UnsafeCell::new(memory.vmmemory());
+        Ok(Self(Arc::new(SharedMemoryInner {
+            plan: plan.clone(),
+            memory: Mutex::new(memory),
+            definition,
+        })))
+    }
+
+    /// Returns the plan this memory was allocated for, which instances
+    /// importing it must be compatible with.
+    pub fn plan(&self) -> &MemoryPlan {
+        &self.0.plan
+    }
+
+    /// Returns whether `self` and `other` refer to the same memory.
+    pub fn same(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
+    }
+
+    /// Returns a pointer to the `VMMemoryDefinition` of this memory, which
+    /// stays valid for as long as the memory is alive.
+    pub fn vmmemory_ptr(&self) -> *mut VMMemoryDefinition {
+        self.0.definition.get()
+    }
+}
+
+impl RuntimeLinearMemory for SharedMemory {
+    fn size(&self) -> u32 {
+        self.0.memory.lock().unwrap().size()
+    }
+
+    fn maximum(&self) -> Option<u32> {
+        self.0.memory.lock().unwrap().maximum()
+    }
+
+    fn grow(&self, delta: u32) -> Option<u32> {
+        let memory = self.0.memory.lock().unwrap();
+        let result = memory.grow(delta);
+        unsafe {
+            *self.0.definition.get() = memory.vmmemory();
+        }
+        result
+    }
+
+    fn vmmemory(&self) -> VMMemoryDefinition {
+        let _memory = self.0.memory.lock().unwrap();
+        unsafe { *self.0.definition.get() }
+    }
+
+    fn map_image(&self, image: &MemoryImage) -> Result<bool, String> {
+        self.0.memory.lock().unwrap().map_image(image)
+    }
+
+    fn shared(&self) -> Option<SharedMemory> {
+        Some(self.clone())
+    }
 }
@@ -0,0 +1,168 @@
+//! Blocking of threads on addresses in shared linear memories, for
+//! implementing `memory.atomic.wait` and `memory.atomic.notify`.
+//!
+//! Waiters are kept in a single process-wide table keyed by native address.
+//! Shared memories are never moved, so the native address identifies the
+//! same location for every instance the memory is imported into.
+
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+/// The outcome of a wait, as returned to wasm by `memory.atomic.wait`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum WaitResult {
+    /// The thread was woken by a notify.
+    Ok = 0,
+    /// The value in memory didn't match the expected one.
+    Mismatch = 1,
+    /// The timeout expired before the thread was woken.
+    TimedOut = 2,
+}
+
+#[derive(Default)]
+struct Spot {
+    /// Signalled whenever some of the threads parked here may be woken.
+    condvar: Arc<Condvar>,
+    /// The number of threads currently parked here.
+    num_parked: u32,
+    /// The number of parked threads which have been notified but haven't
+    /// woken up yet.
+    to_unpark: u32,
+}
+
+lazy_static! {
+    static ref SPOTS: Mutex<HashMap<usize, Spot>> = Mutex::new(HashMap::new());
+}
+
+/// Blocks the current thread on `addr` while it contains `expected`, for at
+/// most `timeout` if one is given.
+///
+/// # Safety
+///
+/// `addr` must point to a naturally aligned location in a shared linear
+/// memory.
+pub unsafe fn wait32(addr: *const u32, expected: u32, timeout: Option<Duration>) -> WaitResult {
+    let atomic = &*(addr as *const AtomicU32);
+    wait(
+        addr as usize,
+        || atomic.load(Ordering::SeqCst) == expected,
+        timeout,
+    )
+}
+
+/// 64-bit version of `wait32`.
+///
+/// # Safety
+///
+/// `addr` must point to a naturally aligned location in a shared linear
+/// memory.
+pub unsafe fn wait64(addr: *const u64, expected: u64, timeout: Option<Duration>) -> WaitResult {
+    let atomic = &*(addr as *const AtomicU64);
+    wait(
+        addr as usize,
+        || atomic.load(Ordering::SeqCst) == expected,
+        timeout,
+    )
+}
+
+fn wait(key: usize, matches: impl FnOnce() -> bool, timeout: Option<Duration>) -> WaitResult {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut spots = SPOTS.lock().unwrap();
+
+    // The value is checked while holding the lock that `notify` takes, so a
+    // store followed by a notify on another thread can't be missed.
+    if !matches() {
+        return WaitResult::Mismatch;
+    }
+
+    let spot = spots.entry(key).or_default();
+    spot.num_parked += 1;
+    let condvar = spot.condvar.clone();
+
+    let result = loop {
+        spots = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    break WaitResult::TimedOut;
+                }
+                condvar.wait_timeout(spots, deadline - now).unwrap().0
+            }
+            None => condvar.wait(spots).unwrap(),
+        };
+        let spot = spots.get_mut(&key).unwrap();
+        if spot.to_unpark > 0 {
+            spot.to_unpark -= 1;
+            break WaitResult::Ok;
+        }
+    };
+
+    let spot = spots.get_mut(&key).unwrap();
+    spot.num_parked -= 1;
+    if spot.num_parked == 0 {
+        spots.remove(&key);
+    }
+    result
+}
+
+/// Wakes up to `count` threads waiting on `addr`, returning the number of
+/// threads woken.
+pub fn notify(addr: *const u8, count: u32) -> u32 {
+    let mut spots = SPOTS.lock().unwrap();
+    let spot = match spots.get_mut(&(addr as usize)) {
+        Some(spot) => spot,
+        None => return 0,
+    };
+    let woken = count.min(spot.num_parked - spot.to_unpark);
+    if woken > 0 {
+        spot.to_unpark += woken;
+        spot.condvar.notify_all();
+    }
+    woken
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn mismatch() {
+        let value = 1u32;
+        let result = unsafe { wait32(&value, 0, None) };
+        assert_eq!(result, WaitResult::Mismatch);
+    }
+
+    #[test]
+    fn timeout() {
+        let value = 0u64;
+        let result = unsafe { wait64(&value, 0, Some(Duration::from_millis(10))) };
+        assert_eq!(result, WaitResult::TimedOut);
+    }
+
+    #[test]
+    fn notify_without_waiters() {
+        let value = 0u32;
+        assert_eq!(notify(&value as *const u32 as *const u8, 1), 0);
+    }
+
+    #[test]
+    fn wake_waiters() {
+        let value = Box::leak(Box::new(0u32)) as *mut u32 as usize;
+        let waiters = (0..4)
+            .map(|_| thread::spawn(move || unsafe { wait32(value as *const u32, 0, None) }))
+            .collect::<Vec<_>>();
+
+        let mut woken = 0;
+        while woken < 4 {
+            woken += notify(value as *const u8, 4 - woken);
+            thread::yield_now();
+        }
+        for waiter in waiters {
+            assert_eq!(waiter.join().unwrap(), WaitResult::Ok);
+        }
+    }
+}
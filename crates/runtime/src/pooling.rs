@@ -0,0 +1,471 @@
+//! A pooling allocator for instances, which reserves space for a fixed number
+//! of instances along with their memories and tables up front.
+//!
+//! Instantiating a module from the pool only needs to claim a free slot rather
+//! than allocate and map new memory, and dropping the instance resets its
+//! memories so the slot can be reused.
+
+use crate::cow::{unmap_image, MemoryImage};
+use crate::instance::InstantiationError;
+use crate::memory::RuntimeLinearMemory;
+use crate::mmap::Mmap;
+use crate::table::Table;
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMMemoryDefinition};
+use std::alloc::Layout;
+use std::cell::Cell;
+use std::convert::TryFrom;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use wasmtime_environ::entity::EntityRef;
+use wasmtime_environ::wasm::{DefinedMemoryIndex, DefinedTableIndex};
+use wasmtime_environ::{
+    MemoryPlan, MemoryStyle, Module, TablePlan, Tunables, WASM_MAX_PAGES, WASM_PAGE_SIZE,
+};
+
+/// Limits on the instances allocated from an [`InstancePool`].
+///
+/// These can't be changed once the pool has been created, since the space
+/// for the maximum number of instances, memories and tables is reserved when
+/// it is.
+#[derive(Debug, Clone, Copy)]
+pub struct PoolingLimits {
+    /// The maximum number of instances which can be alive at the same time.
+    pub instances: u32,
+
+    /// The maximum number of memories each instance can define.
+    pub memories: u32,
+
+    /// The maximum number of tables each instance can define.
+    pub tables: u32,
+
+    /// The maximum number of wasm pages each memory can grow to.
+    pub memory_pages: u32,
+
+    /// The maximum number of elements each table can grow to.
+    pub table_elements: u32,
+
+    /// The maximum size, in bytes, of the runtime state of each instance.
+    ///
+    /// This grows with the number of functions, globals, imports and
+    /// signatures of a module.
+    pub instance_size: usize,
+}
+
+impl Default for PoolingLimits {
+    fn default() -> Self {
+        Self {
+            instances: 1000,
+            memories: 1,
+            tables: 1,
+            memory_pages: 160,
+            table_elements: 10_000,
+            instance_size: 1 << 20,
+        }
+    }
+}
+
+/// A pool of preallocated slots for instances, their memories and their
+/// tables.
+///
+/// Using `clone` on a pool is cheap, and returns a new reference to the same
+/// pool.
+#[derive(Clone)]
+pub struct InstancePool {
+    inner: Arc<PoolInner>,
+}
+
+struct PoolInner {
+    limits: PoolingLimits,
+    instance_slot_size: usize,
+    memory_slot_size: usize,
+    memory_guard_size: usize,
+    table_slot_size: usize,
+    instances: Mmap,
+    memories: Mmap,
+    tables: Mmap,
+    free: Mutex<Vec<usize>>,
+}
+
+impl InstancePool {
+    /// Creates a new pool with the given `limits`.
+    ///
+    /// Memory slots are followed by a guard region large enough for memories
+    /// compiled with `tunables`.
+    pub fn new(limits: PoolingLimits, tunables: &Tunables) -> Result<Self, String> {
+        if limits.memory_pages > WASM_MAX_PAGES {
+            return Err(format!(
+                "pooled memories can't be larger than {} pages",
+                WASM_MAX_PAGES
+            ));
+        }
+
+        let page_size = region::page::size();
+        let round_up = |size: usize| -> Option<usize> {
+            Some(size.checked_add(page_size - 1)? & !(page_size - 1))
+        };
+        let too_large = || "pooling limits are too large to reserve space for".to_string();
+
+        let guard_size = tunables
+            .static_memory_offset_guard_size
+            .max(tunables.dynamic_memory_offset_guard_size);
+        let memory_guard_size = usize::try_from(guard_size)
+            .ok()
+            .and_then(round_up)
+            .ok_or_else(too_large)?;
+        let instance_slot_size = round_up(limits.instance_size).ok_or_else(too_large)?;
+        let memory_slot_size = (limits.memory_pages as usize)
+            .checked_mul(WASM_PAGE_SIZE as usize)
+            .and_then(|size| size.checked_add(memory_guard_size))
+            .ok_or_else(too_large)?;
+        let table_slot_size = (limits.table_elements as usize)
+            .checked_mul(mem::size_of::<VMCallerCheckedAnyfunc>())
+            .and_then(round_up)
+            .ok_or_else(too_large)?;
+
+        let instances = limits.instances as usize;
+        let instances_size = instance_slot_size
+            .checked_mul(instances)
+            .ok_or_else(too_large)?;
+        let memories_size = memory_slot_size
+            .checked_mul(limits.memories as usize)
+            .and_then(|size| size.checked_mul(instances))
+            .ok_or_else(too_large)?;
+        let tables_size = table_slot_size
+            .checked_mul(limits.tables as usize)
+            .and_then(|size| size.checked_mul(instances))
+            .ok_or_else(too_large)?;
+
+        Ok(Self {
+            inner: Arc::new(PoolInner {
+                limits,
+                instance_slot_size,
+                memory_slot_size,
+                memory_guard_size,
+                table_slot_size,
+                instances: Mmap::accessible_reserved(instances_size, instances_size)?,
+                // Memories are made accessible only as they're used, so that
+                // accesses past their end fault.
+                memories: Mmap::accessible_reserved(0, memories_size)?,
+                tables: Mmap::accessible_reserved(tables_size, tables_size)?,
+                free: Mutex::new((0..instances).rev().collect()),
+            }),
+        })
+    }
+
+    /// Returns the limits this pool was created with.
+    pub fn limits(&self) -> &PoolingLimits {
+        &self.inner.limits
+    }
+
+    /// Adjusts `tunables` so that the memories of modules compiled with them
+    /// fit within this pool's memory slots.
+    pub fn adjust_tunables(&self, tunables: &mut Tunables) {
+        let guard_size = self.inner.memory_guard_size as u64;
+        tunables.static_memory_bound = tunables
+            .static_memory_bound
+            .min(self.inner.limits.memory_pages);
+        tunables.static_memory_offset_guard_size =
+            tunables.static_memory_offset_guard_size.min(guard_size);
+        tunables.dynamic_memory_offset_guard_size =
+            tunables.dynamic_memory_offset_guard_size.min(guard_size);
+    }
+
+    /// Claims a free slot for an instance of `module`.
+    pub(crate) fn allocate(&self, module: &Module) -> Result<InstanceSlot, InstantiationError> {
+        let limits = &self.inner.limits;
+        let memories = module.local.memory_plans.len() - module.imported_memories.len();
+        if memories > limits.memories as usize {
+            return Err(InstantiationError::Resource(format!(
+                "defined memories count of {} exceeds the pooling limit of {}",
+                memories, limits.memories
+            )));
+        }
+        let tables = module.local.table_plans.len() - module.imported_tables.len();
+        if tables > limits.tables as usize {
+            return Err(InstantiationError::Resource(format!(
+                "defined tables count of {} exceeds the pooling limit of {}",
+                tables, limits.tables
+            )));
+        }
+
+        let index = self.inner.free.lock().unwrap().pop().ok_or_else(|| {
+            InstantiationError::Resource(format!(
+                "maximum concurrent instance limit of {} reached",
+                limits.instances
+            ))
+        })?;
+        Ok(InstanceSlot {
+            pool: self.clone(),
+            index,
+        })
+    }
+}
+
+/// A slot of an [`InstancePool`] claimed by a single instance, which is
+/// returned to the pool when dropped.
+pub(crate) struct InstanceSlot {
+    pool: InstancePool,
+    index: usize,
+}
+
+impl InstanceSlot {
+    /// Returns a pointer to the space for the instance itself, which must fit
+    /// within `layout`.
+    pub(crate) fn instance_ptr(&self, layout: Layout) -> Result<*mut u8, InstantiationError> {
+        let inner = &self.pool.inner;
+        if layout.size() > inner.instance_slot_size {
+            return Err(InstantiationError::Resource(format!(
+                "instance size of {} bytes exceeds the pooling limit of {} bytes",
+                layout.size(),
+                inner.instance_slot_size
+            )));
+        }
+        debug_assert!(layout.align() <= region::page::size());
+        let offset = self.index * inner.instance_slot_size;
+        Ok(unsafe { (inner.instances.as_ptr() as *mut u8).add(offset) })
+    }
+
+    /// Creates the memory at `index` of this slot's instance.
+    pub(crate) fn memory(
+        &self,
+        index: DefinedMemoryIndex,
+        plan: &MemoryPlan,
+    ) -> Result<Box<dyn RuntimeLinearMemory>, InstantiationError> {
+        let inner = &self.pool.inner;
+        let limit = inner.limits.memory_pages;
+        if plan.memory.minimum > limit {
+            return Err(InstantiationError::Resource(format!(
+                "memory minimum size of {} pages exceeds the pooling limit of {} pages",
+                plan.memory.minimum, limit
+            )));
+        }
+        // Compiled code may access anything up to the bound of a static
+        // memory, plus its guard, without a bounds check, so all of that must
+        // be within the slot.
+        let fits = match plan.style {
+            MemoryStyle::Dynamic => true,
+            MemoryStyle::Static { bound } => bound <= limit,
+        };
+        if !fits || plan.offset_guard_size > inner.memory_guard_size as u64 {
+            return Err(InstantiationError::Resource(
+                "memory was compiled for a larger reservation than pooled memories have"
+                    .to_string(),
+            ));
+        }
+
+        let slot = self.index * inner.limits.memories as usize + index.index();
+        let base =
+            unsafe { (inner.memories.as_ptr() as *mut u8).add(slot * inner.memory_slot_size) };
+        let memory = PooledMemory {
+            base,
+            size: Cell::new(0),
+            maximum: plan.memory.maximum,
+            limit,
+            image_mapped: Cell::new(false),
+        };
+        memory
+            .grow(plan.memory.minimum)
+            .ok_or_else(|| InstantiationError::Resource("failed to commit memory".to_string()))?;
+        Ok(Box::new(memory))
+    }
+
+    /// Creates the table at `index` of this slot's instance.
+    pub(crate) fn table(
+        &self,
+        index: DefinedTableIndex,
+        plan: &TablePlan,
+    ) -> Result<Table, InstantiationError> {
+        let inner = &self.pool.inner;
+        let capacity = inner.limits.table_elements;
+        if plan.table.minimum > capacity {
+            return Err(InstantiationError::Resource(format!(
+                "table minimum size of {} elements exceeds the pooling limit of {} elements",
+                plan.table.minimum, capacity
+            )));
+        }
+        let slot = self.index * inner.limits.tables as usize + index.index();
+        unsafe {
+            let data = (inner.tables.as_ptr() as *mut u8).add(slot * inner.table_slot_size);
+            Ok(Table::new_static(
+                plan,
+                data as *mut VMCallerCheckedAnyfunc,
+                capacity,
+            ))
+        }
+    }
+}
+
+impl Drop for InstanceSlot {
+    fn drop(&mut self) {
+        self.pool.inner.free.lock().unwrap().push(self.index);
+    }
+}
+
+/// A linear memory living in a slot of an [`InstancePool`].
+///
+/// Its base never moves, and it's reset when dropped so the slot can be
+/// reused.
+struct PooledMemory {
+    base: *mut u8,
+    size: Cell<u32>,
+    maximum: Option<u32>,
+    limit: u32,
+    image_mapped: Cell<bool>,
+}
+
+impl RuntimeLinearMemory for PooledMemory {
+    fn size(&self) -> u32 {
+        self.size.get()
+    }
+
+    fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
+    fn grow(&self, delta: u32) -> Option<u32> {
+        let prev_pages = self.size.get();
+        let new_pages = prev_pages.checked_add(delta)?;
+        if new_pages > self.limit || self.maximum.map_or(false, |max| new_pages > max) {
+            return None;
+        }
+        if delta > 0 {
+            let page_size = WASM_PAGE_SIZE as usize;
+            unsafe {
+                commit(
+                    self.base.add(prev_pages as usize * page_size),
+                    delta as usize * page_size,
+                )
+                .ok()?;
+            }
+        }
+        self.size.set(new_pages);
+        Some(prev_pages)
+    }
+
+    fn vmmemory(&self) -> VMMemoryDefinition {
+        VMMemoryDefinition {
+            base: self.base,
+            current_length: self.size.get() as usize * WASM_PAGE_SIZE as usize,
+        }
+    }
+
+    fn map_image(&self, image: &MemoryImage) -> Result<bool, String> {
+        unsafe {
+            image.map_at(self.base)?;
+        }
+        self.image_mapped.set(true);
+        Ok(true)
+    }
+
+    fn shrink(&self, pages: u32) -> bool {
+        let prev_pages = self.size.get();
+        assert!(pages <= prev_pages);
+        let page_size = WASM_PAGE_SIZE as usize;
+        let addr = unsafe { self.base.add(pages as usize * page_size) };
+        let len = (prev_pages - pages) as usize * page_size;
+        // As when dropped, pages which may be mapped from an image are
+        // replaced rather than discarded.
+        let result = unsafe {
+            if self.image_mapped.get() {
+                unmap_image(addr, len)
+            } else {
+                decommit(addr, len)
+            }
+        };
+        if result.is_err() {
+            return false;
+        }
+        self.size.set(pages);
+        true
+    }
+}
+
+impl Drop for PooledMemory {
+    fn drop(&mut self) {
+        let len = self.size.get() as usize * WASM_PAGE_SIZE as usize;
+        // Discarding the contents of pages mapped from an image would reset
+        // them to the image rather than zero them, so those are replaced
+        // with fresh pages instead.
+        let result = unsafe {
+            if self.image_mapped.get() {
+                unmap_image(self.base, len)
+            } else {
+                decommit(self.base, len)
+            }
+        };
+        result.expect("failed to reset pooled memory");
+    }
+}
+
+/// Makes `len` bytes at `addr` readable and writable.
+#[cfg(not(target_os = "windows"))]
+unsafe fn commit(addr: *mut u8, len: usize) -> Result<(), String> {
+    region::protect(addr, len, region::Protection::ReadWrite).map_err(|e| e.to_string())
+}
+
+/// Makes `len` bytes at `addr` readable and writable.
+#[cfg(target_os = "windows")]
+unsafe fn commit(addr: *mut u8, len: usize) -> Result<(), String> {
+    use winapi::um::memoryapi::VirtualAlloc;
+    use winapi::um::winnt::{MEM_COMMIT, PAGE_READWRITE};
+    if VirtualAlloc(addr as _, len, MEM_COMMIT, PAGE_READWRITE).is_null() {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Discards the contents of the `len` bytes at `addr` and makes them
+/// inaccessible again. They read as zero once committed again.
+#[cfg(target_os = "linux")]
+unsafe fn decommit(addr: *mut u8, len: usize) -> Result<(), String> {
+    if len == 0 {
+        return Ok(());
+    }
+    if libc::madvise(addr as _, len, libc::MADV_DONTNEED) != 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    region::protect(addr, len, region::Protection::None).map_err(|e| e.to_string())
+}
+
+/// Discards the contents of the `len` bytes at `addr` and makes them
+/// inaccessible again. They read as zero once committed again.
+#[cfg(all(unix, not(target_os = "linux")))]
+unsafe fn decommit(addr: *mut u8, len: usize) -> Result<(), String> {
+    if len == 0 {
+        return Ok(());
+    }
+    // `MADV_DONTNEED` doesn't zero pages everywhere, so replace the mapping
+    // with a fresh one instead.
+    let ptr = libc::mmap(
+        addr as _,
+        len,
+        libc::PROT_NONE,
+        libc::MAP_PRIVATE | libc::MAP_ANON | libc::MAP_FIXED,
+        -1,
+        0,
+    );
+    if ptr as isize == -1_isize {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+/// Discards the contents of the `len` bytes at `addr` and makes them
+/// inaccessible again. They read as zero once committed again.
+#[cfg(target_os = "windows")]
+unsafe fn decommit(addr: *mut u8, len: usize) -> Result<(), String> {
+    use winapi::um::memoryapi::VirtualFree;
+    use winapi::um::winnt::MEM_DECOMMIT;
+    if len == 0 {
+        return Ok(());
+    }
+    if VirtualFree(addr as _, len, MEM_DECOMMIT) == 0 {
+        return Err(std::io::Error::last_os_error().to_string());
+    }
+    Ok(())
+}
+
+fn _assert() {
+    fn _assert_send_sync<T: Send + Sync>() {}
+    _assert_send_sync::<InstancePool>();
+}
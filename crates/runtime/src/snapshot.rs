@@ -0,0 +1,84 @@
+//! Snapshots of the state of an instance, which it can later be restored to.
+//!
+//! The contents of each memory are kept in a `MemoryImage` where possible, so
+//! restoring a snapshot maps them back in copy-on-write rather than copying
+//! them.
+
+use crate::cow::MemoryImage;
+use crate::table::TableElement;
+use crate::vmcontext::{VMCallerCheckedAnyfunc, VMGlobalDefinition};
+use std::collections::HashMap;
+use std::sync::Arc;
+use wasmtime_environ::entity::PrimaryMap;
+use wasmtime_environ::wasm::{
+    DefinedGlobalIndex, DefinedMemoryIndex, DefinedTableIndex, PassiveElemIndex,
+};
+use wasmtime_environ::Module;
+
+/// The state of the memories, mutable globals, tables and passive elements an
+/// instance defines, taken with `InstanceHandle::snapshot`.
+#[derive(Debug)]
+pub struct InstanceSnapshot {
+    /// The module of the instance the snapshot was taken of.
+    pub(crate) module: Arc<Module>,
+
+    /// The unique identifier of the instance the snapshot was taken of, which
+    /// is the only one it can be restored to. Addresses can't be used since
+    /// pooled instances reuse the memory of those deallocated before them.
+    pub(crate) instance_id: usize,
+
+    /// The defined memories.
+    pub(crate) memories: PrimaryMap<DefinedMemoryIndex, MemorySnapshot>,
+
+    /// The values of the defined mutable globals.
+    pub(crate) globals: Vec<(DefinedGlobalIndex, VMGlobalDefinition)>,
+
+    /// The elements of the defined tables.
+    pub(crate) tables: PrimaryMap<DefinedTableIndex, Box<[TableElement]>>,
+
+    /// The passive elements which hadn't yet been dropped by `elem.drop`.
+    pub(crate) passive_elements: HashMap<PassiveElemIndex, Box<[VMCallerCheckedAnyfunc]>>,
+}
+
+/// The size and contents of a memory.
+#[derive(Debug)]
+pub(crate) struct MemorySnapshot {
+    /// The size of the memory in wasm pages.
+    pub(crate) pages: u32,
+
+    /// The contents of the memory.
+    pub(crate) contents: MemoryContents,
+}
+
+/// The contents of a memory, which are only copied into a buffer if they
+/// can't be kept in an image.
+#[derive(Debug)]
+pub(crate) enum MemoryContents {
+    Image(MemoryImage),
+    Bytes(Box<[u8]>),
+}
+
+impl MemorySnapshot {
+    /// Captures a memory of `pages` wasm pages holding `data`.
+    pub(crate) fn new(pages: u32, data: &[u8]) -> Self {
+        let contents = match MemoryImage::from_memory(data) {
+            Ok(Some(image)) => MemoryContents::Image(image),
+            Ok(None) | Err(_) => MemoryContents::Bytes(data.into()),
+        };
+        Self { pages, contents }
+    }
+}
+
+impl MemoryContents {
+    /// Copies these contents into `memory`, which must be the same size as
+    /// the memory they were captured from.
+    pub(crate) fn copy_to(&self, memory: &mut [u8]) -> Result<(), String> {
+        match self {
+            MemoryContents::Image(image) => image.copy_to(memory),
+            MemoryContents::Bytes(bytes) => {
+                memory.copy_from_slice(bytes);
+                Ok(())
+            }
+        }
+    }
+}
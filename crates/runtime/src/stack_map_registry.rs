@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::{Arc, RwLock};
+use wasmtime_environ::StackMapInformation;
+
+/// The registry maintains the stack maps of the GC safepoints in currently
+/// allocated functions.
+///
+/// Cloning a registry yields another handle to the same set of stack maps.
+#[derive(Clone, Default)]
+pub struct StackMapRegistry {
+    // Like the `TrapRegistry` this is keyed by the end address of each
+    // function, with the value holding the first address of the function as
+    // well as its stack maps, so the function containing a program counter
+    // can be found with a single range query. The end is included in the
+    // range since a call may be the last instruction of a function.
+    //
+    // Only functions with at least one safepoint are registered.
+    ranges: Arc<RwLock<BTreeMap<usize, FunctionStackMaps>>>,
+}
+
+#[derive(Debug)]
+struct FunctionStackMaps {
+    /// The first address of the function.
+    start: usize,
+
+    /// The stack maps of the function's safepoints, sorted by the address of
+    /// the safepoint.
+    stack_maps: Vec<(usize, StackMapInformation)>,
+}
+
+/// RAII structure returned from `StackMapRegistry::register_stack_maps` to
+/// unregister the stack maps on drop.
+pub struct StackMapRegistration {
+    ranges: Arc<RwLock<BTreeMap<usize, FunctionStackMaps>>>,
+    ends: Vec<usize>,
+}
+
+impl StackMapRegistry {
+    /// Registers the stack maps of a list of functions, each given with the
+    /// range of addresses of its code.
+    ///
+    /// Returns a RAII guard that deregisters all the stack maps when dropped.
+    pub fn register_stack_maps<'a>(
+        &self,
+        list: impl IntoIterator<Item = (Range<usize>, &'a [StackMapInformation])>,
+    ) -> StackMapRegistration {
+        let mut ranges = self.ranges.write().unwrap();
+        let mut ends = Vec::new();
+        for (range, infos) in list.into_iter() {
+            if infos.is_empty() || range.start == range.end {
+                continue;
+            }
+            let mut stack_maps = infos
+                .iter()
+                .map(|info| (range.start + info.code_offset as usize, info.clone()))
+                .collect::<Vec<_>>();
+            stack_maps.sort_by_key(|(pc, _)| *pc);
+
+            let end = range.end;
+            if let Some((_, next)) = ranges.range(end..).next() {
+                assert!(next.start >= end);
+            }
+            if let Some((prev_end, _)) = ranges.range(..=range.start).next_back() {
+                assert!(*prev_end <= range.start);
+            }
+            let prev = ranges.insert(
+                end,
+                FunctionStackMaps {
+                    start: range.start,
+                    stack_maps,
+                },
+            );
+            assert!(prev.is_none());
+            ends.push(end);
+        }
+        StackMapRegistration {
+            ranges: self.ranges.clone(),
+            ends,
+        }
+    }
+
+    /// Looks up the stack map describing the frame of a function which is
+    /// about to return to `pc`.
+    ///
+    /// Safepoints are placed right before calls, so this is the stack map of
+    /// the closest safepoint preceding `pc` within the same function. The
+    /// returned stack map may therefore be stale if the call returning to
+    /// `pc` had no live references and so no safepoint of its own.
+    pub fn lookup_stack_map(&self, pc: usize) -> Option<StackMapInformation> {
+        let ranges = self.ranges.read().ok()?;
+        let (end, function) = ranges.range(pc..).next()?;
+        if pc <= function.start || *end < pc {
+            return None;
+        }
+        let index = match function
+            .stack_maps
+            .binary_search_by_key(&pc, |(safepoint, _)| *safepoint)
+        {
+            Ok(0) | Err(0) => return None,
+            Ok(n) | Err(n) => n - 1,
+        };
+        Some(function.stack_maps[index].1.clone())
+    }
+}
+
+impl Drop for StackMapRegistration {
+    fn drop(&mut self) {
+        if let Ok(mut ranges) = self.ranges.write() {
+            for end in self.ends.iter() {
+                ranges.remove(end);
+            }
+        }
+    }
+}
@@ -1,52 +1,286 @@
 //! Memory management for tables.
 //!
-//! `Table` is to WebAssembly tables what `LinearMemory` is to WebAssembly linear memories.
+//! `Table` is to WebAssembly tables what `RuntimeLinearMemory` is to WebAssembly linear memories.
 
+use crate::externref::{VMExternRef, VMExternRefActivationsTable};
+use crate::instance::Instance;
 use crate::vmcontext::{VMCallerCheckedAnyfunc, VMTableDefinition};
 use crate::Trap;
 use std::cell::RefCell;
 use std::convert::{TryFrom, TryInto};
+use std::{mem, ptr, slice};
 use wasmtime_environ::wasm::TableElementType;
 use wasmtime_environ::{ir, TablePlan, TableStyle};
 
+/// An element of a table.
+#[derive(Debug, Clone)]
+pub enum TableElement {
+    /// An element of a `funcref` table, which is null if its `func_ptr` is.
+    FuncRef(VMCallerCheckedAnyfunc),
+    /// An element of an `externref` table.
+    ExternRef(Option<VMExternRef>),
+}
+
+impl TableElement {
+    /// Converts a reference as it's passed around by compiled wasm code into
+    /// an element of a table with elements of type `ty`.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be null or a valid reference of type `ty`: a pointer to a
+    /// `VMCallerCheckedAnyfunc` for `funcref`s, or one handed out by
+    /// `VMExternRef` for `externref`s.
+    pub unsafe fn from_raw(ty: TableElementType, ptr: *mut u8) -> Self {
+        match ty {
+            TableElementType::Func => TableElement::FuncRef(if ptr.is_null() {
+                VMCallerCheckedAnyfunc::default()
+            } else {
+                (*(ptr as *const VMCallerCheckedAnyfunc)).clone()
+            }),
+            TableElementType::Val(_) => TableElement::ExternRef(if ptr.is_null() {
+                None
+            } else {
+                Some(VMExternRef::clone_from_raw(ptr))
+            }),
+        }
+    }
+
+    /// Converts this element into the reference compiled wasm code uses for
+    /// it.
+    ///
+    /// A `funcref` becomes a pointer to a `VMCallerCheckedAnyfunc` owned by
+    /// the instance the function belongs to, and an `externref` is inserted
+    /// into `activations` which keeps it alive while wasm holds it.
+    ///
+    /// # Safety
+    ///
+    /// The `vmctx` of a non-null `funcref` must belong to a live instance.
+    pub unsafe fn into_raw(self, activations: &VMExternRefActivationsTable) -> *mut u8 {
+        match self {
+            TableElement::FuncRef(anyfunc) => {
+                if anyfunc.func_ptr.is_null() {
+                    ptr::null_mut()
+                } else {
+                    Instance::anyfunc_ptr(anyfunc) as *mut u8
+                }
+            }
+            TableElement::ExternRef(r) => r.map_or(ptr::null_mut(), |r| activations.insert(r)),
+        }
+    }
+}
+
 /// A table instance.
 #[derive(Debug)]
 pub struct Table {
-    vec: RefCell<Vec<VMCallerCheckedAnyfunc>>,
+    elements: RefCell<TableElements>,
+    ty: TableElementType,
     maximum: Option<u32>,
 }
 
+/// The elements of a table, by type.
+#[derive(Debug)]
+enum TableElements {
+    FuncRefs(Storage<VMCallerCheckedAnyfunc>),
+    ExternRefs(Storage<Option<VMExternRef>>),
+}
+
+/// The storage backing a table's elements.
+#[derive(Debug)]
+enum Storage<T> {
+    /// Elements allocated on the heap, which may move as the table grows.
+    Dynamic(Vec<T>),
+    /// Elements stored in a fixed-capacity slot of an `InstancePool`, which
+    /// never move.
+    Static {
+        data: *mut T,
+        size: usize,
+        capacity: usize,
+    },
+}
+
+impl<T: Clone> Storage<T> {
+    fn as_slice(&self) -> &[T] {
+        match self {
+            Storage::Dynamic(vec) => vec,
+            Storage::Static { data, size, .. } => unsafe { slice::from_raw_parts(*data, *size) },
+        }
+    }
+
+    fn as_mut_slice(&mut self) -> &mut [T] {
+        match self {
+            Storage::Dynamic(vec) => vec,
+            Storage::Static { data, size, .. } => unsafe {
+                slice::from_raw_parts_mut(*data, *size)
+            },
+        }
+    }
+
+    /// Resizes to `new_len` elements, filling any new ones with `init`.
+    /// Returns `false` if there isn't room for them.
+    fn resize(&mut self, new_len: usize, init: T) -> bool {
+        match self {
+            Storage::Dynamic(vec) => vec.resize(new_len, init),
+            Storage::Static {
+                data,
+                size,
+                capacity,
+            } => {
+                if new_len > *capacity {
+                    return false;
+                }
+                if new_len < *size {
+                    unsafe {
+                        ptr::drop_in_place(slice::from_raw_parts_mut(
+                            data.add(new_len),
+                            *size - new_len,
+                        ));
+                    }
+                }
+                for i in *size..new_len {
+                    unsafe {
+                        ptr::write(data.add(i), init.clone());
+                    }
+                }
+                *size = new_len;
+            }
+        }
+        true
+    }
+}
+
+impl<T> Drop for Storage<T> {
+    fn drop(&mut self) {
+        // The slot of a static table outlives it, so its elements have to be
+        // dropped here.
+        if let Storage::Static { data, size, .. } = self {
+            unsafe {
+                ptr::drop_in_place(slice::from_raw_parts_mut(*data, *size));
+            }
+        }
+    }
+}
+
+impl TableElements {
+    fn len(&self) -> usize {
+        match self {
+            TableElements::FuncRefs(s) => s.as_slice().len(),
+            TableElements::ExternRefs(s) => s.as_slice().len(),
+        }
+    }
+
+    /// Resizes to `new_len` elements, filling any new ones with `init`.
+    /// Returns `false` if there isn't room for them or `init` is of the wrong
+    /// type.
+    fn resize(&mut self, new_len: usize, init: TableElement) -> bool {
+        match (self, init) {
+            (TableElements::FuncRefs(s), TableElement::FuncRef(f)) => s.resize(new_len, f),
+            (TableElements::ExternRefs(s), TableElement::ExternRef(r)) => s.resize(new_len, r),
+            _ => false,
+        }
+    }
+
+    fn base(&mut self) -> *mut u8 {
+        match self {
+            TableElements::FuncRefs(s) => s.as_mut_slice().as_mut_ptr() as *mut u8,
+            TableElements::ExternRefs(s) => s.as_mut_slice().as_mut_ptr() as *mut u8,
+        }
+    }
+}
+
 impl Table {
     /// Create a new table instance with specified minimum and maximum number of elements.
     pub fn new(plan: &TablePlan) -> Self {
-        match plan.table.ty {
-            TableElementType::Func => (),
-            TableElementType::Val(ty) => {
-                unimplemented!("tables of types other than anyfunc ({})", ty)
+        let elements = match plan.table.ty {
+            TableElementType::Func => TableElements::FuncRefs(Storage::Dynamic(Vec::new())),
+            TableElementType::Val(_) => TableElements::ExternRefs(Storage::Dynamic(Vec::new())),
+        };
+        Self::with_elements(plan, elements)
+    }
+
+    /// Create a new table instance whose elements are stored in the
+    /// `capacity` elements at `data`, which it can't grow beyond.
+    ///
+    /// # Safety
+    ///
+    /// `data` must be valid for writes of `capacity` elements for as long as
+    /// the table is alive, and `capacity` must be at least the table's
+    /// minimum size.
+    pub(crate) unsafe fn new_static(
+        plan: &TablePlan,
+        data: *mut VMCallerCheckedAnyfunc,
+        capacity: u32,
+    ) -> Self {
+        let capacity = usize::try_from(capacity).unwrap();
+        let elements = match plan.table.ty {
+            TableElementType::Func => TableElements::FuncRefs(Storage::Static {
+                data,
+                size: 0,
+                capacity,
+            }),
+            TableElementType::Val(_) => {
+                // Slots are sized for `funcref`s, which are larger than
+                // `externref`s.
+                debug_assert!(
+                    mem::size_of::<Option<VMExternRef>>()
+                        <= mem::size_of::<VMCallerCheckedAnyfunc>()
+                );
+                TableElements::ExternRefs(Storage::Static {
+                    data: data as *mut Option<VMExternRef>,
+                    size: 0,
+                    capacity,
+                })
             }
         };
+        Self::with_elements(plan, elements)
+    }
+
+    fn with_elements(plan: &TablePlan, mut elements: TableElements) -> Self {
         match plan.style {
-            TableStyle::CallerChecksSignature => Self {
-                vec: RefCell::new(vec![
-                    VMCallerCheckedAnyfunc::default();
-                    usize::try_from(plan.table.minimum).unwrap()
-                ]),
-                maximum: plan.table.maximum,
-            },
+            TableStyle::CallerChecksSignature => {
+                let minimum = usize::try_from(plan.table.minimum).unwrap();
+                let fits = elements.resize(minimum, Self::null_element(plan.table.ty));
+                assert!(fits, "table storage is too small for its minimum size");
+                Self {
+                    elements: RefCell::new(elements),
+                    ty: plan.table.ty,
+                    maximum: plan.table.maximum,
+                }
+            }
+        }
+    }
+
+    fn null_element(ty: TableElementType) -> TableElement {
+        match ty {
+            TableElementType::Func => TableElement::FuncRef(VMCallerCheckedAnyfunc::default()),
+            TableElementType::Val(_) => TableElement::ExternRef(None),
         }
     }
 
+    /// Returns the type of this table's elements.
+    pub fn element_type(&self) -> TableElementType {
+        self.ty
+    }
+
     /// Returns the number of allocated elements.
     pub fn size(&self) -> u32 {
-        self.vec.borrow().len().try_into().unwrap()
+        self.elements.borrow().len().try_into().unwrap()
     }
 
-    /// Grow table by the specified amount of elements.
+    /// Returns the maximum number of elements this table may grow to, if one
+    /// was declared.
+    pub fn maximum(&self) -> Option<u32> {
+        self.maximum
+    }
+
+    /// Grow table by the specified amount of elements, filling the new ones
+    /// with `init`.
     ///
-    /// Returns `None` if table can't be grown by the specified amount
-    /// of elements.
-    pub fn grow(&self, delta: u32) -> Option<u32> {
-        let new_len = match self.size().checked_add(delta) {
+    /// Returns the previous size of the table, or `None` if table can't be
+    /// grown by the specified amount of elements or if `init` is of the wrong
+    /// type for this table.
+    pub fn grow(&self, delta: u32, init: TableElement) -> Option<u32> {
+        let old_len = self.size();
+        let new_len = match old_len.checked_add(delta) {
             Some(len) => {
                 if let Some(max) = self.maximum {
                     if len > max {
@@ -59,33 +293,104 @@ impl Table {
                 return None;
             }
         };
-        self.vec.borrow_mut().resize(
-            usize::try_from(new_len).unwrap(),
-            VMCallerCheckedAnyfunc::default(),
-        );
-        Some(new_len)
+        if !self
+            .elements
+            .borrow_mut()
+            .resize(usize::try_from(new_len).unwrap(), init)
+        {
+            return None;
+        }
+        Some(old_len)
+    }
+
+    /// Replaces all of the elements of this table with `elements`, shrinking
+    /// or growing it to their number.
+    ///
+    /// Wasm itself can never shrink a table; this is only used to restore
+    /// snapshots.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the table can't hold that many elements or they're
+    /// of the wrong type for this table.
+    pub fn reset(&self, elements: &[TableElement]) -> Result<(), ()> {
+        if self
+            .maximum
+            .map_or(false, |max| elements.len() > max as usize)
+        {
+            return Err(());
+        }
+        let null = Self::null_element(self.ty);
+        if !self.elements.borrow_mut().resize(elements.len(), null) {
+            return Err(());
+        }
+        for (i, elem) in elements.iter().enumerate() {
+            self.set(u32::try_from(i).unwrap(), elem.clone())?;
+        }
+        Ok(())
     }
 
     /// Get reference to the specified element.
     ///
     /// Returns `None` if the index is out of bounds.
-    pub fn get(&self, index: u32) -> Option<VMCallerCheckedAnyfunc> {
-        self.vec.borrow().get(index as usize).cloned()
+    pub fn get(&self, index: u32) -> Option<TableElement> {
+        let index = index as usize;
+        match &*self.elements.borrow() {
+            TableElements::FuncRefs(s) => {
+                s.as_slice().get(index).cloned().map(TableElement::FuncRef)
+            }
+            TableElements::ExternRefs(s) => s
+                .as_slice()
+                .get(index)
+                .cloned()
+                .map(TableElement::ExternRef),
+        }
     }
 
     /// Set reference to the specified element.
     ///
-    /// # Panics
+    /// # Errors
     ///
-    /// Panics if `index` is out of bounds.
-    pub fn set(&self, index: u32, func: VMCallerCheckedAnyfunc) -> Result<(), ()> {
-        match self.vec.borrow_mut().get_mut(index as usize) {
-            Some(slot) => {
-                *slot = func;
-                Ok(())
+    /// Returns an error if `index` is out of bounds or `elem` is of the wrong
+    /// type for this table.
+    pub fn set(&self, index: u32, elem: TableElement) -> Result<(), ()> {
+        let index = index as usize;
+        match (&mut *self.elements.borrow_mut(), elem) {
+            (TableElements::FuncRefs(s), TableElement::FuncRef(f)) => {
+                *s.as_mut_slice().get_mut(index).ok_or(())? = f;
+            }
+            (TableElements::ExternRefs(s), TableElement::ExternRef(r)) => {
+                *s.as_mut_slice().get_mut(index).ok_or(())? = r;
             }
-            None => Err(()),
+            _ => return Err(()),
         }
+        Ok(())
+    }
+
+    /// Fill `len` elements starting at `dst` with `val`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the range is out of bounds of the table.
+    pub fn fill(
+        &self,
+        dst: u32,
+        val: TableElement,
+        len: u32,
+        source_loc: ir::SourceLoc,
+    ) -> Result<(), Trap> {
+        // https://webassembly.github.io/reference-types/core/exec/instructions.html#exec-table-fill
+
+        if dst.checked_add(len).map_or(true, |n| n > self.size()) {
+            return Err(Trap::wasm(source_loc, ir::TrapCode::TableOutOfBounds));
+        }
+
+        // Note on the unwrap: the bounds check above means that this will
+        // never panic, as long as `val` is of the right type.
+        for i in dst..dst + len {
+            self.set(i, val.clone()).unwrap();
+        }
+        Ok(())
     }
 
     /// Copy `len` elements from `src_table[src_index..]` into `dst_table[dst_index..]`.
@@ -118,7 +423,8 @@ impl Table {
         let dsts = dst_index..dst_index + len;
 
         // Note on the unwraps: the bounds check above means that these will
-        // never panic.
+        // never panic, and validation ensures the tables' element types
+        // match.
         //
         // TODO(#983): investigate replacing this get/set loop with a `memcpy`.
         if dst_index <= src_index {
@@ -136,10 +442,10 @@ impl Table {
 
     /// Return a `VMTableDefinition` for exposing the table to compiled wasm code.
     pub fn vmtable(&self) -> VMTableDefinition {
-        let mut vec = self.vec.borrow_mut();
+        let mut elements = self.elements.borrow_mut();
         VMTableDefinition {
-            base: vec.as_mut_ptr() as *mut u8,
-            current_elements: vec.len().try_into().unwrap(),
+            base: elements.base(),
+            current_elements: elements.len().try_into().unwrap(),
         }
     }
 }
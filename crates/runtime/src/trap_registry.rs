@@ -84,6 +84,8 @@ fn trap_code_to_expected_string(trap_code: ir::TrapCode) -> String {
     match trap_code {
         StackOverflow => "call stack exhausted".to_string(),
         HeapOutOfBounds => "out of bounds memory access".to_string(),
+        HeapMisaligned => "unaligned atomic".to_string(),
+        HeapUnshared => "expected shared memory".to_string(),
         TableOutOfBounds => "undefined element: out of bounds".to_string(),
         OutOfBounds => "out of bounds".to_string(), // Note: not covered by the test suite
         IndirectCallToNull => "uninitialized element".to_string(),
@@ -93,6 +95,7 @@ fn trap_code_to_expected_string(trap_code: ir::TrapCode) -> String {
         BadConversionToInteger => "invalid conversion to integer".to_string(),
         UnreachableCodeReached => "unreachable".to_string(),
         Interrupt => "interrupt".to_string(), // Note: not covered by the test suite
+        OutOfFuel => "all fuel consumed by WebAssembly".to_string(), // Note: not covered by the test suite
         User(x) => format!("user trap {}", x), // Note: not covered by the test suite
     }
 }
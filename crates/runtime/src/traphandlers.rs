@@ -252,18 +252,22 @@ impl CallThreadState {
                 UnwindReason::Trap { backtrace, pc } => {
                     debug_assert_eq!(ret, 0);
                     let instance = unsafe { InstanceHandle::from_vmctx(self.vmctx) };
-
-                    Err(Trap::Wasm {
-                        desc: instance
-                            .instance()
-                            .trap_registration
-                            .get_trap(pc)
-                            .unwrap_or_else(|| TrapDescription {
-                                source_loc: ir::SourceLoc::default(),
-                                trap_code: ir::TrapCode::StackOverflow,
-                            }),
-                        backtrace,
-                    })
+                    let desc = instance
+                        .instance()
+                        .trap_registration
+                        .get_trap(pc)
+                        .unwrap_or_else(|| TrapDescription {
+                            source_loc: ir::SourceLoc::default(),
+                            trap_code: ir::TrapCode::StackOverflow,
+                        });
+
+                    // The interrupt has now been delivered, so clear it to
+                    // let subsequent calls run normally.
+                    if desc.trap_code == ir::TrapCode::Interrupt {
+                        instance.instance().interrupts.reset();
+                    }
+
+                    Err(Trap::Wasm { desc, backtrace })
                 }
                 UnwindReason::Panic(panic) => {
                     debug_assert_eq!(ret, 0);
@@ -350,6 +354,30 @@ impl CallThreadState {
     }
 }
 
+/// Trap handling state of calls into wasm saved while the stack they were made
+/// on is switched away from.
+///
+/// The state for each call into wasm lives on the native stack the call was
+/// made from and is linked together through thread-local storage. When a fiber
+/// suspends with wasm frames on its stack that state must be removed from the
+/// thread, otherwise traps and calls made while it's suspended would find
+/// state from a stack which isn't running. It's put back once the fiber is
+/// resumed.
+pub(crate) struct TlsRestore(*const CallThreadState);
+
+impl TlsRestore {
+    /// Takes the state currently configured for this thread, leaving none in
+    /// its place.
+    pub(crate) fn take() -> TlsRestore {
+        TlsRestore(tls::replace(ptr::null()))
+    }
+
+    /// Configures this thread's state back to what was previously taken.
+    pub(crate) fn replace(self) {
+        tls::replace(self.0);
+    }
+}
+
 impl Drop for CallThreadState {
     fn drop(&mut self) {
         if self.reset_guard_page.get() {
@@ -388,6 +416,12 @@ mod tls {
         })
     }
 
+    /// Replaces the currently configured pointer with `ptr`, returning the
+    /// previous one.
+    pub fn replace(ptr: *const CallThreadState) -> *const CallThreadState {
+        PTR.with(|p| p.replace(ptr))
+    }
+
     /// Returns the last pointer configured with `set` above. Panics if `set`
     /// has not been previously called.
     pub fn with<R>(closure: impl FnOnce(Option<&CallThreadState>) -> R) -> R {
@@ -3,6 +3,8 @@
 
 use crate::instance::Instance;
 use std::any::Any;
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
 use std::{ptr, u32};
 use wasmtime_environ::BuiltinFunctionIndex;
 
@@ -421,6 +423,18 @@ impl VMGlobalDefinition {
     pub unsafe fn as_u128_bits_mut(&mut self) -> &mut [u8; 16] {
         &mut *(self.storage.as_mut().as_mut_ptr() as *mut [u8; 16])
     }
+
+    /// Return a reference to the value as a reference type.
+    #[allow(clippy::cast_ptr_alignment)]
+    pub unsafe fn as_reference(&self) -> &*mut u8 {
+        &*(self.storage.as_ref().as_ptr() as *const *mut u8)
+    }
+
+    /// Return a mutable reference to the value as a reference type.
+    #[allow(clippy::cast_ptr_alignment)]
+    pub unsafe fn as_reference_mut(&mut self) -> &mut *mut u8 {
+        &mut *(self.storage.as_mut().as_mut_ptr() as *mut *mut u8)
+    }
 }
 
 /// An index into the shared signature registry, usable for checking signatures
@@ -566,12 +580,99 @@ impl VMBuiltinFunctionsArray {
         ptrs[BuiltinFunctionIndex::get_imported_memory_fill_index().index() as usize] =
             wasmtime_imported_memory_fill as usize;
 
+        ptrs[BuiltinFunctionIndex::get_table_grow_index().index() as usize] =
+            wasmtime_table_grow as usize;
+        ptrs[BuiltinFunctionIndex::get_table_get_index().index() as usize] =
+            wasmtime_table_get as usize;
+        ptrs[BuiltinFunctionIndex::get_table_set_index().index() as usize] =
+            wasmtime_table_set as usize;
+        ptrs[BuiltinFunctionIndex::get_table_fill_index().index() as usize] =
+            wasmtime_table_fill as usize;
+        ptrs[BuiltinFunctionIndex::get_ref_func_index().index() as usize] =
+            wasmtime_ref_func as usize;
+
+        ptrs[BuiltinFunctionIndex::get_memory_atomic_wait32_index().index() as usize] =
+            wasmtime_memory_atomic_wait32 as usize;
+        ptrs[BuiltinFunctionIndex::get_memory_atomic_wait64_index().index() as usize] =
+            wasmtime_memory_atomic_wait64 as usize;
+        ptrs[BuiltinFunctionIndex::get_memory_atomic_notify_index().index() as usize] =
+            wasmtime_memory_atomic_notify as usize;
+
         debug_assert!(ptrs.iter().cloned().all(|p| p != 0));
 
         Self { ptrs }
     }
 }
 
+/// Structure used to request that running wasm code be interrupted. A single
+/// `VMInterrupts` is shared by every instance of a store, and each `VMContext`
+/// points to it so that compiled code can check it at function entries and
+/// loop headers.
+///
+/// It also holds the fuel counter used when fuel consumption is enabled.
+#[derive(Debug, Default)]
+#[repr(C)]
+pub struct VMInterrupts {
+    /// Nonzero when an interrupt has been requested but not yet delivered.
+    pub interrupted: AtomicUsize,
+
+    /// Keeps `fuel_consumed` 8-byte aligned, as `VMOffsets` expects, on
+    /// targets where `i64` is only 4-byte aligned.
+    #[cfg(target_pointer_width = "32")]
+    _padding: u32,
+
+    /// Amount of fuel consumed so far, offset by the amount of fuel given to
+    /// the store so that it starts out negative. Compiled code traps once
+    /// this is no longer negative.
+    ///
+    /// Unlike `interrupted` this is only ever accessed from the thread that
+    /// owns the store.
+    pub fuel_consumed: UnsafeCell<i64>,
+}
+
+// The `fuel_consumed` field is only accessed by the thread which owns the
+// store, while other threads only touch `interrupted`.
+unsafe impl Sync for VMInterrupts {}
+
+#[cfg(test)]
+mod test_vminterrupts {
+    use super::VMInterrupts;
+    use memoffset::offset_of;
+    use std::mem::size_of;
+    use wasmtime_environ::{Module, VMOffsets};
+
+    #[test]
+    fn check_vminterrupts_offsets() {
+        let module = Module::new();
+        let offsets = VMOffsets::new(size_of::<*mut u8>() as u8, &module.local);
+        assert_eq!(
+            size_of::<VMInterrupts>(),
+            usize::from(offsets.size_of_vminterrupts())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, interrupted),
+            usize::from(offsets.vminterrupts_interrupted())
+        );
+        assert_eq!(
+            offset_of!(VMInterrupts, fuel_consumed),
+            usize::from(offsets.vminterrupts_fuel_consumed())
+        );
+    }
+}
+
+impl VMInterrupts {
+    /// Request that wasm code trap with an interrupt the next time it checks
+    /// this flag.
+    pub fn interrupt(&self) {
+        self.interrupted.store(1, SeqCst);
+    }
+
+    /// Clear a pending interrupt request, returning whether one was pending.
+    pub fn reset(&self) -> bool {
+        self.interrupted.swap(0, SeqCst) != 0
+    }
+}
+
 /// The storage for a WebAssembly invocation argument
 ///
 /// TODO: These could be packed more densely, rather than using the same size for every type.
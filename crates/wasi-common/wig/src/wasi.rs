@@ -31,15 +31,20 @@ pub fn define_struct(args: TokenStream) -> TokenStream {
 
     let mut fields = Vec::new();
     let mut get_exports = Vec::new();
+    let mut linker_add = Vec::new();
     let mut ctor_externs = Vec::new();
     let mut ctor_fields = Vec::new();
 
     for module in doc.modules() {
+        let module_name = module.name.as_str();
         for func in module.funcs() {
             let name = func.name.as_str();
             let name_ident = Ident::new(func.name.as_str(), Span::call_site());
             fields.push(quote! { pub #name_ident: wasmtime::Func });
             get_exports.push(quote! { #name => Some(&self.#name_ident) });
+            linker_add.push(quote! {
+                linker.define(#module_name, #name, self.#name_ident.clone())?;
+            });
             ctor_fields.push(name_ident.clone());
 
             let mut shim_arg_decls = Vec::new();
@@ -182,6 +187,26 @@ pub fn define_struct(args: TokenStream) -> TokenStream {
             }
 
             let format_str = format!("{}({})", name, formats.join(", "));
+
+            // `proc_exit` is special: rather than terminating the whole
+            // process it unwinds out of wasm with a trap carrying the exit
+            // status, leaving it to the embedder to decide what to do.
+            if name == "proc_exit" {
+                ctor_externs.push(quote! {
+                    let #name_ident = wasmtime::Func::wrap1(
+                        store,
+                        move |#(#shim_arg_decls),*| -> Result<(), wasmtime::Trap> {
+                            log::trace!(
+                                #format_str,
+                                #(#format_args),*
+                            );
+                            Err(wasmtime::Trap::i32_exit(#(#format_args)* as i32))
+                        }
+                    );
+                });
+                continue;
+            }
+
             let wrap = format_ident!("wrap{}", shim_arg_decls.len() + 1);
             ctor_externs.push(quote! {
                 let my_cx = cx.clone();
@@ -247,6 +272,13 @@ pub fn define_struct(args: TokenStream) -> TokenStream {
                     _ => None,
                 }
             }
+
+            /// Adds all wasi items to the specified `Linker`, under the
+            /// module name that this version of wasi is imported as.
+            pub fn add_to_linker(&self, linker: &mut wasmtime::Linker) -> anyhow::Result<()> {
+                #(#linker_add)*
+                Ok(())
+            }
         }
     }
 }
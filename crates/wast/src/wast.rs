@@ -18,6 +18,8 @@ fn runtime_value(v: &wast::Expression<'_>) -> Result<Val> {
         F32Const(x) => Val::F32(x.bits),
         F64Const(x) => Val::F64(x.bits),
         V128Const(x) => Val::V128(u128::from_le_bytes(x.to_le_bytes())),
+        RefNull => Val::AnyRef(AnyRef::Null),
+        RefHost(x) => Val::AnyRef(AnyRef::new(Box::new(*x))),
         other => bail!("couldn't convert {:?} to a runtime value", other),
     })
 }
@@ -396,6 +398,12 @@ fn val_matches(actual: &Val, expected: &wast::AssertExpression) -> Result<bool>
         (Val::F32(a), wast::AssertExpression::F32(b)) => f32_matches(*a, b),
         (Val::F64(a), wast::AssertExpression::F64(b)) => f64_matches(*a, b),
         (Val::V128(a), wast::AssertExpression::V128(b)) => v128_matches(*a, b),
+        (Val::AnyRef(a), wast::AssertExpression::RefNull) => a.ptr_eq(&AnyRef::Null),
+        (Val::FuncRef(_), wast::AssertExpression::RefNull) => false,
+        (Val::AnyRef(a), wast::AssertExpression::RefHost(x)) => match a {
+            AnyRef::Other(_) => a.data().downcast_ref::<u32>() == Some(x),
+            _ => false,
+        },
         _ => bail!(
             "don't know how to compare {:?} and {:?} yet",
             actual,
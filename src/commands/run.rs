@@ -6,10 +6,11 @@ use std::{
     ffi::{OsStr, OsString},
     fs::File,
     path::{Component, Path, PathBuf},
+    process,
 };
 use structopt::{clap::AppSettings, StructOpt};
 use wasi_common::preopen_dir;
-use wasmtime::{Engine, Instance, Module, Store};
+use wasmtime::{Engine, Instance, Linker, Module, Store, Trap};
 use wasmtime_interface_types::ModuleData;
 use wasmtime_wasi::{old::snapshot_0::Wasi as WasiSnapshot0, Wasi};
 
@@ -104,19 +105,63 @@ impl RunCommand {
         let preopen_dirs = self.compute_preopen_dirs()?;
         let argv = self.compute_argv();
 
-        let module_registry = ModuleRegistry::new(&store, &preopen_dirs, &argv, &self.vars)?;
+        let linker = Self::create_linker(&store, &preopen_dirs, &argv, &self.vars)?;
 
         // Load the preload wasm modules.
         for preload in self.preloads.iter() {
-            Self::instantiate_module(&store, &module_registry, preload)
+            Self::instantiate_module(&store, &linker, preload)
                 .with_context(|| format!("failed to process preload at `{}`", preload.display()))?;
         }
 
         // Load the main wasm module.
-        self.handle_module(&store, &module_registry)
-            .with_context(|| format!("failed to run main module `{}`", self.module.display()))?;
+        let result = self
+            .handle_module(&store, &linker)
+            .with_context(|| format!("failed to run main module `{}`", self.module.display()));
+
+        // A wasm program requesting an exit isn't an error, so exit with the
+        // status it asked for rather than reporting a failure.
+        if let Err(e) = &result {
+            if let Some(status) = e.downcast_ref::<Trap>().and_then(Trap::i32_exit_status) {
+                process::exit(status);
+            }
+        }
 
-        Ok(())
+        result
+    }
+
+    fn create_linker(
+        store: &Store,
+        preopen_dirs: &[(String, File)],
+        argv: &[String],
+        vars: &[(String, String)],
+    ) -> Result<Linker> {
+        let mut linker = Linker::new(store);
+
+        let mut cx1 = wasi_common::WasiCtxBuilder::new();
+
+        cx1.inherit_stdio().args(argv).envs(vars);
+
+        for (name, file) in preopen_dirs {
+            cx1.preopened_dir(file.try_clone()?, name);
+        }
+
+        let cx1 = cx1.build()?;
+
+        let mut cx2 = wasi_common::old::snapshot_0::WasiCtxBuilder::new()
+            .inherit_stdio()
+            .args(argv)
+            .envs(vars);
+
+        for (name, file) in preopen_dirs {
+            cx2 = cx2.preopened_dir(file.try_clone()?, name);
+        }
+
+        let cx2 = cx2.build()?;
+
+        Wasi::new(store, cx1).add_to_linker(&mut linker)?;
+        WasiSnapshot0::new(store, cx2).add_to_linker(&mut linker)?;
+
+        Ok(linker)
     }
 
     fn compute_preopen_dirs(&self) -> Result<Vec<(String, File)>> {
@@ -165,7 +210,7 @@ impl RunCommand {
 
     fn instantiate_module(
         store: &Store,
-        module_registry: &ModuleRegistry,
+        linker: &Linker,
         path: &Path,
     ) -> Result<(Instance, Module, Vec<u8>)> {
         // Read the wasm module binary either as `*.wat` or a raw binary
@@ -173,38 +218,15 @@ impl RunCommand {
 
         let module = Module::new(store, &data)?;
 
-        // Resolve import using module_registry.
-        let imports = module
-            .imports()
-            .iter()
-            .map(|i| {
-                let export = match i.module() {
-                    "wasi_snapshot_preview1" => {
-                        module_registry.wasi_snapshot_preview1.get_export(i.name())
-                    }
-                    "wasi_unstable" => module_registry.wasi_unstable.get_export(i.name()),
-                    other => bail!("import module `{}` was not found", other),
-                };
-                match export {
-                    Some(export) => Ok(export.clone().into()),
-                    None => bail!(
-                        "import `{}` was not found in module `{}`",
-                        i.name(),
-                        i.module()
-                    ),
-                }
-            })
-            .collect::<Result<Vec<_>, _>>()?;
-
-        let instance = Instance::new(&module, &imports)
+        let instance = linker
+            .instantiate(&module)
             .context(format!("failed to instantiate {:?}", path))?;
 
         Ok((instance, module, data))
     }
 
-    fn handle_module(&self, store: &Store, module_registry: &ModuleRegistry) -> Result<()> {
-        let (instance, module, data) =
-            Self::instantiate_module(store, module_registry, &self.module)?;
+    fn handle_module(&self, store: &Store, linker: &Linker) -> Result<()> {
+        let (instance, module, data) = Self::instantiate_module(store, linker, &self.module)?;
 
         // If a function to invoke was given, invoke it.
         if let Some(name) = self.invoke.as_ref() {
@@ -290,43 +312,3 @@ impl RunCommand {
         Ok(())
     }
 }
-
-struct ModuleRegistry {
-    wasi_snapshot_preview1: Wasi,
-    wasi_unstable: WasiSnapshot0,
-}
-
-impl ModuleRegistry {
-    fn new(
-        store: &Store,
-        preopen_dirs: &[(String, File)],
-        argv: &[String],
-        vars: &[(String, String)],
-    ) -> Result<ModuleRegistry> {
-        let mut cx1 = wasi_common::WasiCtxBuilder::new();
-
-        cx1.inherit_stdio().args(argv).envs(vars);
-
-        for (name, file) in preopen_dirs {
-            cx1.preopened_dir(file.try_clone()?, name);
-        }
-
-        let cx1 = cx1.build()?;
-
-        let mut cx2 = wasi_common::old::snapshot_0::WasiCtxBuilder::new()
-            .inherit_stdio()
-            .args(argv)
-            .envs(vars);
-
-        for (name, file) in preopen_dirs {
-            cx2 = cx2.preopened_dir(file.try_clone()?, name);
-        }
-
-        let cx2 = cx2.build()?;
-
-        Ok(ModuleRegistry {
-            wasi_snapshot_preview1: Wasi::new(store, cx1),
-            wasi_unstable: WasiSnapshot0::new(store, cx2),
-        })
-    }
-}
@@ -63,7 +63,7 @@ pub fn compile_to_obj(
         lazy_data_initializers,
         target_config,
     ) = {
-        let environ = ModuleEnvironment::new(isa.frontend_config(), tunables);
+        let environ = ModuleEnvironment::new(isa.frontend_config(), tunables.clone());
 
         let translation = environ
             .translate(wasm)
@@ -87,6 +87,7 @@ pub fn compile_to_obj(
                 lazy_function_body_inputs,
                 &*isa,
                 debug_info,
+                &tunables,
                 cache_config,
             ),
             #[cfg(feature = "lightbeam")]
@@ -96,6 +97,7 @@ pub fn compile_to_obj(
                 lazy_function_body_inputs,
                 &*isa,
                 debug_info,
+                &tunables,
                 cache_config,
             ),
             #[cfg(not(feature = "lightbeam"))]
@@ -5,16 +5,19 @@ use std::path::Path;
 use std::process::{Command, Stdio};
 use tempfile::NamedTempFile;
 
-fn run_wasmtime(args: &[&str]) -> Result<()> {
+fn wasmtime_command(args: &[&str]) -> Command {
     let cargo = env::var("CARGO").unwrap_or("cargo".to_string());
     let pkg_dir = env!("CARGO_MANIFEST_DIR");
-    let success = Command::new(cargo)
-        .current_dir(pkg_dir)
+    let mut cmd = Command::new(cargo);
+    cmd.current_dir(pkg_dir)
         .stdout(Stdio::null())
         .args(&["run", "-q", "--"])
-        .args(args)
-        .status()?
-        .success();
+        .args(args);
+    cmd
+}
+
+fn run_wasmtime(args: &[&str]) -> Result<()> {
+    let success = wasmtime_command(args).status()?.success();
     if !success {
         bail!("Failed to execute wasmtime with: {:?}", args);
     }
@@ -64,3 +67,12 @@ fn run_wasmtime_simple_wat() -> Result<()> {
         "4",
     ])
 }
+
+// Exit with the status requested through WASI's `proc_exit`.
+#[test]
+fn exit_with_status() -> Result<()> {
+    let wasm = build_wasm("tests/wasm/exit_with_status.wat")?;
+    let status = wasmtime_command(&["run", wasm.path().to_str().unwrap()]).status()?;
+    assert_eq!(status.code(), Some(2));
+    Ok(())
+}
@@ -3,7 +3,9 @@ use std::path::PathBuf;
 use wasmtime_environ::settings;
 use wasmtime_environ::settings::Configurable;
 use wasmtime_environ::CacheConfig;
-use wasmtime_jit::{instantiate, native, CompilationStrategy, Compiler, NullResolver};
+use wasmtime_jit::{
+    instantiate, native, target_tunables, CompilationStrategy, Compiler, NullResolver,
+};
 
 const PATH_MODULE_RS2WASM_ADD_FUNC: &str = r"tests/wat/rs2wasm-add-func.wat";
 
@@ -22,7 +24,8 @@ fn test_environ_translate() {
 
     let mut resolver = NullResolver {};
     let cache_config = CacheConfig::new_cache_disabled();
-    let mut compiler = Compiler::new(isa, CompilationStrategy::Auto, cache_config);
+    let tunables = target_tunables(isa.triple());
+    let mut compiler = Compiler::new(isa, CompilationStrategy::Auto, cache_config, tunables);
     unsafe {
         let instance = instantiate(
             &mut compiler,
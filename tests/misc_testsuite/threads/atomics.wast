@@ -0,0 +1,92 @@
+(module
+  (memory (export "memory") 1 1 shared)
+
+  (func (export "init") (param i64)
+    (i64.store (i32.const 0) (local.get 0)))
+
+  (func (export "i32.atomic.load") (param i32) (result i32)
+    (i32.atomic.load (local.get 0)))
+  (func (export "i64.atomic.load") (param i32) (result i64)
+    (i64.atomic.load (local.get 0)))
+  (func (export "i32.atomic.load8_u") (param i32) (result i32)
+    (i32.atomic.load8_u (local.get 0)))
+  (func (export "i64.atomic.load16_u") (param i32) (result i64)
+    (i64.atomic.load16_u (local.get 0)))
+
+  (func (export "i32.atomic.store") (param i32 i32)
+    (i32.atomic.store (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.store8") (param i32 i64)
+    (i64.atomic.store8 (local.get 0) (local.get 1)))
+
+  (func (export "i32.atomic.rmw.add") (param i32 i32) (result i32)
+    (i32.atomic.rmw.add (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw.sub") (param i32 i64) (result i64)
+    (i64.atomic.rmw.sub (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw.and") (param i32 i32) (result i32)
+    (i32.atomic.rmw.and (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw.or") (param i32 i32) (result i32)
+    (i32.atomic.rmw.or (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw.xor") (param i32 i64) (result i64)
+    (i64.atomic.rmw.xor (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw.xchg") (param i32 i32) (result i32)
+    (i32.atomic.rmw.xchg (local.get 0) (local.get 1)))
+  (func (export "i32.atomic.rmw8.add_u") (param i32 i32) (result i32)
+    (i32.atomic.rmw8.add_u (local.get 0) (local.get 1)))
+  (func (export "i64.atomic.rmw32.sub_u") (param i32 i64) (result i64)
+    (i64.atomic.rmw32.sub_u (local.get 0) (local.get 1)))
+
+  (func (export "i32.atomic.rmw.cmpxchg") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i64.atomic.rmw.cmpxchg") (param i32 i64 i64) (result i64)
+    (i64.atomic.rmw.cmpxchg (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i32.atomic.rmw16.cmpxchg_u") (param i32 i32 i32) (result i32)
+    (i32.atomic.rmw16.cmpxchg_u (local.get 0) (local.get 1) (local.get 2)))
+
+  (func (export "fence")
+    (atomic.fence))
+)
+
+(invoke "init" (i64.const 0x0807060504030201))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 0x04030201))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x0807060504030201))
+(assert_return (invoke "i32.atomic.load8_u" (i32.const 1)) (i32.const 0x02))
+(assert_return (invoke "i64.atomic.load16_u" (i32.const 2)) (i64.const 0x0403))
+
+(invoke "i32.atomic.store" (i32.const 0) (i32.const 0xdeadbeef))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 0xdeadbeef))
+(invoke "i64.atomic.store8" (i32.const 4) (i64.const 0x1ff))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 0x080706ffdeadbeef))
+
+(invoke "init" (i64.const 0))
+(assert_return (invoke "i32.atomic.rmw.add" (i32.const 0) (i32.const 5)) (i32.const 0))
+(assert_return (invoke "i32.atomic.rmw.add" (i32.const 0) (i32.const 5)) (i32.const 5))
+(assert_return (invoke "i64.atomic.rmw.sub" (i32.const 0) (i64.const 3)) (i64.const 10))
+(assert_return (invoke "i64.atomic.load" (i32.const 0)) (i64.const 7))
+(assert_return (invoke "i32.atomic.rmw.and" (i32.const 0) (i32.const 6)) (i32.const 7))
+(assert_return (invoke "i32.atomic.rmw.or" (i32.const 0) (i32.const 9)) (i32.const 6))
+(assert_return (invoke "i64.atomic.rmw.xor" (i32.const 0) (i64.const 0xf)) (i64.const 0xf))
+(assert_return (invoke "i32.atomic.rmw.xchg" (i32.const 0) (i32.const 0x1ff)) (i32.const 0))
+(assert_return (invoke "i32.atomic.rmw8.add_u" (i32.const 0) (i32.const 1)) (i32.const 0xff))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 0x100))
+(assert_return (invoke "i64.atomic.rmw32.sub_u" (i32.const 0) (i64.const 0x101)) (i64.const 0x100))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const -1))
+
+(invoke "init" (i64.const 0))
+(assert_return (invoke "i32.atomic.rmw.cmpxchg" (i32.const 0) (i32.const 1) (i32.const 2)) (i32.const 0))
+(assert_return (invoke "i32.atomic.rmw.cmpxchg" (i32.const 0) (i32.const 0) (i32.const 2)) (i32.const 0))
+(assert_return (invoke "i64.atomic.rmw.cmpxchg" (i32.const 0) (i64.const 2) (i64.const 3)) (i64.const 2))
+;; The expected and replacement values are wrapped to the access size.
+(assert_return (invoke "i32.atomic.rmw16.cmpxchg_u" (i32.const 0) (i32.const 0x10003) (i32.const 0x10004)) (i32.const 3))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 4))
+(assert_return (invoke "i32.atomic.rmw16.cmpxchg_u" (i32.const 0) (i32.const 3) (i32.const 5)) (i32.const 4))
+(assert_return (invoke "i32.atomic.load" (i32.const 0)) (i32.const 4))
+
+(assert_return (invoke "fence"))
+
+(assert_trap (invoke "i32.atomic.load" (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "i64.atomic.load" (i32.const 4)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.store" (i32.const 2) (i32.const 0)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.rmw.add" (i32.const 3) (i32.const 0)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.rmw.cmpxchg" (i32.const 1) (i32.const 0) (i32.const 0)) "unaligned atomic")
+(assert_trap (invoke "i32.atomic.load" (i32.const 65536)) "out of bounds memory access")
+(assert_trap (invoke "i64.atomic.rmw.sub" (i32.const 65528) (i64.const 0)) "out of bounds memory access")
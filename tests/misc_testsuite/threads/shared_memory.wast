@@ -0,0 +1,32 @@
+(assert_invalid
+  (module (memory 1 shared))
+  "shared memory must have maximum")
+
+(module $a
+  (memory (export "memory") 1 2 shared)
+  (func (export "store") (param i32 i32)
+    (i32.atomic.store (local.get 0) (local.get 1)))
+  (func (export "grow") (param i32) (result i32)
+    (memory.grow (local.get 0)))
+)
+(register "a" $a)
+
+(module $b
+  (memory (import "a" "memory") 1 2 shared)
+  (func (export "load") (param i32) (result i32)
+    (i32.atomic.load (local.get 0)))
+  (func (export "size") (result i32)
+    (memory.size))
+)
+
+(invoke $a "store" (i32.const 8) (i32.const 42))
+(assert_return (invoke $b "load" (i32.const 8)) (i32.const 42))
+(assert_trap (invoke $b "load" (i32.const 65536)) "out of bounds memory access")
+(assert_return (invoke $a "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke $b "size") (i32.const 2))
+(assert_return (invoke $b "load" (i32.const 65536)) (i32.const 0))
+(assert_return (invoke $a "grow" (i32.const 1)) (i32.const -1))
+
+(assert_unlinkable
+  (module (memory (import "a" "memory") 1 2))
+  "incompatible import type")
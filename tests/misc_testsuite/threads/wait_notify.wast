@@ -0,0 +1,39 @@
+(module
+  (memory 1 1 shared)
+
+  (func (export "init") (param i64)
+    (i64.store (i32.const 0) (local.get 0)))
+
+  (func (export "i32.atomic.wait") (param i32 i32 i64) (result i32)
+    (i32.atomic.wait (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "i64.atomic.wait") (param i32 i64 i64) (result i32)
+    (i64.atomic.wait (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "atomic.notify") (param i32 i32) (result i32)
+    (atomic.notify (local.get 0) (local.get 1)))
+)
+
+(invoke "init" (i64.const 0xffffffff))
+
+;; Values which don't match return immediately.
+(assert_return (invoke "i32.atomic.wait" (i32.const 0) (i32.const 0) (i64.const -1)) (i32.const 1))
+(assert_return (invoke "i64.atomic.wait" (i32.const 0) (i64.const 0) (i64.const -1)) (i32.const 1))
+
+;; Values which do time out, as there's no other thread to notify them.
+(assert_return (invoke "i32.atomic.wait" (i32.const 0) (i32.const -1) (i64.const 0)) (i32.const 2))
+(assert_return (invoke "i64.atomic.wait" (i32.const 0) (i64.const 0xffffffff) (i64.const 1000)) (i32.const 2))
+
+;; Nothing is waiting to be woken.
+(assert_return (invoke "atomic.notify" (i32.const 0) (i32.const 1)) (i32.const 0))
+
+(assert_trap (invoke "i32.atomic.wait" (i32.const 2) (i32.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "i64.atomic.wait" (i32.const 4) (i64.const 0) (i64.const 0)) "unaligned atomic")
+(assert_trap (invoke "atomic.notify" (i32.const 1) (i32.const 1)) "unaligned atomic")
+(assert_trap (invoke "atomic.notify" (i32.const 65536) (i32.const 1)) "out of bounds memory access")
+
+;; Waiting on a memory which isn't shared would block forever, so it's
+;; rejected up front.
+(assert_invalid
+  (module
+    (memory 1 1)
+    (func (drop (i32.atomic.wait (i32.const 0) (i32.const 0) (i64.const 0)))))
+  "atomic accesses require shared memory")
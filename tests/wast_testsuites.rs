@@ -20,11 +20,14 @@ fn run_wast(wast: &str, strategy: Strategy) -> anyhow::Result<()> {
 
     let multi_val = wast.iter().any(|s| s == "multi-value");
 
+    let threads = wast.iter().any(|s| s == "threads");
+
     let mut cfg = Config::new();
     cfg.wasm_simd(simd)
         .wasm_bulk_memory(bulk_mem)
         .wasm_reference_types(reftypes)
         .wasm_multi_value(multi_val)
+        .wasm_threads(threads)
         .strategy(strategy)?
         .cranelift_debug_verifier(true);
 